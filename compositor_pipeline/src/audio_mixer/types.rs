@@ -43,7 +43,7 @@ pub struct InputSamples {
     pub end_pts: Duration,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct OutputSamples {
     pub samples: AudioSamples,
     pub start_pts: Duration,
@@ -6,7 +6,18 @@ use compositor_render::{
     InputId, OutputId,
 };
 
-use crate::pipeline::{decoder::AacDecoderError, VideoCodec};
+use crate::pipeline::{
+    decoder::AacDecoderError,
+    encoder::fdk_aac::AacEncoderError,
+    input::{
+        mp4::{fragmented::Mp4PushError, playback::Mp4PlaybackError},
+        raw::RawFramePushError,
+        rtp::RtpInputStatsError,
+        whip::WhipError,
+    },
+    output::{rtp::RtpOutputSdpError, whep::WhepError},
+    AudioCodec, VideoCodec,
+};
 
 #[derive(Debug, thiserror::Error)]
 pub enum RegisterInputError {
@@ -39,6 +50,9 @@ pub enum RegisterOutputError {
 
     #[error("Failed to register output stream \"{0}\". At least one of \"video\" and \"audio\" must be specified.")]
     NoVideoAndAudio(OutputId),
+
+    #[error("Failed to register output stream \"{0}\". Resolution of a rendition can not be larger than the output resolution.")]
+    RenditionResolutionTooLarge(OutputId),
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -68,6 +82,9 @@ pub enum OutputInitError {
     #[error("An unsupported codec was requested: {0:?}.")]
     UnsupportedVideoCodec(VideoCodec),
 
+    #[error("An unsupported codec was requested: {0:?}.")]
+    UnsupportedAudioCodec(AudioCodec),
+
     #[error(transparent)]
     SocketError(#[from] std::io::Error),
 
@@ -76,6 +93,24 @@ pub enum OutputInitError {
 
     #[error("Failed to register output. All ports in range {lower_bound} to {upper_bound} are already used or not available.")]
     AllPortsAlreadyInUse { lower_bound: u16, upper_bound: u16 },
+
+    #[error(transparent)]
+    FfmpegError(#[from] ffmpeg_next::Error),
+
+    #[error("Couldn't read output init result.")]
+    CannotReadInitResult,
+
+    #[error("Failed to create output directory.")]
+    CreateDirectory(#[source] std::io::Error),
+
+    #[error("Failed to write SDP file.")]
+    WriteSdpFile(#[source] std::io::Error),
+
+    #[error("Failed to write HLS master playlist.")]
+    WriteMasterPlaylist(#[source] std::io::Error),
+
+    #[error(transparent)]
+    Whep(#[from] crate::pipeline::output::whep::WhepSenderError),
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -88,6 +123,12 @@ pub enum EncoderInitError {
 
     #[error(transparent)]
     OpusError(#[from] opus::Error),
+
+    #[error(transparent)]
+    AacError(#[from] AacEncoderError),
+
+    #[error("Couldn't read encoder init result.")]
+    CannotReadInitResult,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -111,6 +152,27 @@ pub enum InputInitError {
 
     #[error(transparent)]
     Mp4(#[from] crate::pipeline::input::mp4::Mp4Error),
+
+    #[error(transparent)]
+    Whip(#[from] crate::pipeline::input::whip::WhipReceiverError),
+
+    #[error(transparent)]
+    Srt(#[from] crate::pipeline::input::srt::SrtReceiverError),
+
+    #[error(transparent)]
+    MpegTs(#[from] crate::pipeline::input::mpeg_ts::MpegTsReceiverError),
+
+    #[error(transparent)]
+    Rtsp(#[from] crate::pipeline::input::rtsp::RtspReceiverError),
+
+    #[error(transparent)]
+    File(#[from] crate::pipeline::input::file::FileInputError),
+
+    #[error(transparent)]
+    Hls(#[from] crate::pipeline::input::hls::HlsInputError),
+
+    #[error(transparent)]
+    Raw(#[from] crate::pipeline::input::raw::RawInputError),
 }
 
 pub enum ErrorType {
@@ -155,11 +217,136 @@ impl From<&RegisterInputError> for PipelineErrorInfo {
     }
 }
 
+const WHIP_INPUT_NOT_FOUND: &str = "WHIP_INPUT_NOT_FOUND";
+const WHIP_SESSION_ALREADY_ACTIVE: &str = "WHIP_SESSION_ALREADY_ACTIVE";
+const WHIP_NO_ACTIVE_SESSION: &str = "WHIP_NO_ACTIVE_SESSION";
+const WHIP_NEGOTIATION_ERROR: &str = "WHIP_NEGOTIATION_ERROR";
+
+impl From<&WhipError> for PipelineErrorInfo {
+    fn from(err: &WhipError) -> Self {
+        match err {
+            WhipError::InputNotFound(_) | WhipError::NotWhipInput(_) => {
+                PipelineErrorInfo::new(WHIP_INPUT_NOT_FOUND, ErrorType::EntityNotFound)
+            }
+            WhipError::SessionAlreadyActive(_) => {
+                PipelineErrorInfo::new(WHIP_SESSION_ALREADY_ACTIVE, ErrorType::UserError)
+            }
+            WhipError::NoActiveSession(_) => {
+                PipelineErrorInfo::new(WHIP_NO_ACTIVE_SESSION, ErrorType::EntityNotFound)
+            }
+            WhipError::NoLocalDescription | WhipError::WebRtc(_) => {
+                PipelineErrorInfo::new(WHIP_NEGOTIATION_ERROR, ErrorType::UserError)
+            }
+        }
+    }
+}
+
+const WHEP_OUTPUT_NOT_FOUND: &str = "WHEP_OUTPUT_NOT_FOUND";
+const WHEP_SESSION_NOT_FOUND: &str = "WHEP_SESSION_NOT_FOUND";
+const WHEP_NEGOTIATION_ERROR: &str = "WHEP_NEGOTIATION_ERROR";
+
+impl From<&WhepError> for PipelineErrorInfo {
+    fn from(err: &WhepError) -> Self {
+        match err {
+            WhepError::OutputNotFound(_) | WhepError::NotWhepOutput(_) => {
+                PipelineErrorInfo::new(WHEP_OUTPUT_NOT_FOUND, ErrorType::EntityNotFound)
+            }
+            WhepError::SessionNotFound { .. } => {
+                PipelineErrorInfo::new(WHEP_SESSION_NOT_FOUND, ErrorType::EntityNotFound)
+            }
+            WhepError::NoLocalDescription | WhepError::WebRtc(_) => {
+                PipelineErrorInfo::new(WHEP_NEGOTIATION_ERROR, ErrorType::UserError)
+            }
+        }
+    }
+}
+
+const RTP_OUTPUT_NOT_FOUND: &str = "RTP_OUTPUT_NOT_FOUND";
+
+impl From<&RtpOutputSdpError> for PipelineErrorInfo {
+    fn from(err: &RtpOutputSdpError) -> Self {
+        match err {
+            RtpOutputSdpError::OutputNotFound(_) | RtpOutputSdpError::NotRtpOutput(_) => {
+                PipelineErrorInfo::new(RTP_OUTPUT_NOT_FOUND, ErrorType::EntityNotFound)
+            }
+        }
+    }
+}
+
+const RTP_INPUT_NOT_FOUND: &str = "RTP_INPUT_NOT_FOUND";
+
+impl From<&RtpInputStatsError> for PipelineErrorInfo {
+    fn from(err: &RtpInputStatsError) -> Self {
+        match err {
+            RtpInputStatsError::InputNotFound(_) | RtpInputStatsError::NotRtpInput(_) => {
+                PipelineErrorInfo::new(RTP_INPUT_NOT_FOUND, ErrorType::EntityNotFound)
+            }
+        }
+    }
+}
+
+const MP4_INPUT_NOT_FOUND: &str = "MP4_INPUT_NOT_FOUND";
+const MP4_PLAYBACK_FINISHED: &str = "MP4_PLAYBACK_FINISHED";
+const MP4_SEEK_OUT_OF_RANGE: &str = "MP4_SEEK_OUT_OF_RANGE";
+const MP4_NOT_SEEKABLE: &str = "MP4_NOT_SEEKABLE";
+
+impl From<&Mp4PlaybackError> for PipelineErrorInfo {
+    fn from(err: &Mp4PlaybackError) -> Self {
+        match err {
+            Mp4PlaybackError::InputNotFound(_) | Mp4PlaybackError::NotMp4Input(_) => {
+                PipelineErrorInfo::new(MP4_INPUT_NOT_FOUND, ErrorType::EntityNotFound)
+            }
+            Mp4PlaybackError::Finished => {
+                PipelineErrorInfo::new(MP4_PLAYBACK_FINISHED, ErrorType::UserError)
+            }
+            Mp4PlaybackError::PositionOutOfRange { .. } => {
+                PipelineErrorInfo::new(MP4_SEEK_OUT_OF_RANGE, ErrorType::UserError)
+            }
+            Mp4PlaybackError::NotSeekable(_) => {
+                PipelineErrorInfo::new(MP4_NOT_SEEKABLE, ErrorType::UserError)
+            }
+        }
+    }
+}
+
+const MP4_PUSH_INPUT_NOT_FOUND: &str = "MP4_PUSH_INPUT_NOT_FOUND";
+const MP4_PUSH_INPUT_CLOSED: &str = "MP4_PUSH_INPUT_CLOSED";
+
+impl From<&Mp4PushError> for PipelineErrorInfo {
+    fn from(err: &Mp4PushError) -> Self {
+        match err {
+            Mp4PushError::InputNotFound(_) | Mp4PushError::NotPushInput(_) => {
+                PipelineErrorInfo::new(MP4_PUSH_INPUT_NOT_FOUND, ErrorType::EntityNotFound)
+            }
+            Mp4PushError::Closed(_) => {
+                PipelineErrorInfo::new(MP4_PUSH_INPUT_CLOSED, ErrorType::UserError)
+            }
+        }
+    }
+}
+
+const RAW_PUSH_INPUT_NOT_FOUND: &str = "RAW_PUSH_INPUT_NOT_FOUND";
+const RAW_PUSH_INPUT_CLOSED: &str = "RAW_PUSH_INPUT_CLOSED";
+
+impl From<&RawFramePushError> for PipelineErrorInfo {
+    fn from(err: &RawFramePushError) -> Self {
+        match err {
+            RawFramePushError::InputNotFound(_) | RawFramePushError::NotPushInput(_) => {
+                PipelineErrorInfo::new(RAW_PUSH_INPUT_NOT_FOUND, ErrorType::EntityNotFound)
+            }
+            RawFramePushError::Closed(_) => {
+                PipelineErrorInfo::new(RAW_PUSH_INPUT_CLOSED, ErrorType::UserError)
+            }
+        }
+    }
+}
+
 const OUTPUT_STREAM_ALREADY_REGISTERED: &str = "OUTPUT_STREAM_ALREADY_REGISTERED";
 const ENCODER_ERROR: &str = "OUTPUT_STREAM_ENCODER_ERROR";
 const OUTPUT_ERROR: &str = "OUTPUT_STREAM_OUTPUT_ERROR";
 const UNSUPPORTED_RESOLUTION: &str = "UNSUPPORTED_RESOLUTION";
 const NO_VIDEO_OR_AUDIO_FOR_OUTPUT: &str = "NO_VIDEO_OR_AUDIO_FOR_OUTPUT";
+const RENDITION_RESOLUTION_TOO_LARGE: &str = "RENDITION_RESOLUTION_TOO_LARGE";
 
 impl From<&RegisterOutputError> for PipelineErrorInfo {
     fn from(err: &RegisterOutputError) -> Self {
@@ -183,6 +370,9 @@ impl From<&RegisterOutputError> for PipelineErrorInfo {
             RegisterOutputError::NoVideoAndAudio(_) => {
                 PipelineErrorInfo::new(NO_VIDEO_OR_AUDIO_FOR_OUTPUT, ErrorType::UserError)
             }
+            RegisterOutputError::RenditionResolutionTooLarge(_) => {
+                PipelineErrorInfo::new(RENDITION_RESOLUTION_TOO_LARGE, ErrorType::UserError)
+            }
         }
     }
 }
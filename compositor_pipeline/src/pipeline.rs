@@ -19,8 +19,8 @@ use crossbeam_channel::{bounded, Receiver};
 use tracing::{error, info, trace, warn};
 
 use crate::audio_mixer::AudioMixer;
+use crate::audio_mixer::AudioMixingParams;
 use crate::audio_mixer::MixingStrategy;
-use crate::audio_mixer::{AudioChannels, AudioMixingParams};
 use crate::error::{
     RegisterInputError, RegisterOutputError, UnregisterInputError, UnregisterOutputError,
 };
@@ -30,8 +30,15 @@ use crate::queue::PipelineEvent;
 use crate::queue::QueueAudioOutput;
 use crate::queue::{self, Queue, QueueOptions, QueueVideoOutput};
 
-use self::encoder::{AudioEncoderPreset, VideoEncoderOptions};
+use self::encoder::{AudioEncoderOptions, VideoEncoderOptions};
+use self::input::mp4::fragmented::{Mp4PushError, Mp4PushSender};
+use self::input::mp4::playback::{Mp4Playback, Mp4PlaybackError};
+use self::input::raw::{RawFramePushError, RawFramePushSender};
+use self::input::rtp::{RtpInputStats, RtpInputStatsError, SharedUdpSockets};
+use self::input::whip::{WhipError, WhipSession};
 use self::input::InputOptions;
+use self::output::rtp::RtpOutputSdpError;
+use self::output::whep::{WhepError, WhepSessions};
 use self::output::OutputOptions;
 
 pub mod decoder;
@@ -41,7 +48,10 @@ pub mod output;
 mod pipeline_input;
 mod pipeline_output;
 pub mod rtp;
+pub mod sdp;
+pub mod srt;
 mod structs;
+pub(crate) mod webrtc_utils;
 
 use self::pipeline_input::register_pipeline_input;
 use self::pipeline_input::PipelineInput;
@@ -63,15 +73,26 @@ pub struct OutputVideoOptions {
     pub encoder_opts: VideoEncoderOptions,
     pub initial: Component,
     pub end_condition: PipelineOutputEndCondition,
+    /// Additional encodings of the same scene. Frames rendered for the output are
+    /// downscaled to the resolution of each rendition.
+    pub renditions: Vec<OutputVideoRendition>,
+}
+
+/// Stream encoded from frames rendered for a different output.
+#[derive(Debug, Clone)]
+pub struct OutputVideoRendition {
+    pub encoder_opts: VideoEncoderOptions,
+    pub output_options: OutputOptions,
+    /// Encode also the audio mixed for the output. HLS variants have to be
+    /// self-contained, other renditions are video-only.
+    pub with_audio: bool,
 }
 
 #[derive(Debug, Clone)]
 pub struct OutputAudioOptions {
     pub initial: AudioMixingParams,
     pub mixing_strategy: MixingStrategy,
-    pub channels: AudioChannels,
-    pub forward_error_correction: bool,
-    pub encoder_preset: AudioEncoderPreset,
+    pub encoder_opts: AudioEncoderOptions,
     pub end_condition: PipelineOutputEndCondition,
 }
 
@@ -96,6 +117,8 @@ pub struct Pipeline {
     audio_mixer: AudioMixer,
     is_started: bool,
     download_dir: PathBuf,
+    /// UDP sockets of RTP inputs that share a port (see `RtpReceiverOptions::ssrc`).
+    shared_udp_sockets: SharedUdpSockets,
     output_sample_rate: u32,
 }
 
@@ -130,6 +153,7 @@ impl Pipeline {
             audio_mixer: AudioMixer::new(opts.output_sample_rate),
             is_started: false,
             download_dir,
+            shared_udp_sockets: SharedUdpSockets::default(),
             output_sample_rate: opts.output_sample_rate,
         };
 
@@ -167,6 +191,92 @@ impl Pipeline {
         Ok(())
     }
 
+    /// Returns a handle to the WHIP session of a registered WHIP input.
+    pub fn whip_session(&self, input_id: &InputId) -> Result<WhipSession, WhipError> {
+        let Some(input) = self.inputs.get(input_id) else {
+            return Err(WhipError::InputNotFound(input_id.clone()));
+        };
+        match &input.input {
+            input::Input::Whip(receiver) => Ok(receiver.session()),
+            _ => Err(WhipError::NotWhipInput(input_id.clone())),
+        }
+    }
+
+    /// Returns packet statistics of a registered RTP based input (RTP, RTSP or WHIP).
+    pub fn rtp_input_stats(&self, input_id: &InputId) -> Result<RtpInputStats, RtpInputStatsError> {
+        let Some(input) = self.inputs.get(input_id) else {
+            return Err(RtpInputStatsError::InputNotFound(input_id.clone()));
+        };
+        match &input.input {
+            input::Input::Rtp(receiver) => Ok(receiver.stats()),
+            input::Input::Rtsp(receiver) => Ok(receiver.stats()),
+            input::Input::Whip(receiver) => Ok(receiver.stats()),
+            _ => Err(RtpInputStatsError::NotRtpInput(input_id.clone())),
+        }
+    }
+
+    /// Returns a handle used to pause, resume and seek a registered MP4 input.
+    pub fn mp4_playback(&self, input_id: &InputId) -> Result<Mp4Playback, Mp4PlaybackError> {
+        let Some(input) = self.inputs.get(input_id) else {
+            return Err(Mp4PlaybackError::InputNotFound(input_id.clone()));
+        };
+        match &input.input {
+            input::Input::Mp4(mp4) => Ok(mp4.playback()),
+            _ => Err(Mp4PlaybackError::NotMp4Input(input_id.clone())),
+        }
+    }
+
+    /// Returns a handle used to push a fragmented MP4 stream to a registered MP4 input.
+    pub fn mp4_push_sender(&self, input_id: &InputId) -> Result<Mp4PushSender, Mp4PushError> {
+        let Some(input) = self.inputs.get(input_id) else {
+            return Err(Mp4PushError::InputNotFound(input_id.clone()));
+        };
+        match &input.input {
+            input::Input::Mp4(mp4) => mp4
+                .push_sender()
+                .ok_or_else(|| Mp4PushError::NotPushInput(input_id.clone())),
+            _ => Err(Mp4PushError::NotPushInput(input_id.clone())),
+        }
+    }
+
+    /// Returns a handle used to push frames to a registered raw input.
+    pub fn raw_push_sender(
+        &self,
+        input_id: &InputId,
+    ) -> Result<RawFramePushSender, RawFramePushError> {
+        let Some(input) = self.inputs.get(input_id) else {
+            return Err(RawFramePushError::InputNotFound(input_id.clone()));
+        };
+        match &input.input {
+            input::Input::Raw(raw) => raw
+                .push_sender()
+                .ok_or_else(|| RawFramePushError::NotPushInput(input_id.clone())),
+            _ => Err(RawFramePushError::NotPushInput(input_id.clone())),
+        }
+    }
+
+    /// Returns a handle to the viewer sessions of a registered WHEP output.
+    pub fn whep_sessions(&self, output_id: &OutputId) -> Result<WhepSessions, WhepError> {
+        let Some(output) = self.outputs.get(output_id) else {
+            return Err(WhepError::OutputNotFound(output_id.clone()));
+        };
+        match &output.output {
+            output::Output::Whep(sender) => Ok(sender.sessions()),
+            _ => Err(WhepError::NotWhepOutput(output_id.clone())),
+        }
+    }
+
+    /// Returns the SDP describing the stream sent by a registered RTP output.
+    pub fn rtp_output_sdp(&self, output_id: &OutputId) -> Result<Arc<str>, RtpOutputSdpError> {
+        let Some(output) = self.outputs.get(output_id) else {
+            return Err(RtpOutputSdpError::OutputNotFound(output_id.clone()));
+        };
+        match &output.output {
+            output::Output::Rtp(sender) => Ok(sender.sdp()),
+            _ => Err(RtpOutputSdpError::NotRtpOutput(output_id.clone())),
+        }
+    }
+
     pub fn register_output(
         &mut self,
         output_id: OutputId,
@@ -389,13 +499,18 @@ fn run_audio_mixer_thread(
         let mixed_samples = audio_mixer.mix_samples(samples.into());
 
         for (output_id, batch) in mixed_samples.0 {
-            let Some(samples_sender) = output_samples_senders.get(&output_id) else {
+            let Some(samples_senders) = output_samples_senders.get(&output_id) else {
                 warn!(?output_id, "Received new mixed samples after EOS.");
                 continue;
             };
 
-            if samples_sender.send(PipelineEvent::Data(batch)).is_err() {
-                warn!(?output_id, "Failed to send mixed audio. Channel closed.");
+            for samples_sender in samples_senders {
+                if samples_sender
+                    .send(PipelineEvent::Data(batch.clone()))
+                    .is_err()
+                {
+                    warn!(?output_id, "Failed to send mixed audio. Channel closed.");
+                }
             }
         }
     }
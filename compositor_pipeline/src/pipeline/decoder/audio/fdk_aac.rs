@@ -1,3 +1,4 @@
+use bytes::Bytes;
 use fdk_aac_sys as fdk;
 use std::sync::Arc;
 use tracing::error;
@@ -48,7 +49,16 @@ impl AacDecoder {
 
         let instance = unsafe { fdk::aacDecoder_Open(transport, 1) };
 
-        if let Some(config) = options.asc {
+        // ADTS streams (e.g. from MPEG-TS) carry the config only in frame headers, but the
+        // stream info is read below before anything is decoded.
+        let asc = options.asc.or_else(|| {
+            if transport != fdk::TRANSPORT_TYPE_TT_MP4_ADTS {
+                return None;
+            }
+            asc_from_adts_header(&first_chunk.data).map(|asc| Bytes::copy_from_slice(&asc))
+        });
+
+        if let Some(config) = asc {
             let result = unsafe {
                 fdk::aacDecoder_ConfigRaw(
                     instance,
@@ -80,6 +90,24 @@ impl AacDecoder {
     }
 }
 
+/// Builds an AudioSpecificConfig from the profile, sampling frequency index and channel
+/// configuration of an ADTS header.
+fn asc_from_adts_header(header: &[u8]) -> Option<[u8; 2]> {
+    if header.len() < 7 || header[0] != 0xFF || header[1] & 0xF0 != 0xF0 {
+        return None;
+    }
+    let object_type = (header[2] >> 6) + 1;
+    let frequency_index = (header[2] >> 2) & 0x0F;
+    let channel_config = ((header[2] & 0x01) << 2) | (header[3] >> 6);
+    if frequency_index > 12 {
+        return None;
+    }
+    Some([
+        (object_type << 3) | (frequency_index >> 1),
+        ((frequency_index & 0x01) << 7) | (channel_config << 3),
+    ])
+}
+
 impl Drop for AacDecoder {
     fn drop(&mut self) {
         unsafe {
@@ -177,3 +205,29 @@ impl AudioDecoderExt for AacDecoder {
         self.sample_rate
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn asc_from_adts_header_for_aac_lc_stereo() {
+        // AAC LC, 44.1kHz, 2 channels
+        let header = [0xFF, 0xF1, 0x50, 0x80, 0x0D, 0x7F, 0xFC];
+        assert_eq!(asc_from_adts_header(&header), Some([0x12, 0x10]));
+    }
+
+    #[test]
+    fn asc_from_adts_header_for_aac_lc_mono_48khz() {
+        let header = [0xFF, 0xF1, 0x4C, 0x40, 0x0D, 0x7F, 0xFC];
+        assert_eq!(asc_from_adts_header(&header), Some([0x11, 0x88]));
+    }
+
+    #[test]
+    fn asc_from_adts_header_rejects_raw_aac() {
+        assert_eq!(
+            asc_from_adts_header(&[0x21, 0x10, 0x04, 0x60, 0x8C, 0x1C, 0x00]),
+            None
+        );
+    }
+}
@@ -18,6 +18,7 @@ use ffmpeg_next::{
 use tracing::{debug, error, span, trace, warn, Level};
 
 pub fn start_ffmpeg_decoder_thread(
+    codec: VideoCodec,
     chunks_receiver: Receiver<PipelineEvent<EncodedChunk>>,
     frame_sender: Sender<PipelineEvent<Frame>>,
     input_id: InputId,
@@ -29,19 +30,25 @@ pub fn start_ffmpeg_decoder_thread(
         let parameters = &mut *parameters.as_mut_ptr();
 
         parameters.codec_type = Type::Video.into();
-        parameters.codec_id = Id::H264.into();
+        parameters.codec_id = codec_id(codec).into();
     };
 
     std::thread::Builder::new()
-        .name(format!("h264 ffmpeg decoder {}", input_id.0))
+        .name(format!(
+            "{} ffmpeg decoder {}",
+            codec_name(codec),
+            input_id.0
+        ))
         .spawn(move || {
             let _span = span!(
                 Level::INFO,
-                "h264 ffmpeg decoder",
+                "ffmpeg decoder",
+                codec = codec_name(codec),
                 input_id = input_id.to_string()
             )
             .entered();
             run_decoder_thread(
+                codec,
                 parameters,
                 init_result_sender,
                 chunks_receiver,
@@ -55,21 +62,46 @@ pub fn start_ffmpeg_decoder_thread(
     Ok(())
 }
 
+fn codec_id(codec: VideoCodec) -> Id {
+    match codec {
+        VideoCodec::H264 => Id::H264,
+        VideoCodec::Vp8 => Id::VP8,
+        VideoCodec::Vp9 => Id::VP9,
+        VideoCodec::Av1 => Id::AV1,
+    }
+}
+
+fn codec_name(codec: VideoCodec) -> &'static str {
+    match codec {
+        VideoCodec::H264 => "h264",
+        VideoCodec::Vp8 => "vp8",
+        VideoCodec::Vp9 => "vp9",
+        VideoCodec::Av1 => "av1",
+    }
+}
+
+fn find_decoder(codec: VideoCodec) -> Option<ffmpeg_next::Codec> {
+    match codec {
+        // Native ffmpeg AV1 decoder supports only hardware decoding.
+        VideoCodec::Av1 => ffmpeg_next::decoder::find_by_name("libdav1d"),
+        codec => ffmpeg_next::decoder::find(codec_id(codec)),
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 enum DecoderChunkConversionError {
-    #[error(
-        "Cannot send a chunk of kind {0:?} to the decoder. The decoder only handles H264-encoded video."
-    )]
-    BadPayloadType(EncodedChunkKind),
+    #[error("Cannot send a chunk of kind {0:?} to the {1:?} decoder.")]
+    BadPayloadType(EncodedChunkKind, VideoCodec),
 }
 
 fn run_decoder_thread(
+    codec: VideoCodec,
     parameters: ffmpeg_next::codec::Parameters,
     init_result_sender: Sender<Result<(), DecoderInitError>>,
     chunks_receiver: Receiver<PipelineEvent<EncodedChunk>>,
     frame_sender: Sender<PipelineEvent<Frame>>,
 ) {
-    let decoder = Context::from_parameters(parameters.clone())
+    let decoder = Context::from_parameters(parameters)
         .map_err(DecoderInitError::FfmpegError)
         .and_then(|mut decoder| {
             unsafe {
@@ -78,9 +110,12 @@ fn run_decoder_thread(
                 (*decoder.as_mut_ptr()).pkt_timebase = Rational::new(1, 1_000_000).into();
             }
 
-            let decoder = decoder.decoder();
+            let ffmpeg_codec = find_decoder(codec).ok_or(DecoderInitError::FfmpegError(
+                ffmpeg_next::Error::DecoderNotFound,
+            ))?;
             decoder
-                .open_as(Into::<Id>::into(parameters.id()))
+                .decoder()
+                .open_as(ffmpeg_codec)
                 .map_err(DecoderInitError::FfmpegError)
         });
 
@@ -104,15 +139,15 @@ fn run_decoder_thread(
                 break;
             }
         };
-        if chunk.kind != EncodedChunkKind::Video(VideoCodec::H264) {
+        if chunk.kind != EncodedChunkKind::Video(codec) {
             error!(
-                "H264 decoder received chunk of wrong kind: {:?}",
-                chunk.kind
+                "{:?} decoder received chunk of wrong kind: {:?}",
+                codec, chunk.kind
             );
             continue;
         }
 
-        let av_packet: ffmpeg_next::Packet = match chunk_to_av(chunk) {
+        let av_packet: ffmpeg_next::Packet = match chunk_to_av(codec, chunk) {
             Ok(packet) => packet,
             Err(err) => {
                 warn!("Dropping frame: {}", err);
@@ -137,21 +172,32 @@ fn run_decoder_thread(
                 }
             };
 
-            trace!(pts=?frame.pts, "H264 decoder produced a frame.");
+            trace!(pts=?frame.pts, "{:?} decoder produced a frame.", codec);
             if frame_sender.send(PipelineEvent::Data(frame)).is_err() {
-                debug!("Failed to send frame from H264 decoder. Channel closed.");
+                debug!(
+                    "Failed to send frame from {:?} decoder. Channel closed.",
+                    codec
+                );
                 return;
             }
         }
     }
     if frame_sender.send(PipelineEvent::EOS).is_err() {
-        debug!("Failed to send EOS from H264 decoder. Channel closed.")
+        debug!(
+            "Failed to send EOS from {:?} decoder. Channel closed.",
+            codec
+        )
     }
 }
 
-fn chunk_to_av(chunk: EncodedChunk) -> Result<ffmpeg_next::Packet, DecoderChunkConversionError> {
-    if chunk.kind != EncodedChunkKind::Video(VideoCodec::H264) {
-        return Err(DecoderChunkConversionError::BadPayloadType(chunk.kind));
+fn chunk_to_av(
+    codec: VideoCodec,
+    chunk: EncodedChunk,
+) -> Result<ffmpeg_next::Packet, DecoderChunkConversionError> {
+    if chunk.kind != EncodedChunkKind::Video(codec) {
+        return Err(DecoderChunkConversionError::BadPayloadType(
+            chunk.kind, codec,
+        ));
     }
 
     let mut packet = ffmpeg_next::Packet::new(chunk.data.len());
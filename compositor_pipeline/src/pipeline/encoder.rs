@@ -1,14 +1,31 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use bytes::Bytes;
 use compositor_render::{Frame, OutputId, Resolution};
 use crossbeam_channel::{bounded, Receiver, Sender};
 use log::error;
 
-use crate::{audio_mixer::OutputSamples, error::EncoderInitError, queue::PipelineEvent};
+use crate::{
+    audio_mixer::{AudioChannels, OutputSamples},
+    error::EncoderInitError,
+    queue::PipelineEvent,
+};
 
-use self::{ffmpeg_h264::LibavH264Encoder, opus::OpusEncoder};
+use self::{
+    fdk_aac::AacEncoder, ffmpeg_av1::LibaomAv1Encoder, ffmpeg_h264::LibavH264Encoder,
+    ffmpeg_vpx::LibvpxEncoder, opus::OpusEncoder,
+};
 
-use super::structs::EncoderOutputEvent;
+use super::structs::{AudioCodec, EncoderOutputEvent, VideoCodec};
 
+pub mod fdk_aac;
+pub mod ffmpeg_av1;
 pub mod ffmpeg_h264;
+mod ffmpeg_utils;
+pub mod ffmpeg_vpx;
 pub mod opus;
 
 pub struct EncoderOptions {
@@ -19,11 +36,15 @@ pub struct EncoderOptions {
 #[derive(Debug, Clone)]
 pub enum VideoEncoderOptions {
     H264(ffmpeg_h264::Options),
+    Vp8(ffmpeg_vpx::Options),
+    Vp9(ffmpeg_vpx::Options),
+    Av1(ffmpeg_av1::Options),
 }
 
 #[derive(Debug, Clone)]
 pub enum AudioEncoderOptions {
     Opus(opus::Options),
+    Aac(fdk_aac::Options),
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -35,15 +56,59 @@ pub enum AudioEncoderPreset {
 
 pub struct Encoder {
     pub video: Option<VideoEncoder>,
-    audio: Option<AudioEncoder>,
+    pub audio: Option<AudioEncoder>,
+}
+
+/// Information about the encoded streams that is required by outputs that
+/// need to describe them up front (e.g. container headers).
+#[derive(Debug, Clone)]
+pub struct EncoderContext {
+    pub video: Option<VideoEncoderContext>,
+    pub audio: Option<AudioEncoderContext>,
+}
+
+#[derive(Debug, Clone)]
+pub struct VideoEncoderContext {
+    pub codec: VideoCodec,
+    pub resolution: Resolution,
+    pub keyframe_request: KeyframeRequest,
+}
+
+/// Handle used by outputs to request a keyframe from the video encoder, e.g. when
+/// a receiver reports a picture loss. Requests made before the next frame is
+/// encoded result in a single keyframe.
+#[derive(Debug, Clone, Default)]
+pub struct KeyframeRequest(Arc<AtomicBool>);
+
+impl KeyframeRequest {
+    pub fn request(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    fn take(&self) -> bool {
+        self.0.swap(false, Ordering::Relaxed)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AudioEncoderContext {
+    pub codec: AudioCodec,
+    pub channels: AudioChannels,
+    pub sample_rate: u32,
+    /// Codec specific configuration, e.g. `OpusHead` for Opus or
+    /// `AudioSpecificConfig` for AAC.
+    pub extradata: Option<Bytes>,
 }
 
 pub enum VideoEncoder {
     H264(LibavH264Encoder),
+    Vpx(LibvpxEncoder),
+    Av1(LibaomAv1Encoder),
 }
 
 pub enum AudioEncoder {
     Opus(OpusEncoder),
+    Aac(AacEncoder),
 }
 
 impl Encoder {
@@ -84,6 +149,8 @@ impl Encoder {
     pub fn frame_sender(&self) -> Option<&Sender<PipelineEvent<Frame>>> {
         match &self.video {
             Some(VideoEncoder::H264(encoder)) => Some(encoder.frame_sender()),
+            Some(VideoEncoder::Vpx(encoder)) => Some(encoder.frame_sender()),
+            Some(VideoEncoder::Av1(encoder)) => Some(encoder.frame_sender()),
             None => {
                 error!("Non video encoder received frame to send.");
                 None
@@ -91,9 +158,17 @@ impl Encoder {
         }
     }
 
+    pub fn context(&self) -> EncoderContext {
+        EncoderContext {
+            video: self.video.as_ref().map(VideoEncoder::context),
+            audio: self.audio.as_ref().map(AudioEncoder::context),
+        }
+    }
+
     pub fn samples_batch_sender(&self) -> Option<&Sender<PipelineEvent<OutputSamples>>> {
         match &self.audio {
             Some(AudioEncoder::Opus(encoder)) => Some(encoder.samples_batch_sender()),
+            Some(AudioEncoder::Aac(encoder)) => Some(encoder.samples_batch_sender()),
             None => {
                 error!("Non audio encoder received samples to send.");
                 None
@@ -106,6 +181,18 @@ impl VideoEncoderOptions {
     pub fn resolution(&self) -> Resolution {
         match self {
             VideoEncoderOptions::H264(opt) => opt.resolution,
+            VideoEncoderOptions::Vp8(opt) => opt.resolution,
+            VideoEncoderOptions::Vp9(opt) => opt.resolution,
+            VideoEncoderOptions::Av1(opt) => opt.resolution,
+        }
+    }
+}
+
+impl AudioEncoderOptions {
+    pub fn channels(&self) -> AudioChannels {
+        match self {
+            AudioEncoderOptions::Opus(opt) => opt.channels,
+            AudioEncoderOptions::Aac(opt) => opt.channels,
         }
     }
 }
@@ -120,12 +207,49 @@ impl VideoEncoder {
             VideoEncoderOptions::H264(options) => Ok(Self::H264(LibavH264Encoder::new(
                 output_id, options, sender,
             )?)),
+            VideoEncoderOptions::Vp8(options) => Ok(Self::Vpx(LibvpxEncoder::new(
+                output_id,
+                VideoCodec::Vp8,
+                options,
+                sender,
+            )?)),
+            VideoEncoderOptions::Vp9(options) => Ok(Self::Vpx(LibvpxEncoder::new(
+                output_id,
+                VideoCodec::Vp9,
+                options,
+                sender,
+            )?)),
+            VideoEncoderOptions::Av1(options) => Ok(Self::Av1(LibaomAv1Encoder::new(
+                output_id, options, sender,
+            )?)),
         }
     }
 
     pub fn resolution(&self) -> Resolution {
         match self {
             Self::H264(encoder) => encoder.resolution(),
+            Self::Vpx(encoder) => encoder.resolution(),
+            Self::Av1(encoder) => encoder.resolution(),
+        }
+    }
+
+    pub fn context(&self) -> VideoEncoderContext {
+        match self {
+            Self::H264(encoder) => VideoEncoderContext {
+                codec: VideoCodec::H264,
+                resolution: encoder.resolution(),
+                keyframe_request: encoder.keyframe_request(),
+            },
+            Self::Vpx(encoder) => VideoEncoderContext {
+                codec: encoder.codec(),
+                resolution: encoder.resolution(),
+                keyframe_request: encoder.keyframe_request(),
+            },
+            Self::Av1(encoder) => VideoEncoderContext {
+                codec: VideoCodec::Av1,
+                resolution: encoder.resolution(),
+                keyframe_request: encoder.keyframe_request(),
+            },
         }
     }
 }
@@ -140,6 +264,16 @@ impl AudioEncoder {
             AudioEncoderOptions::Opus(opus_encoder_options) => {
                 OpusEncoder::new(opus_encoder_options, sample_rate, sender).map(AudioEncoder::Opus)
             }
+            AudioEncoderOptions::Aac(aac_encoder_options) => {
+                AacEncoder::new(aac_encoder_options, sample_rate, sender).map(AudioEncoder::Aac)
+            }
+        }
+    }
+
+    fn context(&self) -> AudioEncoderContext {
+        match self {
+            AudioEncoder::Opus(encoder) => encoder.context(),
+            AudioEncoder::Aac(encoder) => encoder.context(),
         }
     }
 }
@@ -0,0 +1,338 @@
+use std::{mem, os::raw::c_void, ptr, time::Duration};
+
+use bytes::Bytes;
+use crossbeam_channel::{bounded, Receiver, Sender};
+use fdk_aac_sys as fdk;
+use log::error;
+use tracing::{span, trace, warn, Level};
+
+use crate::{
+    audio_mixer::{AudioChannels, AudioSamples, OutputSamples},
+    error::EncoderInitError,
+    pipeline::{
+        structs::{EncodedChunk, EncodedChunkKind, EncoderOutputEvent, IsKeyframe},
+        AudioCodec,
+    },
+    queue::PipelineEvent,
+};
+
+use super::AudioEncoderContext;
+
+/// Maximum size of an encoded AAC frame is 6144 bits per channel.
+const OUTPUT_BUFFER_SIZE: usize = 8192;
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub channels: AudioChannels,
+    pub profile: AacProfile,
+    /// Target bitrate in bits per second. If not specified, the encoder picks
+    /// a bitrate based on the profile, sample rate and number of channels.
+    pub bitrate: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AacProfile {
+    /// AAC Low Complexity
+    Lc,
+    /// High Efficiency AAC (AAC LC + SBR)
+    He,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum AacEncoderError {
+    #[error("The internal fdk encoder returned an error: {0:?}.")]
+    FdkEncoderError(fdk::AACENC_ERROR),
+}
+
+pub struct AacEncoder {
+    samples_batch_sender: Sender<PipelineEvent<OutputSamples>>,
+    context: AudioEncoderContext,
+}
+
+impl AacEncoder {
+    pub fn new(
+        options: Options,
+        sample_rate: u32,
+        packets_sender: Sender<EncoderOutputEvent>,
+    ) -> Result<Self, EncoderInitError> {
+        let (samples_batch_sender, samples_batch_receiver) = bounded(2);
+        let (init_result_sender, init_result_receiver) = bounded(0);
+
+        let channels = options.channels;
+        // Encoder instance is not `Send`, so it has to be created on the encoder thread.
+        std::thread::Builder::new()
+            .name("AAC encoder thread".to_string())
+            .spawn(move || {
+                let _span = span!(Level::INFO, "AAC encoder thread").entered();
+                let encoder = match FdkAacEncoder::new(&options, sample_rate) {
+                    Ok(encoder) => {
+                        init_result_sender.send(Ok(encoder.asc.clone())).unwrap();
+                        encoder
+                    }
+                    Err(err) => {
+                        init_result_sender.send(Err(err)).unwrap();
+                        return;
+                    }
+                };
+                run_encoder_thread(encoder, samples_batch_receiver, packets_sender)
+            })
+            .unwrap();
+
+        let asc = init_result_receiver
+            .recv()
+            .map_err(|_| EncoderInitError::CannotReadInitResult)??;
+
+        Ok(Self {
+            samples_batch_sender,
+            context: AudioEncoderContext {
+                codec: AudioCodec::Aac,
+                channels,
+                sample_rate,
+                extradata: Some(asc),
+            },
+        })
+    }
+
+    pub fn samples_batch_sender(&self) -> &Sender<PipelineEvent<OutputSamples>> {
+        &self.samples_batch_sender
+    }
+
+    pub fn context(&self) -> AudioEncoderContext {
+        self.context.clone()
+    }
+}
+
+struct FdkAacEncoder {
+    handle: fdk::HANDLE_AACENCODER,
+    /// AudioSpecificConfig describing the encoded stream.
+    asc: Bytes,
+    /// Number of samples (per channel) in a single encoded frame.
+    frame_length: u32,
+    sample_rate: u32,
+    output_buffer: Vec<u8>,
+}
+
+impl FdkAacEncoder {
+    fn new(options: &Options, sample_rate: u32) -> Result<Self, AacEncoderError> {
+        let (channel_count, channel_mode) = match options.channels {
+            AudioChannels::Mono => (1, fdk::CHANNEL_MODE_MODE_1),
+            AudioChannels::Stereo => (2, fdk::CHANNEL_MODE_MODE_2),
+        };
+        let audio_object_type = match options.profile {
+            AacProfile::Lc => fdk::AUDIO_OBJECT_TYPE_AOT_AAC_LC,
+            AacProfile::He => fdk::AUDIO_OBJECT_TYPE_AOT_SBR,
+        };
+
+        let mut handle = ptr::null_mut();
+        check(unsafe { fdk::aacEncOpen(&mut handle, 0, channel_count) })?;
+        let mut encoder = Self {
+            handle,
+            asc: Bytes::new(),
+            frame_length: 0,
+            sample_rate,
+            output_buffer: vec![0; OUTPUT_BUFFER_SIZE],
+        };
+
+        encoder.set_param(fdk::AACENC_PARAM_AACENC_AOT, audio_object_type as u32)?;
+        encoder.set_param(fdk::AACENC_PARAM_AACENC_SAMPLERATE, sample_rate)?;
+        encoder.set_param(fdk::AACENC_PARAM_AACENC_CHANNELMODE, channel_mode as u32)?;
+        // Interleaved samples (L, R, L, R, ...)
+        encoder.set_param(fdk::AACENC_PARAM_AACENC_CHANNELORDER, 1)?;
+        // Raw access units without ADTS/LATM framing
+        encoder.set_param(fdk::AACENC_PARAM_AACENC_TRANSMUX, 0)?;
+        encoder.set_param(fdk::AACENC_PARAM_AACENC_AFTERBURNER, 1)?;
+        if let Some(bitrate) = options.bitrate {
+            encoder.set_param(fdk::AACENC_PARAM_AACENC_BITRATE, bitrate)?;
+        }
+
+        // Calling encode with all arguments set to NULL initializes the encoder.
+        check(unsafe {
+            fdk::aacEncEncode(
+                encoder.handle,
+                ptr::null(),
+                ptr::null(),
+                ptr::null(),
+                ptr::null_mut(),
+            )
+        })?;
+
+        let mut info: fdk::AACENC_InfoStruct = unsafe { mem::zeroed() };
+        check(unsafe { fdk::aacEncInfo(encoder.handle, &mut info) })?;
+        encoder.asc = Bytes::copy_from_slice(&info.confBuf[..info.confSize as usize]);
+        encoder.frame_length = info.frameLength;
+
+        Ok(encoder)
+    }
+
+    fn set_param(&mut self, param: fdk::AACENC_PARAM, value: u32) -> Result<(), AacEncoderError> {
+        check(unsafe { fdk::aacEncoder_SetParam(self.handle, param, value) })
+    }
+
+    /// Encodes interleaved samples. Returns the number of consumed samples
+    /// and an encoded frame if the encoder produced one.
+    ///
+    /// Passing `None` flushes the samples buffered inside the encoder.
+    fn encode(
+        &mut self,
+        samples: Option<&[i16]>,
+    ) -> Result<(usize, Option<Bytes>), AacEncoderError> {
+        let input = samples.unwrap_or(&[]);
+
+        let mut in_ptr = input.as_ptr() as *mut c_void;
+        let mut in_identifier = fdk::AACENC_BufferIdentifier_IN_AUDIO_DATA as i32;
+        let mut in_size = mem::size_of_val(input) as i32;
+        let mut in_element_size = mem::size_of::<i16>() as i32;
+        let in_buffer = fdk::AACENC_BufDesc {
+            numBufs: 1,
+            bufs: &mut in_ptr,
+            bufferIdentifiers: &mut in_identifier,
+            bufSizes: &mut in_size,
+            bufElSizes: &mut in_element_size,
+        };
+
+        let mut out_ptr = self.output_buffer.as_mut_ptr() as *mut c_void;
+        let mut out_identifier = fdk::AACENC_BufferIdentifier_OUT_BITSTREAM_DATA as i32;
+        let mut out_size = self.output_buffer.len() as i32;
+        let mut out_element_size = mem::size_of::<u8>() as i32;
+        let out_buffer = fdk::AACENC_BufDesc {
+            numBufs: 1,
+            bufs: &mut out_ptr,
+            bufferIdentifiers: &mut out_identifier,
+            bufSizes: &mut out_size,
+            bufElSizes: &mut out_element_size,
+        };
+
+        let in_args = fdk::AACENC_InArgs {
+            numInSamples: match samples {
+                Some(samples) => samples.len() as i32,
+                None => -1,
+            },
+            numAncBytes: 0,
+        };
+        let mut out_args: fdk::AACENC_OutArgs = unsafe { mem::zeroed() };
+
+        let result = unsafe {
+            fdk::aacEncEncode(
+                self.handle,
+                &in_buffer,
+                &out_buffer,
+                &in_args,
+                &mut out_args,
+            )
+        };
+        if result == fdk::AACENC_ERROR_AACENC_ENCODE_EOF {
+            return Ok((0, None));
+        }
+        check(result)?;
+
+        let consumed = out_args.numInSamples.max(0) as usize;
+        let frame = match out_args.numOutBytes {
+            0 => None,
+            len => Some(Bytes::copy_from_slice(&self.output_buffer[..len as usize])),
+        };
+        Ok((consumed, frame))
+    }
+}
+
+impl Drop for FdkAacEncoder {
+    fn drop(&mut self) {
+        unsafe {
+            fdk::aacEncClose(&mut self.handle);
+        }
+    }
+}
+
+fn check(result: fdk::AACENC_ERROR) -> Result<(), AacEncoderError> {
+    match result {
+        fdk::AACENC_ERROR_AACENC_OK => Ok(()),
+        err => Err(AacEncoderError::FdkEncoderError(err)),
+    }
+}
+
+fn run_encoder_thread(
+    mut encoder: FdkAacEncoder,
+    samples_batch_receiver: Receiver<PipelineEvent<OutputSamples>>,
+    packets_sender: Sender<EncoderOutputEvent>,
+) {
+    // Timestamps of encoded frames are calculated from the number of produced
+    // frames, because the encoder buffers samples internally.
+    let mut first_pts = None;
+    let mut frame_count: u64 = 0;
+    let (frame_length, sample_rate) = (encoder.frame_length, encoder.sample_rate);
+    let mut next_pts = |first_pts: Duration| {
+        let samples = frame_count * frame_length as u64;
+        frame_count += 1;
+        first_pts + Duration::from_secs_f64(samples as f64 / sample_rate as f64)
+    };
+
+    let send_frame = |data: Bytes, pts: Duration| {
+        let chunk = EncodedChunk {
+            data,
+            pts,
+            dts: None,
+            is_keyframe: IsKeyframe::NoKeyframes,
+            kind: EncodedChunkKind::Audio(AudioCodec::Aac),
+        };
+        trace!(pts=?chunk.pts, "AAC encoder produced an encoded chunk.");
+        packets_sender.send(EncoderOutputEvent::Data(chunk))
+    };
+
+    for msg in samples_batch_receiver {
+        let batch = match msg {
+            PipelineEvent::Data(batch) => batch,
+            PipelineEvent::EOS => break,
+        };
+        let start_pts = *first_pts.get_or_insert(batch.start_pts);
+
+        let samples: Vec<i16> = match batch.samples {
+            AudioSamples::Mono(samples) => samples,
+            AudioSamples::Stereo(samples) => samples.iter().flat_map(|(l, r)| [*l, *r]).collect(),
+        };
+
+        let mut remaining = samples.as_slice();
+        while !remaining.is_empty() {
+            let (consumed, frame) = match encoder.encode(Some(remaining)) {
+                Ok(result) => result,
+                Err(err) => {
+                    error!("AAC encoding error: {}", err);
+                    break;
+                }
+            };
+            remaining = &remaining[consumed.min(remaining.len())..];
+
+            if let Some(data) = frame {
+                let pts = next_pts(start_pts);
+                if send_frame(data, pts).is_err() {
+                    warn!("Failed to send encoded audio from AAC encoder. Channel closed.");
+                    return;
+                }
+            } else if consumed == 0 {
+                break;
+            }
+        }
+    }
+
+    // Flush samples buffered inside the encoder.
+    if let Some(start_pts) = first_pts {
+        loop {
+            match encoder.encode(None) {
+                Ok((_, Some(data))) => {
+                    let pts = next_pts(start_pts);
+                    if send_frame(data, pts).is_err() {
+                        warn!("Failed to send encoded audio from AAC encoder. Channel closed.");
+                        return;
+                    }
+                }
+                Ok((_, None)) => break,
+                Err(err) => {
+                    error!("AAC encoding error: {}", err);
+                    break;
+                }
+            }
+        }
+    }
+
+    if let Err(_err) = packets_sender.send(EncoderOutputEvent::AudioEOS) {
+        warn!("Failed to send EOS from AAC encoder. Channel closed.")
+    }
+}
@@ -0,0 +1,161 @@
+use compositor_render::{Frame, OutputId, Resolution};
+use crossbeam_channel::{Receiver, Sender};
+use ffmpeg_next::{codec::Context, format::Pixel, Dictionary, Rational};
+use tracing::{span, Level};
+
+use crate::{
+    error::EncoderInitError,
+    pipeline::{
+        encoder::KeyframeRequest,
+        structs::{EncoderOutputEvent, VideoCodec},
+    },
+    queue::PipelineEvent,
+};
+
+use super::ffmpeg_utils::{encode_frames, merge_options_with_defaults, spawn_encoder_thread};
+
+/// Encoder usage mode. See libaom [docs](https://trac.ffmpeg.org/wiki/Encode/AV1#libaom)
+/// to learn more.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Usage {
+    /// Low latency encoding, suitable for live streaming.
+    Realtime,
+    /// Good quality encoding, suitable for archiving.
+    Good,
+}
+
+impl Usage {
+    fn to_str(self) -> &'static str {
+        match self {
+            Usage::Realtime => "realtime",
+            Usage::Good => "good",
+        }
+    }
+
+    fn default_cpu_used(self) -> &'static str {
+        match self {
+            Usage::Realtime => "8",
+            Usage::Good => "4",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Options {
+    pub resolution: Resolution,
+    /// Target bitrate in bits per second. If not specified, the encoder
+    /// runs in a constant quality mode.
+    pub bitrate: Option<u64>,
+    pub usage: Usage,
+    /// Maximum number of frames between keyframes.
+    pub keyframe_interval: u32,
+    pub raw_options: Vec<(String, String)>,
+}
+
+/// AV1 encoder based on libaom.
+pub struct LibaomAv1Encoder {
+    resolution: Resolution,
+    frame_sender: Sender<PipelineEvent<Frame>>,
+    keyframe_request: KeyframeRequest,
+}
+
+impl LibaomAv1Encoder {
+    pub fn new(
+        output_id: &OutputId,
+        options: Options,
+        chunks_sender: Sender<EncoderOutputEvent>,
+    ) -> Result<Self, EncoderInitError> {
+        let options_clone = options.clone();
+        let keyframe_request = KeyframeRequest::default();
+        let keyframe_request_clone = keyframe_request.clone();
+        let frame_sender = spawn_encoder_thread(
+            output_id,
+            span!(
+                Level::INFO,
+                "av1 ffmpeg encoder",
+                output_id = output_id.to_string()
+            ),
+            move |frame_receiver, result_sender| {
+                run_encoder_thread(
+                    options_clone,
+                    keyframe_request_clone,
+                    frame_receiver,
+                    chunks_sender,
+                    result_sender,
+                )
+            },
+        )?;
+
+        Ok(Self {
+            keyframe_request,
+            frame_sender,
+            resolution: options.resolution,
+        })
+    }
+
+    pub fn frame_sender(&self) -> &Sender<PipelineEvent<Frame>> {
+        &self.frame_sender
+    }
+
+    pub fn keyframe_request(&self) -> KeyframeRequest {
+        self.keyframe_request.clone()
+    }
+
+    pub fn resolution(&self) -> Resolution {
+        self.resolution
+    }
+}
+
+fn run_encoder_thread(
+    options: Options,
+    keyframe_request: KeyframeRequest,
+    frame_receiver: Receiver<PipelineEvent<Frame>>,
+    packet_sender: Sender<EncoderOutputEvent>,
+    result_sender: &Sender<Result<(), EncoderInitError>>,
+) -> Result<(), EncoderInitError> {
+    let codec =
+        ffmpeg_next::codec::encoder::find_by_name("libaom-av1").ok_or(EncoderInitError::NoCodec)?;
+
+    let mut encoder = Context::new().encoder().video()?;
+
+    // We set this to 1 / 1_000_000, bc we use `as_micros` to convert frames to AV packets.
+    let pts_unit_secs = Rational::new(1, 1_000_000);
+    encoder.set_time_base(pts_unit_secs);
+    encoder.set_format(Pixel::YUV420P);
+    encoder.set_width(options.resolution.width as u32);
+    encoder.set_height(options.resolution.height as u32);
+
+    let bitrate = options.bitrate.map(|bitrate| bitrate.to_string());
+    let keyframe_interval = options.keyframe_interval.to_string();
+    let mut defaults = vec![
+        ("usage", options.usage.to_str()),
+        ("cpu-used", options.usage.default_cpu_used()),
+        ("g", keyframe_interval.as_str()),
+        // Multi-threaded encoding of rows
+        ("row-mt", "1"),
+    ];
+    match &bitrate {
+        Some(bitrate) => defaults.push(("b", bitrate.as_str())),
+        // Constant quality mode requires bitrate to be set to 0
+        None => defaults.extend([("crf", "30"), ("b", "0")]),
+    }
+    if options.usage == Usage::Realtime {
+        // Disable frame lookahead, it adds latency
+        defaults.push(("lag-in-frames", "0"));
+    }
+
+    let encoder_opts_iter = merge_options_with_defaults(&defaults, &options.raw_options);
+    let encoder = encoder.open_as_with(codec, Dictionary::from_iter(encoder_opts_iter))?;
+
+    result_sender.send(Ok(())).unwrap();
+
+    encode_frames(
+        encoder,
+        options.resolution,
+        VideoCodec::Av1,
+        &keyframe_request,
+        frame_receiver,
+        packet_sender,
+    );
+    Ok(())
+}
@@ -1,18 +1,25 @@
+use std::str::FromStr;
+
 use compositor_render::{Frame, OutputId, Resolution};
 use crossbeam_channel::{Receiver, Sender};
 use ffmpeg_next::{
     codec::{Context, Id},
     format::Pixel,
-    frame, Dictionary, Packet, Rational,
+    Dictionary, Rational,
 };
-use tracing::{debug, error, span, trace, warn, Level};
+use tracing::span;
 
 use crate::{
     error::EncoderInitError,
-    pipeline::structs::{EncodedChunk, EncodedChunkKind, EncoderOutputEvent, VideoCodec},
+    pipeline::{
+        encoder::KeyframeRequest,
+        structs::{EncoderOutputEvent, VideoCodec},
+    },
     queue::PipelineEvent,
 };
 
+use super::ffmpeg_utils::{encode_frames, merge_options_with_defaults, spawn_encoder_thread};
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum EncoderPreset {
     Ultrafast,
@@ -74,16 +81,171 @@ impl EncoderPreset {
     }
 }
 
+/// Rate control mode of libx264. Bitrates are in bits per second.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BitrateMode {
+    /// Constant quality (0-51, lower values mean better quality). If `max_bitrate` is
+    /// defined, the quality is lowered when the bitrate would exceed it.
+    Crf { crf: u8, max_bitrate: Option<u64> },
+    /// Constant bitrate, signaled in the stream with HRD parameters.
+    Cbr { bitrate: u64 },
+    /// Variable bitrate with an average `bitrate` and an optional upper bound.
+    Vbr {
+        bitrate: u64,
+        max_bitrate: Option<u64>,
+    },
+}
+
+impl Default for BitrateMode {
+    fn default() -> Self {
+        Self::Crf {
+            crf: 23,
+            max_bitrate: None,
+        }
+    }
+}
+
+impl BitrateMode {
+    pub fn max_bitrate(&self) -> Option<u64> {
+        match *self {
+            BitrateMode::Crf { max_bitrate, .. } => max_bitrate,
+            BitrateMode::Cbr { bitrate } => Some(bitrate),
+            BitrateMode::Vbr { max_bitrate, .. } => max_bitrate,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Profile {
+    Baseline,
+    Main,
+    High,
+}
+
+impl Profile {
+    fn to_str(self) -> &'static str {
+        match self {
+            Profile::Baseline => "baseline",
+            Profile::Main => "main",
+            Profile::High => "high",
+        }
+    }
+}
+
+/// H264 level as defined in Annex A of the specification, e.g. `"4.1"` or `"1b"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Level {
+    /// Value of `level_idc` (level multiplied by 10, 9 for level 1b).
+    level_idc: u8,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Invalid H264 level \"{0}\". Expected one of: 1, 1b, 1.1, 1.2, 1.3, 2, 2.1, 2.2, 3, 3.1, 3.2, 4, 4.1, 4.2, 5, 5.1, 5.2, 6, 6.1, 6.2.")]
+pub struct InvalidLevelError(String);
+
+impl FromStr for Level {
+    type Err = InvalidLevelError;
+
+    fn from_str(level: &str) -> Result<Self, Self::Err> {
+        const LEVELS: [(&str, u8); 20] = [
+            ("1", 10),
+            ("1b", 9),
+            ("1.1", 11),
+            ("1.2", 12),
+            ("1.3", 13),
+            ("2", 20),
+            ("2.1", 21),
+            ("2.2", 22),
+            ("3", 30),
+            ("3.1", 31),
+            ("3.2", 32),
+            ("4", 40),
+            ("4.1", 41),
+            ("4.2", 42),
+            ("5", 50),
+            ("5.1", 51),
+            ("5.2", 52),
+            ("6", 60),
+            ("6.1", 61),
+            ("6.2", 62),
+        ];
+        LEVELS
+            .iter()
+            .find(|(name, _)| *name == level)
+            .map(|(_, level_idc)| Self {
+                level_idc: *level_idc,
+            })
+            .ok_or_else(|| InvalidLevelError(level.to_string()))
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Options {
     pub preset: EncoderPreset,
     pub resolution: Resolution,
+    pub bitrate_mode: BitrateMode,
+    /// Size of the VBV buffer in bits. Used only if the bitrate mode has a max
+    /// bitrate, defaults to the max bitrate (1 second buffer).
+    pub vbv_buffer_size: Option<u64>,
+    /// Maximum number of frames between keyframes.
+    pub keyframe_interval: u32,
+    /// Maximum number of consecutive B-frames. If not specified, the preset default is used.
+    pub b_frames: Option<u32>,
+    /// If not specified, libx264 picks the profile based on other options.
+    pub profile: Option<Profile>,
+    /// If not specified, libx264 picks the level based on the resolution and bitrate.
+    pub level: Option<Level>,
     pub raw_options: Vec<(String, String)>,
 }
 
+impl Options {
+    /// Codec string (RFC 6381) of the encoded stream, e.g. `avc1.64002a`. If the profile
+    /// or level is picked by libx264, the highest one it could pick is used, so players
+    /// that accept the string can decode the stream.
+    pub fn codec_string(&self) -> String {
+        let (profile_idc, constraint_flags) = match self.profile.unwrap_or(Profile::High) {
+            Profile::Baseline => (0x42, 0xC0),
+            Profile::Main => (0x4D, 0x40),
+            Profile::High => (0x64, 0x00),
+        };
+        let level_idc = match self.level {
+            Some(level) => level.level_idc,
+            None => max_level_for_resolution(self.resolution),
+        };
+        format!("avc1.{profile_idc:02x}{constraint_flags:02x}{level_idc:02x}")
+    }
+}
+
+/// Lowest level (`level_idc`) that allows the resolution at 60 fps, based on the
+/// frame size and macroblock rate limits from table A-1 of the specification.
+fn max_level_for_resolution(resolution: Resolution) -> u8 {
+    const LEVELS: [(u8, u64, u64); 12] = [
+        (30, 40_500, 1_620),
+        (31, 108_000, 3_600),
+        (32, 216_000, 5_120),
+        (40, 245_760, 8_192),
+        (41, 245_760, 8_192),
+        (42, 522_240, 8_704),
+        (50, 589_824, 22_080),
+        (51, 983_040, 36_864),
+        (52, 2_073_600, 36_864),
+        (60, 4_177_920, 139_264),
+        (61, 8_355_840, 139_264),
+        (62, 16_711_680, 139_264),
+    ];
+    let macroblocks = resolution.width.div_ceil(16) as u64 * resolution.height.div_ceil(16) as u64;
+    LEVELS
+        .iter()
+        .find(|(_, max_macroblock_rate, max_frame_size)| {
+            macroblocks <= *max_frame_size && macroblocks * 60 <= *max_macroblock_rate
+        })
+        .map_or(62, |(level_idc, _, _)| *level_idc)
+}
+
 pub struct LibavH264Encoder {
     resolution: Resolution,
     frame_sender: Sender<PipelineEvent<Frame>>,
+    keyframe_request: KeyframeRequest,
 }
 
 impl LibavH264Encoder {
@@ -92,41 +254,29 @@ impl LibavH264Encoder {
         options: Options,
         chunks_sender: Sender<EncoderOutputEvent>,
     ) -> Result<Self, EncoderInitError> {
-        let (frame_sender, frame_receiver) = crossbeam_channel::bounded(5);
-        let (result_sender, result_receiver) = crossbeam_channel::bounded(0);
-
         let options_clone = options.clone();
-        let output_id = output_id.clone();
-
-        std::thread::Builder::new()
-            .name(format!("Encoder thread for output {}", output_id))
-            .spawn(move || {
-                let _span = span!(
-                    Level::INFO,
-                    "h264 ffmpeg encoder",
-                    output_id = output_id.to_string()
-                )
-                .entered();
-                let encoder_result = run_encoder_thread(
+        let keyframe_request = KeyframeRequest::default();
+        let keyframe_request_clone = keyframe_request.clone();
+        let frame_sender = spawn_encoder_thread(
+            output_id,
+            span!(
+                tracing::Level::INFO,
+                "h264 ffmpeg encoder",
+                output_id = output_id.to_string()
+            ),
+            move |frame_receiver, result_sender| {
+                run_encoder_thread(
                     options_clone,
+                    keyframe_request_clone,
                     frame_receiver,
                     chunks_sender,
-                    &result_sender,
-                );
-
-                if let Err(err) = encoder_result {
-                    warn!(%err, "Encoder thread finished with an error.");
-                    if let Err(err) = result_sender.send(Err(err)) {
-                        warn!(%err, "Failed to send error info. Result channel already closed.");
-                    }
-                }
-                debug!("Encoder thread finished.");
-            })
-            .unwrap();
-
-        result_receiver.recv().unwrap()?;
+                    result_sender,
+                )
+            },
+        )?;
 
         Ok(Self {
+            keyframe_request,
             frame_sender,
             resolution: options.resolution,
         })
@@ -136,6 +286,10 @@ impl LibavH264Encoder {
         &self.frame_sender
     }
 
+    pub fn keyframe_request(&self) -> KeyframeRequest {
+        self.keyframe_request.clone()
+    }
+
     pub fn resolution(&self) -> Resolution {
         self.resolution
     }
@@ -143,6 +297,7 @@ impl LibavH264Encoder {
 
 fn run_encoder_thread(
     options: Options,
+    keyframe_request: KeyframeRequest,
     frame_receiver: Receiver<PipelineEvent<Frame>>,
     packet_sender: Sender<EncoderOutputEvent>,
     result_sender: &Sender<Result<(), EncoderInitError>>,
@@ -158,13 +313,13 @@ fn run_encoder_thread(
     encoder.set_width(options.resolution.width as u32);
     encoder.set_height(options.resolution.height as u32);
 
+    let keyframe_interval = options.keyframe_interval.to_string();
+
     // TODO: audit settings below
     // Those values are copied from somewhere, they have to be set because libx264
     // is throwing an error if it detects default ffmpeg settings.
-    let defaults = [
+    let mut defaults = vec![
         ("preset", options.preset.to_str()),
-        // Quality-based VBR (0-51)
-        ("crf", "23"),
         // Override ffmpeg defaults from https://github.com/mirror/x264/blob/eaa68fad9e5d201d42fde51665f2d137ae96baf0/encoder/encoder.c#L674
         // QP curve compression - libx264 defaults to 0.6 (in case of tune=grain to 0.8)
         ("qcomp", "0.6"),
@@ -178,7 +333,7 @@ fn run_encoder_thread(
         // Max QP - libx264 defaults to QP_MAX = 69
         ("qmax", "69"),
         //  Maximum GOP (Group of Pictures) size - libx264 defaults to 250
-        ("g", "250"),
+        ("g", keyframe_interval.as_str()),
         // QP factor between I and P frames - libx264 defaults to 1.4 (in case of tune=grain to 1.1)
         ("i_qfactor", "1.4"),
         // QP factor between P and B frames - libx264 defaults to 1.4 (in case of tune=grain to 1.1)
@@ -189,142 +344,211 @@ fn run_encoder_thread(
         ("subq", options.preset.default_subq_mode()),
     ];
 
+    let rate_control_options = rate_control_options(&options);
+    defaults.extend(
+        rate_control_options
+            .iter()
+            .map(|(key, value)| (*key, value.as_str())),
+    );
+    let b_frames = options.b_frames.map(|b_frames| b_frames.to_string());
+    if let Some(b_frames) = &b_frames {
+        defaults.push(("bf", b_frames.as_str()));
+    }
+    if let Some(profile) = options.profile {
+        defaults.push(("profile", profile.to_str()));
+    }
+    // Generic `level` option of the codec context expects `level_idc`.
+    let level = options.level.map(|level| level.level_idc.to_string());
+    if let Some(level) = &level {
+        defaults.push(("level", level.as_str()));
+    }
+
     let encoder_opts_iter = merge_options_with_defaults(&defaults, &options.raw_options);
-    let mut encoder = encoder.open_as_with(codec, Dictionary::from_iter(encoder_opts_iter))?;
+    let encoder = encoder.open_as_with(codec, Dictionary::from_iter(encoder_opts_iter))?;
 
     result_sender.send(Ok(())).unwrap();
 
-    let mut packet = Packet::empty();
-
-    loop {
-        let frame = match frame_receiver.recv() {
-            Ok(PipelineEvent::Data(f)) => f,
-            Ok(PipelineEvent::EOS) => break,
-            Err(_) => break,
-        };
-
-        let mut av_frame = frame::Video::new(
-            Pixel::YUV420P,
-            options.resolution.width as u32,
-            options.resolution.height as u32,
-        );
+    encode_frames(
+        encoder,
+        options.resolution,
+        VideoCodec::H264,
+        &keyframe_request,
+        frame_receiver,
+        packet_sender,
+    );
+    Ok(())
+}
 
-        if let Err(e) = frame_into_av(frame, &mut av_frame) {
-            error!(
-                "Failed to convert a frame to an ffmpeg frame: {}. Dropping",
-                e.0
-            );
-            continue;
-        }
+/// libx264 uses CRF whenever `crf` is set, so it is passed only in the CRF mode.
+/// Bitrate and VBV buffer are generic ffmpeg options, they are defined in bits.
+fn rate_control_options(options: &Options) -> Vec<(&'static str, String)> {
+    let mut rate_control = match options.bitrate_mode {
+        BitrateMode::Crf { crf, .. } => vec![("crf", crf.to_string())],
+        BitrateMode::Cbr { bitrate } => vec![
+            ("b", bitrate.to_string()),
+            ("minrate", bitrate.to_string()),
+            ("nal-hrd", "cbr".to_string()),
+        ],
+        BitrateMode::Vbr { bitrate, .. } => vec![("b", bitrate.to_string())],
+    };
+    if let Some(max_bitrate) = options.bitrate_mode.max_bitrate() {
+        let vbv_buffer_size = options.vbv_buffer_size.unwrap_or(max_bitrate);
+        rate_control.extend([
+            ("maxrate", max_bitrate.to_string()),
+            ("bufsize", vbv_buffer_size.to_string()),
+        ]);
+    }
+    rate_control
+}
 
-        if let Err(e) = encoder.send_frame(&av_frame) {
-            error!("Encoder error: {e}.");
-            continue;
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bytes::Bytes;
+    use compositor_render::{Frame, OutputId, Resolution, YuvData, YuvVariant};
+
+    use crate::{
+        pipeline::structs::{EncodedChunkKind, EncoderOutputEvent, IsKeyframe, VideoCodec},
+        queue::PipelineEvent,
+    };
+
+    use super::{
+        rate_control_options, BitrateMode, EncoderPreset, Level, LibavH264Encoder, Options, Profile,
+    };
+
+    fn options(bitrate_mode: BitrateMode, vbv_buffer_size: Option<u64>) -> Options {
+        Options {
+            preset: EncoderPreset::Fast,
+            resolution: Resolution {
+                width: 1280,
+                height: 720,
+            },
+            bitrate_mode,
+            vbv_buffer_size,
+            keyframe_interval: 250,
+            b_frames: None,
+            profile: None,
+            level: None,
+            raw_options: Vec::new(),
         }
+    }
 
-        loop {
-            match encoder.receive_packet(&mut packet) {
-                Ok(_) => {
-                    match EncodedChunk::from_av_packet(
-                        &packet,
-                        EncodedChunkKind::Video(VideoCodec::H264),
-                        1_000_000,
-                    ) {
-                        Ok(chunk) => {
-                            trace!(pts=?packet.pts(), "H264 encoder produced an encoded packet.");
-                            if packet_sender.send(EncoderOutputEvent::Data(chunk)).is_err() {
-                                warn!("Failed to send encoded video from H264 encoder. Channel closed.");
-                                return Ok(());
-                            }
-                        }
-                        Err(e) => {
-                            warn!("failed to parse an ffmpeg packet received from encoder: {e}",);
-                            break;
-                        }
-                    }
-                }
-
-                Err(ffmpeg_next::Error::Other {
-                    errno: ffmpeg_next::error::EAGAIN,
-                }) => break, // encoder needs more frames to produce a packet
-
-                Err(e) => {
-                    error!("Encoder error: {e}.");
-                    break;
-                }
-            }
+    fn frame(index: u64, resolution: Resolution) -> Frame {
+        let pixels = resolution.width * resolution.height;
+        Frame {
+            data: YuvData {
+                variant: YuvVariant::YUV420P,
+                y_plane: Bytes::from(vec![(index * 20) as u8; pixels]),
+                u_plane: Bytes::from(vec![128; pixels / 4]),
+                v_plane: Bytes::from(vec![128; pixels / 4]),
+            },
+            resolution,
+            pts: Duration::from_millis(index * 33),
         }
     }
 
-    if let Err(_err) = packet_sender.send(EncoderOutputEvent::VideoEOS) {
-        warn!("Failed to send EOS from H264 encoder. Channel closed.")
+    fn owned(options: &[(&'static str, &str)]) -> Vec<(&'static str, String)> {
+        options
+            .iter()
+            .map(|(key, value)| (*key, value.to_string()))
+            .collect()
     }
-    Ok(())
-}
 
-#[derive(Debug)]
-struct FrameConversionError(String);
-
-fn frame_into_av(frame: Frame, av_frame: &mut frame::Video) -> Result<(), FrameConversionError> {
-    let expected_y_plane_size = (av_frame.plane_width(0) * av_frame.plane_height(0)) as usize;
-    let expected_u_plane_size = (av_frame.plane_width(1) * av_frame.plane_height(1)) as usize;
-    let expected_v_plane_size = (av_frame.plane_width(2) * av_frame.plane_height(2)) as usize;
-    if expected_y_plane_size != frame.data.y_plane.len() {
-        return Err(FrameConversionError(format!(
-            "Y plane is a wrong size, expected: {} received: {}",
-            expected_y_plane_size,
-            frame.data.y_plane.len()
-        )));
-    }
-    if expected_u_plane_size != frame.data.u_plane.len() {
-        return Err(FrameConversionError(format!(
-            "U plane is a wrong size, expected: {} received: {}",
-            expected_u_plane_size,
-            frame.data.u_plane.len()
-        )));
+    #[test]
+    fn parse_level() {
+        let level_idc = |level: &str| level.parse::<Level>().ok().map(|level| level.level_idc);
+        assert_eq!(level_idc("4.1"), Some(41));
+        assert_eq!(level_idc("3"), Some(30));
+        assert_eq!(level_idc("1b"), Some(9));
+        assert_eq!(level_idc("4.3"), None);
+        assert_eq!(level_idc("4.10"), None);
+        assert_eq!(level_idc("high"), None);
     }
-    if expected_v_plane_size != frame.data.v_plane.len() {
-        return Err(FrameConversionError(format!(
-            "V plane is a wrong size, expected: {} received: {}",
-            expected_v_plane_size,
-            frame.data.v_plane.len()
-        )));
-    }
-
-    av_frame.set_pts(Some(frame.pts.as_micros() as i64));
 
-    write_plane_to_av(av_frame, 0, &frame.data.y_plane);
-    write_plane_to_av(av_frame, 1, &frame.data.u_plane);
-    write_plane_to_av(av_frame, 2, &frame.data.v_plane);
+    #[test]
+    fn codec_string() {
+        let mut opts = options(BitrateMode::default(), None);
+        assert_eq!(opts.codec_string(), "avc1.640020");
 
-    Ok(())
-}
+        opts.resolution = Resolution {
+            width: 1920,
+            height: 1080,
+        };
+        assert_eq!(opts.codec_string(), "avc1.64002a");
 
-fn write_plane_to_av(frame: &mut frame::Video, plane: usize, data: &[u8]) {
-    let stride = frame.stride(plane);
-    let width = frame.plane_width(plane) as usize;
+        opts.profile = Some(Profile::Baseline);
+        opts.level = Some("3.1".parse().unwrap());
+        assert_eq!(opts.codec_string(), "avc1.42c01f");
+    }
 
-    data.chunks(width)
-        .zip(frame.data_mut(plane).chunks_mut(stride))
-        .for_each(|(data, target)| target[..width].copy_from_slice(data));
-}
+    #[test]
+    fn rate_control_modes() {
+        assert_eq!(
+            rate_control_options(&options(BitrateMode::default(), None)),
+            owned(&[("crf", "23")])
+        );
+        assert_eq!(
+            rate_control_options(&options(BitrateMode::Cbr { bitrate: 2_000_000 }, None)),
+            owned(&[
+                ("b", "2000000"),
+                ("minrate", "2000000"),
+                ("nal-hrd", "cbr"),
+                ("maxrate", "2000000"),
+                ("bufsize", "2000000"),
+            ])
+        );
+        assert_eq!(
+            rate_control_options(&options(
+                BitrateMode::Vbr {
+                    bitrate: 2_000_000,
+                    max_bitrate: Some(3_000_000),
+                },
+                Some(6_000_000),
+            )),
+            owned(&[
+                ("b", "2000000"),
+                ("maxrate", "3000000"),
+                ("bufsize", "6000000"),
+            ])
+        );
+    }
 
-fn merge_options_with_defaults<'a>(
-    defaults: &'a [(&str, &str)],
-    overrides: &'a [(String, String)],
-) -> impl Iterator<Item = (&'a str, &'a str)> {
-    defaults
-        .iter()
-        .copied()
-        .filter(|(key, _value)| {
-            // filter out any defaults that are in overrides
-            !overrides
-                .iter()
-                .any(|(override_key, _)| key == override_key)
-        })
-        .chain(
-            overrides
-                .iter()
-                .map(|(key, value)| (key.as_str(), value.as_str())),
-        )
+    #[test]
+    fn encode_frames_with_b_frames() {
+        let mut opts = options(BitrateMode::default(), None);
+        opts.b_frames = Some(2);
+        let resolution = opts.resolution;
+        let (chunks_sender, chunks_receiver) = crossbeam_channel::unbounded();
+        let encoder =
+            LibavH264Encoder::new(&OutputId("output_1".into()), opts, chunks_sender).unwrap();
+
+        // The stream starts at 0, so libx264 returns negative DTS for the first frames.
+        for i in 0..20 {
+            encoder
+                .frame_sender()
+                .send(PipelineEvent::Data(frame(i, resolution)))
+                .unwrap();
+        }
+        encoder.frame_sender().send(PipelineEvent::EOS).unwrap();
+
+        let events: Vec<_> = chunks_receiver.iter().collect();
+        assert!(matches!(events.last(), Some(EncoderOutputEvent::VideoEOS)));
+        let chunks: Vec<_> = events
+            .into_iter()
+            .filter_map(|event| match event {
+                EncoderOutputEvent::Data(chunk) => Some(chunk),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(chunks.len(), 20);
+        assert_eq!(chunks[0].is_keyframe, IsKeyframe::Yes);
+        assert!(chunks
+            .iter()
+            .all(|chunk| chunk.kind == EncodedChunkKind::Video(VideoCodec::H264)));
+
+        let dts: Vec<_> = chunks.iter().map(|chunk| chunk.dts.unwrap()).collect();
+        assert!(dts.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(chunks.iter().all(|chunk| chunk.dts.unwrap() <= chunk.pts));
+    }
 }
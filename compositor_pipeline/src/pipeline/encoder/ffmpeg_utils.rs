@@ -0,0 +1,268 @@
+use compositor_render::{Frame, OutputId, Resolution};
+use crossbeam_channel::{Receiver, Sender};
+use ffmpeg_next::{format::Pixel, frame, picture, Packet};
+use tracing::{debug, error, trace, warn, Span};
+
+use crate::{
+    error::EncoderInitError,
+    pipeline::{
+        encoder::KeyframeRequest,
+        structs::{EncodedChunk, EncodedChunkKind, EncoderOutputEvent, VideoCodec},
+    },
+    queue::PipelineEvent,
+};
+
+/// Spawns an encoder thread and waits until the encoder is initialized.
+///
+/// `run_encoder_thread` has to send `Ok(())` on the result channel once the encoder
+/// is opened. An error returned before that is passed to the caller.
+pub(super) fn spawn_encoder_thread<F>(
+    output_id: &OutputId,
+    span: Span,
+    run_encoder_thread: F,
+) -> Result<Sender<PipelineEvent<Frame>>, EncoderInitError>
+where
+    F: FnOnce(
+            Receiver<PipelineEvent<Frame>>,
+            &Sender<Result<(), EncoderInitError>>,
+        ) -> Result<(), EncoderInitError>
+        + Send
+        + 'static,
+{
+    let (frame_sender, frame_receiver) = crossbeam_channel::bounded(5);
+    let (result_sender, result_receiver) = crossbeam_channel::bounded(0);
+
+    std::thread::Builder::new()
+        .name(format!("Encoder thread for output {}", output_id))
+        .spawn(move || {
+            let _span = span.entered();
+            let encoder_result = run_encoder_thread(frame_receiver, &result_sender);
+
+            if let Err(err) = encoder_result {
+                warn!(%err, "Encoder thread finished with an error.");
+                if let Err(err) = result_sender.send(Err(err)) {
+                    warn!(%err, "Failed to send error info. Result channel already closed.");
+                }
+            }
+            debug!("Encoder thread finished.");
+        })
+        .unwrap();
+
+    result_receiver.recv().unwrap()?;
+
+    Ok(frame_sender)
+}
+
+/// Encodes frames until EOS is received or the channel is closed. Frames buffered
+/// inside the encoder are flushed before sending EOS.
+pub(super) fn encode_frames(
+    mut encoder: ffmpeg_next::encoder::video::Encoder,
+    resolution: Resolution,
+    codec: VideoCodec,
+    keyframe_request: &KeyframeRequest,
+    frame_receiver: Receiver<PipelineEvent<Frame>>,
+    packet_sender: Sender<EncoderOutputEvent>,
+) {
+    let mut packet = Packet::empty();
+    let mut timestamp_shift = None;
+
+    loop {
+        let frame = match frame_receiver.recv() {
+            Ok(PipelineEvent::Data(f)) => f,
+            Ok(PipelineEvent::EOS) => break,
+            Err(_) => break,
+        };
+
+        let mut av_frame = frame::Video::new(
+            Pixel::YUV420P,
+            resolution.width as u32,
+            resolution.height as u32,
+        );
+
+        if let Err(e) = frame_into_av(frame, &mut av_frame) {
+            error!(
+                "Failed to convert a frame to an ffmpeg frame: {}. Dropping",
+                e.0
+            );
+            continue;
+        }
+
+        if keyframe_request.take() {
+            debug!("Forcing a keyframe.");
+            // libx264, libvpx and libaom encode frames marked as I-frames as keyframes.
+            av_frame.set_kind(picture::Type::I);
+        }
+
+        if let Err(e) = encoder.send_frame(&av_frame) {
+            error!("Encoder error: {e}.");
+            continue;
+        }
+
+        if send_encoded_packets(
+            &mut encoder,
+            &mut packet,
+            codec,
+            &mut timestamp_shift,
+            &packet_sender,
+        )
+        .is_err()
+        {
+            return;
+        }
+    }
+
+    // Flush frames buffered inside the encoder, otherwise the end of the stream would be lost.
+    match encoder.send_eof() {
+        Ok(()) => {
+            if send_encoded_packets(
+                &mut encoder,
+                &mut packet,
+                codec,
+                &mut timestamp_shift,
+                &packet_sender,
+            )
+            .is_err()
+            {
+                return;
+            }
+        }
+        Err(e) => error!("Failed to flush the encoder: {e}."),
+    }
+
+    if let Err(_err) = packet_sender.send(EncoderOutputEvent::VideoEOS) {
+        warn!("Failed to send EOS from {codec:?} encoder. Channel closed.")
+    }
+}
+
+pub(super) struct ChannelClosed;
+
+/// Receives all packets that are ready in the encoder and sends them as encoded chunks.
+///
+/// With B-frames, libx264 returns negative DTS for the first frames of a stream that
+/// starts at 0. `timestamp_shift` is picked from the first packet and added to all
+/// timestamps of the stream, so they stay non-negative and DTS is never higher than PTS.
+pub(super) fn send_encoded_packets(
+    encoder: &mut ffmpeg_next::encoder::video::Encoder,
+    packet: &mut Packet,
+    codec: VideoCodec,
+    timestamp_shift: &mut Option<i64>,
+    packet_sender: &Sender<EncoderOutputEvent>,
+) -> Result<(), ChannelClosed> {
+    loop {
+        match encoder.receive_packet(packet) {
+            Ok(_) => {
+                let shift = *timestamp_shift.get_or_insert_with(|| {
+                    let shift = packet.dts().map_or(0, |dts| -dts.min(0));
+                    if shift > 0 {
+                        debug!(shift, "Shifting encoded timestamps to avoid negative DTS.");
+                    }
+                    shift
+                });
+                if shift > 0 {
+                    packet.set_pts(packet.pts().map(|pts| pts + shift));
+                    packet.set_dts(packet.dts().map(|dts| dts + shift));
+                }
+                match EncodedChunk::from_av_packet(
+                    packet,
+                    EncodedChunkKind::Video(codec),
+                    1_000_000,
+                ) {
+                    Ok(chunk) => {
+                        trace!(pts=?packet.pts(), "{codec:?} encoder produced an encoded packet.");
+                        if packet_sender.send(EncoderOutputEvent::Data(chunk)).is_err() {
+                            warn!(
+                                "Failed to send encoded video from {codec:?} encoder. Channel closed."
+                            );
+                            return Err(ChannelClosed);
+                        }
+                    }
+                    Err(e) => {
+                        warn!("failed to parse an ffmpeg packet received from encoder: {e}",);
+                        return Ok(());
+                    }
+                }
+            }
+
+            Err(ffmpeg_next::Error::Other {
+                errno: ffmpeg_next::error::EAGAIN,
+            }) => return Ok(()), // encoder needs more frames to produce a packet
+
+            Err(ffmpeg_next::Error::Eof) => return Ok(()), // encoder was flushed
+
+            Err(e) => {
+                error!("Encoder error: {e}.");
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(super) struct FrameConversionError(pub String);
+
+pub(super) fn frame_into_av(
+    frame: Frame,
+    av_frame: &mut frame::Video,
+) -> Result<(), FrameConversionError> {
+    let expected_y_plane_size = (av_frame.plane_width(0) * av_frame.plane_height(0)) as usize;
+    let expected_u_plane_size = (av_frame.plane_width(1) * av_frame.plane_height(1)) as usize;
+    let expected_v_plane_size = (av_frame.plane_width(2) * av_frame.plane_height(2)) as usize;
+    if expected_y_plane_size != frame.data.y_plane.len() {
+        return Err(FrameConversionError(format!(
+            "Y plane is a wrong size, expected: {} received: {}",
+            expected_y_plane_size,
+            frame.data.y_plane.len()
+        )));
+    }
+    if expected_u_plane_size != frame.data.u_plane.len() {
+        return Err(FrameConversionError(format!(
+            "U plane is a wrong size, expected: {} received: {}",
+            expected_u_plane_size,
+            frame.data.u_plane.len()
+        )));
+    }
+    if expected_v_plane_size != frame.data.v_plane.len() {
+        return Err(FrameConversionError(format!(
+            "V plane is a wrong size, expected: {} received: {}",
+            expected_v_plane_size,
+            frame.data.v_plane.len()
+        )));
+    }
+
+    av_frame.set_pts(Some(frame.pts.as_micros() as i64));
+
+    write_plane_to_av(av_frame, 0, &frame.data.y_plane);
+    write_plane_to_av(av_frame, 1, &frame.data.u_plane);
+    write_plane_to_av(av_frame, 2, &frame.data.v_plane);
+
+    Ok(())
+}
+
+fn write_plane_to_av(frame: &mut frame::Video, plane: usize, data: &[u8]) {
+    let stride = frame.stride(plane);
+    let width = frame.plane_width(plane) as usize;
+
+    data.chunks(width)
+        .zip(frame.data_mut(plane).chunks_mut(stride))
+        .for_each(|(data, target)| target[..width].copy_from_slice(data));
+}
+
+pub(super) fn merge_options_with_defaults<'a>(
+    defaults: &'a [(&str, &str)],
+    overrides: &'a [(String, String)],
+) -> impl Iterator<Item = (&'a str, &'a str)> {
+    defaults
+        .iter()
+        .copied()
+        .filter(|(key, _value)| {
+            // filter out any defaults that are in overrides
+            !overrides
+                .iter()
+                .any(|(override_key, _)| key == override_key)
+        })
+        .chain(
+            overrides
+                .iter()
+                .map(|(key, value)| (key.as_str(), value.as_str())),
+        )
+}
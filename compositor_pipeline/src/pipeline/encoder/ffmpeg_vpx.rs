@@ -0,0 +1,356 @@
+use compositor_render::{Frame, OutputId, Resolution};
+use crossbeam_channel::{Receiver, Sender};
+use ffmpeg_next::{codec::Context, format::Pixel, Dictionary, Rational};
+use tracing::{span, Level};
+
+use crate::{
+    error::EncoderInitError,
+    pipeline::{
+        encoder::KeyframeRequest,
+        structs::{EncoderOutputEvent, VideoCodec},
+    },
+    queue::PipelineEvent,
+};
+
+use super::ffmpeg_utils::{encode_frames, merge_options_with_defaults, spawn_encoder_thread};
+
+/// Trade-off between encoding speed and quality. See libvpx
+/// [docs](https://trac.ffmpeg.org/wiki/Encode/VP9) to learn more.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Deadline {
+    Realtime,
+    Good,
+    Best,
+}
+
+impl Deadline {
+    fn to_str(self) -> &'static str {
+        match self {
+            Deadline::Realtime => "realtime",
+            Deadline::Good => "good",
+            Deadline::Best => "best",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Options {
+    pub resolution: Resolution,
+    /// Target bitrate in bits per second. If not specified, the encoder
+    /// runs in a constant quality mode.
+    pub bitrate: Option<u64>,
+    pub deadline: Deadline,
+    /// Maximum number of frames between keyframes.
+    pub keyframe_interval: u32,
+    pub raw_options: Vec<(String, String)>,
+}
+
+/// VP8 or VP9 encoder based on libvpx.
+pub struct LibvpxEncoder {
+    codec: VideoCodec,
+    resolution: Resolution,
+    frame_sender: Sender<PipelineEvent<Frame>>,
+    keyframe_request: KeyframeRequest,
+}
+
+impl LibvpxEncoder {
+    pub fn new(
+        output_id: &OutputId,
+        codec: VideoCodec,
+        options: Options,
+        chunks_sender: Sender<EncoderOutputEvent>,
+    ) -> Result<Self, EncoderInitError> {
+        let options_clone = options.clone();
+        let keyframe_request = KeyframeRequest::default();
+        let keyframe_request_clone = keyframe_request.clone();
+        let frame_sender = spawn_encoder_thread(
+            output_id,
+            span!(
+                Level::INFO,
+                "libvpx ffmpeg encoder",
+                codec = ?codec,
+                output_id = output_id.to_string()
+            ),
+            move |frame_receiver, result_sender| {
+                run_encoder_thread(
+                    codec,
+                    options_clone,
+                    keyframe_request_clone,
+                    frame_receiver,
+                    chunks_sender,
+                    result_sender,
+                )
+            },
+        )?;
+
+        Ok(Self {
+            keyframe_request,
+            codec,
+            frame_sender,
+            resolution: options.resolution,
+        })
+    }
+
+    pub fn frame_sender(&self) -> &Sender<PipelineEvent<Frame>> {
+        &self.frame_sender
+    }
+
+    pub fn keyframe_request(&self) -> KeyframeRequest {
+        self.keyframe_request.clone()
+    }
+
+    pub fn resolution(&self) -> Resolution {
+        self.resolution
+    }
+
+    pub fn codec(&self) -> VideoCodec {
+        self.codec
+    }
+}
+
+fn run_encoder_thread(
+    codec: VideoCodec,
+    options: Options,
+    keyframe_request: KeyframeRequest,
+    frame_receiver: Receiver<PipelineEvent<Frame>>,
+    packet_sender: Sender<EncoderOutputEvent>,
+    result_sender: &Sender<Result<(), EncoderInitError>>,
+) -> Result<(), EncoderInitError> {
+    let encoder_name = match codec {
+        VideoCodec::Vp8 => "libvpx",
+        VideoCodec::Vp9 => "libvpx-vp9",
+        VideoCodec::H264 | VideoCodec::Av1 => return Err(EncoderInitError::NoCodec),
+    };
+    let ffmpeg_codec =
+        ffmpeg_next::codec::encoder::find_by_name(encoder_name).ok_or(EncoderInitError::NoCodec)?;
+
+    let mut encoder = Context::new().encoder().video()?;
+
+    // We set this to 1 / 1_000_000, bc we use `as_micros` to convert frames to AV packets.
+    let pts_unit_secs = Rational::new(1, 1_000_000);
+    encoder.set_time_base(pts_unit_secs);
+    encoder.set_format(Pixel::YUV420P);
+    encoder.set_width(options.resolution.width as u32);
+    encoder.set_height(options.resolution.height as u32);
+
+    let defaults = default_options(codec, &options);
+    let defaults: Vec<_> = defaults
+        .iter()
+        .map(|(key, value)| (*key, value.as_str()))
+        .collect();
+
+    let encoder_opts_iter = merge_options_with_defaults(&defaults, &options.raw_options);
+    let encoder = encoder.open_as_with(ffmpeg_codec, Dictionary::from_iter(encoder_opts_iter))?;
+
+    result_sender.send(Ok(())).unwrap();
+
+    encode_frames(
+        encoder,
+        options.resolution,
+        codec,
+        &keyframe_request,
+        frame_receiver,
+        packet_sender,
+    );
+    Ok(())
+}
+
+/// Encoder options used unless they are overridden by `raw_options`.
+fn default_options(codec: VideoCodec, options: &Options) -> Vec<(&'static str, String)> {
+    let mut defaults = vec![
+        ("deadline", options.deadline.to_str().to_string()),
+        ("g", options.keyframe_interval.to_string()),
+    ];
+    match (options.bitrate, codec) {
+        (Some(bitrate), _) => defaults.push(("b", bitrate.to_string())),
+        // VP9 constant quality mode requires bitrate to be set to 0
+        (None, VideoCodec::Vp9) => defaults.extend([("crf", "31".into()), ("b", "0".into())]),
+        // VP8 does not support the constant quality mode, bitrate is used as
+        // an upper bound for the constrained quality mode.
+        (None, _) => defaults.extend([("crf", "10".into()), ("b", "2M".into())]),
+    }
+    if options.deadline == Deadline::Realtime {
+        defaults.extend([
+            // Highest speed that is supported by both VP8 and VP9
+            ("cpu-used", "8".into()),
+            // Disable frame lookahead, it adds latency
+            ("lag-in-frames", "0".into()),
+        ]);
+    }
+    if codec == VideoCodec::Vp9 {
+        // Multi-threaded encoding of rows
+        defaults.push(("row-mt", "1".into()));
+    }
+    defaults
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bytes::Bytes;
+    use compositor_render::{Frame, OutputId, Resolution, YuvData, YuvVariant};
+
+    use crate::{
+        pipeline::structs::{EncodedChunkKind, EncoderOutputEvent, IsKeyframe, VideoCodec},
+        queue::PipelineEvent,
+    };
+
+    use super::{default_options, Deadline, LibvpxEncoder, Options};
+
+    const RESOLUTION: Resolution = Resolution {
+        width: 320,
+        height: 240,
+    };
+
+    fn options(bitrate: Option<u64>, deadline: Deadline) -> Options {
+        Options {
+            resolution: RESOLUTION,
+            bitrate,
+            deadline,
+            keyframe_interval: 30,
+            raw_options: Vec::new(),
+        }
+    }
+
+    fn frame(index: u64) -> Frame {
+        let pixels = (RESOLUTION.width * RESOLUTION.height) as usize;
+        Frame {
+            data: YuvData {
+                variant: YuvVariant::YUV420P,
+                y_plane: Bytes::from(vec![(index * 20) as u8; pixels]),
+                u_plane: Bytes::from(vec![128; pixels / 4]),
+                v_plane: Bytes::from(vec![128; pixels / 4]),
+            },
+            resolution: RESOLUTION,
+            pts: Duration::from_millis(index * 33),
+        }
+    }
+
+    fn owned(options: &[(&'static str, &str)]) -> Vec<(&'static str, String)> {
+        options
+            .iter()
+            .map(|(key, value)| (*key, value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn vp8_default_options() {
+        assert_eq!(
+            default_options(VideoCodec::Vp8, &options(None, Deadline::Good)),
+            owned(&[
+                ("deadline", "good"),
+                ("g", "30"),
+                ("crf", "10"),
+                ("b", "2M")
+            ])
+        );
+        assert_eq!(
+            default_options(
+                VideoCodec::Vp8,
+                &options(Some(1_000_000), Deadline::Realtime)
+            ),
+            owned(&[
+                ("deadline", "realtime"),
+                ("g", "30"),
+                ("b", "1000000"),
+                ("cpu-used", "8"),
+                ("lag-in-frames", "0"),
+            ])
+        );
+    }
+
+    #[test]
+    fn vp9_default_options() {
+        assert_eq!(
+            default_options(VideoCodec::Vp9, &options(None, Deadline::Best)),
+            owned(&[
+                ("deadline", "best"),
+                ("g", "30"),
+                ("crf", "31"),
+                ("b", "0"),
+                ("row-mt", "1"),
+            ])
+        );
+        assert_eq!(
+            default_options(VideoCodec::Vp9, &options(Some(500_000), Deadline::Good)),
+            owned(&[
+                ("deadline", "good"),
+                ("g", "30"),
+                ("b", "500000"),
+                ("row-mt", "1"),
+            ])
+        );
+    }
+
+    #[test]
+    fn encode_frames() {
+        for codec in [VideoCodec::Vp8, VideoCodec::Vp9] {
+            let (chunks_sender, chunks_receiver) = crossbeam_channel::unbounded();
+            let encoder = LibvpxEncoder::new(
+                &OutputId("output_1".into()),
+                codec,
+                options(None, Deadline::Realtime),
+                chunks_sender,
+            )
+            .unwrap();
+
+            for i in 0..10 {
+                encoder
+                    .frame_sender()
+                    .send(PipelineEvent::Data(frame(i)))
+                    .unwrap();
+            }
+            encoder.frame_sender().send(PipelineEvent::EOS).unwrap();
+
+            let events: Vec<_> = chunks_receiver.iter().collect();
+            assert!(matches!(events.last(), Some(EncoderOutputEvent::VideoEOS)));
+            let chunks: Vec<_> = events
+                .into_iter()
+                .filter_map(|event| match event {
+                    EncoderOutputEvent::Data(chunk) => Some(chunk),
+                    _ => None,
+                })
+                .collect();
+            assert_eq!(chunks.len(), 10, "{codec:?}");
+            assert_eq!(chunks[0].is_keyframe, IsKeyframe::Yes, "{codec:?}");
+            assert!(chunks
+                .iter()
+                .all(|chunk| chunk.kind == EncodedChunkKind::Video(codec)));
+        }
+    }
+
+    #[test]
+    fn keyframe_request() {
+        let (chunks_sender, chunks_receiver) = crossbeam_channel::unbounded();
+        let encoder = LibvpxEncoder::new(
+            &OutputId("output_1".into()),
+            VideoCodec::Vp8,
+            options(None, Deadline::Realtime),
+            chunks_sender,
+        )
+        .unwrap();
+        let send_frame = |index| {
+            encoder
+                .frame_sender()
+                .send(PipelineEvent::Data(frame(index)))
+                .unwrap()
+        };
+        let recv_keyframe = || match chunks_receiver.recv_timeout(Duration::from_secs(5)) {
+            Ok(EncoderOutputEvent::Data(chunk)) => chunk.is_keyframe,
+            _ => panic!("Expected an encoded chunk."),
+        };
+
+        // Without frame lookahead, every frame is encoded before the next one is sent.
+        send_frame(0);
+        assert_eq!(recv_keyframe(), IsKeyframe::Yes);
+        send_frame(1);
+        assert_eq!(recv_keyframe(), IsKeyframe::No);
+
+        encoder.keyframe_request().request();
+        send_frame(2);
+        assert_eq!(recv_keyframe(), IsKeyframe::Yes);
+        send_frame(3);
+        assert_eq!(recv_keyframe(), IsKeyframe::No);
+    }
+}
@@ -6,22 +6,25 @@ use crate::{
     audio_mixer::{AudioChannels, AudioSamples, OutputSamples},
     error::EncoderInitError,
     pipeline::{
-        structs::{EncodedChunk, EncodedChunkKind, EncoderOutputEvent},
+        structs::{EncodedChunk, EncodedChunkKind, EncoderOutputEvent, IsKeyframe},
         AudioCodec,
     },
     queue::PipelineEvent,
 };
 
-use super::AudioEncoderPreset;
+use super::{AudioEncoderContext, AudioEncoderPreset};
 
 #[derive(Debug, Clone)]
 pub struct Options {
     pub channels: AudioChannels,
     pub preset: AudioEncoderPreset,
+    pub forward_error_correction: bool,
 }
 
 pub struct OpusEncoder {
     samples_batch_sender: Sender<PipelineEvent<OutputSamples>>,
+    channels: AudioChannels,
+    sample_rate: u32,
 }
 
 impl OpusEncoder {
@@ -45,12 +48,43 @@ impl OpusEncoder {
 
         Ok(Self {
             samples_batch_sender,
+            channels: options.channels,
+            sample_rate,
         })
     }
 
     pub fn samples_batch_sender(&self) -> &Sender<PipelineEvent<OutputSamples>> {
         &self.samples_batch_sender
     }
+
+    pub fn context(&self) -> AudioEncoderContext {
+        AudioEncoderContext {
+            codec: AudioCodec::Opus,
+            channels: self.channels,
+            sample_rate: self.sample_rate,
+            extradata: Some(opus_head(self.channels, self.sample_rate)),
+        }
+    }
+}
+
+/// Identification header as described in [RFC 7845, section 5.1](https://datatracker.ietf.org/doc/html/rfc7845#section-5.1).
+/// Containers (MP4, Matroska, Ogg) use it as a codec specific configuration.
+fn opus_head(channels: AudioChannels, sample_rate: u32) -> bytes::Bytes {
+    let channel_count: u8 = match channels {
+        AudioChannels::Mono => 1,
+        AudioChannels::Stereo => 2,
+    };
+    let mut head = bytes::BytesMut::with_capacity(19);
+    head.extend_from_slice(b"OpusHead");
+    head.extend_from_slice(&[1, channel_count]);
+    // pre-skip
+    head.extend_from_slice(&0u16.to_le_bytes());
+    head.extend_from_slice(&sample_rate.to_le_bytes());
+    // output gain
+    head.extend_from_slice(&0i16.to_le_bytes());
+    // channel mapping family 0 (mono/stereo)
+    head.extend_from_slice(&[0]);
+    head.freeze()
 }
 
 fn run_encoder_thread(
@@ -94,6 +128,7 @@ fn run_encoder_thread(
             data,
             pts: batch.start_pts,
             dts: None,
+            is_keyframe: IsKeyframe::NoKeyframes,
             kind: EncodedChunkKind::Audio(AudioCodec::Opus),
         };
 
@@ -4,18 +4,46 @@ use crate::{error::InputInitError, queue::PipelineEvent};
 
 use compositor_render::InputId;
 use crossbeam_channel::Receiver;
-use rtp::{RtpReceiver, RtpReceiverOptions};
+use rtp::{RtpReceiver, RtpReceiverOptions, SharedUdpSockets};
 
-use self::mp4::{Mp4, Mp4Options};
+use self::{
+    file::{FileInput, FileInputOptions},
+    hls::{HlsInput, HlsInputOptions},
+    mp4::{Mp4, Mp4Options},
+    mpeg_ts::{MpegTsReceiver, MpegTsReceiverOptions},
+    raw::{RawInput, RawInputOptions},
+    rtsp::{RtspReceiver, RtspReceiverOptions},
+    srt::{SrtReceiver, SrtReceiverOptions},
+    whip::{WhipReceiver, WhipReceiverOptions},
+};
 
-use super::{decoder::DecoderOptions, structs::EncodedChunk, Port};
+use super::{
+    decoder::{DecodedDataReceiver, DecoderOptions},
+    structs::EncodedChunk,
+    Port,
+};
 
+pub mod file;
+pub mod hls;
 pub mod mp4;
+pub mod mpeg_ts;
+mod push;
+pub mod raw;
 pub mod rtp;
+pub mod rtsp;
+pub mod srt;
+pub mod whip;
 
 pub enum Input {
     Rtp(RtpReceiver),
     Mp4(Mp4),
+    Whip(WhipReceiver),
+    Srt(SrtReceiver),
+    MpegTs(MpegTsReceiver),
+    Rtsp(RtspReceiver),
+    File(FileInput),
+    Hls(HlsInput),
+    Raw(RawInput),
 }
 
 impl Input {
@@ -23,31 +51,103 @@ impl Input {
         input_id: &InputId,
         options: InputOptions,
         download_dir: &Path,
-    ) -> Result<(Self, ChunksReceiver, DecoderOptions, Option<Port>), InputInitError> {
-        match options {
-            InputOptions::Rtp(opts) => Ok(RtpReceiver::new(input_id, opts).map(
-                |(receiver, chunks_receiver, decoder_options, port)| {
+        shared_udp_sockets: &SharedUdpSockets,
+    ) -> Result<(Self, InputReceiver, Option<Port>), InputInitError> {
+        let (input, chunks_receiver, decoder_options, port) = match options {
+            InputOptions::Rtp(opts) => Ok(RtpReceiver::new(input_id, opts, shared_udp_sockets)
+                .map(|(receiver, chunks_receiver, decoder_options, port)| {
                     (
                         Self::Rtp(receiver),
                         chunks_receiver,
                         decoder_options,
                         Some(port),
                     )
-                },
-            )?),
+                })?),
 
             InputOptions::Mp4(opts) => Ok(Mp4::new(input_id, opts, download_dir).map(
                 |(mp4, chunks_receiver, decoder_options)| {
                     (Self::Mp4(mp4), chunks_receiver, decoder_options, None)
                 },
             )?),
-        }
+
+            InputOptions::Whip(opts) => Ok(WhipReceiver::new(input_id, opts).map(
+                |(receiver, chunks_receiver, decoder_options)| {
+                    (Self::Whip(receiver), chunks_receiver, decoder_options, None)
+                },
+            )?),
+
+            InputOptions::Srt(opts) => Ok(SrtReceiver::new(input_id, opts).map(
+                |(receiver, chunks_receiver, decoder_options)| {
+                    (Self::Srt(receiver), chunks_receiver, decoder_options, None)
+                },
+            )?),
+
+            InputOptions::MpegTs(opts) => Ok(MpegTsReceiver::new(input_id, opts).map(
+                |(receiver, chunks_receiver, decoder_options, port)| {
+                    (
+                        Self::MpegTs(receiver),
+                        chunks_receiver,
+                        decoder_options,
+                        Some(port),
+                    )
+                },
+            )?),
+
+            InputOptions::Rtsp(opts) => Ok(RtspReceiver::new(input_id, opts).map(
+                |(receiver, chunks_receiver, decoder_options)| {
+                    (Self::Rtsp(receiver), chunks_receiver, decoder_options, None)
+                },
+            )?),
+
+            InputOptions::File(opts) => Ok(FileInput::new(input_id, opts).map(
+                |(input, chunks_receiver, decoder_options)| {
+                    (Self::File(input), chunks_receiver, decoder_options, None)
+                },
+            )?),
+
+            InputOptions::Hls(opts) => Ok(HlsInput::new(input_id, opts).map(
+                |(input, chunks_receiver, decoder_options)| {
+                    (Self::Hls(input), chunks_receiver, decoder_options, None)
+                },
+            )?),
+
+            InputOptions::Raw(opts) => {
+                let (input, receiver) = RawInput::new(input_id, opts)?;
+                return Ok((Self::Raw(input), InputReceiver::Decoded(receiver), None));
+            }
+        }?;
+
+        Ok((
+            input,
+            InputReceiver::Encoded {
+                chunks_receiver,
+                decoder_options,
+            },
+            port,
+        ))
     }
 }
 
 pub enum InputOptions {
     Rtp(RtpReceiverOptions),
     Mp4(Mp4Options),
+    Whip(WhipReceiverOptions),
+    Srt(SrtReceiverOptions),
+    MpegTs(MpegTsReceiverOptions),
+    Rtsp(RtspReceiverOptions),
+    File(FileInputOptions),
+    Hls(HlsInputOptions),
+    Raw(RawInputOptions),
+}
+
+/// Data produced by an input. Raw inputs deliver frames that are sent directly to the
+/// queue, other inputs deliver encoded chunks that have to be decoded first.
+pub enum InputReceiver {
+    Encoded {
+        chunks_receiver: ChunksReceiver,
+        decoder_options: DecoderOptions,
+    },
+    Decoded(DecodedDataReceiver),
 }
 
 #[derive(Debug)]
@@ -0,0 +1,762 @@
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use bytes::{BufMut, Bytes, BytesMut};
+use compositor_render::InputId;
+use crossbeam_channel::{bounded, Sender};
+use ffmpeg_next::{self as ffmpeg, codec::Id, media::Type, Rational};
+use tracing::{debug, info, span, trace, warn, Level};
+
+use crate::{
+    pipeline::{
+        decoder::{
+            AacDecoderOptions, AudioDecoderOptions, DecoderOptions, OpusDecoderOptions,
+            VideoDecoderOptions,
+        },
+        structs::{EncodedChunk, EncodedChunkKind, IsKeyframe},
+        AudioCodec, VideoCodec,
+    },
+    queue::PipelineEvent,
+};
+
+use super::ChunksReceiver;
+
+#[derive(Debug, thiserror::Error)]
+pub enum FileInputError {
+    #[error("Failed to open file \"{path}\".")]
+    Open {
+        path: PathBuf,
+        #[source]
+        source: ffmpeg::Error,
+    },
+
+    #[error("Input does not contain a {0} track matching the selection.")]
+    TrackNotFound(&'static str),
+
+    #[error("{kind} track {index} uses codec {codec:?}, which is not supported. Supported codecs are H264, VP8, VP9 and AV1 for video and AAC and Opus for audio.")]
+    UnsupportedCodec {
+        kind: &'static str,
+        index: usize,
+        codec: Id,
+    },
+
+    #[error("H264 track has invalid decoder configuration (avcC).")]
+    InvalidH264Config,
+
+    #[error("Input does not contain any video or audio track.")]
+    NoTracks,
+}
+
+/// Selects which track of a given type is read from the file.
+#[derive(Debug, Clone)]
+pub enum FileTrack {
+    /// Track of this type is not read.
+    Disabled,
+    /// Default track of this type (as chosen by ffmpeg), if the file contains one.
+    Default,
+    /// Track matching all the provided criteria. Registration fails if there is no such track.
+    Select {
+        /// Index among tracks of this type (and language, if specified).
+        index: Option<usize>,
+        /// Language from the track metadata, e.g. `"eng"`.
+        language: Option<Arc<str>>,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct FileInputOptions {
+    pub path: PathBuf,
+    pub video: FileTrack,
+    pub audio: FileTrack,
+}
+
+/// Input that reads a media file in any container supported by ffmpeg (e.g. MKV, MOV,
+/// MPEG-TS or WebM).
+///
+/// Tracks are selected and validated when the input is registered. Packets are demuxed
+/// into encoded chunks, so only codecs supported by the decoders can be used. H264 stored
+/// in the AVCC format (MKV, MOV) is converted to Annex B.
+pub struct FileInput {
+    stop_thread: Arc<AtomicBool>,
+}
+
+impl FileInput {
+    pub fn new(
+        input_id: &InputId,
+        opts: FileInputOptions,
+    ) -> Result<(Self, ChunksReceiver, DecoderOptions), FileInputError> {
+        let input_ctx =
+            ffmpeg::format::input(&opts.path).map_err(|source| FileInputError::Open {
+                path: opts.path.clone(),
+                source,
+            })?;
+
+        let (stop_thread, chunks_receiver, decoder_options) =
+            start_demuxer(input_id, "File", input_ctx, &opts.video, &opts.audio)?;
+        Ok((Self { stop_thread }, chunks_receiver, decoder_options))
+    }
+}
+
+impl Drop for FileInput {
+    fn drop(&mut self) {
+        self.stop_thread.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Selects tracks of an opened ffmpeg input and starts a thread that demuxes them into
+/// encoded chunks. Other tracks are discarded, so ffmpeg does not read them (e.g. HLS
+/// variants that are not used are not downloaded). Returns a flag that stops the thread.
+pub(super) fn start_demuxer(
+    input_id: &InputId,
+    source_name: &'static str,
+    mut input_ctx: ffmpeg::format::context::Input,
+    video: &FileTrack,
+    audio: &FileTrack,
+) -> Result<(Arc<AtomicBool>, ChunksReceiver, DecoderOptions), FileInputError> {
+    let video_stream = select_stream(&input_ctx, Type::Video, video)?
+        .map(|stream| StreamInfo::video(&stream))
+        .transpose()?;
+    let audio_stream = select_stream(&input_ctx, Type::Audio, audio)?
+        .map(|stream| StreamInfo::audio(&stream))
+        .transpose()?;
+    if video_stream.is_none() && audio_stream.is_none() {
+        return Err(FileInputError::NoTracks);
+    }
+
+    discard_other_streams(
+        &mut input_ctx,
+        [video_stream.as_ref(), audio_stream.as_ref()],
+    );
+
+    let decoder_options = DecoderOptions {
+        video: video_stream.as_ref().map(StreamInfo::video_decoder_options),
+        audio: audio_stream.as_ref().map(StreamInfo::audio_decoder_options),
+    };
+
+    let (demuxer, chunks_receiver) = Demuxer::new(video_stream.is_some(), audio_stream.is_some());
+    let stop_thread = demuxer.stop_thread.clone();
+    spawn_demuxer_thread(input_id, source_name, move || {
+        demuxer.run(input_ctx, video_stream, audio_stream)
+    });
+
+    Ok((stop_thread, chunks_receiver, decoder_options))
+}
+
+/// Starts a thread that demuxes a live stream (e.g. SRT or MPEG-TS over UDP) into encoded
+/// chunks of the declared codecs. `connect` is called until it returns an input and again
+/// after the connection is lost, timestamps continue from where the previous connection
+/// ended. EOS is never sent. Returns a flag that stops the thread.
+pub(super) fn start_live_demuxer(
+    input_id: &InputId,
+    source_name: &'static str,
+    connect: impl FnMut() -> Option<ffmpeg::format::context::Input> + Send + 'static,
+    video: Option<VideoCodec>,
+    audio: Option<AudioCodec>,
+) -> (Arc<AtomicBool>, ChunksReceiver) {
+    let (demuxer, chunks_receiver) = Demuxer::new(video.is_some(), audio.is_some());
+    let stop_thread = demuxer.stop_thread.clone();
+    spawn_demuxer_thread(input_id, source_name, move || {
+        demuxer.run_live(connect, video, audio)
+    });
+
+    (stop_thread, chunks_receiver)
+}
+
+fn spawn_demuxer_thread(
+    input_id: &InputId,
+    source_name: &'static str,
+    run: impl FnOnce() + Send + 'static,
+) {
+    let input_id = input_id.clone();
+    std::thread::Builder::new()
+        .name(format!("{source_name} demuxer for input {input_id}"))
+        .spawn(move || {
+            let _span = span!(
+                Level::INFO,
+                "ffmpeg demuxer",
+                input_id = input_id.to_string(),
+                source = source_name
+            )
+            .entered();
+            run();
+            debug!("Closing demuxer thread.");
+        })
+        .unwrap();
+}
+
+/// Tells ffmpeg to skip streams that are not selected, so they are not read at all.
+fn discard_other_streams(
+    input_ctx: &mut ffmpeg::format::context::Input,
+    selected: [Option<&StreamInfo>; 2],
+) {
+    for index in 0..input_ctx.nb_streams() as usize {
+        if selected
+            .iter()
+            .flatten()
+            .any(|stream| stream.index == index)
+        {
+            continue;
+        }
+        if let Some(mut stream) = input_ctx.stream_mut(index) {
+            unsafe {
+                (*stream.as_mut_ptr()).discard = ffmpeg::ffi::AVDiscard::AVDISCARD_ALL;
+            }
+        }
+    }
+}
+
+/// Finds the default stream of a given type in a live stream. Returns `None` if there is
+/// no such stream or it uses a different codec than the one declared for the input.
+fn find_declared_stream(
+    input_ctx: &ffmpeg::format::context::Input,
+    kind: EncodedChunkKind,
+) -> Option<StreamInfo> {
+    let media_type = match kind {
+        EncodedChunkKind::Video(_) => Type::Video,
+        EncodedChunkKind::Audio(_) => Type::Audio,
+    };
+    let Some(stream) = input_ctx.streams().best(media_type) else {
+        warn!(?media_type, "Stream does not contain a track of this type.");
+        return None;
+    };
+    let stream_info = match kind {
+        EncodedChunkKind::Video(_) => StreamInfo::video(&stream),
+        EncodedChunkKind::Audio(_) => StreamInfo::audio(&stream),
+    };
+    match stream_info {
+        Ok(stream_info) if stream_info.kind == kind => Some(stream_info),
+        _ => {
+            warn!(
+                expected = ?kind,
+                actual = ?stream.parameters().id(),
+                "Track uses a different codec than the one declared for the input. The track is ignored."
+            );
+            None
+        }
+    }
+}
+
+fn select_stream<'a>(
+    input_ctx: &'a ffmpeg::format::context::Input,
+    media_type: Type,
+    track: &FileTrack,
+) -> Result<Option<ffmpeg::format::stream::Stream<'a>>, FileInputError> {
+    match track {
+        FileTrack::Disabled => Ok(None),
+        FileTrack::Default => Ok(input_ctx.streams().best(media_type)),
+        FileTrack::Select { index, language } => {
+            let stream = input_ctx
+                .streams()
+                .filter(|stream| stream.parameters().medium() == media_type)
+                .filter(|stream| {
+                    language.as_ref().map_or(true, |language| {
+                        stream.metadata().get("language") == Some(language.as_ref())
+                    })
+                })
+                .nth(index.unwrap_or(0));
+            match stream {
+                Some(stream) => Ok(Some(stream)),
+                None => Err(FileInputError::TrackNotFound(media_type_name(media_type))),
+            }
+        }
+    }
+}
+
+fn media_type_name(media_type: Type) -> &'static str {
+    match media_type {
+        Type::Video => "video",
+        Type::Audio => "audio",
+        _ => "other",
+    }
+}
+
+struct StreamInfo {
+    index: usize,
+    time_base: Rational,
+    kind: EncodedChunkKind,
+    extradata: Option<Bytes>,
+    /// Set for H264 tracks stored in the AVCC format.
+    avcc: Option<AvccConfig>,
+}
+
+impl StreamInfo {
+    fn video(stream: &ffmpeg::format::stream::Stream) -> Result<Self, FileInputError> {
+        let codec = match stream.parameters().id() {
+            Id::H264 => VideoCodec::H264,
+            Id::VP8 => VideoCodec::Vp8,
+            Id::VP9 => VideoCodec::Vp9,
+            Id::AV1 => VideoCodec::Av1,
+            codec => {
+                return Err(FileInputError::UnsupportedCodec {
+                    kind: "Video",
+                    index: stream.index(),
+                    codec,
+                })
+            }
+        };
+        let extradata = extradata(stream);
+        let avcc = match (codec, &extradata) {
+            (VideoCodec::H264, Some(extradata)) if extradata[0] == 1 => {
+                Some(AvccConfig::parse(extradata).ok_or(FileInputError::InvalidH264Config)?)
+            }
+            _ => None,
+        };
+        Ok(Self {
+            index: stream.index(),
+            time_base: stream.time_base(),
+            kind: EncodedChunkKind::Video(codec),
+            extradata,
+            avcc,
+        })
+    }
+
+    fn audio(stream: &ffmpeg::format::stream::Stream) -> Result<Self, FileInputError> {
+        let codec = match stream.parameters().id() {
+            Id::AAC => AudioCodec::Aac,
+            Id::OPUS => AudioCodec::Opus,
+            codec => {
+                return Err(FileInputError::UnsupportedCodec {
+                    kind: "Audio",
+                    index: stream.index(),
+                    codec,
+                })
+            }
+        };
+        let extradata = match codec {
+            AudioCodec::Aac => extradata(stream).or_else(|| aac_config_from_parameters(stream)),
+            AudioCodec::Opus => extradata(stream),
+        };
+        Ok(Self {
+            index: stream.index(),
+            time_base: stream.time_base(),
+            kind: EncodedChunkKind::Audio(codec),
+            extradata,
+            avcc: None,
+        })
+    }
+
+    fn video_decoder_options(&self) -> VideoDecoderOptions {
+        let EncodedChunkKind::Video(codec) = self.kind else {
+            unreachable!("Not a video stream.");
+        };
+        VideoDecoderOptions { codec }
+    }
+
+    fn audio_decoder_options(&self) -> AudioDecoderOptions {
+        match self.kind {
+            EncodedChunkKind::Audio(AudioCodec::Opus) => {
+                AudioDecoderOptions::Opus(OpusDecoderOptions {
+                    forward_error_correction: false,
+                })
+            }
+            // Frames from MPEG-TS are in the ADTS format, the config for them is built
+            // from the stream parameters.
+            EncodedChunkKind::Audio(AudioCodec::Aac) => {
+                AudioDecoderOptions::Aac(AacDecoderOptions {
+                    depayloader_mode: None,
+                    asc: self.extradata.clone(),
+                })
+            }
+            EncodedChunkKind::Video(_) => unreachable!("Not an audio stream."),
+        }
+    }
+}
+
+/// Builds an AudioSpecificConfig for AAC streams that do not carry it in extradata,
+/// e.g. ADTS in MPEG-TS.
+fn aac_config_from_parameters(stream: &ffmpeg::format::stream::Stream) -> Option<Bytes> {
+    const SAMPLE_RATES: [i32; 13] = [
+        96000, 88200, 64000, 48000, 44100, 32000, 24000, 22050, 16000, 12000, 11025, 8000, 7350,
+    ];
+    let parameters = stream.parameters();
+    let (profile, sample_rate, channels) = unsafe {
+        let codecpar = &*parameters.as_ptr();
+        (
+            codecpar.profile,
+            codecpar.sample_rate,
+            codecpar.ch_layout.nb_channels,
+        )
+    };
+
+    let frequency_index = SAMPLE_RATES.iter().position(|rate| *rate == sample_rate)? as u8;
+    let channel_config = u8::try_from(channels)
+        .ok()
+        .filter(|channels| (1..=6).contains(channels))?;
+    // AAC profiles are numbered from 0 (Main), the object types from 1. HE-AAC is
+    // signaled implicitly, so its core (LC) object type is used.
+    let object_type = match profile {
+        0..=3 => profile as u8 + 1,
+        _ => 2,
+    };
+    Some(Bytes::copy_from_slice(&[
+        (object_type << 3) | (frequency_index >> 1),
+        ((frequency_index & 0x01) << 7) | (channel_config << 3),
+    ]))
+}
+
+fn extradata(stream: &ffmpeg::format::stream::Stream) -> Option<Bytes> {
+    let parameters = stream.parameters();
+    unsafe {
+        let codecpar = &*parameters.as_ptr();
+        if codecpar.extradata.is_null() || codecpar.extradata_size <= 0 {
+            return None;
+        }
+        Some(Bytes::copy_from_slice(std::slice::from_raw_parts(
+            codecpar.extradata,
+            codecpar.extradata_size as usize,
+        )))
+    }
+}
+
+struct Demuxer {
+    video_sender: Option<Sender<PipelineEvent<EncodedChunk>>>,
+    audio_sender: Option<Sender<PipelineEvent<EncodedChunk>>>,
+    stop_thread: Arc<AtomicBool>,
+    timestamps: ContinuousTimestamps,
+}
+
+enum DemuxEnd {
+    /// End of the input or a read error.
+    InputEnded,
+    /// Input was unregistered.
+    Stopped,
+}
+
+impl Demuxer {
+    fn new(video: bool, audio: bool) -> (Self, ChunksReceiver) {
+        let (video_sender, video_receiver) = video
+            .then(|| bounded(10))
+            .map_or((None, None), |(tx, rx)| (Some(tx), Some(rx)));
+        let (audio_sender, audio_receiver) = audio
+            .then(|| bounded(10))
+            .map_or((None, None), |(tx, rx)| (Some(tx), Some(rx)));
+        let demuxer = Self {
+            video_sender,
+            audio_sender,
+            stop_thread: Arc::new(AtomicBool::new(false)),
+            timestamps: ContinuousTimestamps::default(),
+        };
+        let chunks_receiver = ChunksReceiver {
+            video: video_receiver,
+            audio: audio_receiver,
+        };
+        (demuxer, chunks_receiver)
+    }
+
+    fn run(
+        mut self,
+        mut input_ctx: ffmpeg::format::context::Input,
+        video: Option<StreamInfo>,
+        audio: Option<StreamInfo>,
+    ) {
+        if let DemuxEnd::Stopped = self.demux(&mut input_ctx, video.as_ref(), audio.as_ref()) {
+            return;
+        }
+
+        for sender in self.video_sender.iter().chain(self.audio_sender.iter()) {
+            if sender.send(PipelineEvent::EOS).is_err() {
+                debug!("Failed to send EOS from demuxer. Channel closed.");
+            }
+        }
+    }
+
+    fn run_live(
+        mut self,
+        mut connect: impl FnMut() -> Option<ffmpeg::format::context::Input>,
+        video: Option<VideoCodec>,
+        audio: Option<AudioCodec>,
+    ) {
+        while !self.stop_thread.load(Ordering::Relaxed) {
+            let Some(mut input_ctx) = connect() else {
+                continue;
+            };
+            info!("Connection established.");
+
+            let video_stream = video
+                .and_then(|codec| find_declared_stream(&input_ctx, EncodedChunkKind::Video(codec)));
+            let audio_stream = audio
+                .and_then(|codec| find_declared_stream(&input_ctx, EncodedChunkKind::Audio(codec)));
+            discard_other_streams(
+                &mut input_ctx,
+                [video_stream.as_ref(), audio_stream.as_ref()],
+            );
+
+            self.timestamps.start_new_connection();
+            match self.demux(&mut input_ctx, video_stream.as_ref(), audio_stream.as_ref()) {
+                DemuxEnd::InputEnded => info!("Connection ended. Waiting for a new one."),
+                DemuxEnd::Stopped => return,
+            }
+        }
+    }
+
+    fn demux(
+        &mut self,
+        input_ctx: &mut ffmpeg::format::context::Input,
+        video: Option<&StreamInfo>,
+        audio: Option<&StreamInfo>,
+    ) -> DemuxEnd {
+        loop {
+            if self.stop_thread.load(Ordering::Relaxed) {
+                return DemuxEnd::Stopped;
+            }
+
+            let mut packet = ffmpeg::Packet::empty();
+            match packet.read(input_ctx) {
+                Ok(()) => {}
+                Err(ffmpeg::Error::Eof) => return DemuxEnd::InputEnded,
+                Err(err) => {
+                    warn!("Failed to read the input: {err}.");
+                    return DemuxEnd::InputEnded;
+                }
+            }
+
+            let (stream, sender) = match (video, audio) {
+                (Some(video), _) if video.index == packet.stream() => {
+                    (video, self.video_sender.as_ref())
+                }
+                (_, Some(audio)) if audio.index == packet.stream() => {
+                    (audio, self.audio_sender.as_ref())
+                }
+                _ => continue,
+            };
+            let (Some(sender), Some(data), Some(pts)) =
+                (sender, packet.data(), packet.pts().or(packet.dts()))
+            else {
+                continue;
+            };
+
+            let is_keyframe = packet.is_key();
+            let data = match &stream.avcc {
+                Some(avcc) => match avcc.to_annex_b(data, is_keyframe) {
+                    Some(data) => data,
+                    None => {
+                        warn!("Skipping malformed H264 packet.");
+                        continue;
+                    }
+                },
+                None => Bytes::copy_from_slice(data),
+            };
+
+            let to_seconds = |timestamp: i64| timestamp as f64 * f64::from(stream.time_base);
+            let (pts, dts) =
+                self.timestamps
+                    .map(stream.kind, to_seconds(pts), packet.dts().map(to_seconds));
+            let chunk = EncodedChunk {
+                data,
+                pts,
+                dts,
+                is_keyframe: match is_keyframe {
+                    true => IsKeyframe::Yes,
+                    false => IsKeyframe::No,
+                },
+                kind: stream.kind,
+            };
+
+            trace!(pts=?chunk.pts, kind=?chunk.kind, "Demuxed chunk.");
+            if sender.send(PipelineEvent::Data(chunk)).is_err() {
+                debug!("Failed to send demuxed chunk. Channel closed.");
+                return DemuxEnd::Stopped;
+            }
+        }
+    }
+}
+
+/// Timestamps of consecutive packets of a track that differ by more than this are treated
+/// as a discontinuity.
+const MAX_TIMESTAMP_JUMP: f64 = 5.0;
+
+/// Maps timestamps of all tracks onto a single timeline that starts at zero. Jumps in the
+/// source timestamps (e.g. HLS discontinuities or MPEG-TS timestamp resets) are removed,
+/// so the timeline continues from the latest chunk of the track that detected the jump.
+/// A new connection of a live stream continues from the latest chunk of any track.
+#[derive(Default)]
+struct ContinuousTimestamps {
+    /// Value (in seconds) added to the source timestamps.
+    offset: Option<f64>,
+    /// Latest output timestamp (in seconds) of the video and audio track.
+    last_video: Option<f64>,
+    last_audio: Option<f64>,
+}
+
+impl ContinuousTimestamps {
+    fn start_new_connection(&mut self) {
+        self.offset = None;
+    }
+
+    fn map(
+        &mut self,
+        kind: EncodedChunkKind,
+        pts: f64,
+        dts: Option<f64>,
+    ) -> (Duration, Option<Duration>) {
+        let timestamp = dts.unwrap_or(pts);
+        let start = self
+            .last_video
+            .into_iter()
+            .chain(self.last_audio)
+            .fold(0.0, f64::max);
+        let mut offset = *self.offset.get_or_insert(start - timestamp);
+        let last = match kind {
+            EncodedChunkKind::Video(_) => &mut self.last_video,
+            EncodedChunkKind::Audio(_) => &mut self.last_audio,
+        };
+        if let Some(last) = *last {
+            if (timestamp + offset - last).abs() > MAX_TIMESTAMP_JUMP {
+                debug!(
+                    from = last,
+                    to = timestamp + offset,
+                    "Timestamp discontinuity."
+                );
+                offset = last - timestamp;
+                self.offset = Some(offset);
+            }
+        }
+        *last = Some(timestamp + offset);
+
+        // B-frames and interleaved tracks can be slightly behind the first packet.
+        let to_duration = |timestamp: f64| Duration::from_secs_f64((timestamp + offset).max(0.0));
+        (to_duration(pts), dts.map(to_duration))
+    }
+}
+
+/// H264 decoder configuration from the `avcC` box (ISO/IEC 14496-15, section 5.3.3).
+struct AvccConfig {
+    nal_length_size: usize,
+    /// SPS and PPS NAL units in the Annex B format.
+    parameter_sets: Bytes,
+}
+
+impl AvccConfig {
+    fn parse(data: &[u8]) -> Option<Self> {
+        let nal_length_size = (*data.get(4)? & 0x03) as usize + 1;
+        let mut parameter_sets = BytesMut::new();
+        let mut rest = data.get(5..)?;
+
+        // Number of SPS is stored on 5 bits, number of PPS on the whole byte.
+        for count_mask in [0x1F, 0xFF] {
+            let (count, tail) = rest.split_first()?;
+            rest = tail;
+            for _ in 0..(count & count_mask) {
+                let len = u16::from_be_bytes([*rest.first()?, *rest.get(1)?]) as usize;
+                let nal = rest.get(2..2 + len)?;
+                parameter_sets.put_slice(&[0, 0, 0, 1]);
+                parameter_sets.put_slice(nal);
+                rest = &rest[2 + len..];
+            }
+        }
+
+        Some(Self {
+            nal_length_size,
+            parameter_sets: parameter_sets.freeze(),
+        })
+    }
+
+    /// Replaces length prefixes of NAL units with start codes. Parameter sets are prepended
+    /// to keyframes, because the decoder does not receive the `avcC` box.
+    fn to_annex_b(&self, mut data: &[u8], is_keyframe: bool) -> Option<Bytes> {
+        let mut result = BytesMut::with_capacity(data.len() + self.parameter_sets.len());
+        if is_keyframe {
+            result.put_slice(&self.parameter_sets);
+        }
+        while !data.is_empty() {
+            let len = data
+                .get(..self.nal_length_size)?
+                .iter()
+                .fold(0usize, |len, byte| (len << 8) | *byte as usize);
+            let nal = data.get(self.nal_length_size..self.nal_length_size + len)?;
+            result.put_slice(&[0, 0, 0, 1]);
+            result.put_slice(nal);
+            data = &data[self.nal_length_size + len..];
+        }
+        Some(result.freeze())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::pipeline::{structs::EncodedChunkKind, AudioCodec, VideoCodec};
+
+    use super::{AvccConfig, ContinuousTimestamps};
+
+    #[test]
+    fn timestamps_continue_after_discontinuity() {
+        const VIDEO: EncodedChunkKind = EncodedChunkKind::Video(VideoCodec::H264);
+        const AUDIO: EncodedChunkKind = EncodedChunkKind::Audio(AudioCodec::Aac);
+        let mut timestamps = ContinuousTimestamps::default();
+
+        assert_eq!(timestamps.map(VIDEO, 10.0, None).0, Duration::ZERO);
+        assert_eq!(
+            timestamps.map(AUDIO, 10.5, None).0,
+            Duration::from_millis(500)
+        );
+        assert_eq!(
+            timestamps.map(VIDEO, 12.0, Some(11.0)),
+            (Duration::from_secs(2), Some(Duration::from_secs(1)))
+        );
+
+        // Source timestamps reset to zero.
+        assert_eq!(timestamps.map(VIDEO, 0.0, None).0, Duration::from_secs(1));
+        assert_eq!(
+            timestamps.map(AUDIO, 0.5, None).0,
+            Duration::from_millis(1500)
+        );
+        assert_eq!(timestamps.map(VIDEO, 1.0, None).0, Duration::from_secs(2));
+    }
+
+    #[test]
+    fn timestamps_continue_after_reconnect() {
+        const VIDEO: EncodedChunkKind = EncodedChunkKind::Video(VideoCodec::H264);
+        const AUDIO: EncodedChunkKind = EncodedChunkKind::Audio(AudioCodec::Aac);
+        let mut timestamps = ContinuousTimestamps::default();
+
+        timestamps.start_new_connection();
+        assert_eq!(timestamps.map(VIDEO, 10.0, None).0, Duration::ZERO);
+        assert_eq!(timestamps.map(VIDEO, 11.0, None).0, Duration::from_secs(1));
+        assert_eq!(
+            timestamps.map(AUDIO, 11.5, None).0,
+            Duration::from_millis(1500)
+        );
+
+        // Sender timestamps of a new connection are unrelated to the previous one.
+        timestamps.start_new_connection();
+        assert_eq!(
+            timestamps.map(VIDEO, 0.5, None).0,
+            Duration::from_millis(1500)
+        );
+        assert_eq!(timestamps.map(VIDEO, 1.0, None).0, Duration::from_secs(2));
+        assert_eq!(timestamps.map(AUDIO, 1.0, None).0, Duration::from_secs(2));
+    }
+
+    #[test]
+    fn converts_avcc_to_annex_b() {
+        let avcc = [
+            0x01, 0x64, 0x00, 0x1F,
+            0xFF, // version, profile, compatibility, level, length size
+            0xE1, 0x00, 0x02, 0x67, 0x64, // 1 SPS
+            0x01, 0x00, 0x03, 0x68, 0xEE, 0x3C, // 1 PPS
+        ];
+        let config = AvccConfig::parse(&avcc).unwrap();
+
+        let packet = [0, 0, 0, 2, 0x65, 0x88, 0, 0, 0, 1, 0x06];
+        assert_eq!(
+            config.to_annex_b(&packet, true).unwrap().as_ref(),
+            [
+                0, 0, 0, 1, 0x67, 0x64, 0, 0, 0, 1, 0x68, 0xEE, 0x3C, 0, 0, 0, 1, 0x65, 0x88, 0, 0,
+                0, 1, 0x06
+            ]
+        );
+        assert_eq!(
+            config.to_annex_b(&packet, false).unwrap().as_ref(),
+            [0, 0, 0, 1, 0x65, 0x88, 0, 0, 0, 1, 0x06]
+        );
+        assert!(config.to_annex_b(&packet[..7], false).is_none());
+    }
+}
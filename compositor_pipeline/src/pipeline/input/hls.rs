@@ -0,0 +1,83 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use compositor_render::InputId;
+use ffmpeg_next::{self as ffmpeg, Dictionary};
+
+use crate::pipeline::decoder::DecoderOptions;
+
+use super::{
+    file::{start_demuxer, FileInputError, FileTrack},
+    ChunksReceiver,
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum HlsInputError {
+    #[error("Failed to open HLS playlist \"{url}\".")]
+    Open {
+        url: Arc<str>,
+        #[source]
+        source: ffmpeg::Error,
+    },
+
+    #[error(transparent)]
+    Tracks(#[from] FileInputError),
+}
+
+#[derive(Debug, Clone)]
+pub struct HlsInputOptions {
+    /// URL or local path of the m3u8 playlist.
+    pub url: Arc<str>,
+    /// Receive the video track if the stream contains one.
+    pub video: bool,
+    /// Receive the audio track if the stream contains one.
+    pub audio: bool,
+}
+
+/// Input that pulls an HLS stream from a local or remote m3u8 playlist.
+///
+/// Playlists are handled by the ffmpeg HLS demuxer. It downloads TS or fragmented MP4
+/// segments and refreshes live playlists. For a master playlist, the default variant is
+/// selected when the input is registered and segments of other variants are not
+/// downloaded. Timestamps continue across discontinuities, so segments with reset
+/// timestamps (e.g. inserted ads) play right after the previous ones.
+pub struct HlsInput {
+    stop_thread: Arc<AtomicBool>,
+}
+
+impl HlsInput {
+    pub fn new(
+        input_id: &InputId,
+        opts: HlsInputOptions,
+    ) -> Result<(Self, ChunksReceiver, DecoderOptions), HlsInputError> {
+        // Retry refreshing a live playlist for longer than the default 3 attempts before
+        // ending the stream.
+        let options = Dictionary::from_iter([("max_reload", "10")]);
+        let input_ctx = ffmpeg::format::input_with_dictionary(&opts.url.as_ref(), options)
+            .map_err(|source| HlsInputError::Open {
+                url: opts.url.clone(),
+                source,
+            })?;
+
+        let track = |enabled: bool| match enabled {
+            true => FileTrack::Default,
+            false => FileTrack::Disabled,
+        };
+        let (stop_thread, chunks_receiver, decoder_options) = start_demuxer(
+            input_id,
+            "HLS",
+            input_ctx,
+            &track(opts.video),
+            &track(opts.audio),
+        )?;
+        Ok((Self { stop_thread }, chunks_receiver, decoder_options))
+    }
+}
+
+impl Drop for HlsInput {
+    fn drop(&mut self) {
+        self.stop_thread.store(true, Ordering::Relaxed);
+    }
+}
@@ -1,30 +1,46 @@
-use std::path::{Path, PathBuf};
+use std::{
+    io::Read,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
 
 use bytes::Bytes;
 use compositor_render::InputId;
-use crossbeam_channel::Receiver;
-use tracing::error;
+use crossbeam_channel::{Receiver, Sender};
+use tracing::{debug, error, span, Level};
 
 use crate::{
-    pipeline::decoder::{AudioDecoderOptions, DecoderOptions, VideoDecoderOptions},
+    pipeline::{
+        decoder::{AacDecoderOptions, AudioDecoderOptions, DecoderOptions, VideoDecoderOptions},
+        structs::EncodedChunk,
+        VideoCodec,
+    },
     queue::PipelineEvent,
 };
 
+use fragmented::{read_init_segment, run_fragment_splitter, BoxHeader, InitSegment, Mp4PushSender};
 use mp4_file_reader::Mp4FileReader;
+use playback::{Mp4Playback, TrackPlayback};
 
-use super::ChunksReceiver;
+use super::{push::push_channel, ChunksReceiver};
 
+pub mod fragmented;
 pub mod mp4_file_reader;
+pub mod playback;
 
 pub struct Mp4Options {
     pub source: Source,
+    /// Start the file over after reaching its end instead of sending EOS.
+    pub should_loop: bool,
 }
 
 pub(crate) enum Mp4ReaderOptions {
     NonFragmented {
         file: PathBuf,
     },
-    #[allow(dead_code)]
     Fragmented {
         header: Bytes,
         fragment_receiver: Receiver<PipelineEvent<Bytes>>,
@@ -32,8 +48,16 @@ pub(crate) enum Mp4ReaderOptions {
 }
 
 pub enum Source {
+    /// Fragmented files are played while they are downloaded, other files are downloaded
+    /// before the playback starts.
     Url(String),
     File(PathBuf),
+    /// Fragmented MP4 stream pushed with [`Mp4PushSender`]. Tracks have to be declared
+    /// upfront, because decoders are created before the stream header arrives.
+    Push {
+        video: bool,
+        audio: bool,
+    },
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -51,12 +75,27 @@ pub enum Mp4Error {
     NoTrack,
 }
 
+type ChunkChannel = (
+    Sender<PipelineEvent<EncodedChunk>>,
+    Receiver<PipelineEvent<EncodedChunk>>,
+);
+
+#[derive(Default)]
+struct Mp4Tracks {
+    video: Option<Mp4FileReader<VideoDecoderOptions>>,
+    audio: Option<Mp4FileReader<AudioDecoderOptions>>,
+}
+
 pub struct Mp4 {
     pub input_id: InputId,
-    _video_thread: Option<Mp4FileReader<VideoDecoderOptions>>,
-    _audio_thread: Option<Mp4FileReader<AudioDecoderOptions>>,
-    source: Source,
-    path_to_file: PathBuf,
+    /// Dropping the readers stops their threads. Readers of a pushed stream are created
+    /// when the stream header arrives.
+    _tracks: Arc<Mutex<Mp4Tracks>>,
+    playback: Mp4Playback,
+    push_sender: Option<Mp4PushSender>,
+    stop_thread: Arc<AtomicBool>,
+    /// File downloaded from the URL, removed when the input is unregistered.
+    downloaded_file: Option<PathBuf>,
 }
 
 impl Mp4 {
@@ -65,80 +104,334 @@ impl Mp4 {
         options: Mp4Options,
         download_dir: &Path,
     ) -> Result<(Self, ChunksReceiver, DecoderOptions), Mp4Error> {
-        let input_path = match options.source {
-            Source::Url(ref url) => {
-                let file_response = reqwest::blocking::get(url)?;
-                let mut file_response = file_response.error_for_status()?;
-
-                let mut path = download_dir.to_owned();
-                path.push(format!(
-                    "live-compositor-user-file-{}.mp4",
-                    rand::random::<u64>()
-                ));
+        let (playback, tracks) = Mp4Playback::new(input_id.clone(), options.should_loop, 2);
 
-                let mut file = std::fs::File::create(&path)?;
-
-                std::io::copy(&mut file_response, &mut file)?;
-
-                path
+        match options.source {
+            Source::File(path) => Self::new_from_file(input_id, path, false, playback, tracks),
+            // Looping requires the whole file, so it is downloaded before the playback starts.
+            Source::Url(url) if options.should_loop => {
+                let response = reqwest::blocking::get(url)?.error_for_status()?;
+                let path = download(download_dir, Bytes::new(), response)?;
+                Self::new_from_file(input_id, path, true, playback, tracks)
             }
-            Source::File(ref path) => path.clone(),
-        };
+            Source::Url(url) => {
+                let mut response = reqwest::blocking::get(url)?.error_for_status()?;
+                match read_init_segment(&mut response)? {
+                    InitSegment::Fragmented {
+                        header,
+                        first_fragment,
+                    } => Self::new_fragmented(
+                        input_id,
+                        header,
+                        response,
+                        first_fragment,
+                        playback,
+                        tracks,
+                    ),
+                    InitSegment::NonFragmented { prefix } => {
+                        let path = download(download_dir, prefix, response)?;
+                        Self::new_from_file(input_id, path, true, playback, tracks)
+                    }
+                }
+            }
+            Source::Push { video, audio } => {
+                Ok(Self::new_push(input_id, video, audio, playback, tracks))
+            }
+        }
+    }
 
+    fn new_from_file(
+        input_id: &InputId,
+        path: PathBuf,
+        is_downloaded: bool,
+        mut playback: Mp4Playback,
+        mut tracks: Vec<TrackPlayback>,
+    ) -> Result<(Self, ChunksReceiver, DecoderOptions), Mp4Error> {
+        let (video_sender, video_receiver) = crossbeam_channel::bounded(10);
         let video = Mp4FileReader::new_video(
-            Mp4ReaderOptions::NonFragmented {
-                file: input_path.clone(),
-            },
+            Mp4ReaderOptions::NonFragmented { file: path.clone() },
+            input_id.clone(),
+            video_sender,
+            tracks.remove(0),
+        )?;
+
+        let (audio_sender, audio_receiver) = crossbeam_channel::bounded(10);
+        let audio = Mp4FileReader::new_audio(
+            Mp4ReaderOptions::NonFragmented { file: path.clone() },
             input_id.clone(),
+            audio_sender,
+            tracks.remove(0),
+            false,
         )?;
 
-        let (video_reader, video_receiver, video_decoder_options) = match video {
-            Some((reader, receiver)) => {
-                let decoder_options = reader.decoder_options();
-                (Some(reader), Some(receiver), Some(decoder_options))
+        match (&video, &audio) {
+            (Some(video), _) => {
+                playback.set_file_info(video.keyframes().to_vec(), video.duration())
             }
-            None => (None, None, None),
+            (None, Some(audio)) => playback.set_file_info(Vec::new(), audio.duration()),
+            (None, None) => {}
+        }
+
+        let tracks = Mp4Tracks { video, audio };
+        let (chunks_receiver, decoder_options) = tracks.receivers(video_receiver, audio_receiver);
+        let mp4 = Self {
+            input_id: input_id.clone(),
+            _tracks: Arc::new(Mutex::new(tracks)),
+            playback,
+            push_sender: None,
+            stop_thread: Arc::new(AtomicBool::new(false)),
+            downloaded_file: is_downloaded.then_some(path),
         };
+        Ok((mp4, chunks_receiver, decoder_options))
+    }
 
-        let audio = Mp4FileReader::new_audio(
-            Mp4ReaderOptions::NonFragmented {
-                file: input_path.clone(),
-            },
-            input_id.clone(),
+    /// Plays a fragmented MP4 while it is downloaded.
+    fn new_fragmented(
+        input_id: &InputId,
+        header: Bytes,
+        response: reqwest::blocking::Response,
+        first_fragment: BoxHeader,
+        playback: Mp4Playback,
+        tracks: Vec<TrackPlayback>,
+    ) -> Result<(Self, ChunksReceiver, DecoderOptions), Mp4Error> {
+        let video_channel = crossbeam_channel::bounded(10);
+        let audio_channel = crossbeam_channel::bounded(10);
+        let (fragmented_tracks, fragment_senders) = Mp4Tracks::new_fragmented(
+            input_id,
+            header,
+            Some(video_channel.0),
+            Some(audio_channel.0),
+            tracks,
+            false,
         )?;
 
-        let (audio_reader, audio_receiver, audio_deocder_options) = match audio {
-            Some((reader, receiver)) => {
-                let decoder_options = reader.decoder_options();
-                (Some(reader), Some(receiver), Some(decoder_options))
+        let stop_thread = Arc::new(AtomicBool::new(false));
+        spawn_fragment_splitter(
+            input_id,
+            response,
+            first_fragment,
+            fragment_senders,
+            stop_thread.clone(),
+        );
+
+        let (chunks_receiver, decoder_options) =
+            fragmented_tracks.receivers(video_channel.1, audio_channel.1);
+        let mp4 = Self {
+            input_id: input_id.clone(),
+            _tracks: Arc::new(Mutex::new(fragmented_tracks)),
+            playback,
+            push_sender: None,
+            stop_thread,
+            downloaded_file: None,
+        };
+        Ok((mp4, chunks_receiver, decoder_options))
+    }
+
+    fn new_push(
+        input_id: &InputId,
+        video: bool,
+        audio: bool,
+        playback: Mp4Playback,
+        tracks: Vec<TrackPlayback>,
+    ) -> (Self, ChunksReceiver, DecoderOptions) {
+        let (push_sender, mut reader) = push_channel();
+        let video_channel: Option<ChunkChannel> = video.then(|| crossbeam_channel::bounded(10));
+        let audio_channel: Option<ChunkChannel> = audio.then(|| crossbeam_channel::bounded(10));
+        let video_sender = video_channel.as_ref().map(|(sender, _)| sender.clone());
+        let audio_sender = audio_channel.as_ref().map(|(sender, _)| sender.clone());
+
+        let stop_thread = Arc::new(AtomicBool::new(false));
+        let pushed_tracks = Arc::new(Mutex::new(Mp4Tracks::default()));
+        let span = span!(Level::INFO, "MP4 push", input_id = input_id.to_string());
+        let thread_input_id = input_id.clone();
+        let thread_tracks = pushed_tracks.clone();
+        let thread_stop = stop_thread.clone();
+        std::thread::Builder::new()
+            .name(format!("mp4 push demuxer {input_id}"))
+            .spawn(move || {
+                let _guard = span.enter();
+                let (header, first_fragment) = match read_init_segment(&mut reader) {
+                    Ok(InitSegment::Fragmented {
+                        header,
+                        first_fragment,
+                    }) => (header, first_fragment),
+                    Ok(InitSegment::NonFragmented { .. }) => {
+                        error!("Pushed MP4 stream is not fragmented.");
+                        return;
+                    }
+                    Err(err) => {
+                        error!(%err, "Failed to read the header of the pushed MP4 stream.");
+                        return;
+                    }
+                };
+
+                let fragment_senders = match Mp4Tracks::new_fragmented(
+                    &thread_input_id,
+                    header,
+                    video_sender,
+                    audio_sender,
+                    tracks,
+                    true,
+                ) {
+                    Ok((tracks, fragment_senders)) => {
+                        *thread_tracks.lock().unwrap() = tracks;
+                        fragment_senders
+                    }
+                    Err(err) => {
+                        error!(%err, "Failed to read the header of the pushed MP4 stream.");
+                        return;
+                    }
+                };
+                drop(thread_tracks);
+
+                run_fragment_splitter(reader, Some(first_fragment), fragment_senders, thread_stop);
+                debug!("Closing MP4 push demuxer thread.");
+            })
+            .unwrap();
+
+        let decoder_options = DecoderOptions {
+            video: video.then_some(VideoDecoderOptions {
+                codec: VideoCodec::H264,
+            }),
+            // The `esds` config is known only after the header is pushed. It reaches the
+            // decoder in the ADTS headers that the fragment reader adds to every sample.
+            audio: audio.then_some(AudioDecoderOptions::Aac(AacDecoderOptions {
+                asc: None,
+                depayloader_mode: None,
+            })),
+        };
+        let chunks_receiver = ChunksReceiver {
+            video: video_channel.map(|(_, receiver)| receiver),
+            audio: audio_channel.map(|(_, receiver)| receiver),
+        };
+        let mp4 = Self {
+            input_id: input_id.clone(),
+            _tracks: pushed_tracks,
+            playback,
+            push_sender: Some(Mp4PushSender::new(input_id.clone(), push_sender)),
+            stop_thread,
+            downloaded_file: None,
+        };
+        (mp4, chunks_receiver, decoder_options)
+    }
+
+    pub fn playback(&self) -> Mp4Playback {
+        self.playback.clone()
+    }
+
+    /// Returns `None` if the input does not accept a pushed stream.
+    pub fn push_sender(&self) -> Option<Mp4PushSender> {
+        self.push_sender.clone()
+    }
+}
+
+impl Mp4Tracks {
+    /// Starts readers of the tracks that have a sender. Returns senders that deliver
+    /// movie fragments to the readers.
+    fn new_fragmented(
+        input_id: &InputId,
+        header: Bytes,
+        video_sender: Option<Sender<PipelineEvent<EncodedChunk>>>,
+        audio_sender: Option<Sender<PipelineEvent<EncodedChunk>>>,
+        mut tracks: Vec<TrackPlayback>,
+        adts: bool,
+    ) -> Result<(Self, Vec<Sender<PipelineEvent<Bytes>>>), Mp4Error> {
+        let mut fragment_senders = Vec::new();
+        let mut fragmented_options = || {
+            let (sender, fragment_receiver) = crossbeam_channel::bounded(4);
+            fragment_senders.push(sender);
+            Mp4ReaderOptions::Fragmented {
+                header: header.clone(),
+                fragment_receiver,
             }
-            None => (None, None, None),
         };
 
-        Ok((
-            Self {
-                input_id: input_id.clone(),
-                _video_thread: video_reader,
-                _audio_thread: audio_reader,
-                source: options.source,
-                path_to_file: input_path,
-            },
-            ChunksReceiver {
-                video: video_receiver,
-                audio: audio_receiver,
-            },
-            DecoderOptions {
-                video: video_decoder_options,
-                audio: audio_deocder_options,
-            },
-        ))
+        let video_playback = tracks.remove(0);
+        let video = match video_sender {
+            Some(sender) => Mp4FileReader::new_video(
+                fragmented_options(),
+                input_id.clone(),
+                sender,
+                video_playback,
+            )?,
+            None => None,
+        };
+        let audio_playback = tracks.remove(0);
+        let audio = match audio_sender {
+            Some(sender) => Mp4FileReader::new_audio(
+                fragmented_options(),
+                input_id.clone(),
+                sender,
+                audio_playback,
+                adts,
+            )?,
+            None => None,
+        };
+
+        Ok((Self { video, audio }, fragment_senders))
     }
+
+    /// Chunk receivers and decoder options of the tracks found in the file.
+    fn receivers(
+        &self,
+        video_receiver: Receiver<PipelineEvent<EncodedChunk>>,
+        audio_receiver: Receiver<PipelineEvent<EncodedChunk>>,
+    ) -> (ChunksReceiver, DecoderOptions) {
+        let chunks_receiver = ChunksReceiver {
+            video: self.video.as_ref().map(|_| video_receiver),
+            audio: self.audio.as_ref().map(|_| audio_receiver),
+        };
+        let decoder_options = DecoderOptions {
+            video: self.video.as_ref().map(Mp4FileReader::decoder_options),
+            audio: self.audio.as_ref().map(Mp4FileReader::decoder_options),
+        };
+        (chunks_receiver, decoder_options)
+    }
+}
+
+fn spawn_fragment_splitter(
+    input_id: &InputId,
+    reader: impl Read + Send + 'static,
+    first_fragment: BoxHeader,
+    fragment_senders: Vec<Sender<PipelineEvent<Bytes>>>,
+    stop_thread: Arc<AtomicBool>,
+) {
+    let span = span!(Level::INFO, "MP4 download", input_id = input_id.to_string());
+    std::thread::Builder::new()
+        .name(format!("mp4 download {input_id}"))
+        .spawn(move || {
+            let _guard = span.enter();
+            run_fragment_splitter(reader, Some(first_fragment), fragment_senders, stop_thread);
+            debug!("Closing MP4 download thread.");
+        })
+        .unwrap();
+}
+
+/// Writes `prefix` (already downloaded part of the file) and the rest of the response
+/// to a new file in `download_dir`.
+fn download(
+    download_dir: &Path,
+    prefix: Bytes,
+    mut response: reqwest::blocking::Response,
+) -> Result<PathBuf, Mp4Error> {
+    let mut path = download_dir.to_owned();
+    path.push(format!(
+        "live-compositor-user-file-{}.mp4",
+        rand::random::<u64>()
+    ));
+
+    let mut file = std::fs::File::create(&path)?;
+    std::io::copy(&mut prefix.as_ref(), &mut file)?;
+    std::io::copy(&mut response, &mut file)?;
+
+    Ok(path)
 }
 
 impl Drop for Mp4 {
     fn drop(&mut self) {
-        if let Source::Url(_) = self.source {
-            if let Err(e) = std::fs::remove_file(&self.path_to_file) {
+        self.stop_thread.store(true, Ordering::Relaxed);
+        if let Some(ref path) = self.downloaded_file {
+            if let Err(e) = std::fs::remove_file(path) {
                 error!(input_id=?self.input_id.0, "Error while removing the downloaded mp4 file: {e}");
             }
         }
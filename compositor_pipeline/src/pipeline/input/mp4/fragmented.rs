@@ -0,0 +1,338 @@
+use std::{
+    io::{self, Read},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use bytes::{Bytes, BytesMut};
+use compositor_render::InputId;
+use crossbeam_channel::Sender;
+use tracing::{debug, warn};
+
+use crate::queue::PipelineEvent;
+
+type BoxType = [u8; 4];
+
+const MOOV: &BoxType = b"moov";
+const MOOF: &BoxType = b"moof";
+const MDAT: &BoxType = b"mdat";
+
+#[derive(Debug, thiserror::Error)]
+pub enum Mp4PushError {
+    #[error("Input \"{0}\" is not registered.")]
+    InputNotFound(InputId),
+
+    #[error("Input \"{0}\" is not an MP4 input that accepts pushed fragments.")]
+    NotPushInput(InputId),
+
+    #[error("MP4 input \"{0}\" does not accept more data.")]
+    Closed(InputId),
+}
+
+/// Handle used to push a fragmented MP4 stream to a registered MP4 input. Data can be split
+/// into chunks at arbitrary points, chunks have to be pushed in order. Pushing blocks while
+/// the input is behind.
+#[derive(Clone)]
+pub struct Mp4PushSender {
+    input_id: InputId,
+    sender: Sender<Bytes>,
+}
+
+impl Mp4PushSender {
+    pub(super) fn new(input_id: InputId, sender: Sender<Bytes>) -> Self {
+        Self { input_id, sender }
+    }
+
+    pub fn push(&self, data: Bytes) -> Result<(), Mp4PushError> {
+        self.sender
+            .send(data)
+            .map_err(|_| Mp4PushError::Closed(self.input_id.clone()))
+    }
+}
+
+#[derive(Debug)]
+pub(super) struct BoxHeader {
+    box_type: BoxType,
+    /// Raw bytes of the header.
+    header: Bytes,
+    /// `None` if the box extends to the end of the file.
+    body_size: Option<u64>,
+}
+
+pub(super) enum InitSegment {
+    /// `moov` is followed by movie fragments. `header` contains all boxes before the first
+    /// fragment.
+    Fragmented {
+        header: Bytes,
+        first_fragment: BoxHeader,
+    },
+    /// Regular MP4 file, the rest of the file has to be appended to `prefix` before it can
+    /// be read.
+    NonFragmented { prefix: Bytes },
+}
+
+/// Reads boxes from the beginning of the stream up to the first movie fragment. Stops early
+/// if the stream turns out not to be fragmented, without reading the media data.
+pub(super) fn read_init_segment(reader: &mut impl Read) -> io::Result<InitSegment> {
+    let mut header = BytesMut::new();
+    let mut has_moov = false;
+
+    loop {
+        let Some(box_header) = read_box_header(reader)? else {
+            return Ok(InitSegment::NonFragmented {
+                prefix: header.freeze(),
+            });
+        };
+        match &box_header.box_type {
+            MOOF if has_moov => {
+                return Ok(InitSegment::Fragmented {
+                    header: header.freeze(),
+                    first_fragment: box_header,
+                })
+            }
+            MDAT | MOOF => {
+                header.extend_from_slice(&box_header.header);
+                return Ok(InitSegment::NonFragmented {
+                    prefix: header.freeze(),
+                });
+            }
+            box_type => {
+                has_moov |= box_type == MOOV;
+                let body = read_box_body(reader, &box_header)?;
+                header.extend_from_slice(&box_header.header);
+                header.extend_from_slice(&body);
+            }
+        }
+    }
+}
+
+/// Splits the stream into movie fragments (`moof` and the following `mdat`) and sends each
+/// of them to all track readers. Other top-level boxes (e.g. `styp` or `sidx`) are skipped.
+pub(super) fn run_fragment_splitter(
+    mut reader: impl Read,
+    first_fragment: Option<BoxHeader>,
+    senders: Vec<Sender<PipelineEvent<Bytes>>>,
+    stop_thread: Arc<AtomicBool>,
+) {
+    let mut next_header = first_fragment;
+    let mut fragment = BytesMut::new();
+
+    loop {
+        if stop_thread.load(Ordering::Relaxed) {
+            return;
+        }
+        let box_header = match next_header.take() {
+            Some(box_header) => box_header,
+            None => match read_box_header(&mut reader) {
+                Ok(Some(box_header)) => box_header,
+                Ok(None) => break,
+                Err(err) => {
+                    warn!(%err, "Failed to read fragmented MP4 stream.");
+                    break;
+                }
+            },
+        };
+        let body = match read_box_body(&mut reader, &box_header) {
+            Ok(body) => body,
+            Err(err) => {
+                warn!(%err, "Failed to read fragmented MP4 stream.");
+                break;
+            }
+        };
+
+        match &box_header.box_type {
+            MOOF => {
+                fragment.clear();
+                fragment.extend_from_slice(&box_header.header);
+                fragment.extend_from_slice(&body);
+            }
+            MDAT if fragment.is_empty() => {
+                debug!("Skipping media data without a movie fragment.");
+            }
+            MDAT => {
+                fragment.extend_from_slice(&box_header.header);
+                fragment.extend_from_slice(&body);
+                let fragment = fragment.split().freeze();
+                let sent = senders
+                    .iter()
+                    .filter(|sender| sender.send(PipelineEvent::Data(fragment.clone())).is_ok())
+                    .count();
+                if sent == 0 {
+                    debug!("Failed to send MP4 fragment. Channels closed.");
+                    return;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    for sender in senders {
+        if sender.send(PipelineEvent::EOS).is_err() {
+            debug!("Failed to send EOS from MP4 fragment splitter. Channel closed.");
+        }
+    }
+}
+
+fn read_box_header(reader: &mut impl Read) -> io::Result<Option<BoxHeader>> {
+    let mut header = [0u8; 16];
+    match reader.read_exact(&mut header[..8]) {
+        Ok(()) => {}
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err),
+    }
+
+    let size = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as u64;
+    let box_type = [header[4], header[5], header[6], header[7]];
+    let (header_len, size) = match size {
+        0 => (8, None),
+        1 => {
+            reader.read_exact(&mut header[8..16])?;
+            let mut large_size = [0u8; 8];
+            large_size.copy_from_slice(&header[8..16]);
+            (16, Some(u64::from_be_bytes(large_size)))
+        }
+        size => (8, Some(size)),
+    };
+
+    let body_size = match size {
+        Some(size) if size < header_len as u64 => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Invalid MP4 box size.",
+            ))
+        }
+        Some(size) => Some(size - header_len as u64),
+        None => None,
+    };
+
+    Ok(Some(BoxHeader {
+        box_type,
+        header: Bytes::copy_from_slice(&header[..header_len]),
+        body_size,
+    }))
+}
+
+fn read_box_body(reader: &mut impl Read, box_header: &BoxHeader) -> io::Result<Bytes> {
+    let mut body = Vec::new();
+    match box_header.body_size {
+        Some(size) => {
+            reader.take(size).read_to_end(&mut body)?;
+            if body.len() as u64 != size {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+        }
+        None => {
+            reader.read_to_end(&mut body)?;
+        }
+    }
+    Ok(body.into())
+}
+
+/// Builds an ADTS header for raw AAC frames described by the AudioSpecificConfig. Returns
+/// `None` if the config can not be expressed in ADTS.
+pub(super) fn adts_header(asc: &[u8], frame_len: usize) -> Option<[u8; 7]> {
+    if asc.len() < 2 {
+        return None;
+    }
+    let object_type = asc[0] >> 3;
+    let frequency_index = ((asc[0] & 0x07) << 1) | (asc[1] >> 7);
+    let channel_config = (asc[1] >> 3) & 0x0F;
+    if !(1..=4).contains(&object_type) || frequency_index > 12 {
+        return None;
+    }
+
+    let len = frame_len + 7;
+    if len > 0x1FFF {
+        return None;
+    }
+    Some([
+        0xFF,
+        0xF1,
+        ((object_type - 1) << 6) | (frequency_index << 2) | (channel_config >> 2),
+        ((channel_config & 0x03) << 6) | ((len >> 11) as u8 & 0x03),
+        (len >> 3) as u8,
+        (((len & 0x07) as u8) << 5) | 0x1F,
+        0xFC,
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    fn mp4_box(box_type: &BoxType, body: &[u8]) -> Vec<u8> {
+        let mut data = ((body.len() + 8) as u32).to_be_bytes().to_vec();
+        data.extend_from_slice(box_type);
+        data.extend_from_slice(body);
+        data
+    }
+
+    #[test]
+    fn splits_fragmented_stream() {
+        let header = [mp4_box(b"ftyp", &[1, 2]), mp4_box(MOOV, &[3])].concat();
+        let fragment = [mp4_box(MOOF, &[4, 5]), mp4_box(MDAT, &[6, 7, 8])].concat();
+        let stream = [
+            header.clone(),
+            fragment.clone(),
+            mp4_box(b"sidx", &[9]),
+            fragment.clone(),
+        ]
+        .concat();
+
+        let mut reader = Cursor::new(stream);
+        let InitSegment::Fragmented {
+            header: init,
+            first_fragment,
+        } = read_init_segment(&mut reader).unwrap()
+        else {
+            panic!("Expected a fragmented stream.");
+        };
+        assert_eq!(init, header);
+
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        run_fragment_splitter(
+            reader,
+            Some(first_fragment),
+            vec![sender],
+            Arc::new(AtomicBool::new(false)),
+        );
+        let events: Vec<_> = receiver.try_iter().collect();
+        assert_eq!(events.len(), 3);
+        for event in &events[..2] {
+            match event {
+                PipelineEvent::Data(data) => assert_eq!(data, &fragment),
+                PipelineEvent::EOS => panic!("Unexpected EOS."),
+            }
+        }
+        assert!(matches!(events[2], PipelineEvent::EOS));
+    }
+
+    #[test]
+    fn detects_non_fragmented_file() {
+        let stream = [
+            mp4_box(b"ftyp", &[1]),
+            mp4_box(MDAT, &[2, 3]),
+            mp4_box(MOOV, &[4]),
+        ]
+        .concat();
+
+        let mut reader = Cursor::new(stream.clone());
+        let InitSegment::NonFragmented { prefix } = read_init_segment(&mut reader).unwrap() else {
+            panic!("Expected a non-fragmented file.");
+        };
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!([prefix.to_vec(), rest].concat(), stream);
+    }
+
+    #[test]
+    fn adts_header_for_aac_lc_stereo() {
+        // AAC LC, 44.1kHz, 2 channels
+        let header = adts_header(&[0x12, 0x10], 100).unwrap();
+        assert_eq!(header, [0xFF, 0xF1, 0x50, 0x80, 0x0D, 0x7F, 0xFC]);
+    }
+}
@@ -1,5 +1,5 @@
 use std::{
-    io::{Read, Seek},
+    io::{Cursor, Read, Seek},
     os::unix::fs::MetadataExt,
     sync::{atomic::AtomicBool, Arc},
     time::Duration,
@@ -7,43 +7,57 @@ use std::{
 
 use bytes::{Buf, Bytes, BytesMut};
 use compositor_render::InputId;
-use crossbeam_channel::{Receiver, Sender};
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender};
 use mp4::Mp4Reader;
 use tracing::{debug, span, trace, warn, Level, Span};
 
 use crate::{
     pipeline::{
         decoder::{AacDecoderOptions, AudioDecoderOptions, VideoDecoderOptions},
-        structs::{EncodedChunk, EncodedChunkKind},
+        structs::{EncodedChunk, EncodedChunkKind, IsKeyframe},
         AudioCodec, VideoCodec,
     },
     queue::PipelineEvent,
 };
 
-use super::{Mp4Error, Mp4ReaderOptions};
+use super::{
+    fragmented::adts_header,
+    playback::{EndOfFile, PlaybackCommand, Timeline, TrackPlayback},
+    Mp4Error, Mp4ReaderOptions,
+};
 
-type ChunkReceiver = Receiver<PipelineEvent<EncodedChunk>>;
+type ChunkSender = Sender<PipelineEvent<EncodedChunk>>;
 
 pub(crate) struct Mp4FileReader<DecoderOptions> {
     stop_thread: Arc<AtomicBool>,
-    fragment_sender: Option<Sender<PipelineEvent<Bytes>>>,
     decoder_options: DecoderOptions,
+    keyframes: Vec<Duration>,
+    duration: Duration,
 }
 
 struct TrackInfo<DecoderOptions, SampleUnpacker: FnMut(mp4::Mp4Sample) -> Bytes> {
     sample_count: u32,
     timescale: u32,
     track_id: u32,
+    /// Decoding timestamps of the samples, in the track timescale.
+    sample_start_times: Vec<u64>,
+    /// Timestamps of samples that can be used as a starting point after a seek.
+    keyframes: Vec<Duration>,
     decoder_options: DecoderOptions,
     sample_unpacker: SampleUnpacker,
     chunk_kind: EncodedChunkKind,
 }
 
 impl Mp4FileReader<AudioDecoderOptions> {
+    /// If `adts` is set, ADTS headers are added to the AAC frames, so they can be decoded
+    /// without the AudioSpecificConfig.
     pub(crate) fn new_audio(
         options: Mp4ReaderOptions,
         input_id: InputId,
-    ) -> Result<Option<(Self, ChunkReceiver)>, Mp4Error> {
+        sender: ChunkSender,
+        playback: TrackPlayback,
+        adts: bool,
+    ) -> Result<Option<Self>, Mp4Error> {
         let stop_thread = Arc::new(AtomicBool::new(false));
         let span = span!(Level::INFO, "MP4 audio", input_id = input_id.to_string());
 
@@ -54,9 +68,11 @@ impl Mp4FileReader<AudioDecoderOptions> {
                 Self::new(
                     input_file,
                     size,
-                    Self::find_aac_info,
+                    |reader| Self::find_aac_info(reader, adts),
                     None,
+                    sender,
                     stop_thread,
+                    playback,
                     span,
                 )
             }
@@ -65,13 +81,15 @@ impl Mp4FileReader<AudioDecoderOptions> {
                 fragment_receiver,
             } => {
                 let size = header.len() as u64;
-                let reader = std::io::Cursor::new(header);
+                let reader = Cursor::new(header);
                 Self::new(
                     reader,
                     size,
-                    Self::find_aac_info,
+                    |reader| Self::find_aac_info(reader, adts),
                     Some(fragment_receiver),
+                    sender,
                     stop_thread,
+                    playback,
                     span,
                 )
             }
@@ -80,6 +98,7 @@ impl Mp4FileReader<AudioDecoderOptions> {
 
     fn find_aac_info<Reader: Read + Seek + Send + 'static>(
         reader: &mp4::Mp4Reader<Reader>,
+        adts: bool,
     ) -> Option<TrackInfo<AudioDecoderOptions, impl FnMut(mp4::Mp4Sample) -> Bytes>> {
         let (&track_id, track, aac) = reader.tracks().iter().find_map(|(id, track)| {
             let track_type = track.track_type().ok()?;
@@ -102,17 +121,39 @@ impl Mp4FileReader<AudioDecoderOptions> {
             .and_then(|esds| esds.es_desc.dec_config.dec_specific.full_config.clone())
             .map(Bytes::from);
 
+        let adts_asc = asc.clone().filter(|_| adts);
+        let sample_unpacker = move |sample: mp4::Mp4Sample| {
+            let Some(ref asc) = adts_asc else {
+                return sample.bytes;
+            };
+            match adts_header(asc, sample.bytes.len()) {
+                Some(header) => [&header[..], &sample.bytes].concat().into(),
+                None => {
+                    warn!("AudioSpecificConfig can not be expressed in the ADTS header.");
+                    sample.bytes
+                }
+            }
+        };
+
+        // The decoder also accepts ADTS frames when it is configured out of band.
         let decoder_options = AudioDecoderOptions::Aac(AacDecoderOptions {
             asc,
             depayloader_mode: None,
         });
 
+        let sample_start_times = sample_start_times(track);
+        let timescale = track.timescale();
+        // Seek positions are aligned to the keyframes of the video track.
+        let keyframes = Vec::new();
+
         Some(TrackInfo {
             sample_count: track.sample_count(),
-            timescale: track.timescale(),
+            timescale,
             track_id,
+            sample_start_times,
+            keyframes,
             decoder_options,
-            sample_unpacker: |sample| sample.bytes,
+            sample_unpacker,
             chunk_kind: EncodedChunkKind::Audio(AudioCodec::Aac),
         })
     }
@@ -122,7 +163,9 @@ impl Mp4FileReader<VideoDecoderOptions> {
     pub(crate) fn new_video(
         options: Mp4ReaderOptions,
         input_id: InputId,
-    ) -> Result<Option<(Mp4FileReader<VideoDecoderOptions>, ChunkReceiver)>, Mp4Error> {
+        sender: ChunkSender,
+        playback: TrackPlayback,
+    ) -> Result<Option<Self>, Mp4Error> {
         let stop_thread = Arc::new(AtomicBool::new(false));
         let span = span!(Level::INFO, "MP4 video", input_id = input_id.to_string());
 
@@ -135,7 +178,9 @@ impl Mp4FileReader<VideoDecoderOptions> {
                     size,
                     Self::find_h264_info,
                     None,
+                    sender,
                     stop_thread,
+                    playback,
                     span,
                 )
             }
@@ -144,13 +189,15 @@ impl Mp4FileReader<VideoDecoderOptions> {
                 fragment_receiver,
             } => {
                 let size = header.len() as u64;
-                let reader = std::io::Cursor::new(header);
+                let reader = Cursor::new(header);
                 Self::new(
                     reader,
                     size,
                     Self::find_h264_info,
                     Some(fragment_receiver),
+                    sender,
                     stop_thread,
+                    playback,
                     span,
                 )
             }
@@ -176,7 +223,8 @@ impl Mp4FileReader<VideoDecoderOptions> {
         })?;
 
         // sps and pps have to be extracted from the container, interleaved with [0, 0, 0, 1],
-        // concatenated and prepended to the first frame.
+        // concatenated and prepended to the keyframes, so the decoder can start (again) from
+        // any of them after a seek or a loop.
         let sps = avc
             .avcc
             .sequence_parameter_sets
@@ -189,17 +237,16 @@ impl Mp4FileReader<VideoDecoderOptions> {
             .iter()
             .flat_map(|s| [0, 0, 0, 1].iter().chain(s.bytes.iter()));
 
-        let mut sps_and_pps_payload = Some(sps.chain(pps).copied().collect::<Bytes>());
+        let sps_and_pps_payload = sps.chain(pps).copied().collect::<Bytes>();
 
         let length_size = avc.avcc.length_size_minus_one + 1;
 
         let sample_unpacker = move |sample: mp4::Mp4Sample| {
-            let mut sample_data = sample.bytes.reader();
             let mut data: BytesMut = Default::default();
-
-            if let Some(first_nal) = sps_and_pps_payload.take() {
-                data.extend_from_slice(&first_nal);
+            if sample.is_sync {
+                data.extend_from_slice(&sps_and_pps_payload);
             }
+            let mut sample_data = sample.bytes.reader();
 
             // the mp4 sample contains one h264 access unit (possibly more than one NAL).
             // the NALs are stored as: <length_size bytes long big endian encoded length><the NAL>.
@@ -231,20 +278,31 @@ impl Mp4FileReader<VideoDecoderOptions> {
             codec: VideoCodec::H264,
         };
 
+        let sample_start_times = sample_start_times(track);
+        let timescale = track.timescale();
+        let to_duration = |start_time: &u64| sample_timestamp(*start_time as i64, timescale);
+        let keyframes = match track.trak.mdia.minf.stbl.stss {
+            Some(ref stss) => stss
+                .entries
+                .iter()
+                .filter_map(|sample_id| sample_start_times.get(sample_id.checked_sub(1)? as usize))
+                .map(to_duration)
+                .collect(),
+            // All samples are keyframes if the sync sample box is missing.
+            None => sample_start_times.iter().map(to_duration).collect(),
+        };
+
         Some(TrackInfo {
             sample_count: track.sample_count(),
-            timescale: track.timescale(),
+            timescale,
+            sample_start_times,
+            keyframes,
             decoder_options,
             track_id,
             sample_unpacker,
             chunk_kind: EncodedChunkKind::Video(VideoCodec::H264),
         })
     }
-
-    #[allow(dead_code)]
-    pub(crate) fn fragment_sender(&self) -> Option<Sender<PipelineEvent<Bytes>>> {
-        self.fragment_sender.clone()
-    }
 }
 
 impl<DecoderOptions: Clone + Send + 'static> Mp4FileReader<DecoderOptions> {
@@ -258,47 +316,61 @@ impl<DecoderOptions: Clone + Send + 'static> Mp4FileReader<DecoderOptions> {
             &mp4::Mp4Reader<Reader>,
         ) -> Option<TrackInfo<DecoderOptions, SampleUnpacker>>,
         fragment_receiver: Option<Receiver<PipelineEvent<Bytes>>>,
+        sender: ChunkSender,
         stop_thread: Arc<AtomicBool>,
+        playback: TrackPlayback,
         span: Span,
-    ) -> Result<Option<(Self, ChunkReceiver)>, Mp4Error> {
+    ) -> Result<Option<Self>, Mp4Error> {
         let reader = mp4::Mp4Reader::read_header(reader, size)?;
 
         let Some(track_info) = track_info_reader(&reader) else {
             return Ok(None);
         };
 
-        let (sender, receiver) = crossbeam_channel::bounded(10);
-
         let stop_thread_clone = stop_thread.clone();
         let decoder_options = track_info.decoder_options.clone();
+        let keyframes = track_info.keyframes.clone();
+        let duration = reader.duration();
         std::thread::Builder::new()
             .name("mp4 reader".to_string())
             .spawn(move || {
                 let _guard = span.enter();
-                run_reader_thread(
-                    reader,
-                    sender,
-                    stop_thread_clone,
-                    fragment_receiver,
-                    track_info,
-                );
+                match fragment_receiver {
+                    Some(fragment_receiver) => run_fragmented_reader_thread(
+                        reader,
+                        fragment_receiver,
+                        sender,
+                        stop_thread_clone,
+                        track_info,
+                        playback,
+                    ),
+                    None => {
+                        run_reader_thread(reader, sender, stop_thread_clone, track_info, playback)
+                    }
+                }
                 debug!("Closing MP4 reader thread");
             })
             .unwrap();
 
-        Ok(Some((
-            Mp4FileReader {
-                stop_thread,
-                fragment_sender: None,
-                decoder_options,
-            },
-            receiver,
-        )))
+        Ok(Some(Mp4FileReader {
+            stop_thread,
+            decoder_options,
+            keyframes,
+            duration,
+        }))
     }
 
     pub(crate) fn decoder_options(&self) -> DecoderOptions {
         self.decoder_options.clone()
     }
+
+    pub(crate) fn keyframes(&self) -> &[Duration] {
+        &self.keyframes
+    }
+
+    pub(crate) fn duration(&self) -> Duration {
+        self.duration
+    }
 }
 
 impl<D> Drop for Mp4FileReader<D> {
@@ -310,50 +382,310 @@ impl<D> Drop for Mp4FileReader<D> {
 
 fn run_reader_thread<Reader: Read + Seek, DecoderOptions>(
     mut reader: Mp4Reader<Reader>,
-    sender: Sender<PipelineEvent<EncodedChunk>>,
+    sender: ChunkSender,
     stop_thread: Arc<AtomicBool>,
-    _fragment_receiver: Option<Receiver<PipelineEvent<Bytes>>>,
     track_info: TrackInfo<DecoderOptions, impl FnMut(mp4::Mp4Sample) -> Bytes>,
+    mut playback: TrackPlayback,
 ) {
     let mut sample_unpacker = track_info.sample_unpacker;
-
-    for i in 1..track_info.sample_count {
+    let file_duration = reader.duration();
+    let mut position = ReaderPosition {
+        sample_id: 1,
+        timeline: Timeline::default(),
+        paused: false,
+    };
+
+    loop {
         if stop_thread.load(std::sync::atomic::Ordering::Relaxed) {
             return;
         }
 
-        match reader.read_sample(track_info.track_id, i) {
-            Ok(Some(sample)) => {
-                let rendering_offset = sample.rendering_offset;
-                let start_time = sample.start_time;
-                let data = sample_unpacker(sample);
-
-                let dts = Duration::from_secs_f64(start_time as f64 / track_info.timescale as f64);
-                let chunk = EncodedChunk {
-                    data,
-                    pts: Duration::from_secs_f64(
-                        (start_time as f64 + rendering_offset as f64) / track_info.timescale as f64,
-                    ),
-                    dts: Some(dts),
-                    kind: track_info.chunk_kind,
+        if position.paused {
+            let Some(command) = wait_for_command(&playback, &stop_thread) else {
+                return;
+            };
+            position.apply(
+                command,
+                &track_info.sample_start_times,
+                track_info.timescale,
+            );
+            continue;
+        }
+
+        if position.sample_id > track_info.sample_count {
+            if !playback.should_loop || track_info.sample_count == 0 {
+                let command = match playback.on_end_of_file() {
+                    EndOfFile::Command(command) => command,
+                    EndOfFile::Wait => match wait_for_command(&playback, &stop_thread) {
+                        Some(command) => command,
+                        None => return,
+                    },
+                    EndOfFile::Finish => break,
                 };
+                position.apply(
+                    command,
+                    &track_info.sample_start_times,
+                    track_info.timescale,
+                );
+                continue;
+            }
+            position.timeline.on_loop(file_duration);
+            position.sample_id = 1;
+            continue;
+        }
 
-                trace!(pts=?chunk.pts, "MP4 reader produced a chunk.");
-                match sender.send(PipelineEvent::Data(chunk)) {
-                    Ok(_) => {}
-                    Err(_) => {
-                        debug!("Failed to send MP4 chunk. Channel closed.");
-                        return;
+        let sample = match reader.read_sample(track_info.track_id, position.sample_id) {
+            Ok(Some(sample)) => sample,
+            Ok(None) => {
+                position.sample_id += 1;
+                continue;
+            }
+            Err(e) => {
+                warn!("Error while reading MP4 sample: {:?}", e);
+                position.sample_id += 1;
+                continue;
+            }
+        };
+
+        let (chunk, end) = chunk_from_sample(
+            sample,
+            &mut sample_unpacker,
+            track_info.timescale,
+            track_info.chunk_kind,
+            position.timeline,
+        );
+        if let Some(command) = record_chunk_end(&playback, end) {
+            position.apply(
+                command,
+                &track_info.sample_start_times,
+                track_info.timescale,
+            );
+            continue;
+        }
+        position.sample_id += 1;
+
+        trace!(pts=?chunk.pts, "MP4 reader produced a chunk.");
+        if sender.send(PipelineEvent::Data(chunk)).is_err() {
+            debug!("Failed to send MP4 chunk. Channel closed.");
+            return;
+        }
+    }
+    if let Err(_err) = sender.send(PipelineEvent::EOS) {
+        debug!("Failed to send EOS from MP4 reader. Channel closed.");
+    }
+}
+
+/// Reads samples of the track from movie fragments as they are received. `header_reader`
+/// contains only the movie header (`moov`), timestamps are taken from the fragments.
+fn run_fragmented_reader_thread<Reader: Read + Seek, DecoderOptions>(
+    header_reader: Mp4Reader<Reader>,
+    fragment_receiver: Receiver<PipelineEvent<Bytes>>,
+    sender: ChunkSender,
+    stop_thread: Arc<AtomicBool>,
+    track_info: TrackInfo<DecoderOptions, impl FnMut(mp4::Mp4Sample) -> Bytes>,
+    mut playback: TrackPlayback,
+) {
+    let mut sample_unpacker = track_info.sample_unpacker;
+    let mut position = ReaderPosition {
+        sample_id: 1,
+        timeline: Timeline::default(),
+        paused: false,
+    };
+    let mut fragment: Option<(Mp4Reader<Cursor<Bytes>>, u32)> = None;
+
+    loop {
+        if stop_thread.load(std::sync::atomic::Ordering::Relaxed) {
+            return;
+        }
+
+        if position.paused {
+            let Some(command) = wait_for_command(&playback, &stop_thread) else {
+                return;
+            };
+            position.apply(
+                command,
+                &track_info.sample_start_times,
+                track_info.timescale,
+            );
+            continue;
+        }
+
+        let fragment_finished = fragment
+            .as_ref()
+            .map_or(true, |(_, sample_count)| position.sample_id > *sample_count);
+        if fragment_finished {
+            match fragment_receiver.recv_timeout(Duration::from_millis(100)) {
+                Ok(PipelineEvent::Data(data)) => {
+                    let size = data.len() as u64;
+                    match header_reader.read_fragment_header(Cursor::new(data), size) {
+                        Ok(fragment_reader) => {
+                            let sample_count = fragment_reader
+                                .tracks()
+                                .get(&track_info.track_id)
+                                .map_or(0, |track| track.sample_count());
+                            fragment = Some((fragment_reader, sample_count));
+                            position.sample_id = 1;
+                        }
+                        Err(e) => warn!("Error while reading MP4 fragment: {:?}", e),
                     }
                 }
+                Ok(PipelineEvent::EOS) | Err(RecvTimeoutError::Disconnected) => {
+                    playback.on_end_of_stream();
+                    break;
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+            }
+            continue;
+        }
+        let Some((fragment_reader, _)) = fragment.as_mut() else {
+            continue;
+        };
+
+        let sample = match fragment_reader.read_sample(track_info.track_id, position.sample_id) {
+            Ok(Some(sample)) => sample,
+            Ok(None) => {
+                position.sample_id += 1;
+                continue;
             }
             Err(e) => {
-                warn!("Error while reading MP4 video sample: {:?}", e);
+                warn!("Error while reading MP4 sample: {:?}", e);
+                position.sample_id += 1;
+                continue;
             }
-            _ => {}
+        };
+
+        let (chunk, end) = chunk_from_sample(
+            sample,
+            &mut sample_unpacker,
+            track_info.timescale,
+            track_info.chunk_kind,
+            position.timeline,
+        );
+        if let Some(command) = record_chunk_end(&playback, end) {
+            position.apply(
+                command,
+                &track_info.sample_start_times,
+                track_info.timescale,
+            );
+            continue;
+        }
+        position.sample_id += 1;
+
+        trace!(pts=?chunk.pts, "MP4 reader produced a chunk.");
+        if sender.send(PipelineEvent::Data(chunk)).is_err() {
+            debug!("Failed to send MP4 chunk. Channel closed.");
+            return;
         }
     }
     if let Err(_err) = sender.send(PipelineEvent::EOS) {
-        debug!("Failed to send EOS from MP4 video reader. Channel closed.");
+        debug!("Failed to send EOS from MP4 reader. Channel closed.");
+    }
+}
+
+/// Returns the chunk and the end of the sample in the input timeline.
+fn chunk_from_sample(
+    sample: mp4::Mp4Sample,
+    sample_unpacker: &mut impl FnMut(mp4::Mp4Sample) -> Bytes,
+    timescale: u32,
+    chunk_kind: EncodedChunkKind,
+    timeline: Timeline,
+) -> (EncodedChunk, Duration) {
+    let start_time = sample.start_time as i64;
+    let rendering_offset = sample.rendering_offset as i64;
+    let sample_duration = sample.duration as i64;
+    let is_sync = sample.is_sync;
+    let data = sample_unpacker(sample);
+
+    let pts = sample_timestamp(start_time + rendering_offset, timescale);
+    let end = sample_timestamp(start_time + rendering_offset + sample_duration, timescale);
+    let chunk = EncodedChunk {
+        data,
+        pts: timeline.map(pts),
+        dts: Some(timeline.map(sample_timestamp(start_time, timescale))),
+        is_keyframe: match chunk_kind {
+            EncodedChunkKind::Video(_) if is_sync => IsKeyframe::Yes,
+            EncodedChunkKind::Video(_) => IsKeyframe::No,
+            EncodedChunkKind::Audio(_) => IsKeyframe::NoKeyframes,
+        },
+        kind: chunk_kind,
+    };
+    (chunk, timeline.map(end))
+}
+
+/// Commands are checked under the same lock that is used to compute the position of
+/// a seek, so the chunk is either accounted for in `last_end` or dropped. Returns
+/// the command if the chunk should be dropped.
+fn record_chunk_end(playback: &TrackPlayback, end: Duration) -> Option<PlaybackCommand> {
+    let mut state = playback.state.lock().unwrap();
+    if let Ok(command) = playback.commands.try_recv() {
+        return Some(command);
     }
+    state.last_end = state.last_end.max(end);
+    None
+}
+
+/// Blocks until the next command is received. Returns `None` if the thread should stop.
+fn wait_for_command(playback: &TrackPlayback, stop_thread: &AtomicBool) -> Option<PlaybackCommand> {
+    loop {
+        if stop_thread.load(std::sync::atomic::Ordering::Relaxed) {
+            return None;
+        }
+        match playback.commands.recv_timeout(Duration::from_millis(100)) {
+            Ok(command) => return Some(command),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return None,
+        }
+    }
+}
+
+struct ReaderPosition {
+    /// Next sample to read (sample ids start from 1).
+    sample_id: u32,
+    timeline: Timeline,
+    paused: bool,
+}
+
+impl ReaderPosition {
+    fn apply(&mut self, command: PlaybackCommand, sample_start_times: &[u64], timescale: u32) {
+        match command {
+            PlaybackCommand::Pause => self.paused = true,
+            PlaybackCommand::Resume { paused_for } => {
+                self.paused = false;
+                self.timeline.on_resume(paused_for);
+            }
+            PlaybackCommand::Seek { position, base } => {
+                // First sample that starts at or after the position. For video, the position
+                // is always a timestamp of a keyframe.
+                let index = sample_start_times.partition_point(|start_time| {
+                    sample_timestamp(*start_time as i64, timescale) < position
+                });
+                self.sample_id = index as u32 + 1;
+                self.timeline.on_seek(position, base);
+            }
+        }
+    }
+}
+
+fn sample_start_times(track: &mp4::Mp4Track) -> Vec<u64> {
+    track
+        .trak
+        .mdia
+        .minf
+        .stbl
+        .stts
+        .entries
+        .iter()
+        .flat_map(|entry| {
+            std::iter::repeat(entry.sample_delta as u64).take(entry.sample_count as usize)
+        })
+        .scan(0, |start_time, delta| {
+            let current = *start_time;
+            *start_time += delta;
+            Some(current)
+        })
+        .collect()
+}
+
+fn sample_timestamp(time: i64, timescale: u32) -> Duration {
+    Duration::from_secs_f64(time.max(0) as f64 / timescale as f64)
 }
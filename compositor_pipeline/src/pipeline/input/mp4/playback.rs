@@ -0,0 +1,372 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use compositor_render::InputId;
+use crossbeam_channel::{Receiver, Sender};
+
+#[derive(Debug, thiserror::Error)]
+pub enum Mp4PlaybackError {
+    #[error("Input \"{0}\" is not registered.")]
+    InputNotFound(InputId),
+
+    #[error("Input \"{0}\" is not an MP4 input.")]
+    NotMp4Input(InputId),
+
+    #[error("Playback of the MP4 input has already finished.")]
+    Finished,
+
+    #[error("MP4 input \"{0}\" is streamed and can not be seeked or restarted.")]
+    NotSeekable(InputId),
+
+    #[error("Position {position:?} is past the end of the MP4 file ({duration:?}).")]
+    PositionOutOfRange {
+        position: Duration,
+        duration: Duration,
+    },
+}
+
+pub(super) enum PlaybackCommand {
+    Pause,
+    Resume {
+        paused_for: Duration,
+    },
+    /// Continue reading from `position` (in the file timeline). Chunks read after the seek
+    /// start at `base` in the input timeline.
+    Seek {
+        position: Duration,
+        base: Duration,
+    },
+}
+
+#[derive(Debug, Default)]
+pub(super) struct PlaybackState {
+    /// End of the latest chunk produced by any of the tracks, in the input timeline.
+    pub(super) last_end: Duration,
+    /// Set when all tracks reached the end of the file and sent EOS.
+    pub(super) finished: bool,
+    /// Tracks that did not send EOS yet. Tracks missing from the file are counted as
+    /// ended when their `TrackPlayback` is dropped.
+    active_tracks: usize,
+    /// Set after the first playback command. Tracks of a controlled input do not send
+    /// EOS at the end of the file, they wait for a seek or restart instead.
+    controlled: bool,
+    /// When the first track started waiting at the end of the file, and `last_end` at
+    /// that moment.
+    waiting_since: Option<(Instant, Duration)>,
+    paused_at: Option<Instant>,
+}
+
+impl PlaybackState {
+    fn on_track_ended(&mut self) {
+        self.active_tracks = self.active_tracks.saturating_sub(1);
+        self.finished = self.active_tracks == 0;
+    }
+}
+
+/// What a track does after reading the last sample of a file that is not looped.
+pub(super) enum EndOfFile {
+    /// Command received before the end of the file was reached.
+    Command(PlaybackCommand),
+    /// Wait for the next command without sending EOS.
+    Wait,
+    /// Send EOS, the playback can not be controlled anymore.
+    Finish,
+}
+
+/// Maps timestamps from the file to the input timeline. Reading the file from `position`
+/// produces chunks starting at `base`.
+#[derive(Debug, Default, Clone, Copy)]
+pub(super) struct Timeline {
+    base: Duration,
+    position: Duration,
+}
+
+impl Timeline {
+    pub(super) fn map(&self, file_timestamp: Duration) -> Duration {
+        self.base + file_timestamp.saturating_sub(self.position)
+    }
+
+    /// Start the file over after reaching its end.
+    pub(super) fn on_loop(&mut self, file_duration: Duration) {
+        self.base += file_duration.saturating_sub(self.position);
+        self.position = Duration::ZERO;
+    }
+
+    pub(super) fn on_resume(&mut self, paused_for: Duration) {
+        self.base += paused_for;
+    }
+
+    pub(super) fn on_seek(&mut self, position: Duration, base: Duration) {
+        self.position = position;
+        self.base = base;
+    }
+}
+
+/// Part of the playback control passed to the reader thread of a single track.
+pub(super) struct TrackPlayback {
+    pub(super) commands: Receiver<PlaybackCommand>,
+    pub(super) state: Arc<Mutex<PlaybackState>>,
+    pub(super) should_loop: bool,
+    ended: bool,
+}
+
+impl TrackPlayback {
+    pub(super) fn on_end_of_file(&mut self) -> EndOfFile {
+        let mut state = self.state.lock().unwrap();
+        if let Ok(command) = self.commands.try_recv() {
+            return EndOfFile::Command(command);
+        }
+        if state.controlled {
+            let last_end = state.last_end;
+            state
+                .waiting_since
+                .get_or_insert((Instant::now(), last_end));
+            EndOfFile::Wait
+        } else {
+            // Marked under the same lock as the command check, so no command is
+            // accepted after the last track decided to finish.
+            if !self.ended {
+                self.ended = true;
+                state.on_track_ended();
+            }
+            EndOfFile::Finish
+        }
+    }
+
+    /// Marks the track as ended before it sends EOS.
+    pub(super) fn on_end_of_stream(&mut self) {
+        if !self.ended {
+            self.ended = true;
+            self.state.lock().unwrap().on_track_ended();
+        }
+    }
+}
+
+impl Drop for TrackPlayback {
+    fn drop(&mut self) {
+        if self.ended {
+            return;
+        }
+        if let Ok(mut state) = self.state.lock() {
+            state.on_track_ended();
+        }
+    }
+}
+
+/// Handle used to pause, resume and seek a registered MP4 input. Commands are applied by
+/// the reader threads of all tracks at the same point of the input timeline, so audio and
+/// video stay in sync and timestamps delivered to the queue never go back.
+#[derive(Clone)]
+pub struct Mp4Playback {
+    input_id: InputId,
+    commands: Vec<Sender<PlaybackCommand>>,
+    state: Arc<Mutex<PlaybackState>>,
+    /// Keyframes and duration of the file. `None` for streamed (fragmented) files.
+    file_info: Option<(Arc<[Duration]>, Duration)>,
+}
+
+impl Mp4Playback {
+    pub(super) fn new(
+        input_id: InputId,
+        should_loop: bool,
+        track_count: usize,
+    ) -> (Self, Vec<TrackPlayback>) {
+        let state = Arc::new(Mutex::new(PlaybackState {
+            active_tracks: track_count,
+            ..Default::default()
+        }));
+        let (commands, tracks) = (0..track_count)
+            .map(|_| {
+                let (sender, receiver) = crossbeam_channel::unbounded();
+                let track = TrackPlayback {
+                    commands: receiver,
+                    state: state.clone(),
+                    should_loop,
+                    ended: false,
+                };
+                (sender, track)
+            })
+            .unzip();
+
+        let playback = Self {
+            input_id,
+            commands,
+            state,
+            file_info: None,
+        };
+        (playback, tracks)
+    }
+
+    pub(super) fn set_file_info(&mut self, keyframes: Vec<Duration>, duration: Duration) {
+        self.file_info = Some((keyframes.into(), duration));
+    }
+
+    pub fn pause(&self) -> Result<(), Mp4PlaybackError> {
+        let mut state = self.state.lock().unwrap();
+        if state.paused_at.is_some() {
+            return Ok(());
+        }
+        self.send(&mut state, || PlaybackCommand::Pause)?;
+        state.paused_at = Some(Instant::now());
+        Ok(())
+    }
+
+    pub fn resume(&self) -> Result<(), Mp4PlaybackError> {
+        let mut state = self.state.lock().unwrap();
+        let Some(paused_at) = state.paused_at else {
+            return Ok(());
+        };
+        let paused_for = paused_at.elapsed();
+        self.send(&mut state, || PlaybackCommand::Resume { paused_for })?;
+        state.paused_at = None;
+        Ok(())
+    }
+
+    /// Continue playback from `position` in the file. The video track starts from the
+    /// nearest preceding keyframe, so the actual position can be earlier than requested.
+    pub fn seek(&self, position: Duration) -> Result<(), Mp4PlaybackError> {
+        let Some((ref keyframes, duration)) = self.file_info else {
+            return Err(Mp4PlaybackError::NotSeekable(self.input_id.clone()));
+        };
+        if position > duration {
+            return Err(Mp4PlaybackError::PositionOutOfRange { position, duration });
+        }
+        let keyframe = keyframes.iter().rev().find(|k| **k <= position);
+        let position = match keyframe {
+            Some(keyframe) => *keyframe,
+            // No video track
+            None if keyframes.is_empty() => position,
+            None => Duration::ZERO,
+        };
+
+        // Holding the lock guarantees that no track produces a chunk past `last_end`
+        // before it receives the command.
+        let mut state = self.state.lock().unwrap();
+        let mut base = state.last_end;
+        // Time spent waiting at the end of the file is not played again, like a pause.
+        // If the input is paused, the resume accounts for the rest of the wait.
+        if let Some((waiting_since, last_end)) = state.waiting_since.take() {
+            let waiting_until = state.paused_at.unwrap_or_else(Instant::now);
+            base = base.max(last_end + waiting_until.saturating_duration_since(waiting_since));
+        }
+        self.send(&mut state, || PlaybackCommand::Seek { position, base })
+    }
+
+    pub fn restart(&self) -> Result<(), Mp4PlaybackError> {
+        self.seek(Duration::ZERO)
+    }
+
+    fn send(
+        &self,
+        state: &mut PlaybackState,
+        command: impl Fn() -> PlaybackCommand,
+    ) -> Result<(), Mp4PlaybackError> {
+        if state.finished {
+            return Err(Mp4PlaybackError::Finished);
+        }
+        state.controlled = true;
+        for sender in self.commands.iter() {
+            // Fails only if the file does not contain the track.
+            let _ = sender.send(command());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use compositor_render::InputId;
+
+    use super::{EndOfFile, Mp4Playback, PlaybackCommand, Timeline};
+
+    fn file_playback() -> (Mp4Playback, super::TrackPlayback) {
+        let (mut playback, mut tracks) = Mp4Playback::new(InputId("input_1".into()), false, 1);
+        playback.set_file_info(vec![Duration::ZERO], Duration::from_secs(10));
+        (playback, tracks.remove(0))
+    }
+
+    #[test]
+    fn playback_finishes_at_end_of_file() {
+        let (playback, mut track) = file_playback();
+
+        assert!(matches!(track.on_end_of_file(), EndOfFile::Finish));
+        assert!(playback.restart().is_err());
+    }
+
+    #[test]
+    fn playback_finishes_after_all_tracks_end() {
+        let (playback, mut tracks) = Mp4Playback::new(InputId("input_1".into()), false, 3);
+        let mut audio = tracks.pop().unwrap();
+        let mut video = tracks.pop().unwrap();
+        // Track missing from the file
+        drop(tracks);
+
+        assert!(matches!(video.on_end_of_file(), EndOfFile::Finish));
+        assert!(matches!(video.on_end_of_file(), EndOfFile::Finish));
+        assert!(!playback.state.lock().unwrap().finished);
+
+        assert!(matches!(audio.on_end_of_file(), EndOfFile::Finish));
+        assert!(playback.state.lock().unwrap().finished);
+        assert!(playback.restart().is_err());
+    }
+
+    #[test]
+    fn controlled_playback_waits_at_end_of_file() {
+        let (playback, mut track) = file_playback();
+        track.state.lock().unwrap().last_end = Duration::from_secs(10);
+
+        playback.pause().unwrap();
+        playback.resume().unwrap();
+        assert!(matches!(
+            track.on_end_of_file(),
+            EndOfFile::Command(PlaybackCommand::Pause)
+        ));
+        assert!(matches!(
+            track.on_end_of_file(),
+            EndOfFile::Command(PlaybackCommand::Resume { .. })
+        ));
+        assert!(matches!(track.on_end_of_file(), EndOfFile::Wait));
+        assert!(matches!(track.on_end_of_file(), EndOfFile::Wait));
+
+        std::thread::sleep(Duration::from_millis(50));
+        playback.restart().unwrap();
+        let EndOfFile::Command(PlaybackCommand::Seek { position, base }) = track.on_end_of_file()
+        else {
+            panic!("Expected the restart to be received.");
+        };
+        assert_eq!(position, Duration::ZERO);
+        assert!(base >= Duration::from_millis(10_050));
+    }
+
+    #[test]
+    fn timeline_is_monotonic_across_loops_and_seeks() {
+        let mut timeline = Timeline::default();
+        assert_eq!(timeline.map(Duration::from_secs(3)), Duration::from_secs(3));
+
+        timeline.on_loop(Duration::from_secs(10));
+        assert_eq!(timeline.map(Duration::ZERO), Duration::from_secs(10));
+
+        timeline.on_seek(Duration::from_secs(8), Duration::from_secs(12));
+        assert_eq!(
+            timeline.map(Duration::from_secs(8)),
+            Duration::from_secs(12)
+        );
+        assert_eq!(
+            timeline.map(Duration::from_secs(9)),
+            Duration::from_secs(13)
+        );
+
+        timeline.on_resume(Duration::from_secs(5));
+        assert_eq!(
+            timeline.map(Duration::from_secs(9)),
+            Duration::from_secs(18)
+        );
+
+        timeline.on_loop(Duration::from_secs(10));
+        assert_eq!(timeline.map(Duration::ZERO), Duration::from_secs(19));
+    }
+}
@@ -0,0 +1,216 @@
+use std::{
+    net::Ipv4Addr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use compositor_render::InputId;
+use ffmpeg_next as ffmpeg;
+use tracing::trace;
+
+use crate::pipeline::{
+    decoder::{AudioDecoderOptions, DecoderOptions, VideoDecoderOptions},
+    rtp::{bind_to_requested_port, BindToPortError, RequestedPort},
+    AudioCodec, Port, VideoCodec,
+};
+
+use super::{file::start_live_demuxer, ChunksReceiver};
+
+/// If no data arrives for this long, the demuxer is restarted and waits for a new
+/// stream. It also bounds how long unregistering the input can take.
+const MPEG_TS_READ_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Size of the OS receive buffer for the UDP socket. High-bitrate streams lose
+/// packets with the default size.
+const MPEG_TS_SOCKET_BUFFER_SIZE: usize = 16 * 1024 * 1024;
+
+#[derive(Debug, thiserror::Error)]
+pub enum MpegTsReceiverError {
+    #[error("MPEG-TS input supports only H264 video, got {0:?}.")]
+    UnsupportedVideoCodec(VideoCodec),
+
+    #[error("Error while setting socket options.")]
+    SocketOptions(#[source] std::io::Error),
+
+    #[error("Error while binding the socket.")]
+    SocketBind(#[source] std::io::Error),
+
+    #[error("Failed to register input. Port: {0} is already used or not available.")]
+    PortAlreadyInUse(u16),
+
+    #[error("Failed to register input. All ports in range {lower_bound} to {upper_bound} are already used or not available.")]
+    AllPortsAlreadyInUse { lower_bound: u16, upper_bound: u16 },
+
+    #[error("Failed to join multicast group {0}.")]
+    MulticastJoin(Ipv4Addr, #[source] std::io::Error),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MulticastOptions {
+    pub group: Ipv4Addr,
+    /// Address of the local interface used to join the group. `0.0.0.0` lets
+    /// the OS choose one.
+    pub interface: Ipv4Addr,
+}
+
+pub struct MpegTsReceiverOptions {
+    pub port: RequestedPort,
+    pub multicast: Option<MulticastOptions>,
+    pub video: Option<VideoDecoderOptions>,
+    pub audio: Option<AudioDecoderOptions>,
+}
+
+/// Input that receives an MPEG-TS stream sent over UDP (unicast or multicast),
+/// without any RTP encapsulation.
+///
+/// The stream is received and demuxed by ffmpeg (`udp://` protocol), the same way
+/// SRT input is. When no data arrives for [`MPEG_TS_READ_TIMEOUT`], the demuxer waits
+/// for a new stream, and timestamps continue from where the previous one ended.
+pub struct MpegTsReceiver {
+    should_close: Arc<AtomicBool>,
+}
+
+impl MpegTsReceiver {
+    pub fn new(
+        input_id: &InputId,
+        opts: MpegTsReceiverOptions,
+    ) -> Result<(Self, ChunksReceiver, DecoderOptions, Port), MpegTsReceiverError> {
+        if let Some(video) = &opts.video {
+            if video.codec != VideoCodec::H264 {
+                return Err(MpegTsReceiverError::UnsupportedVideoCodec(video.codec));
+            }
+        }
+
+        let port = reserve_port(&opts)?;
+        let url = udp_url(port, opts.multicast);
+        let (should_close, chunks_receiver) = start_live_demuxer(
+            input_id,
+            "MPEG-TS UDP",
+            move || connect(&url),
+            opts.video.as_ref().map(|video| video.codec),
+            opts.audio.as_ref().map(|audio| match audio {
+                AudioDecoderOptions::Opus(_) => AudioCodec::Opus,
+                AudioDecoderOptions::Aac(_) => AudioCodec::Aac,
+            }),
+        );
+
+        Ok((
+            Self { should_close },
+            chunks_receiver,
+            DecoderOptions {
+                video: opts.video,
+                audio: opts.audio,
+            },
+            port,
+        ))
+    }
+}
+
+impl Drop for MpegTsReceiver {
+    fn drop(&mut self) {
+        self.should_close.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Picks a port from the requested range and checks that the multicast group can be
+/// joined, so configuration errors are reported when the input is registered. The
+/// socket is closed afterwards and ffmpeg binds the same port again.
+fn reserve_port(opts: &MpegTsReceiverOptions) -> Result<Port, MpegTsReceiverError> {
+    let socket = socket2::Socket::new(
+        socket2::Domain::IPV4,
+        socket2::Type::DGRAM,
+        Some(socket2::Protocol::UDP),
+    )
+    .map_err(MpegTsReceiverError::SocketOptions)?;
+
+    // ffmpeg binds with SO_REUSEADDR, so the port has to be checked the same way.
+    socket
+        .set_reuse_address(true)
+        .map_err(MpegTsReceiverError::SocketOptions)?;
+
+    let port = bind_to_requested_port(opts.port, &socket)?;
+
+    if let Some(multicast) = opts.multicast {
+        socket
+            .join_multicast_v4(&multicast.group, &multicast.interface)
+            .map_err(|err| MpegTsReceiverError::MulticastJoin(multicast.group, err))?;
+    }
+
+    Ok(port)
+}
+
+fn udp_url(port: Port, multicast: Option<MulticastOptions>) -> String {
+    let mut query = vec![
+        "reuse=1".to_string(),
+        format!("timeout={}", MPEG_TS_READ_TIMEOUT.as_micros()),
+        format!("buffer_size={MPEG_TS_SOCKET_BUFFER_SIZE}"),
+        "overrun_nonfatal=1".to_string(),
+    ];
+    let host = match multicast {
+        Some(multicast) => {
+            query.push(format!("localaddr={}", multicast.interface));
+            multicast.group
+        }
+        None => Ipv4Addr::UNSPECIFIED,
+    };
+    format!("udp://{host}:{}?{}", port.0, query.join("&"))
+}
+
+/// Opens the UDP socket and waits until the stream can be probed. Returns `None` if
+/// no stream arrived before the timeout.
+fn connect(url: &str) -> Option<ffmpeg::format::context::Input> {
+    let start = Instant::now();
+    match ffmpeg::format::input(url) {
+        Ok(input_ctx) => Some(input_ctx),
+        Err(err) => {
+            trace!("Failed to open MPEG-TS UDP input: {err}.");
+            // Errors other than a timeout are returned immediately.
+            std::thread::sleep(MPEG_TS_READ_TIMEOUT.saturating_sub(start.elapsed()));
+            None
+        }
+    }
+}
+
+impl From<BindToPortError> for MpegTsReceiverError {
+    fn from(value: BindToPortError) -> Self {
+        match value {
+            BindToPortError::SocketBind(err) => MpegTsReceiverError::SocketBind(err),
+            BindToPortError::PortAlreadyInUse(port) => MpegTsReceiverError::PortAlreadyInUse(port),
+            BindToPortError::AllPortsAlreadyInUse {
+                lower_bound,
+                upper_bound,
+            } => MpegTsReceiverError::AllPortsAlreadyInUse {
+                lower_bound,
+                upper_bound,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unicast_url() {
+        assert_eq!(
+            udp_url(Port(9000), None),
+            "udp://0.0.0.0:9000?reuse=1&timeout=1000000&buffer_size=16777216&overrun_nonfatal=1"
+        );
+    }
+
+    #[test]
+    fn multicast_url() {
+        let multicast = MulticastOptions {
+            group: Ipv4Addr::new(239, 0, 0, 1),
+            interface: Ipv4Addr::new(192, 168, 1, 10),
+        };
+        assert_eq!(
+            udp_url(Port(9000), Some(multicast)),
+            "udp://239.0.0.1:9000?reuse=1&timeout=1000000&buffer_size=16777216&overrun_nonfatal=1&localaddr=192.168.1.10"
+        );
+    }
+}
@@ -0,0 +1,79 @@
+use std::io::{self, Read};
+
+use bytes::Bytes;
+use crossbeam_channel::{Receiver, Sender};
+
+/// Number of chunks buffered between a client pushing a stream and the input reading it.
+/// Pushing blocks when the buffer is full, so a client can not send data faster than the
+/// input consumes it.
+const PUSH_CHANNEL_CAPACITY: usize = 16;
+
+/// Channel for a byte stream pushed to an input, e.g. in HTTP request bodies.
+pub(super) fn push_channel() -> (Sender<Bytes>, ChannelReader) {
+    let (sender, receiver) = crossbeam_channel::bounded(PUSH_CHANNEL_CAPACITY);
+    (sender, ChannelReader::new(receiver))
+}
+
+/// Blocking reader over chunks of a stream received from a channel. Returns EOF when
+/// the channel is closed.
+pub(super) struct ChannelReader {
+    receiver: Receiver<Bytes>,
+    current: Bytes,
+}
+
+impl ChannelReader {
+    fn new(receiver: Receiver<Bytes>) -> Self {
+        Self {
+            receiver,
+            current: Bytes::new(),
+        }
+    }
+}
+
+impl Read for ChannelReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.current.is_empty() {
+            match self.receiver.recv() {
+                Ok(data) => self.current = data,
+                Err(_) => return Ok(0),
+            }
+        }
+        let len = usize::min(buf.len(), self.current.len());
+        buf[..len].copy_from_slice(&self.current.split_to(len));
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use bytes::Bytes;
+
+    use super::push_channel;
+
+    #[test]
+    fn reads_chunks_across_boundaries() {
+        let (sender, mut reader) = push_channel();
+        sender.send(Bytes::from_static(&[1, 2, 3])).unwrap();
+        sender.send(Bytes::from_static(&[4, 5])).unwrap();
+        drop(sender);
+
+        let mut data = [0u8; 4];
+        reader.read_exact(&mut data).unwrap();
+        assert_eq!(data, [1, 2, 3, 4]);
+
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, [5]);
+    }
+
+    #[test]
+    fn push_blocks_when_buffer_is_full() {
+        let (sender, _reader) = push_channel();
+        for _ in 0..super::PUSH_CHANNEL_CAPACITY {
+            sender.try_send(Bytes::from_static(&[0])).unwrap();
+        }
+        assert!(sender.try_send(Bytes::from_static(&[0])).is_err());
+    }
+}
@@ -0,0 +1,563 @@
+use std::{
+    io::{self, Read},
+    os::unix::net::UnixListener,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use bytes::{Bytes, BytesMut};
+use compositor_render::{scene::RGBColor, Frame, InputId, Resolution, YuvData, YuvVariant};
+use crossbeam_channel::{bounded, Sender};
+use tracing::{debug, info, span, trace, warn, Level};
+
+use crate::{pipeline::decoder::DecodedDataReceiver, queue::PipelineEvent};
+
+use super::push::push_channel;
+
+/// Size of the header that precedes every frame.
+const FRAME_HEADER_SIZE: usize = 24;
+
+/// Upper limit of the frame width and height. Frame data is allocated up front based on
+/// the header, so without it a single header could allocate gigabytes of memory.
+const MAX_FRAME_DIMENSION: usize = 7680;
+
+/// How often a blocked read on the socket checks if the input was unregistered.
+const SOCKET_READ_TIMEOUT: Duration = Duration::from_millis(50);
+
+const FORMAT_RGBA: u8 = 0;
+const FORMAT_YUV420: u8 = 1;
+
+#[derive(Debug, thiserror::Error)]
+pub enum RawInputError {
+    #[error("Failed to bind Unix socket \"{0}\".")]
+    SocketBind(PathBuf, #[source] io::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RawFramePushError {
+    #[error("Input \"{0}\" is not registered.")]
+    InputNotFound(InputId),
+
+    #[error("Input \"{0}\" is not a raw input that accepts pushed frames.")]
+    NotPushInput(InputId),
+
+    #[error("Raw input \"{0}\" does not accept more data.")]
+    Closed(InputId),
+}
+
+#[derive(Debug, Clone)]
+pub enum RawInputTransport {
+    /// Frames are pushed with [`RawFramePushSender`].
+    Push,
+    /// Frames are read from clients connected to a Unix socket created at the path.
+    /// Clients are handled one after another.
+    UnixSocket(PathBuf),
+}
+
+#[derive(Debug, Clone)]
+pub struct RawInputOptions {
+    pub transport: RawInputTransport,
+}
+
+/// Handle used to push raw frames to a registered raw input. Data can be split into chunks
+/// at arbitrary points, chunks have to be pushed in order. Pushing blocks while the input
+/// is behind.
+#[derive(Clone)]
+pub struct RawFramePushSender {
+    input_id: InputId,
+    sender: Sender<Bytes>,
+}
+
+impl RawFramePushSender {
+    pub fn push(&self, data: Bytes) -> Result<(), RawFramePushError> {
+        self.sender
+            .send(data)
+            .map_err(|_| RawFramePushError::Closed(self.input_id.clone()))
+    }
+}
+
+/// Input that receives uncompressed video frames, e.g. generated by another process.
+///
+/// Every frame is preceded by a 24-byte big-endian header:
+/// - PTS in microseconds (`u64`)
+/// - width and height (`u32` each)
+/// - pixel format (`u8`, `0` for RGBA, `1` for planar YUV 4:2:0), followed by 3 reserved bytes
+/// - size of the frame data in bytes (`u32`)
+///
+/// Frames are not decoded, they are sent directly to the queue. RGBA frames are converted
+/// to YUV, so the alpha channel is ignored.
+pub struct RawInput {
+    push_sender: Option<RawFramePushSender>,
+    stop_thread: Arc<AtomicBool>,
+    /// Unix socket created by the input, removed when the input is unregistered.
+    socket_path: Option<PathBuf>,
+}
+
+impl RawInput {
+    pub fn new(
+        input_id: &InputId,
+        opts: RawInputOptions,
+    ) -> Result<(Self, DecodedDataReceiver), RawInputError> {
+        let (frame_sender, frame_receiver) = bounded(10);
+        let stop_thread = Arc::new(AtomicBool::new(false));
+        let span = span!(Level::INFO, "raw input", input_id = input_id.to_string());
+
+        let input = match opts.transport {
+            RawInputTransport::Push => {
+                let (push_sender, push_reader) = push_channel();
+                let thread_stop = stop_thread.clone();
+                std::thread::Builder::new()
+                    .name(format!("raw frame reader {input_id}"))
+                    .spawn(move || {
+                        let _guard = span.enter();
+                        let mut reader = FrameReader::new(frame_sender);
+                        let _ = reader.run(push_reader, &thread_stop);
+                        debug!("Closing raw frame reader thread.");
+                    })
+                    .unwrap();
+
+                Self {
+                    push_sender: Some(RawFramePushSender {
+                        input_id: input_id.clone(),
+                        sender: push_sender,
+                    }),
+                    stop_thread,
+                    socket_path: None,
+                }
+            }
+            RawInputTransport::UnixSocket(path) => {
+                let listener = UnixListener::bind(&path)
+                    .and_then(|listener| listener.set_nonblocking(true).map(|_| listener))
+                    .map_err(|err| RawInputError::SocketBind(path.clone(), err))?;
+                let thread_stop = stop_thread.clone();
+                std::thread::Builder::new()
+                    .name(format!("raw frame socket {input_id}"))
+                    .spawn(move || {
+                        let _guard = span.enter();
+                        run_socket_thread(listener, frame_sender, thread_stop);
+                        debug!("Closing raw frame socket thread.");
+                    })
+                    .unwrap();
+
+                Self {
+                    push_sender: None,
+                    stop_thread,
+                    socket_path: Some(path),
+                }
+            }
+        };
+
+        Ok((
+            input,
+            DecodedDataReceiver {
+                video: Some(frame_receiver),
+                audio: None,
+            },
+        ))
+    }
+
+    /// Returns `None` if the input does not accept pushed frames.
+    pub fn push_sender(&self) -> Option<RawFramePushSender> {
+        self.push_sender.clone()
+    }
+}
+
+impl Drop for RawInput {
+    fn drop(&mut self) {
+        self.stop_thread.store(true, Ordering::Relaxed);
+        if let Some(ref path) = self.socket_path {
+            if let Err(err) = std::fs::remove_file(path) {
+                warn!(
+                    ?path,
+                    "Failed to remove Unix socket of the raw input: {err}"
+                );
+            }
+        }
+    }
+}
+
+fn run_socket_thread(
+    listener: UnixListener,
+    frame_sender: Sender<PipelineEvent<Frame>>,
+    stop_thread: Arc<AtomicBool>,
+) {
+    let mut reader = FrameReader::new(frame_sender);
+    while !stop_thread.load(Ordering::Relaxed) {
+        let stream = match listener.accept() {
+            Ok((stream, _)) => stream,
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                std::thread::sleep(Duration::from_millis(100));
+                continue;
+            }
+            Err(err) => {
+                warn!("Failed to accept a connection on the raw input socket: {err}");
+                continue;
+            }
+        };
+        if let Err(err) = stream.set_nonblocking(false) {
+            warn!("Failed to configure the raw input connection: {err}");
+            continue;
+        }
+        if let Err(err) = stream.set_read_timeout(Some(SOCKET_READ_TIMEOUT)) {
+            warn!("Failed to configure the raw input connection: {err}");
+            continue;
+        }
+
+        info!("Raw input client connected.");
+        let stream = StoppableReader {
+            inner: stream,
+            stop_thread: &stop_thread,
+        };
+        if reader.run(stream, &stop_thread).is_err() {
+            return;
+        }
+        info!("Raw input client disconnected.");
+    }
+}
+
+/// Retries reads that timed out until data arrives or the input is unregistered.
+/// Retrying inside `read` keeps a partially read frame intact.
+struct StoppableReader<'a, R> {
+    inner: R,
+    stop_thread: &'a AtomicBool,
+}
+
+impl<R: Read> Read for StoppableReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            match self.inner.read(buf) {
+                Err(err)
+                    if matches!(
+                        err.kind(),
+                        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                    ) =>
+                {
+                    if self.stop_thread.load(Ordering::Relaxed) {
+                        return Err(io::Error::new(
+                            io::ErrorKind::ConnectionAborted,
+                            "Input was unregistered.",
+                        ));
+                    }
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+struct ChannelClosed;
+
+struct FrameReader {
+    sender: Sender<PipelineEvent<Frame>>,
+    /// PTS of the first received frame, frames are sent relative to it.
+    first_pts: Option<Duration>,
+}
+
+impl FrameReader {
+    fn new(sender: Sender<PipelineEvent<Frame>>) -> Self {
+        Self {
+            sender,
+            first_pts: None,
+        }
+    }
+
+    /// Reads frames until the end of the stream or until the input is unregistered.
+    fn run(
+        &mut self,
+        mut reader: impl Read,
+        stop_thread: &AtomicBool,
+    ) -> Result<(), ChannelClosed> {
+        let mut header = [0u8; FRAME_HEADER_SIZE];
+        while !stop_thread.load(Ordering::Relaxed) {
+            match reader.read_exact(&mut header) {
+                Ok(()) => {}
+                Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+                Err(_) if stop_thread.load(Ordering::Relaxed) => return Ok(()),
+                Err(err) => {
+                    warn!("Failed to read raw frame: {err}");
+                    return Ok(());
+                }
+            }
+            let header = match FrameHeader::parse(&header) {
+                Ok(header) => header,
+                Err((err, data_len)) => {
+                    warn!("Skipping invalid raw frame: {err}");
+                    let skipped = io::copy(&mut (&mut reader).take(data_len), &mut io::sink());
+                    if skipped.ok() != Some(data_len) {
+                        return Ok(());
+                    }
+                    continue;
+                }
+            };
+
+            let mut data = BytesMut::zeroed(header.data_len);
+            if let Err(err) = reader.read_exact(&mut data) {
+                if !stop_thread.load(Ordering::Relaxed) {
+                    warn!("Failed to read raw frame: {err}");
+                }
+                return Ok(());
+            }
+
+            let frame = header.frame(data.freeze());
+            let first_pts = *self.first_pts.get_or_insert(frame.pts);
+            let frame = Frame {
+                pts: frame.pts.saturating_sub(first_pts),
+                ..frame
+            };
+
+            trace!(pts=?frame.pts, "Received raw frame.");
+            if self.sender.send(PipelineEvent::Data(frame)).is_err() {
+                debug!("Failed to send raw frame. Channel closed.");
+                return Err(ChannelClosed);
+            }
+        }
+        Ok(())
+    }
+}
+
+enum PixelFormat {
+    Rgba,
+    Yuv420,
+}
+
+struct FrameHeader {
+    pts: Duration,
+    resolution: Resolution,
+    format: PixelFormat,
+    data_len: usize,
+}
+
+impl FrameHeader {
+    /// On error, returns the size of the frame data that has to be skipped.
+    fn parse(header: &[u8; FRAME_HEADER_SIZE]) -> Result<Self, (String, u64)> {
+        let u32_at =
+            |i: usize| u32::from_be_bytes([header[i], header[i + 1], header[i + 2], header[i + 3]]);
+        let mut pts = [0u8; 8];
+        pts.copy_from_slice(&header[..8]);
+        let (width, height) = (u32_at(8) as usize, u32_at(12) as usize);
+        let data_len = u32_at(20) as usize;
+        let invalid = |err: String| Err((err, data_len as u64));
+
+        if width == 0 || height == 0 || width % 2 != 0 || height % 2 != 0 {
+            return invalid(format!(
+                "Resolution {width}x{height} is not supported, dimensions have to be even."
+            ));
+        }
+        if width > MAX_FRAME_DIMENSION || height > MAX_FRAME_DIMENSION {
+            return invalid(format!(
+                "Resolution {width}x{height} exceeds the {MAX_FRAME_DIMENSION}px limit."
+            ));
+        }
+        let (format, expected_len) = match header[16] {
+            FORMAT_RGBA => (PixelFormat::Rgba, width * height * 4),
+            FORMAT_YUV420 => (PixelFormat::Yuv420, width * height * 3 / 2),
+            format => return invalid(format!("Unknown pixel format {format}.")),
+        };
+        if data_len != expected_len {
+            return invalid(format!(
+                "Expected {expected_len} bytes of frame data, got {data_len}."
+            ));
+        }
+
+        Ok(Self {
+            pts: Duration::from_micros(u64::from_be_bytes(pts)),
+            resolution: Resolution { width, height },
+            format,
+            data_len,
+        })
+    }
+
+    fn frame(&self, data: Bytes) -> Frame {
+        let Resolution { width, height } = self.resolution;
+        let data = match self.format {
+            PixelFormat::Rgba => rgba_to_yuv420(&data, self.resolution),
+            PixelFormat::Yuv420 => {
+                let chroma_len = width * height / 4;
+                YuvData {
+                    variant: YuvVariant::YUV420P,
+                    y_plane: data.slice(..width * height),
+                    u_plane: data.slice(width * height..width * height + chroma_len),
+                    v_plane: data.slice(width * height + chroma_len..),
+                }
+            }
+        };
+        Frame {
+            data,
+            resolution: self.resolution,
+            pts: self.pts,
+        }
+    }
+}
+
+/// Converts RGBA pixels to full range YUV 4:2:0, the same way the renderer does. Chroma is
+/// averaged over 2x2 blocks.
+fn rgba_to_yuv420(data: &[u8], resolution: Resolution) -> YuvData {
+    let Resolution { width, height } = resolution;
+    let pixel = |x: usize, y: usize| {
+        let i = (y * width + x) * 4;
+        RGBColor(data[i], data[i + 1], data[i + 2])
+    };
+    let to_byte = |value: f32| (value * 255.0).round() as u8;
+
+    let mut y_plane = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            y_plane.push(to_byte(pixel(x, y).to_yuv().0));
+        }
+    }
+
+    let mut u_plane = Vec::with_capacity(width * height / 4);
+    let mut v_plane = Vec::with_capacity(width * height / 4);
+    for y in (0..height).step_by(2) {
+        for x in (0..width).step_by(2) {
+            let block = [
+                pixel(x, y),
+                pixel(x + 1, y),
+                pixel(x, y + 1),
+                pixel(x + 1, y + 1),
+            ];
+            let average = |channel: fn(&RGBColor) -> u8| {
+                (block.iter().map(|p| channel(p) as u16).sum::<u16>() / 4) as u8
+            };
+            let (_, u, v) = RGBColor(average(|p| p.0), average(|p| p.1), average(|p| p.2)).to_yuv();
+            u_plane.push(to_byte(u));
+            v_plane.push(to_byte(v));
+        }
+    }
+
+    YuvData {
+        variant: YuvVariant::YUV420P,
+        y_plane: y_plane.into(),
+        u_plane: u_plane.into(),
+        v_plane: v_plane.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{self, Cursor, Read},
+        sync::atomic::{AtomicBool, Ordering},
+        thread,
+        time::Duration,
+    };
+
+    use compositor_render::Resolution;
+
+    use crate::queue::PipelineEvent;
+
+    use super::{FrameReader, StoppableReader, FORMAT_RGBA, FORMAT_YUV420, MAX_FRAME_DIMENSION};
+
+    fn raw_frame(pts_us: u64, width: u32, height: u32, format: u8, data: &[u8]) -> Vec<u8> {
+        let mut frame = pts_us.to_be_bytes().to_vec();
+        frame.extend_from_slice(&width.to_be_bytes());
+        frame.extend_from_slice(&height.to_be_bytes());
+        frame.extend_from_slice(&[format, 0, 0, 0]);
+        frame.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        frame.extend_from_slice(data);
+        frame
+    }
+
+    #[test]
+    fn reads_raw_frames() {
+        let yuv: Vec<u8> = (0..6).collect();
+        let white_rgba = [255; 16];
+        let stream = [
+            raw_frame(1_000_000, 2, 2, FORMAT_YUV420, &yuv),
+            // Invalid size, skipped.
+            raw_frame(1_020_000, 2, 2, FORMAT_YUV420, &yuv[..5]),
+            raw_frame(1_040_000, 2, 2, FORMAT_RGBA, &white_rgba),
+        ]
+        .concat();
+
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        let mut reader = FrameReader::new(sender);
+        assert!(reader
+            .run(Cursor::new(stream), &AtomicBool::new(false))
+            .is_ok());
+
+        let frames: Vec<_> = receiver
+            .try_iter()
+            .map(|event| match event {
+                PipelineEvent::Data(frame) => frame,
+                PipelineEvent::EOS => panic!("Unexpected EOS."),
+            })
+            .collect();
+        assert_eq!(frames.len(), 2);
+
+        assert_eq!(frames[0].pts, Duration::ZERO);
+        assert_eq!(frames[0].data.y_plane.as_ref(), [0, 1, 2, 3]);
+        assert_eq!(frames[0].data.u_plane.as_ref(), [4]);
+        assert_eq!(frames[0].data.v_plane.as_ref(), [5]);
+
+        assert_eq!(frames[1].pts, Duration::from_millis(40));
+        assert_eq!(frames[1].data.y_plane.as_ref(), [255; 4]);
+        assert_eq!(frames[1].data.u_plane.as_ref(), [128]);
+        assert_eq!(frames[1].data.v_plane.as_ref(), [128]);
+    }
+
+    struct IdleSocket;
+
+    impl Read for IdleSocket {
+        fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+            thread::sleep(Duration::from_millis(10));
+            Err(io::ErrorKind::WouldBlock.into())
+        }
+    }
+
+    #[test]
+    fn stops_reading_idle_socket_when_unregistered() {
+        let stop_thread = AtomicBool::new(false);
+        let (sender, _receiver) = crossbeam_channel::unbounded();
+        let mut reader = FrameReader::new(sender);
+
+        thread::scope(|scope| {
+            let handle = scope.spawn(|| {
+                let socket = StoppableReader {
+                    inner: IdleSocket,
+                    stop_thread: &stop_thread,
+                };
+                reader.run(socket, &stop_thread).is_ok()
+            });
+            thread::sleep(Duration::from_millis(50));
+            stop_thread.store(true, Ordering::Relaxed);
+            assert!(handle.join().unwrap());
+        });
+    }
+
+    #[test]
+    fn skips_frames_above_max_resolution() {
+        let width = MAX_FRAME_DIMENSION as u32 + 2;
+        let too_large = vec![0; width as usize * 2 * 3 / 2];
+        let stream = [
+            raw_frame(0, width, 2, FORMAT_YUV420, &too_large),
+            raw_frame(40_000, 2, 2, FORMAT_YUV420, &[0; 6]),
+        ]
+        .concat();
+
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        let mut reader = FrameReader::new(sender);
+        assert!(reader
+            .run(Cursor::new(stream), &AtomicBool::new(false))
+            .is_ok());
+
+        let resolutions: Vec<_> = receiver
+            .try_iter()
+            .map(|event| match event {
+                PipelineEvent::Data(frame) => frame.resolution,
+                PipelineEvent::EOS => panic!("Unexpected EOS."),
+            })
+            .collect();
+        assert_eq!(
+            resolutions,
+            [Resolution {
+                width: 2,
+                height: 2
+            }]
+        );
+    }
+}
@@ -1,4 +1,9 @@
-use std::sync::{atomic::AtomicBool, Arc};
+use std::{
+    mem,
+    sync::{atomic::AtomicBool, Arc, Mutex},
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
 
 use crate::{
     pipeline::{
@@ -11,21 +16,32 @@ use crate::{
     queue::PipelineEvent,
 };
 use compositor_render::InputId;
-use crossbeam_channel::{bounded, Receiver, Sender};
-use rtcp::header::PacketType;
+use crossbeam_channel::{bounded, unbounded, Receiver, RecvTimeoutError, Sender};
+use rtcp::{header::PacketType, sender_report::SenderReport};
 use tracing::{debug, error, span, warn, Level};
 use webrtc_util::Unmarshal;
 
 use self::{
-    depayloader::{Depayloader, DepayloaderNewError},
+    depayloader::Depayloader,
+    feedback::RtcpFeedback,
+    jitter_buffer::{JitterBuffer, JitterBufferEvent},
+    tcp_client::start_tcp_client_thread,
     tcp_server::start_tcp_server_thread,
-    udp::start_udp_reader_thread,
+    timestamp_sync::TimestampSync,
+    udp::{register_shared_udp_input, start_udp_reader_thread, SharedSocketRegistration},
 };
 
+pub use self::depayloader::DepayloaderNewError;
+pub use self::udp::SharedUdpSockets;
+
 use super::ChunksReceiver;
 
 mod depayloader;
+mod feedback;
+mod jitter_buffer;
+mod tcp_client;
 mod tcp_server;
+mod timestamp_sync;
 mod udp;
 
 #[derive(Debug, thiserror::Error)]
@@ -42,24 +58,53 @@ pub enum RtpReceiverError {
     #[error("Failed to register input. All ports in range {lower_bound} to {upper_bound} are already used or not available.")]
     AllPortsAlreadyInUse { lower_bound: u16, upper_bound: u16 },
 
+    #[error("Failed to register input. SSRC {ssrc} on port {port} is already used by a different input.")]
+    SsrcAlreadyInUse { port: u16, ssrc: u32 },
+
+    #[error("Failed to register input. Port range can not be used when connecting to a remote TCP server.")]
+    PortRangeWithTcpClient,
+
     #[error(transparent)]
     DepayloaderError(#[from] DepayloaderNewError),
 }
 
+#[derive(Debug, thiserror::Error)]
+pub enum RtpInputStatsError {
+    #[error("Input \"{0}\" is not registered.")]
+    InputNotFound(InputId),
+
+    #[error("Input \"{0}\" is not an RTP, RTSP or WHIP input.")]
+    NotRtpInput(InputId),
+}
+
+/// Jitter buffer latency used by inputs that don't allow configuring it.
+pub const DEFAULT_JITTER_BUFFER_LATENCY: Duration = Duration::from_millis(100);
+
 pub struct RtpReceiverOptions {
+    /// Local port (or port range) to listen on, or a port of the remote server if
+    /// [`TransportProtocol::TcpClient`] is used.
     pub port: RequestedPort,
     pub transport_protocol: TransportProtocol,
     pub stream: RtpStream,
+    /// How long packets are held while waiting for a missing packet that
+    /// precedes them.
+    pub jitter_buffer_latency: Duration,
+    /// SSRCs of the streams received by this input. If defined, the UDP port can be
+    /// shared with other inputs that define SSRCs, packets are routed to inputs
+    /// based on their SSRC. Only supported with UDP transport.
+    pub ssrc: Option<Vec<u32>>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct InputVideoStream {
     pub options: decoder::VideoDecoderOptions,
+    pub payload_type: u8,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct InputAudioStream {
     pub options: decoder::AudioDecoderOptions,
+    pub payload_type: u8,
 }
 
 pub struct OutputAudioStream {
@@ -73,8 +118,33 @@ pub struct RtpStream {
     pub audio: Option<InputAudioStream>,
 }
 
+/// Packet statistics of a single track of an RTP based input.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RtpTrackStats {
+    /// Number of received packets, including late and duplicated ones.
+    pub packets_received: u64,
+    /// Number of packets that did not arrive within the jitter buffer latency.
+    pub packets_lost: u64,
+    /// Number of packets dropped because they were duplicated or arrived after
+    /// they were already reported as lost.
+    pub packets_late: u64,
+    pub nacks_sent: u64,
+    pub plis_sent: u64,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RtpInputStats {
+    pub video: Option<RtpTrackStats>,
+    pub audio: Option<RtpTrackStats>,
+}
+
 pub struct RtpReceiver {
     should_close: Arc<AtomicBool>,
+    stats: Arc<Mutex<RtpInputStats>>,
+    /// Set if the input shares the UDP port with other inputs.
+    _shared_socket_registration: Option<SharedSocketRegistration>,
+    /// Set if the input uses its own UDP socket. It is closed when the thread ends.
+    udp_receiver_thread: Option<JoinHandle<()>>,
     pub port: u16,
 }
 
@@ -82,26 +152,73 @@ impl RtpReceiver {
     pub fn new(
         input_id: &InputId,
         opts: RtpReceiverOptions,
+        shared_udp_sockets: &SharedUdpSockets,
     ) -> Result<(Self, ChunksReceiver, DecoderOptions, Port), RtpReceiverError> {
         let should_close = Arc::new(AtomicBool::new(false));
+        let (feedback_sender, feedback_receiver) = unbounded();
+        let mut udp_receiver_thread = None;
 
-        let (port, packets_rx) = match opts.transport_protocol {
-            TransportProtocol::Udp => {
-                start_udp_reader_thread(input_id, &opts, should_close.clone())?
-            }
-            TransportProtocol::TcpServer => {
-                start_tcp_server_thread(input_id, &opts, should_close.clone())?
-            }
-        };
-
-        let depayloader = Depayloader::new(&opts.stream)?;
+        let (port, packets_rx, shared_socket_registration) =
+            match (&opts.transport_protocol, &opts.ssrc) {
+                (TransportProtocol::Udp, Some(ssrcs)) => {
+                    let (port, packets_rx, registration) = register_shared_udp_input(
+                        shared_udp_sockets,
+                        input_id,
+                        &opts,
+                        ssrcs,
+                        feedback_receiver,
+                    )?;
+                    (port, packets_rx, Some(registration))
+                }
+                (TransportProtocol::Udp, None) => {
+                    let (port, packets_rx, receiver_thread) = start_udp_reader_thread(
+                        input_id,
+                        &opts,
+                        feedback_receiver,
+                        should_close.clone(),
+                    )?;
+                    udp_receiver_thread = Some(receiver_thread);
+                    (port, packets_rx, None)
+                }
+                (TransportProtocol::TcpServer, _) => {
+                    let (port, packets_rx) = start_tcp_server_thread(
+                        input_id,
+                        &opts,
+                        feedback_receiver,
+                        should_close.clone(),
+                    )?;
+                    (port, packets_rx, None)
+                }
+                (TransportProtocol::TcpClient { ip }, _) => {
+                    let RequestedPort::Exact(port) = opts.port else {
+                        return Err(RtpReceiverError::PortRangeWithTcpClient);
+                    };
+                    let (port, packets_rx) = start_tcp_client_thread(
+                        input_id,
+                        ip.clone(),
+                        port,
+                        feedback_receiver,
+                        should_close.clone(),
+                    )?;
+                    (port, packets_rx, None)
+                }
+            };
 
-        let chunks_receiver = Self::start_depayloader_thread(input_id, packets_rx, depayloader);
+        let (chunks_receiver, stats) = start_depayloader_thread(
+            input_id,
+            packets_rx,
+            &opts.stream,
+            opts.jitter_buffer_latency,
+            Some(feedback_sender),
+        )?;
 
         Ok((
             Self {
                 port: port.0,
                 should_close,
+                stats,
+                _shared_socket_registration: shared_socket_registration,
+                udp_receiver_thread,
             },
             chunks_receiver,
             DecoderOptions {
@@ -112,40 +229,8 @@ impl RtpReceiver {
         ))
     }
 
-    fn start_depayloader_thread(
-        input_id: &InputId,
-        receiver: Receiver<bytes::Bytes>,
-        depayloader: Depayloader,
-    ) -> ChunksReceiver {
-        let (video_sender, video_receiver) = depayloader
-            .video
-            .as_ref()
-            .map(|_| bounded(5))
-            .map_or((None, None), |(tx, rx)| (Some(tx), Some(rx)));
-        let (audio_sender, audio_receiver) = depayloader
-            .audio
-            .as_ref()
-            .map(|_| bounded(5))
-            .map_or((None, None), |(tx, rx)| (Some(tx), Some(rx)));
-
-        let input_id = input_id.clone();
-        std::thread::Builder::new()
-            .name(format!("Depayloading thread for input: {}", input_id.0))
-            .spawn(move || {
-                let _span = span!(
-                    Level::INFO,
-                    "RTP depayloader",
-                    input_id = input_id.to_string()
-                )
-                .entered();
-                run_depayloader_thread(receiver, depayloader, video_sender, audio_sender)
-            })
-            .unwrap();
-
-        ChunksReceiver {
-            video: video_receiver,
-            audio: audio_receiver,
-        }
+    pub fn stats(&self) -> RtpInputStats {
+        *self.stats.lock().unwrap()
     }
 }
 
@@ -153,19 +238,104 @@ impl Drop for RtpReceiver {
     fn drop(&mut self) {
         self.should_close
             .store(true, std::sync::atomic::Ordering::Relaxed);
+        // Waits until the socket is closed, so the port can be registered again
+        // right after the input is unregistered.
+        if let Some(receiver_thread) = self.udp_receiver_thread.take() {
+            if receiver_thread.join().is_err() {
+                warn!(port = self.port, "RTP UDP receiver thread panicked.");
+            }
+        }
     }
 }
 
+/// Starts a thread that depayloads RTP packets received on `receiver`. Packets are
+/// expected to use payload types specified in `stream`.
+///
+/// If `feedback_sender` is provided, RTCP NACK and PLI packets addressed to the
+/// sender of the stream are sent through it.
+pub(super) fn start_depayloader_thread(
+    input_id: &InputId,
+    receiver: Receiver<bytes::Bytes>,
+    stream: &RtpStream,
+    jitter_buffer_latency: Duration,
+    feedback_sender: Option<Sender<bytes::Bytes>>,
+) -> Result<(ChunksReceiver, Arc<Mutex<RtpInputStats>>), DepayloaderNewError> {
+    let depayloader = Depayloader::new(stream)?;
+
+    let (video_sender, video_receiver) = depayloader
+        .video
+        .as_ref()
+        .map(|_| bounded(5))
+        .map_or((None, None), |(tx, rx)| (Some(tx), Some(rx)));
+    let (audio_sender, audio_receiver) = depayloader
+        .audio
+        .as_ref()
+        .map(|_| bounded(5))
+        .map_or((None, None), |(tx, rx)| (Some(tx), Some(rx)));
+
+    let stats = Arc::new(Mutex::new(RtpInputStats::default()));
+    let thread_stats = stats.clone();
+    let input_id = input_id.clone();
+    std::thread::Builder::new()
+        .name(format!("Depayloading thread for input: {}", input_id.0))
+        .spawn(move || {
+            let _span = span!(
+                Level::INFO,
+                "RTP depayloader",
+                input_id = input_id.to_string()
+            )
+            .entered();
+            run_depayloader_thread(
+                receiver,
+                depayloader,
+                video_sender,
+                audio_sender,
+                jitter_buffer_latency,
+                feedback_sender.map(RtcpFeedback::new),
+                thread_stats,
+            )
+        })
+        .unwrap();
+
+    Ok((
+        ChunksReceiver {
+            video: video_receiver,
+            audio: audio_receiver,
+        },
+        stats,
+    ))
+}
+
 fn run_depayloader_thread(
     receiver: Receiver<bytes::Bytes>,
     mut depayloader: Depayloader,
     video_sender: Option<Sender<PipelineEvent<EncodedChunk>>>,
     audio_sender: Option<Sender<PipelineEvent<EncodedChunk>>>,
+    jitter_buffer_latency: Duration,
+    mut feedback: Option<RtcpFeedback>,
+    stats: Arc<Mutex<RtpInputStats>>,
 ) {
     let mut audio_eos_received = audio_sender.as_ref().map(|_| false);
     let mut video_eos_received = video_sender.as_ref().map(|_| false);
     let mut audio_ssrc = None;
     let mut video_ssrc = None;
+    let mut audio_goodbye_received = false;
+    let mut video_goodbye_received = false;
+    let video_payload_type = depayloader.video_payload_type();
+    let audio_payload_type = depayloader.audio_payload_type();
+    let mut timestamp_sync = TimestampSync::new(
+        depayloader.video_clock_rate(),
+        depayloader.audio_clock_rate(),
+    );
+    let mut video_jitter_buffer =
+        video_payload_type.map(|_| JitterBuffer::new(jitter_buffer_latency));
+    let mut audio_jitter_buffer =
+        audio_payload_type.map(|_| JitterBuffer::new(jitter_buffer_latency));
+    // After video packets are lost, the first frame received after the gap can be
+    // incomplete. Packets with its timestamp are dropped instead of being passed to
+    // the decoder.
+    let mut drop_next_video_frame = false;
+    let mut dropped_video_timestamp = None;
 
     let mut maybe_send_video_eos = || {
         if let (Some(sender), Some(false)) = (&video_sender, video_eos_received) {
@@ -184,82 +354,207 @@ fn run_depayloader_thread(
         }
     };
     loop {
-        let Ok(mut buffer) = receiver.recv() else {
-            debug!("Closing RTP depayloader thread.");
-            break;
+        let deadline = [&video_jitter_buffer, &audio_jitter_buffer]
+            .into_iter()
+            .flatten()
+            .filter_map(JitterBuffer::deadline)
+            .min();
+        let buffer = match deadline {
+            Some(deadline) => match receiver.recv_deadline(deadline) {
+                Ok(buffer) => Some(buffer),
+                Err(RecvTimeoutError::Timeout) => None,
+                Err(RecvTimeoutError::Disconnected) => break,
+            },
+            None => match receiver.recv() {
+                Ok(buffer) => Some(buffer),
+                Err(_) => break,
+            },
         };
 
-        match rtp::packet::Packet::unmarshal(&mut buffer.clone()) {
-            // https://datatracker.ietf.org/doc/html/rfc5761#section-4
-            //
-            // Given these constraints, it is RECOMMENDED to follow the guidelines
-            // in the RTP/AVP profile [7] for the choice of RTP payload type values,
-            // with the additional restriction that payload type values in the range
-            // 64-95 MUST NOT be used.
-            Ok(packet) if packet.header.payload_type < 64 || packet.header.payload_type > 95 => {
-                if packet.header.payload_type == 96 && video_ssrc.is_none() {
-                    video_ssrc = Some(packet.header.ssrc);
-                }
-                if packet.header.payload_type == 97 && audio_ssrc.is_none() {
-                    audio_ssrc = Some(packet.header.ssrc);
-                }
-
-                match depayloader.depayload(packet) {
-                    Ok(chunks) => {
-                        for chunk in chunks {
-                            match &chunk.kind {
-                                EncodedChunkKind::Video(_) => {
-                                    video_sender.as_ref().map(|video_sender| {
-                                        video_sender.send(PipelineEvent::Data(chunk))
-                                    })
-                                }
-                                EncodedChunkKind::Audio(_) => {
-                                    audio_sender.as_ref().map(|audio_sender| {
-                                        audio_sender.send(PipelineEvent::Data(chunk))
-                                    })
-                                }
-                            };
+        if let Some(mut buffer) = buffer {
+            match rtp::packet::Packet::unmarshal(&mut buffer.clone()) {
+                // https://datatracker.ietf.org/doc/html/rfc5761#section-4
+                //
+                // Given these constraints, it is RECOMMENDED to follow the guidelines
+                // in the RTP/AVP profile [7] for the choice of RTP payload type values,
+                // with the additional restriction that payload type values in the range
+                // 64-95 MUST NOT be used.
+                Ok(packet)
+                    if packet.header.payload_type < 64 || packet.header.payload_type > 95 =>
+                {
+                    let received_at = Instant::now();
+                    let payload_type = Some(packet.header.payload_type);
+                    match (&mut video_jitter_buffer, &mut audio_jitter_buffer) {
+                        (Some(jitter_buffer), _) if payload_type == video_payload_type => {
+                            video_ssrc.get_or_insert(packet.header.ssrc);
+                            jitter_buffer.push(packet, received_at);
+                        }
+                        (_, Some(jitter_buffer)) if payload_type == audio_payload_type => {
+                            audio_ssrc.get_or_insert(packet.header.ssrc);
+                            jitter_buffer.push(packet, received_at);
+                        }
+                        _ => {
+                            let err = DepayloadingError::BadPayloadType(packet.header.payload_type);
+                            warn!("RTP depayloading error: {}", err);
                         }
-                    }
-                    Err(err) => {
-                        warn!("RTP depayloading error: {}", err);
-                        continue;
                     }
                 }
-            }
-            Ok(_) | Err(_) => {
-                match rtcp::packet::unmarshal(&mut buffer) {
+                Ok(_) | Err(_) => match rtcp::packet::unmarshal(&mut buffer) {
                     Ok(rtcp_packets) => {
                         for rtcp_packet in rtcp_packets {
-                            if let PacketType::Goodbye = rtcp_packet.header().packet_type {
-                                for ssrc in rtcp_packet.destination_ssrc() {
-                                    if Some(ssrc) == audio_ssrc {
-                                        maybe_send_audio_eos()
+                            match rtcp_packet.header().packet_type {
+                                PacketType::Goodbye => {
+                                    for ssrc in rtcp_packet.destination_ssrc() {
+                                        if Some(ssrc) == audio_ssrc {
+                                            audio_goodbye_received = true;
+                                        }
+                                        if Some(ssrc) == video_ssrc {
+                                            video_goodbye_received = true;
+                                        }
                                     }
-                                    if Some(ssrc) == video_ssrc {
-                                        maybe_send_video_eos()
+                                }
+                                PacketType::SenderReport => {
+                                    let Some(report) =
+                                        rtcp_packet.as_any().downcast_ref::<SenderReport>()
+                                    else {
+                                        continue;
+                                    };
+                                    if Some(report.ssrc) == video_ssrc {
+                                        timestamp_sync.on_video_sender_report(
+                                            report.ntp_time,
+                                            report.rtp_time,
+                                        );
                                     }
+                                    if Some(report.ssrc) == audio_ssrc {
+                                        timestamp_sync.on_audio_sender_report(
+                                            report.ntp_time,
+                                            report.rtp_time,
+                                        );
+                                    }
+                                }
+                                packet_type => {
+                                    debug!(?packet_type, "Received RTCP packet")
                                 }
-                            } else {
-                                debug!(
-                                    packet_type=?rtcp_packet.header().packet_type,
-                                    "Received RTCP packet"
-                                )
                             }
                         }
                     }
                     Err(err) => {
                         warn!(%err, "Received an unexpected packet, which is not recognized either as RTP or RTCP. Dropping.");
                     }
+                },
+            };
+        }
+
+        let now = Instant::now();
+        if let Some(jitter_buffer) = &mut video_jitter_buffer {
+            let missing = jitter_buffer.take_missing();
+            if let (Some(feedback), Some(ssrc)) = (&feedback, video_ssrc) {
+                if feedback.send_nack(ssrc, &missing) {
+                    jitter_buffer.stats_mut().nacks_sent += 1;
+                }
+            }
+
+            while let Some(event) = jitter_buffer.pop(now) {
+                match event {
+                    JitterBufferEvent::PacketsLost(count) => {
+                        debug!(count, "Video RTP packets lost.");
+                        depayloader.reset_video();
+                        drop_next_video_frame = true;
+                        if let (Some(feedback), Some(ssrc)) = (&mut feedback, video_ssrc) {
+                            if feedback.send_pli(ssrc) {
+                                jitter_buffer.stats_mut().plis_sent += 1;
+                            }
+                        }
+                    }
+                    JitterBufferEvent::Packet(packet) => {
+                        let timestamp = packet.header.timestamp;
+                        if mem::take(&mut drop_next_video_frame) {
+                            dropped_video_timestamp = Some(timestamp);
+                        }
+                        if dropped_video_timestamp == Some(timestamp) {
+                            continue;
+                        }
+                        dropped_video_timestamp = None;
+                        depayload_and_send(
+                            &mut depayloader,
+                            &mut timestamp_sync,
+                            packet,
+                            &video_sender,
+                            &audio_sender,
+                        );
+                    }
                 }
-                continue;
             }
+            if video_goodbye_received && jitter_buffer.is_empty() {
+                maybe_send_video_eos();
+            }
+        }
+
+        if let Some(jitter_buffer) = &mut audio_jitter_buffer {
+            let missing = jitter_buffer.take_missing();
+            if let (Some(feedback), Some(ssrc)) = (&feedback, audio_ssrc) {
+                if feedback.send_nack(ssrc, &missing) {
+                    jitter_buffer.stats_mut().nacks_sent += 1;
+                }
+            }
+
+            while let Some(event) = jitter_buffer.pop(now) {
+                match event {
+                    JitterBufferEvent::PacketsLost(count) => {
+                        debug!(count, "Audio RTP packets lost.");
+                    }
+                    JitterBufferEvent::Packet(packet) => depayload_and_send(
+                        &mut depayloader,
+                        &mut timestamp_sync,
+                        packet,
+                        &video_sender,
+                        &audio_sender,
+                    ),
+                }
+            }
+            if audio_goodbye_received && jitter_buffer.is_empty() {
+                maybe_send_audio_eos();
+            }
+        }
+
+        *stats.lock().unwrap() = RtpInputStats {
+            video: video_jitter_buffer.as_ref().map(JitterBuffer::stats),
+            audio: audio_jitter_buffer.as_ref().map(JitterBuffer::stats),
         };
     }
+    debug!("Closing RTP depayloader thread.");
     maybe_send_audio_eos();
     maybe_send_video_eos();
 }
 
+fn depayload_and_send(
+    depayloader: &mut Depayloader,
+    timestamp_sync: &mut TimestampSync,
+    packet: rtp::packet::Packet,
+    video_sender: &Option<Sender<PipelineEvent<EncodedChunk>>>,
+    audio_sender: &Option<Sender<PipelineEvent<EncodedChunk>>>,
+) {
+    let chunks = match depayloader.depayload(packet) {
+        Ok(chunks) => chunks,
+        Err(err) => {
+            warn!("RTP depayloading error: {}", err);
+            return;
+        }
+    };
+    for mut chunk in chunks {
+        timestamp_sync.synchronize(&mut chunk);
+        let sender = match &chunk.kind {
+            EncodedChunkKind::Video(_) => video_sender,
+            EncodedChunkKind::Audio(_) => audio_sender,
+        };
+        if let Some(sender) = sender {
+            if sender.send(PipelineEvent::Data(chunk)).is_err() {
+                debug!("Failed to send a chunk from RTP depayloader. Channel closed.");
+            }
+        }
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum DepayloadingError {
     #[error("Bad payload type {0}")]
@@ -268,6 +563,8 @@ pub enum DepayloadingError {
     Rtp(#[from] rtp::Error),
     #[error("AAC depayoading error")]
     Aac(#[from] depayloader::AacDepayloadingError),
+    #[error("AV1 depayloading error")]
+    Av1(#[from] depayloader::Av1DepayloadingError),
 }
 
 impl From<BindToPortError> for RtpReceiverError {
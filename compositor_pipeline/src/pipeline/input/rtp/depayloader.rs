@@ -3,14 +3,13 @@ use std::{mem, time::Duration};
 use bytes::Bytes;
 use log::error;
 use rtp::{
-    codecs::{h264::H264Packet, opus::OpusPacket},
+    codecs::{h264::H264Packet, opus::OpusPacket, vp8::Vp8Packet, vp9::Vp9Packet},
     packetizer::Depacketizer,
 };
 
 use crate::pipeline::{
     decoder::{self, AacDecoderOptions},
-    rtp::{AUDIO_PAYLOAD_TYPE, VIDEO_PAYLOAD_TYPE},
-    structs::{AudioCodec, EncodedChunk, EncodedChunkKind, VideoCodec},
+    structs::{AudioCodec, EncodedChunk, EncodedChunkKind, IsKeyframe, VideoCodec},
 };
 
 use self::aac::AacDepayloaderNewError;
@@ -18,8 +17,13 @@ use self::aac::AacDepayloaderNewError;
 use super::{DepayloadingError, RtpStream};
 
 pub use aac::{AacDepayloader, AacDepayloadingError};
+pub use av1::{Av1Depayloader, Av1DepayloadingError};
 
 mod aac;
+mod av1;
+
+const VIDEO_CLOCK_RATE: u32 = 90000;
+const OPUS_CLOCK_RATE: u32 = 48000;
 
 #[derive(Debug, thiserror::Error)]
 pub enum DepayloaderNewError {
@@ -29,8 +33,8 @@ pub enum DepayloaderNewError {
 
 pub(crate) struct Depayloader {
     /// (Depayloader, payload type)
-    pub video: Option<VideoDepayloader>,
-    pub audio: Option<AudioDepayloader>,
+    pub video: Option<(VideoDepayloader, u8)>,
+    pub audio: Option<(AudioDepayloader, u8)>,
 }
 
 impl Depayloader {
@@ -38,12 +42,15 @@ impl Depayloader {
         let video = stream
             .video
             .as_ref()
-            .map(|video| VideoDepayloader::new(&video.options));
+            .map(|video| (VideoDepayloader::new(&video.options), video.payload_type));
 
         let audio = stream
             .audio
             .as_ref()
-            .map(|audio| AudioDepayloader::new(&audio.options))
+            .map(|audio| {
+                AudioDepayloader::new(&audio.options)
+                    .map(|depayloader| (depayloader, audio.payload_type))
+            })
             .transpose()?;
 
         Ok(Self { video, audio })
@@ -53,22 +60,47 @@ impl Depayloader {
         &mut self,
         packet: rtp::packet::Packet,
     ) -> Result<Vec<EncodedChunk>, DepayloadingError> {
-        match packet.header.payload_type {
-            VIDEO_PAYLOAD_TYPE => match self.video.as_mut() {
-                Some(video_depayloader) => video_depayloader.depayload(packet),
-                None => Err(DepayloadingError::BadPayloadType(
-                    packet.header.payload_type,
-                )),
-            },
-            AUDIO_PAYLOAD_TYPE => match self.audio.as_mut() {
-                Some(audio_depayloader) => audio_depayloader.depayload(packet),
-                None => Err(DepayloadingError::BadPayloadType(
-                    packet.header.payload_type,
-                )),
-            },
-            other => Err(DepayloadingError::BadPayloadType(other)),
+        let payload_type = packet.header.payload_type;
+        match (self.video.as_mut(), self.audio.as_mut()) {
+            (Some((video_depayloader, video_payload_type)), _)
+                if *video_payload_type == payload_type =>
+            {
+                video_depayloader.depayload(packet)
+            }
+            (_, Some((audio_depayloader, audio_payload_type)))
+                if *audio_payload_type == payload_type =>
+            {
+                audio_depayloader.depayload(packet)
+            }
+            _ => Err(DepayloadingError::BadPayloadType(payload_type)),
+        }
+    }
+
+    /// Drops partially assembled video frame, e.g. after packet loss was detected.
+    pub fn reset_video(&mut self) {
+        if let Some((video, _)) = &mut self.video {
+            video.reset();
         }
     }
+
+    pub fn video_payload_type(&self) -> Option<u8> {
+        self.video.as_ref().map(|(_, payload_type)| *payload_type)
+    }
+
+    pub fn audio_payload_type(&self) -> Option<u8> {
+        self.audio.as_ref().map(|(_, payload_type)| *payload_type)
+    }
+
+    pub fn video_clock_rate(&self) -> Option<u32> {
+        self.video.as_ref().map(|_| VIDEO_CLOCK_RATE)
+    }
+
+    pub fn audio_clock_rate(&self) -> Option<u32> {
+        self.audio.as_ref().map(|(audio, _)| match audio {
+            AudioDepayloader::Opus { .. } => OPUS_CLOCK_RATE,
+            AudioDepayloader::Aac(aac) => aac.clock_rate(),
+        })
+    }
 }
 
 pub enum VideoDepayloader {
@@ -77,6 +109,23 @@ pub enum VideoDepayloader {
         buffer: Vec<Bytes>,
         rollover_state: RolloverState,
     },
+    /// [RFC 7741](https://datatracker.ietf.org/doc/html/rfc7741)
+    Vp8 {
+        depayloader: Vp8Packet,
+        buffer: Vec<Bytes>,
+        rollover_state: RolloverState,
+    },
+    /// [RTP Payload Format for VP9](https://datatracker.ietf.org/doc/html/draft-ietf-payload-vp9)
+    Vp9 {
+        depayloader: Vp9Packet,
+        buffer: Vec<Bytes>,
+        rollover_state: RolloverState,
+        /// Whether the first packet of the currently buffered frame
+        /// was marked as inter-picture predicted.
+        inter_predicted: bool,
+    },
+    /// [RTP Payload Format for AV1](https://aomediacodec.github.io/av1-rtp-spec/)
+    Av1(Av1Depayloader),
 }
 
 impl VideoDepayloader {
@@ -87,6 +136,50 @@ impl VideoDepayloader {
                 buffer: vec![],
                 rollover_state: RolloverState::default(),
             },
+            VideoCodec::Vp8 => VideoDepayloader::Vp8 {
+                depayloader: Vp8Packet::default(),
+                buffer: vec![],
+                rollover_state: RolloverState::default(),
+            },
+            VideoCodec::Vp9 => VideoDepayloader::Vp9 {
+                depayloader: Vp9Packet::default(),
+                buffer: vec![],
+                rollover_state: RolloverState::default(),
+                inter_predicted: false,
+            },
+            VideoCodec::Av1 => VideoDepayloader::Av1(Av1Depayloader::default()),
+        }
+    }
+
+    fn reset(&mut self) {
+        match self {
+            VideoDepayloader::H264 {
+                depayloader,
+                buffer,
+                ..
+            } => {
+                *depayloader = H264Packet::default();
+                buffer.clear();
+            }
+            VideoDepayloader::Vp8 {
+                depayloader,
+                buffer,
+                ..
+            } => {
+                *depayloader = Vp8Packet::default();
+                buffer.clear();
+            }
+            VideoDepayloader::Vp9 {
+                depayloader,
+                buffer,
+                inter_predicted,
+                ..
+            } => {
+                *depayloader = Vp9Packet::default();
+                buffer.clear();
+                *inter_predicted = false;
+            }
+            VideoDepayloader::Av1(av1) => av1.reset(),
         }
     }
 
@@ -116,13 +209,84 @@ impl VideoDepayloader {
                 let timestamp = rollover_state.timestamp(packet.header.timestamp);
                 let new_chunk = EncodedChunk {
                     data: mem::take(buffer).concat().into(),
-                    pts: Duration::from_secs_f64(timestamp as f64 / 90000.0),
+                    pts: Duration::from_secs_f64(timestamp as f64 / VIDEO_CLOCK_RATE as f64),
                     dts: None,
+                    is_keyframe: IsKeyframe::Unknown,
                     kind,
                 };
 
                 Ok(vec![new_chunk])
             }
+            VideoDepayloader::Vp8 {
+                depayloader,
+                buffer,
+                rollover_state,
+            } => {
+                let kind = EncodedChunkKind::Video(VideoCodec::Vp8);
+                let vp8_chunk = depayloader.depacketize(&packet.payload)?;
+
+                if vp8_chunk.is_empty() {
+                    return Ok(Vec::new());
+                }
+
+                buffer.push(vp8_chunk);
+                if !packet.header.marker {
+                    // the marker bit is set on the last packet of a frame
+                    return Ok(Vec::new());
+                }
+
+                let data: Bytes = mem::take(buffer).concat().into();
+                // RFC 7741, section 4.3: inverse key frame flag is the lowest bit
+                // of the first byte of the VP8 payload header.
+                let is_keyframe = match data.first() {
+                    Some(byte) if byte & 0x01 == 0 => IsKeyframe::Yes,
+                    _ => IsKeyframe::No,
+                };
+
+                let timestamp = rollover_state.timestamp(packet.header.timestamp);
+                Ok(vec![EncodedChunk {
+                    data,
+                    pts: Duration::from_secs_f64(timestamp as f64 / VIDEO_CLOCK_RATE as f64),
+                    dts: None,
+                    is_keyframe,
+                    kind,
+                }])
+            }
+            VideoDepayloader::Vp9 {
+                depayloader,
+                buffer,
+                rollover_state,
+                inter_predicted,
+            } => {
+                let kind = EncodedChunkKind::Video(VideoCodec::Vp9);
+                let vp9_chunk = depayloader.depacketize(&packet.payload)?;
+
+                if vp9_chunk.is_empty() {
+                    return Ok(Vec::new());
+                }
+
+                if buffer.is_empty() {
+                    *inter_predicted = depayloader.p;
+                }
+                buffer.push(vp9_chunk);
+                if !packet.header.marker {
+                    // the marker bit is set on the last packet of a picture
+                    return Ok(Vec::new());
+                }
+
+                let timestamp = rollover_state.timestamp(packet.header.timestamp);
+                Ok(vec![EncodedChunk {
+                    data: mem::take(buffer).concat().into(),
+                    pts: Duration::from_secs_f64(timestamp as f64 / VIDEO_CLOCK_RATE as f64),
+                    dts: None,
+                    is_keyframe: match inter_predicted {
+                        true => IsKeyframe::No,
+                        false => IsKeyframe::Yes,
+                    },
+                    kind,
+                }])
+            }
+            VideoDepayloader::Av1(av1) => Ok(av1.depayload(packet)?),
         }
     }
 }
@@ -184,8 +348,9 @@ impl AudioDepayloader {
                 let timestamp = rollover_state.timestamp(packet.header.timestamp);
                 Ok(vec![EncodedChunk {
                     data: opus_packet,
-                    pts: Duration::from_secs_f64(timestamp as f64 / 48000.0),
+                    pts: Duration::from_secs_f64(timestamp as f64 / OPUS_CLOCK_RATE as f64),
                     dts: None,
+                    is_keyframe: IsKeyframe::NoKeyframes,
                     kind,
                 }])
             }
@@ -226,6 +391,8 @@ impl RolloverState {
 
 #[cfg(test)]
 mod tests {
+    use crate::pipeline::rtp::VIDEO_PAYLOAD_TYPE;
+
     use super::*;
 
     #[test]
@@ -278,4 +445,37 @@ mod tests {
             u32::MAX as u64 + 1 + current_timestamp as u64
         );
     }
+
+    fn video_packet(payload: &'static [u8], timestamp: u32, marker: bool) -> rtp::packet::Packet {
+        rtp::packet::Packet {
+            header: rtp::header::Header {
+                marker,
+                timestamp,
+                payload_type: VIDEO_PAYLOAD_TYPE,
+                ..Default::default()
+            },
+            payload: Bytes::from_static(payload),
+        }
+    }
+
+    #[test]
+    fn vp8_frame_assembly() {
+        let mut depayloader = VideoDepayloader::new(&decoder::VideoDecoderOptions {
+            codec: VideoCodec::Vp8,
+        });
+
+        // start of a partition (S=1), first byte of VP8 payload has P=0 (keyframe)
+        let first = video_packet(&[0x10, 0x00, 0x9d, 0x01, 0x2a], 9000, false);
+        assert!(depayloader.depayload(first).unwrap().is_empty());
+
+        let last = video_packet(&[0x00, 0x01, 0x02, 0x03, 0x04], 9000, true);
+        let chunks = depayloader.depayload(last).unwrap();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(
+            chunks[0].data.as_ref(),
+            [0x00, 0x9d, 0x01, 0x2a, 0x01, 0x02, 0x03, 0x04]
+        );
+        assert!(matches!(chunks[0].is_keyframe, IsKeyframe::Yes));
+        assert_eq!(chunks[0].pts, Duration::from_millis(100));
+    }
 }
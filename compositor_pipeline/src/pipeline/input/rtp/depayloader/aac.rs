@@ -4,7 +4,7 @@ use bytes::{Buf, BytesMut};
 
 use crate::pipeline::{
     decoder::AacDepayloaderMode,
-    structs::{EncodedChunk, EncodedChunkKind},
+    structs::{EncodedChunk, EncodedChunkKind, IsKeyframe},
     AudioCodec,
 };
 
@@ -199,6 +199,10 @@ impl AacDepayloader {
         })
     }
 
+    pub fn clock_rate(&self) -> u32 {
+        self.asc.frequency
+    }
+
     /// Related spec:
     ///  - [RFC 3640, section 3.2. RTP Payload Structure](https://datatracker.ietf.org/doc/html/rfc3640#section-3.2)
     ///  - [RFC 3640, section 3.3.5. Low Bit-rate AAC](https://datatracker.ietf.org/doc/html/rfc3640#section-3.3.5)
@@ -269,6 +273,7 @@ impl AacDepayloader {
                 pts,
                 data: payload,
                 dts: None,
+                is_keyframe: IsKeyframe::NoKeyframes,
                 kind: EncodedChunkKind::Audio(AudioCodec::Aac),
             });
         }
@@ -0,0 +1,172 @@
+use std::{mem, time::Duration};
+
+use bytes::{BufMut, BytesMut};
+
+use crate::pipeline::{
+    rtp::av1::{
+        read_leb128, write_obu_with_size_field, ObuParseError, AGGREGATION_HEADER_N,
+        AGGREGATION_HEADER_Y, AGGREGATION_HEADER_Z,
+    },
+    structs::{EncodedChunk, EncodedChunkKind, IsKeyframe},
+    VideoCodec,
+};
+
+use super::RolloverState;
+
+/// Temporal delimiter OBU with size field set to 0.
+const TEMPORAL_DELIMITER: [u8; 2] = [0x12, 0x00];
+
+#[derive(Debug, thiserror::Error)]
+pub enum Av1DepayloadingError {
+    #[error("Packet too short")]
+    PacketTooShort,
+
+    #[error("Invalid OBU element length")]
+    InvalidElementLength,
+
+    #[error(transparent)]
+    ObuParseError(#[from] ObuParseError),
+}
+
+#[derive(Default)]
+pub struct Av1Depayloader {
+    /// OBUs of the current temporal unit in the low overhead bitstream format.
+    temporal_unit: BytesMut,
+    /// Beginning of an OBU that is fragmented across packets.
+    fragment: Option<BytesMut>,
+    /// Whether the current temporal unit starts a new coded video sequence.
+    new_sequence: bool,
+    rollover_state: RolloverState,
+}
+
+impl Av1Depayloader {
+    /// Drops the partially assembled temporal unit.
+    pub(super) fn reset(&mut self) {
+        self.temporal_unit.clear();
+        self.fragment = None;
+        self.new_sequence = false;
+    }
+
+    /// Related spec:
+    ///  - [RTP Payload Format for AV1, section 4. Payload format](https://aomediacodec.github.io/av1-rtp-spec/#4-payload-format)
+    ///  - [RTP Payload Format for AV1, section 7. Depacketization](https://aomediacodec.github.io/av1-rtp-spec/#7-depacketization)
+    pub(super) fn depayload(
+        &mut self,
+        packet: rtp::packet::Packet,
+    ) -> Result<Vec<EncodedChunk>, Av1DepayloadingError> {
+        let Some((&aggregation_header, mut payload)) = packet.payload.split_first() else {
+            return Err(Av1DepayloadingError::PacketTooShort);
+        };
+        let is_continuation = aggregation_header & AGGREGATION_HEADER_Z != 0;
+        let continues_in_next_packet = aggregation_header & AGGREGATION_HEADER_Y != 0;
+        // W - number of OBU elements, 0 means that every element is preceded by its length.
+        let element_count = ((aggregation_header >> 4) & 0b11) as usize;
+        if aggregation_header & AGGREGATION_HEADER_N != 0 {
+            self.new_sequence = true;
+        }
+
+        let mut elements = Vec::new();
+        while !payload.is_empty() {
+            let len = if elements.len() + 1 == element_count {
+                // The last element does not have the length field if W is set.
+                payload.len()
+            } else {
+                let (len, len_size) =
+                    read_leb128(payload).ok_or(Av1DepayloadingError::InvalidElementLength)?;
+                payload = &payload[len_size..];
+                len
+            };
+            if len > payload.len() {
+                return Err(Av1DepayloadingError::InvalidElementLength);
+            }
+            elements.push(&payload[..len]);
+            payload = &payload[len..];
+        }
+
+        let elements_len = elements.len();
+        for (i, element) in elements.into_iter().enumerate() {
+            let obu = match (i == 0, self.fragment.take()) {
+                (true, Some(mut fragment)) if is_continuation => {
+                    fragment.put_slice(element);
+                    fragment
+                }
+                // The beginning of the OBU was lost.
+                (true, None) if is_continuation => continue,
+                _ => BytesMut::from(element),
+            };
+
+            if i + 1 == elements_len && continues_in_next_packet {
+                self.fragment = Some(obu);
+            } else if !obu.is_empty() {
+                write_obu_with_size_field(&mut self.temporal_unit, &obu)?;
+            }
+        }
+
+        if !packet.header.marker {
+            // the marker bit is set on the last packet of a temporal unit
+            return Ok(Vec::new());
+        }
+
+        self.fragment = None;
+        if self.temporal_unit.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut data = BytesMut::with_capacity(TEMPORAL_DELIMITER.len() + self.temporal_unit.len());
+        data.put_slice(&TEMPORAL_DELIMITER);
+        data.put(mem::take(&mut self.temporal_unit));
+
+        let timestamp = self.rollover_state.timestamp(packet.header.timestamp);
+        Ok(vec![EncodedChunk {
+            data: data.freeze(),
+            pts: Duration::from_secs_f64(timestamp as f64 / 90000.0),
+            dts: None,
+            is_keyframe: match mem::take(&mut self.new_sequence) {
+                true => IsKeyframe::Yes,
+                false => IsKeyframe::No,
+            },
+            kind: EncodedChunkKind::Video(VideoCodec::Av1),
+        }])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+
+    use super::*;
+
+    fn packet(payload: &'static [u8], marker: bool) -> rtp::packet::Packet {
+        rtp::packet::Packet {
+            header: rtp::header::Header {
+                marker,
+                timestamp: 9000,
+                ..Default::default()
+            },
+            payload: Bytes::from_static(payload),
+        }
+    }
+
+    #[test]
+    fn av1_fragmented_obu() {
+        let mut depayloader = Av1Depayloader::default();
+
+        let first = packet(&[0x48, 0x03, 0x08, 0xaa, 0xbb, 0x02, 0x30, 1], false);
+        assert!(depayloader.depayload(first).unwrap().is_empty());
+
+        let second = packet(&[0xc0, 0x06, 2, 3, 4, 5, 6, 7], false);
+        assert!(depayloader.depayload(second).unwrap().is_empty());
+
+        // W=1, the only element does not have the length field
+        let last = packet(&[0x90, 8], true);
+        let chunks = depayloader.depayload(last).unwrap();
+
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(
+            chunks[0].data.as_ref(),
+            [0x12, 0x00, 0x0a, 0x02, 0xaa, 0xbb, 0x32, 0x08, 1, 2, 3, 4, 5, 6, 7, 8]
+        );
+        assert!(matches!(chunks[0].is_keyframe, IsKeyframe::Yes));
+        assert_eq!(chunks[0].pts, Duration::from_millis(100));
+    }
+}
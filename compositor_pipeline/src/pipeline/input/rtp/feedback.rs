@@ -0,0 +1,72 @@
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use crossbeam_channel::Sender;
+use rtcp::{
+    payload_feedbacks::picture_loss_indication::PictureLossIndication,
+    transport_feedbacks::transport_layer_nack::{
+        nack_pairs_from_sequence_numbers, TransportLayerNack,
+    },
+};
+use tracing::{debug, warn};
+use webrtc_util::Marshal;
+
+/// Keyframe requests are not repeated more often than this, a single keyframe
+/// fixes all losses that happened before it.
+const MIN_PLI_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Sends RTCP feedback packets back to the sender of the stream.
+pub(super) struct RtcpFeedback {
+    sender: Sender<Bytes>,
+    /// SSRC identifying the compositor as a source of RTCP packets.
+    ssrc: u32,
+    last_pli: Option<Instant>,
+}
+
+impl RtcpFeedback {
+    pub fn new(sender: Sender<Bytes>) -> Self {
+        Self {
+            sender,
+            ssrc: rand::random(),
+            last_pli: None,
+        }
+    }
+
+    /// [RFC 4585, section 6.2.1](https://datatracker.ietf.org/doc/html/rfc4585#section-6.2.1)
+    /// Returns `true` if the NACK was sent.
+    pub fn send_nack(&self, media_ssrc: u32, sequence_numbers: &[u16]) -> bool {
+        if sequence_numbers.is_empty() {
+            return false;
+        }
+        debug!(media_ssrc, ?sequence_numbers, "Sending RTCP NACK.");
+        self.send(&TransportLayerNack {
+            sender_ssrc: self.ssrc,
+            media_ssrc,
+            nacks: nack_pairs_from_sequence_numbers(sequence_numbers),
+        })
+    }
+
+    /// [RFC 4585, section 6.3.1](https://datatracker.ietf.org/doc/html/rfc4585#section-6.3.1)
+    /// Returns `true` if the PLI was sent.
+    pub fn send_pli(&mut self, media_ssrc: u32) -> bool {
+        if matches!(self.last_pli, Some(last_pli) if last_pli.elapsed() < MIN_PLI_INTERVAL) {
+            return false;
+        }
+        self.last_pli = Some(Instant::now());
+        debug!(media_ssrc, "Sending RTCP PLI.");
+        self.send(&PictureLossIndication {
+            sender_ssrc: self.ssrc,
+            media_ssrc,
+        })
+    }
+
+    fn send(&self, packet: &impl Marshal) -> bool {
+        match packet.marshal() {
+            Ok(packet) => self.sender.send(packet).is_ok(),
+            Err(err) => {
+                warn!(%err, "Failed to serialize RTCP feedback packet.");
+                false
+            }
+        }
+    }
+}
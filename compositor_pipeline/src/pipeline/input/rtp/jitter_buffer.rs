@@ -0,0 +1,248 @@
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant},
+};
+
+use tracing::debug;
+
+use super::RtpTrackStats;
+
+/// Sequence number jumps larger than this are treated as a restart of the stream.
+/// Values based on [RFC 3550, appendix A.1](https://datatracker.ietf.org/doc/html/rfc3550#appendix-A.1).
+const MAX_DROPOUT: i64 = 3000;
+const MAX_MISORDER: i64 = 100;
+
+pub(super) enum JitterBufferEvent {
+    Packet(rtp::packet::Packet),
+    /// Packets with sequence numbers right before the next returned packet will
+    /// never be returned.
+    PacketsLost(u64),
+}
+
+/// Reorders RTP packets of a single track based on their sequence numbers.
+///
+/// Packets are returned as soon as all the previous ones were returned. If a
+/// packet is missing, following packets are held until the missing one arrives
+/// or until `latency` passes since the oldest held packet was received.
+pub(super) struct JitterBuffer {
+    latency: Duration,
+    /// Packets indexed by extended (unwrapped) sequence numbers.
+    packets: BTreeMap<u64, (Instant, rtp::packet::Packet)>,
+    next_sequence_number: Option<u64>,
+    highest_sequence_number: Option<u64>,
+    /// First packet after a sequence number jump. Gap before it is not a loss.
+    restart: Option<u64>,
+    /// Missing packets that were not yet reported with [`JitterBuffer::take_missing`].
+    missing: Vec<u16>,
+    stats: RtpTrackStats,
+}
+
+impl JitterBuffer {
+    pub fn new(latency: Duration) -> Self {
+        Self {
+            latency,
+            packets: BTreeMap::new(),
+            next_sequence_number: None,
+            highest_sequence_number: None,
+            restart: None,
+            missing: Vec::new(),
+            stats: RtpTrackStats::default(),
+        }
+    }
+
+    pub fn push(&mut self, packet: rtp::packet::Packet, received_at: Instant) {
+        self.stats.packets_received += 1;
+        let sequence_number = packet.header.sequence_number;
+
+        let Some(highest) = self.highest_sequence_number else {
+            self.next_sequence_number = Some(sequence_number as u64);
+            self.highest_sequence_number = Some(sequence_number as u64);
+            self.packets
+                .insert(sequence_number as u64, (received_at, packet));
+            return;
+        };
+
+        let diff = sequence_number.wrapping_sub(highest as u16) as i16 as i64;
+        if !(-MAX_MISORDER..=MAX_DROPOUT).contains(&diff) || highest as i64 + diff < 0 {
+            debug!(
+                sequence_number,
+                "RTP sequence number jump, restarting jitter buffer."
+            );
+            // Place the new sequence after all held packets, so they are returned first.
+            let extended = (((highest >> 16) + 1) << 16) + sequence_number as u64;
+            if self.packets.is_empty() {
+                self.next_sequence_number = Some(extended);
+            } else {
+                self.restart = Some(extended);
+            }
+            self.highest_sequence_number = Some(extended);
+            self.missing.clear();
+            self.packets.insert(extended, (received_at, packet));
+            return;
+        }
+
+        let extended = (highest as i64 + diff) as u64;
+        let next = self.next_sequence_number.unwrap_or(extended);
+        if extended < next || self.packets.contains_key(&extended) {
+            self.stats.packets_late += 1;
+            return;
+        }
+
+        if extended > highest {
+            self.missing
+                .extend((highest + 1..extended).map(|missing| missing as u16));
+            self.highest_sequence_number = Some(extended);
+        }
+        self.packets.insert(extended, (received_at, packet));
+    }
+
+    pub fn pop(&mut self, now: Instant) -> Option<JitterBufferEvent> {
+        let next = self.next_sequence_number?;
+        let (&first, (received_at, _)) = self.packets.first_key_value()?;
+
+        if first != next {
+            if self.restart == Some(first) {
+                // All packets from before the restart were returned.
+                self.restart = None;
+            } else if now.saturating_duration_since(*received_at) < self.latency {
+                return None;
+            } else {
+                let lost = first - next;
+                self.stats.packets_lost += lost;
+                self.next_sequence_number = Some(first);
+                return Some(JitterBufferEvent::PacketsLost(lost));
+            }
+        }
+
+        let (_, (_, packet)) = self.packets.pop_first()?;
+        self.next_sequence_number = Some(first + 1);
+        Some(JitterBufferEvent::Packet(packet))
+    }
+
+    /// Time when the oldest held packet has to be returned, even if previous
+    /// packets are still missing.
+    pub fn deadline(&self) -> Option<Instant> {
+        let next = self.next_sequence_number?;
+        match self.packets.first_key_value() {
+            Some((&first, (received_at, _))) if first != next => Some(*received_at + self.latency),
+            _ => None,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.packets.is_empty()
+    }
+
+    /// Returns sequence numbers of packets that were detected as missing since the
+    /// last call.
+    pub fn take_missing(&mut self) -> Vec<u16> {
+        std::mem::take(&mut self.missing)
+    }
+
+    pub fn stats(&self) -> RtpTrackStats {
+        self.stats
+    }
+
+    pub fn stats_mut(&mut self) -> &mut RtpTrackStats {
+        &mut self.stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{JitterBuffer, JitterBufferEvent};
+
+    fn packet(sequence_number: u16) -> rtp::packet::Packet {
+        rtp::packet::Packet {
+            header: rtp::header::Header {
+                sequence_number,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    fn pop_all(jitter_buffer: &mut JitterBuffer, now: Instant) -> Vec<Result<u16, u64>> {
+        std::iter::from_fn(|| jitter_buffer.pop(now))
+            .map(|event| match event {
+                JitterBufferEvent::Packet(packet) => Ok(packet.header.sequence_number),
+                JitterBufferEvent::PacketsLost(count) => Err(count),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reorders_packets() {
+        let start = Instant::now();
+        let mut jitter_buffer = JitterBuffer::new(Duration::from_millis(100));
+
+        jitter_buffer.push(packet(65534), start);
+        assert_eq!(pop_all(&mut jitter_buffer, start), vec![Ok(65534)]);
+
+        jitter_buffer.push(packet(0), start);
+        jitter_buffer.push(packet(1), start);
+        assert_eq!(pop_all(&mut jitter_buffer, start), vec![]);
+        assert_eq!(jitter_buffer.take_missing(), vec![65535]);
+        assert_eq!(
+            jitter_buffer.deadline(),
+            Some(start + Duration::from_millis(100))
+        );
+
+        jitter_buffer.push(packet(65535), start);
+        assert_eq!(
+            pop_all(&mut jitter_buffer, start),
+            vec![Ok(65535), Ok(0), Ok(1)]
+        );
+        assert_eq!(jitter_buffer.deadline(), None);
+        assert_eq!(jitter_buffer.stats().packets_lost, 0);
+    }
+
+    #[test]
+    fn reports_lost_packets_after_latency() {
+        let start = Instant::now();
+        let mut jitter_buffer = JitterBuffer::new(Duration::from_millis(100));
+
+        jitter_buffer.push(packet(10), start);
+        jitter_buffer.push(packet(13), start);
+        jitter_buffer.push(packet(14), start);
+        assert_eq!(pop_all(&mut jitter_buffer, start), vec![Ok(10)]);
+
+        let after_latency = start + Duration::from_millis(100);
+        assert_eq!(
+            pop_all(&mut jitter_buffer, after_latency),
+            vec![Err(2), Ok(13), Ok(14)]
+        );
+
+        // Lost packet arrived after it was skipped.
+        jitter_buffer.push(packet(11), after_latency);
+        assert_eq!(pop_all(&mut jitter_buffer, after_latency), vec![]);
+
+        let stats = jitter_buffer.stats();
+        assert_eq!(stats.packets_received, 4);
+        assert_eq!(stats.packets_lost, 2);
+        assert_eq!(stats.packets_late, 1);
+    }
+
+    #[test]
+    fn handles_sequence_number_jump() {
+        let start = Instant::now();
+        let mut jitter_buffer = JitterBuffer::new(Duration::from_millis(100));
+
+        jitter_buffer.push(packet(100), start);
+        jitter_buffer.push(packet(102), start);
+        jitter_buffer.push(packet(40000), start);
+        assert_eq!(pop_all(&mut jitter_buffer, start), vec![Ok(100)]);
+
+        let after_latency = start + Duration::from_millis(100);
+        assert_eq!(
+            pop_all(&mut jitter_buffer, after_latency),
+            vec![Err(1), Ok(102), Ok(40000)]
+        );
+
+        jitter_buffer.push(packet(40001), after_latency);
+        assert_eq!(pop_all(&mut jitter_buffer, after_latency), vec![Ok(40001)]);
+        assert_eq!(jitter_buffer.stats().packets_lost, 1);
+    }
+}
@@ -0,0 +1,76 @@
+use std::{
+    sync::{atomic::AtomicBool, Arc},
+    thread,
+    time::Instant,
+};
+
+use compositor_render::InputId;
+use crossbeam_channel::{bounded, Receiver, Sender};
+use tracing::{debug, info, span, trace, warn, Level};
+
+use crate::pipeline::{
+    rtp::{connect_tcp, TCP_CONNECT_TIMEOUT},
+    Port,
+};
+
+use super::{tcp_server::receive_packets, RtpReceiverError};
+
+/// Starts a thread that connects to a remote TCP server and receives RTP packets
+/// from it. The connection is re-established until the input is unregistered.
+pub(super) fn start_tcp_client_thread(
+    input_id: &InputId,
+    ip: Arc<str>,
+    port: u16,
+    feedback_receiver: Receiver<bytes::Bytes>,
+    should_close: Arc<AtomicBool>,
+) -> Result<(Port, Receiver<bytes::Bytes>), RtpReceiverError> {
+    let (packets_tx, packets_rx) = bounded(1000);
+    let input_id = input_id.clone();
+
+    thread::Builder::new()
+        .name(format!("RTP TCP client receiver {}", input_id))
+        .spawn(move || {
+            let _span = span!(
+                Level::INFO,
+                "RTP TCP client",
+                input_id = input_id.to_string()
+            )
+            .entered();
+            run_tcp_client_thread(&ip, port, packets_tx, feedback_receiver, should_close);
+            debug!("Closing RTP receiver thread (TCP client).");
+        })
+        .unwrap();
+
+    Ok((Port(port), packets_rx))
+}
+
+fn run_tcp_client_thread(
+    ip: &str,
+    port: u16,
+    packets_tx: Sender<bytes::Bytes>,
+    feedback_receiver: Receiver<bytes::Bytes>,
+    should_close: Arc<AtomicBool>,
+) {
+    while !should_close.load(std::sync::atomic::Ordering::Relaxed) {
+        let start = Instant::now();
+        let socket = match connect_tcp(ip, port) {
+            Ok(socket) => socket,
+            Err(err) => {
+                trace!("RTP TCP connection attempt failed: {err}.");
+                thread::sleep(TCP_CONNECT_TIMEOUT.saturating_sub(start.elapsed()));
+                continue;
+            }
+        };
+
+        info!("RTP TCP connection established.");
+        if receive_packets(socket, &packets_tx, &feedback_receiver, &should_close).is_err() {
+            debug!("Failed to send raw RTP packet from TCP client element. Channel closed.");
+            return;
+        }
+        if !should_close.load(std::sync::atomic::Ordering::Relaxed) {
+            warn!("RTP TCP connection lost. Reconnecting.");
+            // Avoid reconnecting in a loop to a server that closes connections immediately.
+            thread::sleep(TCP_CONNECT_TIMEOUT.saturating_sub(start.elapsed()));
+        }
+    }
+}
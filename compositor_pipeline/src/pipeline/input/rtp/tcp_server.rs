@@ -1,6 +1,6 @@
 use std::{
     collections::VecDeque,
-    io::Read,
+    io::{Read, Write},
     net::TcpStream,
     sync::{atomic::AtomicBool, Arc},
     thread,
@@ -9,9 +9,9 @@ use std::{
 
 use bytes::BytesMut;
 use compositor_render::{error::ErrorStack, InputId};
-use crossbeam_channel::{bounded, Receiver, Sender};
+use crossbeam_channel::{bounded, Receiver, SendError, Sender};
 use log::error;
-use tracing::{debug, info, span, trace, Level};
+use tracing::{debug, info, span, trace, warn, Level};
 
 use crate::pipeline::{rtp::bind_to_requested_port, Port};
 
@@ -20,6 +20,7 @@ use super::{RtpReceiverError, RtpReceiverOptions};
 pub(super) fn start_tcp_server_thread(
     input_id: &InputId,
     opts: &RtpReceiverOptions,
+    feedback_receiver: Receiver<bytes::Bytes>,
     should_close: Arc<AtomicBool>,
 ) -> Result<(Port, Receiver<bytes::Bytes>), RtpReceiverError> {
     let (packets_tx, packets_rx) = bounded(1000);
@@ -48,7 +49,7 @@ pub(super) fn start_tcp_server_thread(
                 input_id = input_id.to_string()
             )
             .entered();
-            run_tcp_server_thread(socket, packets_tx, should_close);
+            run_tcp_server_thread(socket, packets_tx, feedback_receiver, should_close);
             debug!("Closing RTP receiver thread (TCP server).");
         })
         .unwrap();
@@ -59,6 +60,7 @@ pub(super) fn start_tcp_server_thread(
 fn run_tcp_server_thread(
     socket: std::net::TcpListener,
     packets_tx: Sender<bytes::Bytes>,
+    feedback_receiver: Receiver<bytes::Bytes>,
     should_close: Arc<AtomicBool>,
 ) {
     // make accept non blocking so we have a chance to handle should_close value
@@ -76,20 +78,48 @@ fn run_tcp_server_thread(
         connected_socket = Some(socket);
     }
 
-    let socket = match connected_socket {
-        Some(socket) => TcpReadPacketStream::new(socket, should_close.clone()),
-        None => {
-            return;
+    let Some(socket) = connected_socket else {
+        return;
+    };
+    if receive_packets(socket, &packets_tx, &feedback_receiver, &should_close).is_err() {
+        debug!("Failed to send raw RTP packet from TCP server element. Channel closed.");
+    }
+}
+
+/// Reads RFC 4571 framed packets from an established connection until it is closed
+/// or `should_close` is set. RTCP feedback is written back on the same connection.
+pub(super) fn receive_packets(
+    socket: TcpStream,
+    packets_tx: &Sender<bytes::Bytes>,
+    feedback_receiver: &Receiver<bytes::Bytes>,
+    should_close: &Arc<AtomicBool>,
+) -> Result<(), SendError<bytes::Bytes>> {
+    let mut feedback_socket = match socket.try_clone() {
+        Ok(socket) => Some(socket),
+        Err(err) => {
+            warn!(%err, "Failed to clone TCP socket. RTCP feedback will not be sent.");
+            None
         }
     };
 
-    for packet in socket {
+    for packet in TcpReadPacketStream::new(socket, should_close.clone()) {
         trace!(size_bytes = packet.len(), "Received RTP packet");
-        if packets_tx.send(packet).is_err() {
-            debug!("Failed to send raw RTP packet from TCP server element. Channel closed.");
-            return;
+        packets_tx.send(packet)?;
+
+        if let Some(socket) = &mut feedback_socket {
+            for packet in feedback_receiver.try_iter() {
+                // RFC 4571 framing, the same as for received packets.
+                let len = (packet.len() as u16).to_be_bytes();
+                if let Err(err) = socket
+                    .write_all(&len)
+                    .and_then(|_| socket.write_all(&packet))
+                {
+                    debug!(%err, "Failed to send RTCP feedback packet.");
+                }
+            }
         }
     }
+    Ok(())
 }
 
 struct TcpReadPacketStream {
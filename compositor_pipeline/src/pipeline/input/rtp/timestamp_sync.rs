@@ -0,0 +1,240 @@
+use std::time::Duration;
+
+use tracing::debug;
+
+use crate::pipeline::structs::{EncodedChunk, EncodedChunkKind};
+
+/// Video is shifted only if the new offset differs from the current one by more
+/// than this value, small differences are caused by jitter of sender reports.
+const RESYNC_THRESHOLD_SECS: f64 = 0.01;
+
+/// When video has to be moved back, its offset decreases by at most this fraction
+/// of the elapsed stream time, i.e. video plays 10% slower until it is in sync.
+/// Moving it back at once would make PTS go backwards.
+const MAX_BACKWARD_RESYNC_RATE: f64 = 0.1;
+
+/// Maps PTS of depayloaded chunks onto a timeline shared by the audio and video
+/// tracks of an input.
+///
+/// The first chunk of each track starts at PTS 0. After RTCP Sender Reports are
+/// received for both tracks, video PTS are shifted, so video and audio captured at
+/// the same NTP wallclock time have the same PTS. Audio PTS are never shifted, to
+/// avoid gaps or overlapping samples. Video is moved forward at once, but moved
+/// back gradually, so its timestamps stay monotonic.
+pub(super) struct TimestampSync {
+    video: Option<TrackSync>,
+    audio: Option<TrackSync>,
+}
+
+struct TrackSync {
+    clock_rate: u32,
+    /// Value (in seconds) added to the PTS calculated from RTP timestamps.
+    offset: Option<f64>,
+    /// Offset calculated from sender reports that `offset` is moving towards.
+    target_offset: Option<f64>,
+    /// Last DTS (or PTS if DTS is not known) calculated from RTP timestamps.
+    last_decode_time: Option<Duration>,
+    /// Last PTS calculated from RTP timestamps. Used to resolve the rollover of
+    /// RTP timestamps from sender reports.
+    last_pts: Option<Duration>,
+    /// NTP time (in seconds) and matching PTS from the last sender report.
+    sender_report: Option<(f64, f64)>,
+}
+
+impl TimestampSync {
+    pub fn new(video_clock_rate: Option<u32>, audio_clock_rate: Option<u32>) -> Self {
+        Self {
+            video: video_clock_rate.map(TrackSync::new),
+            audio: audio_clock_rate.map(TrackSync::new),
+        }
+    }
+
+    pub fn synchronize(&mut self, chunk: &mut EncodedChunk) {
+        let track = match chunk.kind {
+            EncodedChunkKind::Video(_) => &mut self.video,
+            EncodedChunkKind::Audio(_) => &mut self.audio,
+        };
+        let Some(track) = track else {
+            return;
+        };
+
+        let offset = track.update_offset(chunk);
+        track.last_pts = Some(chunk.pts);
+        chunk.pts = apply_offset(chunk.pts, offset);
+        chunk.dts = chunk.dts.map(|dts| apply_offset(dts, offset));
+    }
+
+    pub fn on_video_sender_report(&mut self, ntp_time: u64, rtp_time: u32) {
+        if let Some(video) = &mut self.video {
+            video.on_sender_report(ntp_time, rtp_time);
+            self.resync_video();
+        }
+    }
+
+    pub fn on_audio_sender_report(&mut self, ntp_time: u64, rtp_time: u32) {
+        if let Some(audio) = &mut self.audio {
+            audio.on_sender_report(ntp_time, rtp_time);
+            self.resync_video();
+        }
+    }
+
+    fn resync_video(&mut self) {
+        let (Some(video), Some(audio)) = (&mut self.video, &self.audio) else {
+            return;
+        };
+        let (Some((video_ntp, video_pts)), Some((audio_ntp, audio_pts)), Some(audio_offset)) =
+            (video.sender_report, audio.sender_report, audio.offset)
+        else {
+            return;
+        };
+
+        // Audio sample captured at NTP time `t` has PTS:
+        //   t - audio_ntp + audio_pts + audio_offset
+        // Video frame captured at the same time should have the same PTS.
+        let offset = video_ntp - video_pts - audio_ntp + audio_pts + audio_offset;
+        match video.target_offset.or(video.offset) {
+            Some(current) if (current - offset).abs() <= RESYNC_THRESHOLD_SECS => (),
+            current => {
+                debug!(
+                    ?current,
+                    offset, "Synchronizing video with audio based on RTCP sender reports."
+                );
+                video.target_offset = Some(offset);
+            }
+        }
+    }
+}
+
+impl TrackSync {
+    fn new(clock_rate: u32) -> Self {
+        Self {
+            clock_rate,
+            offset: None,
+            target_offset: None,
+            last_decode_time: None,
+            last_pts: None,
+            sender_report: None,
+        }
+    }
+
+    /// Returns offset for the chunk, moving the current offset towards the target.
+    fn update_offset(&mut self, chunk: &EncodedChunk) -> f64 {
+        let decode_time = chunk.dts.unwrap_or(chunk.pts);
+        let elapsed = self
+            .last_decode_time
+            .map(|last| decode_time.saturating_sub(last).as_secs_f64())
+            .unwrap_or(0.0);
+        self.last_decode_time = Some(decode_time);
+
+        let offset = self.offset.get_or_insert(-chunk.pts.as_secs_f64());
+        if let Some(target) = self.target_offset {
+            *offset = match target < *offset {
+                true => f64::max(target, *offset - elapsed * MAX_BACKWARD_RESYNC_RATE),
+                false => target,
+            };
+        }
+        *offset
+    }
+
+    fn on_sender_report(&mut self, ntp_time: u64, rtp_time: u32) {
+        // Sender reports received before the first packet can't be mapped to PTS,
+        // but they are sent periodically.
+        let Some(last_pts) = self.last_pts else {
+            return;
+        };
+        let clock_rate = self.clock_rate as f64;
+        let last_timestamp = (last_pts.as_secs_f64() * clock_rate).round() as u64;
+        let timestamp_diff = rtp_time.wrapping_sub(last_timestamp as u32) as i32;
+        let pts = (last_timestamp as f64 + timestamp_diff as f64) / clock_rate;
+
+        self.sender_report = Some((ntp_time_secs(ntp_time), pts));
+    }
+}
+
+/// [RFC 3550, section 4](https://datatracker.ietf.org/doc/html/rfc3550#section-4):
+/// the integer part is in the first 32 bits and the fraction part in the last 32 bits.
+fn ntp_time_secs(ntp_time: u64) -> f64 {
+    (ntp_time >> 32) as f64 + (ntp_time & 0xFFFF_FFFF) as f64 / (1u64 << 32) as f64
+}
+
+fn apply_offset(pts: Duration, offset: f64) -> Duration {
+    Duration::from_secs_f64((pts.as_secs_f64() + offset).max(0.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bytes::Bytes;
+
+    use crate::pipeline::{
+        structs::{EncodedChunk, EncodedChunkKind, IsKeyframe},
+        AudioCodec, VideoCodec,
+    };
+
+    use super::TimestampSync;
+
+    fn chunk(kind: EncodedChunkKind, timestamp: u32, clock_rate: u32) -> EncodedChunk {
+        EncodedChunk {
+            data: Bytes::new(),
+            pts: Duration::from_secs_f64(timestamp as f64 / clock_rate as f64),
+            dts: None,
+            is_keyframe: IsKeyframe::NoKeyframes,
+            kind,
+        }
+    }
+
+    fn ntp_time(secs: f64) -> u64 {
+        (secs * (1u64 << 32) as f64) as u64
+    }
+
+    #[test]
+    fn video_is_synchronized_with_audio() {
+        let video = EncodedChunkKind::Video(VideoCodec::H264);
+        let audio = EncodedChunkKind::Audio(AudioCodec::Opus);
+        let mut sync = TimestampSync::new(Some(90_000), Some(48_000));
+
+        let mut first_video = chunk(video, 900_000, 90_000);
+        let mut first_audio = chunk(audio, 4_800_000, 48_000);
+        sync.synchronize(&mut first_video);
+        sync.synchronize(&mut first_audio);
+        assert_eq!(first_video.pts, Duration::ZERO);
+        assert_eq!(first_audio.pts, Duration::ZERO);
+
+        // The first video frame was captured 0.5s after the first audio sample.
+        sync.on_audio_sender_report(ntp_time(1000.0), 4_800_000);
+        sync.on_video_sender_report(ntp_time(1000.5), 900_000);
+
+        let mut video_chunk = chunk(video, 900_000 + 9_000, 90_000);
+        sync.synchronize(&mut video_chunk);
+        assert!((video_chunk.pts.as_secs_f64() - 0.6).abs() < 1e-5);
+
+        let mut audio_chunk = chunk(audio, 4_800_000 + 4_800, 48_000);
+        sync.synchronize(&mut audio_chunk);
+        assert!((audio_chunk.pts.as_secs_f64() - 0.1).abs() < 1e-5);
+    }
+
+    #[test]
+    fn video_is_moved_back_gradually() {
+        let video = EncodedChunkKind::Video(VideoCodec::H264);
+        let audio = EncodedChunkKind::Audio(AudioCodec::Opus);
+        let mut sync = TimestampSync::new(Some(90_000), Some(48_000));
+
+        sync.synchronize(&mut chunk(video, 900_000, 90_000));
+        sync.synchronize(&mut chunk(audio, 4_800_000, 48_000));
+
+        // The first video frame was captured 0.5s before the first audio sample.
+        sync.on_audio_sender_report(ntp_time(1000.0), 4_800_000);
+        sync.on_video_sender_report(ntp_time(999.5), 900_000);
+
+        let mut last_pts = Duration::ZERO;
+        for frame in 1..=250 {
+            let mut video_chunk = chunk(video, 900_000 + frame * 3_600, 90_000);
+            sync.synchronize(&mut video_chunk);
+            assert!(video_chunk.pts > last_pts);
+            last_pts = video_chunk.pts;
+        }
+        // After 10 seconds the whole 0.5s difference is applied.
+        assert!((last_pts.as_secs_f64() - 9.5).abs() < 1e-5);
+    }
+}
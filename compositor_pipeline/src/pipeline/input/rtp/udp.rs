@@ -1,24 +1,198 @@
 use std::{
-    sync::{atomic::AtomicBool, Arc},
-    thread,
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{atomic::AtomicBool, Arc, Mutex},
+    thread::{self, JoinHandle},
 };
 
 use bytes::{Bytes, BytesMut};
 use compositor_render::InputId;
 use crossbeam_channel::{unbounded, Receiver, Sender};
-use tracing::{debug, span, warn, Level};
+use tracing::{debug, span, trace, warn, Level};
 
-use crate::pipeline::{rtp::bind_to_requested_port, Port};
+use crate::pipeline::{
+    rtp::{bind_to_requested_port, RequestedPort},
+    Port,
+};
 
 use super::{RtpReceiverError, RtpReceiverOptions};
 
+/// Starts a thread that receives packets on a UDP port used only by this input. The
+/// socket is closed when the returned thread finishes, after `should_close` is set.
 pub(super) fn start_udp_reader_thread(
     input_id: &InputId,
     opts: &RtpReceiverOptions,
+    feedback_receiver: Receiver<Bytes>,
     should_close: Arc<AtomicBool>,
-) -> Result<(Port, Receiver<bytes::Bytes>), RtpReceiverError> {
+) -> Result<(Port, Receiver<bytes::Bytes>, JoinHandle<()>), RtpReceiverError> {
+    let (packets_tx, packets_rx) = unbounded();
+
+    let (port, socket) = bind_udp_socket(opts.port)?;
+
+    let input_id = input_id.clone();
+    let receiver_thread = thread::Builder::new()
+        .name(format!("RTP UDP receiver {}", input_id))
+        .spawn(move || {
+            let _span = span!(
+                Level::INFO,
+                "RTP TCP server",
+                input_id = input_id.to_string()
+            )
+            .entered();
+            run_udp_receiver_thread(socket, packets_tx, feedback_receiver, should_close);
+            debug!("Closing RTP receiver thread (UDP).");
+        })
+        .unwrap();
+
+    Ok((port, packets_rx, receiver_thread))
+}
+
+/// UDP sockets shared by multiple RTP inputs of a pipeline, indexed by port.
+#[derive(Default, Clone)]
+pub struct SharedUdpSockets(Arc<Mutex<HashMap<u16, SharedSocket>>>);
+
+/// Registers an input on a UDP port that can be shared with other inputs registered
+/// the same way. Packets are routed to the input based on their SSRC.
+///
+/// The socket is closed when all inputs using it are unregistered (their
+/// [`SharedSocketRegistration`] is dropped).
+pub(super) fn register_shared_udp_input(
+    shared_sockets: &SharedUdpSockets,
+    input_id: &InputId,
+    opts: &RtpReceiverOptions,
+    ssrcs: &[u32],
+    feedback_receiver: Receiver<Bytes>,
+) -> Result<(Port, Receiver<bytes::Bytes>, SharedSocketRegistration), RtpReceiverError> {
+    let mut sockets = shared_sockets.0.lock().unwrap();
+
+    let existing_port = sockets.keys().copied().find(|port| match opts.port {
+        RequestedPort::Exact(requested_port) => requested_port == *port,
+        RequestedPort::Range((lower_bound, upper_bound)) => {
+            (lower_bound..upper_bound).contains(port)
+        }
+    });
+    let port = match existing_port {
+        Some(port) => port,
+        None => {
+            let (port, socket) = bind_udp_socket(opts.port)?;
+            let inputs = Arc::new(Mutex::new(SharedSocketInputs::default()));
+            let should_close = Arc::new(AtomicBool::new(false));
+            let receiver_inputs = inputs.clone();
+            let receiver_should_close = should_close.clone();
+            let receiver_thread = thread::Builder::new()
+                .name(format!("RTP UDP shared receiver {}", port.0))
+                .spawn(move || {
+                    let _span =
+                        span!(Level::INFO, "RTP UDP shared receiver", port = port.0).entered();
+                    run_shared_udp_receiver_thread(socket, receiver_inputs, receiver_should_close);
+                    debug!("Closing RTP receiver thread (shared UDP).");
+                })
+                .unwrap();
+            sockets.insert(
+                port.0,
+                SharedSocket {
+                    inputs,
+                    should_close,
+                    receiver_thread,
+                },
+            );
+            port.0
+        }
+    };
+
+    let mut inputs = sockets[&port].inputs.lock().unwrap();
+    if let Some(ssrc) = ssrcs.iter().find(|ssrc| inputs.ssrcs.contains_key(ssrc)) {
+        return Err(RtpReceiverError::SsrcAlreadyInUse { port, ssrc: *ssrc });
+    }
+
     let (packets_tx, packets_rx) = unbounded();
+    for ssrc in ssrcs {
+        inputs.ssrcs.insert(*ssrc, input_id.clone());
+    }
+    inputs.inputs.insert(
+        input_id.clone(),
+        SharedSocketInput {
+            packets_tx,
+            feedback_receiver,
+            peer_address: None,
+        },
+    );
+
+    Ok((
+        Port(port),
+        packets_rx,
+        SharedSocketRegistration {
+            shared_sockets: shared_sockets.clone(),
+            port,
+            input_id: input_id.clone(),
+        },
+    ))
+}
 
+/// Unregisters the input from a shared UDP socket when dropped. If it was the last
+/// input using the socket, waits until the socket is closed, so the port can be
+/// registered again right away.
+pub(super) struct SharedSocketRegistration {
+    shared_sockets: SharedUdpSockets,
+    port: u16,
+    input_id: InputId,
+}
+
+impl Drop for SharedSocketRegistration {
+    fn drop(&mut self) {
+        let mut sockets = self.shared_sockets.0.lock().unwrap();
+        let Some(socket) = sockets.get(&self.port) else {
+            return;
+        };
+
+        let is_unused = {
+            let mut inputs = socket.inputs.lock().unwrap();
+            inputs.inputs.remove(&self.input_id);
+            inputs
+                .ssrcs
+                .retain(|_, input_id| *input_id != self.input_id);
+            inputs.inputs.is_empty()
+        };
+        if !is_unused {
+            return;
+        }
+        let Some(socket) = sockets.remove(&self.port) else {
+            return;
+        };
+        // The lock is held until the socket is closed, so registering the same port
+        // in the meantime can't fail.
+        socket
+            .should_close
+            .store(true, std::sync::atomic::Ordering::Relaxed);
+        if socket.receiver_thread.join().is_err() {
+            warn!(port = self.port, "Shared UDP receiver thread panicked.");
+        }
+    }
+}
+
+struct SharedSocket {
+    inputs: Arc<Mutex<SharedSocketInputs>>,
+    should_close: Arc<AtomicBool>,
+    receiver_thread: JoinHandle<()>,
+}
+
+#[derive(Default)]
+struct SharedSocketInputs {
+    /// Input that receives packets with a given SSRC.
+    ssrcs: HashMap<u32, InputId>,
+    inputs: HashMap<InputId, SharedSocketInput>,
+}
+
+struct SharedSocketInput {
+    packets_tx: Sender<Bytes>,
+    feedback_receiver: Receiver<Bytes>,
+    /// Address the last packet routed to this input was received from.
+    peer_address: Option<SocketAddr>,
+}
+
+fn bind_udp_socket(
+    requested_port: RequestedPort,
+) -> Result<(Port, std::net::UdpSocket), RtpReceiverError> {
     let socket = socket2::Socket::new(
         socket2::Domain::IPV4,
         socket2::Type::DGRAM,
@@ -36,46 +210,41 @@ pub(super) fn start_udp_reader_thread(
         }
     }
 
-    let port = bind_to_requested_port(opts.port, &socket)?;
+    let port = bind_to_requested_port(requested_port, &socket)?;
 
     socket
         .set_read_timeout(Some(std::time::Duration::from_millis(50)))
         .map_err(RtpReceiverError::SocketOptions)?;
 
-    let socket = std::net::UdpSocket::from(socket);
-
-    let input_id = input_id.clone();
-    thread::Builder::new()
-        .name(format!("RTP UDP receiver {}", input_id))
-        .spawn(move || {
-            let _span = span!(
-                Level::INFO,
-                "RTP TCP server",
-                input_id = input_id.to_string()
-            )
-            .entered();
-            run_udp_receiver_thread(socket, packets_tx, should_close);
-            debug!("Closing RTP receiver thread (UDP).");
-        })
-        .unwrap();
-
-    Ok((port, packets_rx))
+    Ok((port, std::net::UdpSocket::from(socket)))
 }
 
 fn run_udp_receiver_thread(
     socket: std::net::UdpSocket,
     packets_tx: Sender<Bytes>,
+    feedback_receiver: Receiver<Bytes>,
     should_close: Arc<AtomicBool>,
 ) {
     let mut buffer = BytesMut::zeroed(65536);
+    // RTCP feedback is sent to the address the stream is received from.
+    let mut peer_address = None;
 
     loop {
         if should_close.load(std::sync::atomic::Ordering::Relaxed) {
             return;
         }
 
+        for packet in feedback_receiver.try_iter() {
+            let Some(peer_address) = peer_address else {
+                continue;
+            };
+            if let Err(err) = socket.send_to(&packet, peer_address) {
+                debug!(%err, "Failed to send RTCP feedback packet.");
+            }
+        }
+
         // This can be faster if we batched sending the packets through the channel
-        let (received_bytes, _) = match socket.recv_from(&mut buffer) {
+        let (received_bytes, address) = match socket.recv_from(&mut buffer) {
             Ok(n) => n,
             Err(e) => match e.kind() {
                 std::io::ErrorKind::WouldBlock => continue,
@@ -86,6 +255,8 @@ fn run_udp_receiver_thread(
             },
         };
 
+        peer_address = Some(address);
+
         if packets_tx
             .send(Bytes::copy_from_slice(&buffer[..received_bytes]))
             .is_err()
@@ -95,3 +266,162 @@ fn run_udp_receiver_thread(
         }
     }
 }
+
+fn run_shared_udp_receiver_thread(
+    socket: std::net::UdpSocket,
+    inputs: Arc<Mutex<SharedSocketInputs>>,
+    should_close: Arc<AtomicBool>,
+) {
+    let mut buffer = BytesMut::zeroed(65536);
+
+    loop {
+        if should_close.load(std::sync::atomic::Ordering::Relaxed) {
+            return;
+        }
+
+        for input in inputs.lock().unwrap().inputs.values() {
+            for packet in input.feedback_receiver.try_iter() {
+                let Some(peer_address) = input.peer_address else {
+                    continue;
+                };
+                if let Err(err) = socket.send_to(&packet, peer_address) {
+                    debug!(%err, "Failed to send RTCP feedback packet.");
+                }
+            }
+        }
+
+        let (received_bytes, address) = match socket.recv_from(&mut buffer) {
+            Ok(n) => n,
+            Err(e) => match e.kind() {
+                std::io::ErrorKind::WouldBlock => continue,
+                _ => {
+                    log::error!("Error while receiving UDP packet: {}", e);
+                    continue;
+                }
+            },
+        };
+
+        let packet = &buffer[..received_bytes];
+        let Some(ssrc) = packet_ssrc(packet) else {
+            debug!("Received packet is too short to be RTP or RTCP. Dropping.");
+            continue;
+        };
+
+        let mut inputs = inputs.lock().unwrap();
+        let SharedSocketInputs { ssrcs, inputs } = &mut *inputs;
+        let Some(input) = ssrcs
+            .get(&ssrc)
+            .and_then(|input_id| inputs.get_mut(input_id))
+        else {
+            trace!(ssrc, "Received packet with unknown SSRC. Dropping.");
+            continue;
+        };
+        input.peer_address = Some(address);
+        if input
+            .packets_tx
+            .send(Bytes::copy_from_slice(packet))
+            .is_err()
+        {
+            debug!(
+                ssrc,
+                "Failed to send raw RTP packet from shared UDP socket. Channel closed."
+            );
+        }
+    }
+}
+
+/// Returns SSRC of the sender of an RTP or RTCP packet, without parsing the whole packet.
+fn packet_ssrc(packet: &[u8]) -> Option<u32> {
+    // RFC 5761, section 4: RTCP packet types (e.g. 200 - SR, 203 - BYE) fall into
+    // 64-95 range when interpreted as the RTP payload type. In RTCP packets SSRC
+    // of the sender (or the first source of BYE) follows the 4 byte header.
+    let offset = match packet.get(1)? & 0x7F {
+        64..=95 => 4,
+        _ => 8,
+    };
+    let ssrc = packet.get(offset..offset + 4)?;
+    Some(u32::from_be_bytes([ssrc[0], ssrc[1], ssrc[2], ssrc[3]]))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::UdpSocket, time::Duration};
+
+    use compositor_render::InputId;
+    use crossbeam_channel::unbounded;
+
+    use crate::pipeline::{
+        input::rtp::{RtpReceiverError, RtpReceiverOptions, RtpStream},
+        rtp::{RequestedPort, TransportProtocol},
+    };
+
+    use super::{packet_ssrc, register_shared_udp_input, SharedUdpSockets};
+
+    fn shared_input_options(port: u16, ssrc: u32) -> RtpReceiverOptions {
+        RtpReceiverOptions {
+            port: RequestedPort::Exact(port),
+            transport_protocol: TransportProtocol::Udp,
+            stream: RtpStream {
+                video: None,
+                audio: None,
+            },
+            jitter_buffer_latency: Duration::from_millis(100),
+            ssrc: Some(vec![ssrc]),
+        }
+    }
+
+    #[test]
+    fn shared_sockets_belong_to_a_pipeline() {
+        let port = UdpSocket::bind("0.0.0.0:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let pipeline_sockets = SharedUdpSockets::default();
+        let other_pipeline_sockets = SharedUdpSockets::default();
+
+        let (_, _, registration) = register_shared_udp_input(
+            &pipeline_sockets,
+            &InputId("input_1".into()),
+            &shared_input_options(port, 1),
+            &[1],
+            unbounded().1,
+        )
+        .unwrap();
+
+        // Inputs of another pipeline can't share the socket.
+        let result = register_shared_udp_input(
+            &other_pipeline_sockets,
+            &InputId("input_2".into()),
+            &shared_input_options(port, 2),
+            &[2],
+            unbounded().1,
+        );
+        assert!(matches!(result, Err(RtpReceiverError::PortAlreadyInUse(_))));
+
+        // The socket is closed as soon as the last input is unregistered.
+        drop(registration);
+        register_shared_udp_input(
+            &other_pipeline_sockets,
+            &InputId("input_2".into()),
+            &shared_input_options(port, 2),
+            &[2],
+            unbounded().1,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn ssrc_of_rtp_and_rtcp_packets() {
+        let rtp_packet = [
+            0x80, 0x60, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x12, 0x34, 0x56, 0x78, 0xAA,
+        ];
+        assert_eq!(packet_ssrc(&rtp_packet), Some(0x12345678));
+
+        // Sender report header followed by SSRC of the sender.
+        let rtcp_packet = [0x80, 0xC8, 0x00, 0x06, 0x9A, 0xBC, 0xDE, 0xF0];
+        assert_eq!(packet_ssrc(&rtcp_packet), Some(0x9ABCDEF0));
+
+        assert_eq!(packet_ssrc(&rtp_packet[..10]), None);
+    }
+}
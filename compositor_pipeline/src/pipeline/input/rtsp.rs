@@ -0,0 +1,785 @@
+use std::{
+    mem,
+    net::UdpSocket,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+use compositor_render::InputId;
+use crossbeam_channel::{unbounded, SendError, Sender};
+use rand::Rng;
+use tracing::{debug, info, span, trace, warn, Level};
+use webrtc_util::{Marshal, Unmarshal};
+
+use crate::pipeline::{
+    decoder::DecoderOptions,
+    rtp::{AUDIO_PAYLOAD_TYPE, VIDEO_PAYLOAD_TYPE},
+    sdp::{SdpError, SessionDescription},
+};
+
+use self::client::{RtspClient, RtspMessage};
+
+use super::{
+    rtp::{
+        start_depayloader_thread, DepayloaderNewError, InputAudioStream, InputVideoStream,
+        RtpInputStats, RtpStream, DEFAULT_JITTER_BUFFER_LATENCY,
+    },
+    ChunksReceiver,
+};
+
+pub use self::client::RtspError;
+
+mod client;
+
+/// Delay before the first attempt to restore a lost session. It doubles after each
+/// failed attempt, up to [`RTSP_MAX_RECONNECT_DELAY`].
+const RTSP_MIN_RECONNECT_DELAY: Duration = Duration::from_millis(500);
+const RTSP_MAX_RECONNECT_DELAY: Duration = Duration::from_secs(10);
+
+#[derive(Debug, thiserror::Error)]
+pub enum RtspReceiverError {
+    #[error(transparent)]
+    Rtsp(#[from] RtspError),
+
+    #[error("Failed to parse the SDP returned by the RTSP server.")]
+    Sdp(#[from] SdpError),
+
+    #[error("RTSP stream does not contain any video or audio track with a supported codec.")]
+    NoSupportedTracks,
+
+    #[error("RTSP server responded with an invalid Transport header: \"{0}\".")]
+    InvalidTransport(String),
+
+    #[error("Failed to bind UDP sockets for the RTSP stream.")]
+    SocketBind(#[source] std::io::Error),
+
+    #[error(transparent)]
+    DepayloaderError(#[from] DepayloaderNewError),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RtspTransport {
+    /// RTP packets are interleaved in the RTSP connection.
+    Tcp,
+    /// RTP packets are sent to separate UDP ports.
+    Udp,
+}
+
+#[derive(Debug, Clone)]
+pub struct RtspReceiverOptions {
+    pub url: Arc<str>,
+    pub transport: RtspTransport,
+    /// Receive the video track if the stream contains one.
+    pub video: bool,
+    /// Receive the audio track if the stream contains one.
+    pub audio: bool,
+}
+
+/// Input that pulls a stream from an RTSP server, e.g. an IP camera.
+///
+/// The session is negotiated when the input is registered, so codecs (and their
+/// parameters, e.g. AAC AudioSpecificConfig) are taken from the SDP returned by the
+/// server. Received RTP packets are fed to the same depayloader that is used by the
+/// RTP input. A lost session is negotiated again until the input is unregistered.
+pub struct RtspReceiver {
+    should_close: Arc<AtomicBool>,
+    stats: Arc<Mutex<RtpInputStats>>,
+}
+
+impl RtspReceiver {
+    pub fn new(
+        input_id: &InputId,
+        opts: RtspReceiverOptions,
+    ) -> Result<(Self, ChunksReceiver, DecoderOptions), RtspReceiverError> {
+        let presentation = describe(&opts)?;
+        let stream = presentation.stream.clone();
+
+        let (packets_sender, packets_receiver) = unbounded();
+        let (chunks_receiver, stats) = start_depayloader_thread(
+            input_id,
+            packets_receiver,
+            &stream,
+            DEFAULT_JITTER_BUFFER_LATENCY,
+            None,
+        )?;
+
+        let session = presentation.play(opts.transport, &packets_sender)?;
+
+        let should_close = Arc::new(AtomicBool::new(false));
+        let receiver_should_close = should_close.clone();
+        let input_id = input_id.clone();
+        std::thread::Builder::new()
+            .name(format!("RTSP receiver for input {}", input_id))
+            .spawn(move || {
+                let _span = span!(
+                    Level::INFO,
+                    "RTSP receiver",
+                    input_id = input_id.to_string()
+                )
+                .entered();
+                run_receiver_thread(session, opts, packets_sender, receiver_should_close);
+                debug!("Closing RTSP receiver thread.");
+            })
+            .unwrap();
+
+        Ok((
+            Self {
+                should_close,
+                stats,
+            },
+            chunks_receiver,
+            DecoderOptions {
+                video: stream.video.map(|video| video.options),
+                audio: stream.audio.map(|audio| audio.options),
+            },
+        ))
+    }
+
+    pub fn stats(&self) -> RtpInputStats {
+        *self.stats.lock().unwrap()
+    }
+}
+
+impl Drop for RtspReceiver {
+    fn drop(&mut self) {
+        self.should_close.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Resolves `a=control` attribute against the base URL of the presentation.
+fn resolve_control_url(base_url: &str, control: Option<&str>) -> String {
+    match control {
+        None | Some("*") | Some("") => base_url.to_string(),
+        Some(control) if control.starts_with("rtsp://") => control.to_string(),
+        Some(control) => format!("{}/{}", base_url.trim_end_matches('/'), control),
+    }
+}
+
+/// Presentation returned by DESCRIBE, with the tracks that will be set up.
+struct Presentation {
+    client: RtspClient,
+    session_url: String,
+    stream: RtpStream,
+    tracks: Vec<TrackSetup>,
+}
+
+fn describe(opts: &RtspReceiverOptions) -> Result<Presentation, RtspReceiverError> {
+    let mut client = RtspClient::connect(&opts.url)?;
+    let url = client.url().to_string();
+
+    let describe = client.request("DESCRIBE", &url, &[("Accept", "application/sdp")])?;
+    let base_url = describe
+        .header("Content-Base")
+        .or_else(|| describe.header("Content-Location"))
+        .unwrap_or(&url)
+        .to_string();
+    let sdp = SessionDescription::parse(&String::from_utf8_lossy(&describe.body))?;
+
+    let mut tracks = Vec::new();
+    let mut stream = RtpStream {
+        video: None,
+        audio: None,
+    };
+    if let Some(media) = sdp.video().filter(|_| opts.video) {
+        match media.video_track()? {
+            Some(track) => {
+                stream.video = Some(InputVideoStream {
+                    options: track.options,
+                    payload_type: VIDEO_PAYLOAD_TYPE,
+                });
+                tracks.push(TrackSetup {
+                    control_url: resolve_control_url(&base_url, media.control.as_deref()),
+                    remote_payload_type: track.payload_type,
+                    local_payload_type: VIDEO_PAYLOAD_TYPE,
+                    parameter_sets: track.parameter_sets,
+                });
+            }
+            None => warn!("RTSP stream does not contain a video track with a supported codec."),
+        }
+    }
+    if let Some(media) = sdp.audio().filter(|_| opts.audio) {
+        match media.audio_track()? {
+            Some(track) => {
+                stream.audio = Some(InputAudioStream {
+                    options: track.options,
+                    payload_type: AUDIO_PAYLOAD_TYPE,
+                });
+                tracks.push(TrackSetup {
+                    control_url: resolve_control_url(&base_url, media.control.as_deref()),
+                    remote_payload_type: track.payload_type,
+                    local_payload_type: AUDIO_PAYLOAD_TYPE,
+                    parameter_sets: Vec::new(),
+                });
+            }
+            None => {
+                warn!("RTSP stream does not contain an audio track with a supported codec.")
+            }
+        }
+    }
+    if tracks.is_empty() {
+        return Err(RtspReceiverError::NoSupportedTracks);
+    }
+
+    Ok(Presentation {
+        client,
+        session_url: resolve_control_url(&base_url, sdp.control.as_deref()),
+        stream,
+        tracks,
+    })
+}
+
+impl Presentation {
+    /// Sets up all tracks and starts the playback.
+    fn play(
+        self,
+        transport: RtspTransport,
+        packets_sender: &Sender<Bytes>,
+    ) -> Result<Session, RtspReceiverError> {
+        let Presentation {
+            mut client,
+            session_url,
+            tracks,
+            ..
+        } = self;
+        let closed = Arc::new(AtomicBool::new(false));
+
+        let setup_and_play = || {
+            let mut interleaved_tracks = Vec::new();
+            for (index, track) in tracks.into_iter().enumerate() {
+                let Track {
+                    forwarder,
+                    transport: track_transport,
+                } = track.setup(&mut client, transport, index, packets_sender.clone())?;
+                match track_transport {
+                    TrackTransport::Interleaved {
+                        rtp_channel,
+                        rtcp_channel,
+                    } => interleaved_tracks.push((rtp_channel, rtcp_channel, forwarder)),
+                    TrackTransport::Udp {
+                        rtp_socket,
+                        rtcp_socket,
+                    } => start_udp_reader_threads(rtp_socket, rtcp_socket, forwarder, &closed),
+                }
+            }
+            client.request("PLAY", &session_url, &[("Range", "npt=0.000-")])?;
+            Ok::<_, RtspReceiverError>(interleaved_tracks)
+        };
+
+        match setup_and_play() {
+            Ok(interleaved_tracks) => {
+                info!(url = %client.url(), "RTSP session started.");
+                Ok(Session {
+                    client,
+                    session_url,
+                    interleaved_tracks,
+                    closed,
+                })
+            }
+            Err(err) => {
+                // Stops UDP reader threads of tracks that were already set up.
+                closed.store(true, Ordering::Relaxed);
+                Err(err)
+            }
+        }
+    }
+}
+
+struct TrackSetup {
+    control_url: String,
+    remote_payload_type: u8,
+    local_payload_type: u8,
+    parameter_sets: Vec<Bytes>,
+}
+
+struct Track {
+    forwarder: TrackForwarder,
+    transport: TrackTransport,
+}
+
+enum TrackTransport {
+    Interleaved {
+        rtp_channel: u8,
+        rtcp_channel: u8,
+    },
+    Udp {
+        rtp_socket: UdpSocket,
+        rtcp_socket: UdpSocket,
+    },
+}
+
+impl TrackSetup {
+    fn setup(
+        self,
+        client: &mut RtspClient,
+        transport: RtspTransport,
+        index: usize,
+        packets_sender: Sender<Bytes>,
+    ) -> Result<Track, RtspReceiverError> {
+        let transport = match transport {
+            RtspTransport::Tcp => {
+                let channels = (2 * index as u8, 2 * index as u8 + 1);
+                let requested = format!(
+                    "RTP/AVP/TCP;unicast;interleaved={}-{}",
+                    channels.0, channels.1
+                );
+                let response =
+                    client.request("SETUP", &self.control_url, &[("Transport", &requested)])?;
+                // Server can choose different channels than the requested ones.
+                let (rtp_channel, rtcp_channel) = match response.header("Transport") {
+                    Some(transport) => match transport_param(transport, "interleaved") {
+                        Some(channels) => parse_range(channels)
+                            .ok_or_else(|| RtspReceiverError::InvalidTransport(transport.into()))?,
+                        None => channels,
+                    },
+                    None => channels,
+                };
+                TrackTransport::Interleaved {
+                    rtp_channel,
+                    rtcp_channel,
+                }
+            }
+            RtspTransport::Udp => {
+                let (port, rtp_socket, rtcp_socket) =
+                    bind_udp_socket_pair().map_err(RtspReceiverError::SocketBind)?;
+                let requested = format!("RTP/AVP;unicast;client_port={}-{}", port, port + 1);
+                client.request("SETUP", &self.control_url, &[("Transport", &requested)])?;
+                TrackTransport::Udp {
+                    rtp_socket,
+                    rtcp_socket,
+                }
+            }
+        };
+
+        Ok(Track {
+            forwarder: TrackForwarder {
+                remote_payload_type: self.remote_payload_type,
+                local_payload_type: self.local_payload_type,
+                parameter_sets: self.parameter_sets,
+                packets_sender,
+            },
+            transport,
+        })
+    }
+}
+
+/// Returns value of a parameter from the Transport header, e.g. `interleaved=0-1`.
+fn transport_param<'a>(transport: &'a str, name: &str) -> Option<&'a str> {
+    transport
+        .split(';')
+        .filter_map(|param| param.trim().split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
+
+fn parse_range<T: std::str::FromStr>(range: &str) -> Option<(T, T)> {
+    let (start, end) = range.split_once('-')?;
+    Some((start.parse().ok()?, end.parse().ok()?))
+}
+
+/// RTP has to be received on an even port and RTCP on the next one.
+fn bind_udp_socket_pair() -> Result<(u16, UdpSocket, UdpSocket), std::io::Error> {
+    let mut last_error = None;
+    for _ in 0..100 {
+        let port = rand::thread_rng().gen_range(10_000..30_000) * 2;
+        match (bind_udp_socket(port), bind_udp_socket(port + 1)) {
+            (Ok(rtp_socket), Ok(rtcp_socket)) => return Ok((port, rtp_socket, rtcp_socket)),
+            (Err(err), _) | (_, Err(err)) => last_error = Some(err),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| std::io::ErrorKind::AddrInUse.into()))
+}
+
+fn bind_udp_socket(port: u16) -> Result<UdpSocket, std::io::Error> {
+    let socket = socket2::Socket::new(
+        socket2::Domain::IPV4,
+        socket2::Type::DGRAM,
+        Some(socket2::Protocol::UDP),
+    )?;
+    if let Err(err) = socket.set_recv_buffer_size(16 * 1024 * 1024) {
+        warn!("Failed to set socket receive buffer size: {err} This may cause packet loss, especially on high-bitrate streams.");
+    }
+    socket.bind(&std::net::SocketAddr::from((std::net::Ipv4Addr::UNSPECIFIED, port)).into())?;
+    socket.set_read_timeout(Some(Duration::from_millis(50)))?;
+    Ok(socket.into())
+}
+
+/// Forwards packets of a single track to the depayloader. Payload type announced
+/// in the SDP is replaced with the one expected by the depayloader.
+struct TrackForwarder {
+    remote_payload_type: u8,
+    local_payload_type: u8,
+    /// H264 parameter sets from the SDP. They are sent before the first packet,
+    /// because some cameras do not repeat them in the stream.
+    parameter_sets: Vec<Bytes>,
+    packets_sender: Sender<Bytes>,
+}
+
+impl TrackForwarder {
+    fn forward_rtp(&mut self, data: Bytes) -> Result<(), SendError<Bytes>> {
+        let mut packet = match rtp::packet::Packet::unmarshal(&mut data.clone()) {
+            Ok(packet) => packet,
+            Err(err) => {
+                debug!(%err, "Failed to parse RTP packet.");
+                return Ok(());
+            }
+        };
+        if packet.header.payload_type != self.remote_payload_type {
+            trace!(
+                payload_type = packet.header.payload_type,
+                "Dropping RTP packet with an unexpected payload type."
+            );
+            return Ok(());
+        }
+        packet.header.payload_type = self.local_payload_type;
+
+        for parameter_set in mem::take(&mut self.parameter_sets) {
+            // Parameter sets become a part of the first access unit.
+            let mut header = packet.header.clone();
+            header.marker = false;
+            self.send(rtp::packet::Packet {
+                header,
+                payload: parameter_set,
+            })?;
+        }
+        self.send(packet)
+    }
+
+    fn forward_rtcp(&self, data: Bytes) -> Result<(), SendError<Bytes>> {
+        self.packets_sender.send(data)
+    }
+
+    fn send(&self, packet: rtp::packet::Packet) -> Result<(), SendError<Bytes>> {
+        match packet.marshal() {
+            Ok(packet) => self.packets_sender.send(packet),
+            Err(err) => {
+                warn!(%err, "Failed to serialize RTP packet.");
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Playing RTSP session. Packets of tracks that use UDP transport are received by
+/// separate threads that run until `closed` is set.
+struct Session {
+    client: RtspClient,
+    session_url: String,
+    interleaved_tracks: Vec<(u8, u8, TrackForwarder)>,
+    closed: Arc<AtomicBool>,
+}
+
+enum SessionEnd {
+    /// Input was unregistered or the depayloader is gone.
+    Closed,
+    ConnectionLost,
+}
+
+impl Session {
+    fn receive(mut self, should_close: &AtomicBool) -> SessionEnd {
+        // Requests in the RTSP connection keep the session alive.
+        let keepalive_interval = self.client.session_timeout() / 2;
+        let mut last_keepalive = Instant::now();
+
+        let session_end = loop {
+            if should_close.load(Ordering::Relaxed) {
+                break SessionEnd::Closed;
+            }
+            if last_keepalive.elapsed() > keepalive_interval {
+                last_keepalive = Instant::now();
+                if let Err(err) = self.client.send_request("OPTIONS", &self.session_url, &[]) {
+                    warn!("Failed to send RTSP keep-alive request: {err}");
+                    break SessionEnd::ConnectionLost;
+                }
+            }
+
+            let message = match self.client.read_message() {
+                Ok(Some(message)) => message,
+                Ok(None) => continue,
+                Err(err) => {
+                    warn!("RTSP connection lost: {err}");
+                    break SessionEnd::ConnectionLost;
+                }
+            };
+            let RtspMessage::Interleaved { channel, data } = message else {
+                // Responses to keep-alive requests.
+                continue;
+            };
+
+            if self.forward_interleaved(channel, data).is_err() {
+                debug!("Failed to forward RTSP packet. Channel closed.");
+                break SessionEnd::Closed;
+            }
+        };
+
+        self.closed.store(true, Ordering::Relaxed);
+        if let Err(err) = self.client.send_request("TEARDOWN", &self.session_url, &[]) {
+            debug!("Failed to send RTSP TEARDOWN request: {err}");
+        }
+        session_end
+    }
+
+    fn forward_interleaved(&mut self, channel: u8, data: Bytes) -> Result<(), SendError<Bytes>> {
+        for (rtp_channel, rtcp_channel, forwarder) in &mut self.interleaved_tracks {
+            match channel {
+                channel if channel == *rtp_channel => forwarder.forward_rtp(data.clone())?,
+                channel if channel == *rtcp_channel => forwarder.forward_rtcp(data.clone())?,
+                _ => continue,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Receives packets until the input is unregistered. When the connection drops
+/// (e.g. the camera reboots), the session is negotiated again with DESCRIBE, SETUP
+/// and PLAY. Packets of the new session are sent to the same depayloader, which
+/// treats it like a sequence number jump.
+fn run_receiver_thread(
+    mut session: Session,
+    opts: RtspReceiverOptions,
+    packets_sender: Sender<Bytes>,
+    should_close: Arc<AtomicBool>,
+) {
+    loop {
+        if let SessionEnd::Closed = session.receive(&should_close) {
+            return;
+        }
+
+        let mut delay = RTSP_MIN_RECONNECT_DELAY;
+        session = loop {
+            if !sleep_unless_closed(delay, &should_close) {
+                return;
+            }
+            match describe(&opts)
+                .and_then(|presentation| presentation.play(opts.transport, &packets_sender))
+            {
+                Ok(session) => break session,
+                Err(err) => {
+                    debug!(?delay, "Failed to restore RTSP session: {err}");
+                    delay = (delay * 2).min(RTSP_MAX_RECONNECT_DELAY);
+                }
+            }
+        };
+        info!("RTSP session restored.");
+    }
+}
+
+/// Returns `false` if the input was closed before the delay elapsed.
+fn sleep_unless_closed(delay: Duration, should_close: &AtomicBool) -> bool {
+    let start = Instant::now();
+    while start.elapsed() < delay {
+        if should_close.load(Ordering::Relaxed) {
+            return false;
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    !should_close.load(Ordering::Relaxed)
+}
+
+fn start_udp_reader_threads(
+    rtp_socket: UdpSocket,
+    rtcp_socket: UdpSocket,
+    mut forwarder: TrackForwarder,
+    should_close: &Arc<AtomicBool>,
+) {
+    let packets_sender = forwarder.packets_sender.clone();
+    let readers: [(
+        UdpSocket,
+        Box<dyn FnMut(Bytes) -> Result<(), SendError<Bytes>> + Send>,
+    ); 2] = [
+        (
+            rtp_socket,
+            Box::new(move |data| forwarder.forward_rtp(data)),
+        ),
+        (rtcp_socket, Box::new(move |data| packets_sender.send(data))),
+    ];
+
+    for (socket, mut forward) in readers {
+        let should_close = should_close.clone();
+        let span = tracing::Span::current();
+        std::thread::Builder::new()
+            .name("RTSP UDP receiver".to_string())
+            .spawn(move || {
+                let _span = span.entered();
+                let mut buffer = vec![0; 65536];
+                while !should_close.load(Ordering::Relaxed) {
+                    let received_bytes = match socket.recv(&mut buffer) {
+                        Ok(received_bytes) => received_bytes,
+                        Err(err) => match err.kind() {
+                            std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut => {
+                                continue
+                            }
+                            _ => {
+                                warn!("Error while receiving UDP packet: {err}");
+                                continue;
+                            }
+                        },
+                    };
+                    if forward(Bytes::copy_from_slice(&buffer[..received_bytes])).is_err() {
+                        debug!("Failed to forward RTSP packet. Channel closed.");
+                        return;
+                    }
+                }
+            })
+            .unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::{TcpListener, TcpStream},
+        sync::Arc,
+        time::Duration,
+    };
+
+    use bytes::Bytes;
+    use compositor_render::InputId;
+    use crossbeam_channel::Receiver;
+    use webrtc_util::Marshal;
+
+    use crate::{
+        pipeline::{structs::EncodedChunk, VideoCodec},
+        queue::PipelineEvent,
+    };
+
+    use super::{RtspReceiver, RtspReceiverOptions, RtspTransport};
+
+    const SDP: &str = "v=0\r\no=- 0 0 IN IP4 127.0.0.1\r\ns=Test\r\nt=0 0\r\na=control:*\r\nm=video 0 RTP/AVP 96\r\na=rtpmap:96 H264/90000\r\na=fmtp:96 packetization-mode=1;sprop-parameter-sets=Z0LAHg==,aM4=\r\na=control:trackID=0\r\n";
+
+    /// Serves `sessions` sessions with one video track, one connection each. A single
+    /// RTP packet is sent over the RTSP connection after PLAY. All connections except
+    /// the last one are dropped right after that.
+    fn start_test_server(sessions: usize) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        std::thread::spawn(move || {
+            for session in 0..sessions {
+                let (stream, _) = listener.accept().unwrap();
+                serve_session(stream, session as u16, session + 1 < sessions);
+            }
+        });
+        port
+    }
+
+    fn serve_session(stream: TcpStream, session: u16, drop_after_play: bool) {
+        let mut writer = stream.try_clone().unwrap();
+        let mut reader = BufReader::new(stream);
+        loop {
+            let mut request = Vec::new();
+            loop {
+                let mut line = String::new();
+                if reader.read_line(&mut line).unwrap_or(0) == 0 {
+                    return;
+                }
+                if line == "\r\n" {
+                    break;
+                }
+                request.push(line.trim().to_string());
+            }
+            let method = request[0].split(' ').next().unwrap().to_string();
+            let cseq = request
+                .iter()
+                .find_map(|line| line.strip_prefix("CSeq: "))
+                .unwrap()
+                .to_string();
+
+            let response = match method.as_str() {
+                "DESCRIBE" => format!(
+                    "RTSP/1.0 200 OK\r\nCSeq: {cseq}\r\nContent-Type: application/sdp\r\nContent-Length: {}\r\n\r\n{SDP}",
+                    SDP.len()
+                ),
+                "SETUP" => format!(
+                    "RTSP/1.0 200 OK\r\nCSeq: {cseq}\r\nSession: 1234;timeout=60\r\nTransport: RTP/AVP/TCP;unicast;interleaved=4-5\r\n\r\n"
+                ),
+                _ => format!("RTSP/1.0 200 OK\r\nCSeq: {cseq}\r\nSession: 1234\r\n\r\n"),
+            };
+            writer.write_all(response.as_bytes()).unwrap();
+
+            if method == "PLAY" {
+                let packet = rtp::packet::Packet {
+                    header: rtp::header::Header {
+                        version: 2,
+                        marker: true,
+                        payload_type: 96,
+                        // Restarted camera starts a new sequence.
+                        sequence_number: session * 1000,
+                        timestamp: 3000 + session as u32 * 90_000,
+                        ..Default::default()
+                    },
+                    payload: bytes::Bytes::from_static(&[0x65, 0x88]),
+                }
+                .marshal()
+                .unwrap();
+                let mut frame = vec![b'$', 4];
+                frame.extend_from_slice(&(packet.len() as u16).to_be_bytes());
+                frame.extend_from_slice(&packet);
+                writer.write_all(&frame).unwrap();
+                if drop_after_play {
+                    return;
+                }
+            }
+        }
+    }
+
+    fn receive_video_frame(chunks_receiver: &Receiver<PipelineEvent<EncodedChunk>>) -> Bytes {
+        let PipelineEvent::Data(EncodedChunk { data, .. }) = chunks_receiver
+            .recv_timeout(Duration::from_secs(5))
+            .unwrap()
+        else {
+            panic!("Expected a video chunk.");
+        };
+        data
+    }
+
+    /// Parameter sets from the SDP are prepended to the first frame of a session.
+    const FIRST_FRAME: [u8; 20] = [
+        0, 0, 0, 1, 0x67, 0x42, 0xC0, 0x1E, 0, 0, 0, 1, 0x68, 0xCE, 0, 0, 0, 1, 0x65, 0x88,
+    ];
+
+    #[test]
+    fn receive_interleaved_h264() {
+        let port = start_test_server(1);
+        let (_receiver, chunks_receiver, decoder_options) = RtspReceiver::new(
+            &InputId("rtsp".into()),
+            RtspReceiverOptions {
+                url: Arc::from(format!("rtsp://127.0.0.1:{port}/stream")),
+                transport: RtspTransport::Tcp,
+                video: true,
+                audio: true,
+            },
+        )
+        .unwrap();
+
+        assert_eq!(decoder_options.video.unwrap().codec, VideoCodec::H264);
+        assert!(decoder_options.audio.is_none());
+
+        let video = chunks_receiver.video.unwrap();
+        assert_eq!(receive_video_frame(&video).as_ref(), FIRST_FRAME);
+    }
+
+    #[test]
+    fn reconnect_after_connection_loss() {
+        let port = start_test_server(2);
+        let (_receiver, chunks_receiver, _) = RtspReceiver::new(
+            &InputId("rtsp".into()),
+            RtspReceiverOptions {
+                url: Arc::from(format!("rtsp://127.0.0.1:{port}/stream")),
+                transport: RtspTransport::Tcp,
+                video: true,
+                audio: false,
+            },
+        )
+        .unwrap();
+
+        let video = chunks_receiver.video.unwrap();
+        assert_eq!(receive_video_frame(&video).as_ref(), FIRST_FRAME);
+        // Session is negotiated again after the server drops the connection.
+        assert_eq!(receive_video_frame(&video).as_ref(), FIRST_FRAME);
+    }
+}
@@ -0,0 +1,452 @@
+use std::{
+    io::{Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    time::{Duration, Instant},
+};
+
+use bytes::{Bytes, BytesMut};
+use md5::{Digest, Md5};
+use rand::Rng;
+use reqwest::Url;
+use tracing::{debug, trace};
+
+const DEFAULT_RTSP_PORT: u16 = 554;
+const USER_AGENT: &str = "LiveCompositor";
+
+/// Session timeout assumed if the server does not specify one
+/// ([RFC 2326, section 12.37](https://datatracker.ietf.org/doc/html/rfc2326#section-12.37)).
+const DEFAULT_SESSION_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Timeout of connecting and of waiting for a response to a single request.
+const RTSP_REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Read timeout of the underlying socket. It bounds how long reading interleaved
+/// data can block, so the receiver thread can check whether it should close.
+const RTSP_READ_TIMEOUT: Duration = Duration::from_millis(50);
+
+#[derive(Debug, thiserror::Error)]
+pub enum RtspError {
+    #[error("Invalid RTSP URL \"{0}\".")]
+    InvalidUrl(String),
+
+    #[error("Failed to connect to the RTSP server.")]
+    Connect(#[source] std::io::Error),
+
+    #[error("RTSP connection failed.")]
+    Io(#[from] std::io::Error),
+
+    #[error("RTSP server closed the connection.")]
+    ConnectionClosed,
+
+    #[error("RTSP server did not respond to {0} request in time.")]
+    Timeout(&'static str),
+
+    #[error("Received malformed RTSP response.")]
+    MalformedResponse,
+
+    #[error("RTSP server requires authentication. Credentials are either missing or invalid.")]
+    Unauthorized,
+
+    #[error("RTSP {method} request failed with status {status}.")]
+    UnexpectedStatus { method: &'static str, status: u16 },
+}
+
+#[derive(Debug)]
+pub(super) struct RtspResponse {
+    pub status: u16,
+    headers: Vec<(String, String)>,
+    pub body: Bytes,
+}
+
+impl RtspResponse {
+    pub(super) fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+#[derive(Debug)]
+pub(super) enum RtspMessage {
+    Response(RtspResponse),
+    /// RTP or RTCP packet interleaved in the RTSP connection
+    /// ([RFC 2326, section 10.12](https://datatracker.ietf.org/doc/html/rfc2326#section-10.12)).
+    Interleaved {
+        channel: u8,
+        data: Bytes,
+    },
+}
+
+/// Minimal RTSP 1.0 client. Requests are sent one at a time, over a single
+/// TCP connection, which is also used to receive interleaved packets.
+pub(super) struct RtspClient {
+    stream: TcpStream,
+    /// URL of the presentation without credentials.
+    url: Url,
+    credentials: Option<(String, String)>,
+    authorization: Option<Authorization>,
+    cseq: u32,
+    session: Option<String>,
+    session_timeout: Duration,
+    buffer: BytesMut,
+    read_buffer: Vec<u8>,
+}
+
+impl RtspClient {
+    pub(super) fn connect(url: &str) -> Result<Self, RtspError> {
+        let invalid_url = || RtspError::InvalidUrl(url.to_string());
+        let mut url = Url::parse(url).map_err(|_| invalid_url())?;
+        if url.scheme() != "rtsp" {
+            return Err(invalid_url());
+        }
+        let host = url.host_str().ok_or_else(invalid_url)?.to_string();
+        let port = url.port().unwrap_or(DEFAULT_RTSP_PORT);
+
+        let credentials = match url.username() {
+            "" => None,
+            username => Some((
+                percent_decode(username),
+                percent_decode(url.password().unwrap_or_default()),
+            )),
+        };
+        url.set_username("").map_err(|_| invalid_url())?;
+        url.set_password(None).map_err(|_| invalid_url())?;
+
+        let address = (host.as_str(), port)
+            .to_socket_addrs()
+            .map_err(RtspError::Connect)?
+            .next()
+            .ok_or_else(invalid_url)?;
+        let stream = TcpStream::connect_timeout(&address, RTSP_REQUEST_TIMEOUT)
+            .map_err(RtspError::Connect)?;
+        stream.set_read_timeout(Some(RTSP_READ_TIMEOUT))?;
+        stream.set_write_timeout(Some(RTSP_REQUEST_TIMEOUT))?;
+        stream.set_nodelay(true)?;
+
+        Ok(Self {
+            stream,
+            url,
+            credentials,
+            authorization: None,
+            cseq: 0,
+            session: None,
+            session_timeout: DEFAULT_SESSION_TIMEOUT,
+            buffer: BytesMut::new(),
+            read_buffer: vec![0; 65536],
+        })
+    }
+
+    pub(super) fn url(&self) -> &Url {
+        &self.url
+    }
+
+    /// The session expires if no request is sent for this long.
+    pub(super) fn session_timeout(&self) -> Duration {
+        self.session_timeout
+    }
+
+    /// Sends a request and waits for the response. Interleaved packets received in
+    /// the meantime are dropped. Returns an error if the response status is not 2xx.
+    pub(super) fn request(
+        &mut self,
+        method: &'static str,
+        url: &str,
+        headers: &[(&str, &str)],
+    ) -> Result<RtspResponse, RtspError> {
+        let mut response = self.request_once(method, url, headers)?;
+
+        // Retry once with credentials if the server sent an authentication challenge.
+        if response.status == 401 && self.authorization.is_none() {
+            let Some((username, password)) = &self.credentials else {
+                return Err(RtspError::Unauthorized);
+            };
+            let challenge = response
+                .header("WWW-Authenticate")
+                .ok_or(RtspError::MalformedResponse)?;
+            self.authorization = Some(Authorization::new(challenge, username, password)?);
+            response = self.request_once(method, url, headers)?;
+        }
+
+        match response.status {
+            200..=299 => {
+                if let Some(session) = response.header("Session") {
+                    // Session header can contain a timeout, e.g. "12345678;timeout=60".
+                    let mut params = session.split(';').map(str::trim);
+                    self.session = params.next().map(str::to_string);
+                    if let Some(timeout) = params
+                        .filter_map(|param| param.strip_prefix("timeout="))
+                        .find_map(|timeout| timeout.parse().ok())
+                    {
+                        self.session_timeout = Duration::from_secs(timeout);
+                    }
+                }
+                Ok(response)
+            }
+            401 => Err(RtspError::Unauthorized),
+            status => Err(RtspError::UnexpectedStatus { method, status }),
+        }
+    }
+
+    fn request_once(
+        &mut self,
+        method: &'static str,
+        url: &str,
+        headers: &[(&str, &str)],
+    ) -> Result<RtspResponse, RtspError> {
+        self.send_request(method, url, headers)?;
+
+        let deadline = Instant::now() + RTSP_REQUEST_TIMEOUT;
+        while Instant::now() < deadline {
+            match self.read_message()? {
+                Some(RtspMessage::Response(response)) => return Ok(response),
+                Some(RtspMessage::Interleaved { .. }) | None => continue,
+            }
+        }
+        Err(RtspError::Timeout(method))
+    }
+
+    /// Sends a request without waiting for the response. Used for keep-alive requests
+    /// while packets are interleaved in the connection.
+    pub(super) fn send_request(
+        &mut self,
+        method: &'static str,
+        url: &str,
+        headers: &[(&str, &str)],
+    ) -> Result<(), RtspError> {
+        self.cseq += 1;
+        let mut request = format!(
+            "{method} {url} RTSP/1.0\r\nCSeq: {}\r\nUser-Agent: {USER_AGENT}\r\n",
+            self.cseq
+        );
+        if let Some(session) = &self.session {
+            request.push_str(&format!("Session: {session}\r\n"));
+        }
+        if let Some(authorization) = &mut self.authorization {
+            let authorization = authorization.header_value(method, url);
+            request.push_str(&format!("Authorization: {authorization}\r\n"));
+        }
+        for (name, value) in headers {
+            request.push_str(&format!("{name}: {value}\r\n"));
+        }
+        request.push_str("\r\n");
+
+        trace!(request, "Sending RTSP request.");
+        self.stream.write_all(request.as_bytes())?;
+        Ok(())
+    }
+
+    /// Reads a single message from the connection. Returns `None` if nothing was
+    /// received before the read timeout.
+    pub(super) fn read_message(&mut self) -> Result<Option<RtspMessage>, RtspError> {
+        loop {
+            if let Some(message) = parse_message(&mut self.buffer)? {
+                return Ok(Some(message));
+            }
+            match self.stream.read(&mut self.read_buffer) {
+                Ok(0) => return Err(RtspError::ConnectionClosed),
+                Ok(read_bytes) => self
+                    .buffer
+                    .extend_from_slice(&self.read_buffer[..read_bytes]),
+                Err(err) => match err.kind() {
+                    std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut => {
+                        return Ok(None)
+                    }
+                    _ => return Err(err.into()),
+                },
+            }
+        }
+    }
+}
+
+fn parse_message(buffer: &mut BytesMut) -> Result<Option<RtspMessage>, RtspError> {
+    if buffer.first() == Some(&b'$') {
+        if buffer.len() < 4 {
+            return Ok(None);
+        }
+        let length = u16::from_be_bytes([buffer[2], buffer[3]]) as usize;
+        if buffer.len() < 4 + length {
+            return Ok(None);
+        }
+        let frame = buffer.split_to(4 + length).freeze();
+        return Ok(Some(RtspMessage::Interleaved {
+            channel: frame[1],
+            data: frame.slice(4..),
+        }));
+    }
+
+    let Some(header_end) = buffer.windows(4).position(|window| window == b"\r\n\r\n") else {
+        return Ok(None);
+    };
+    let head =
+        std::str::from_utf8(&buffer[..header_end]).map_err(|_| RtspError::MalformedResponse)?;
+    let mut lines = head.split("\r\n");
+
+    let status_line = lines.next().unwrap_or_default();
+    let status = match status_line.split_whitespace().collect::<Vec<_>>()[..] {
+        [version, status, ..] if version.starts_with("RTSP/") => {
+            status.parse().map_err(|_| RtspError::MalformedResponse)?
+        }
+        _ => return Err(RtspError::MalformedResponse),
+    };
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+        .collect();
+
+    let content_length = headers
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("Content-Length"))
+        .map(|(_, value)| value.parse::<usize>())
+        .transpose()
+        .map_err(|_| RtspError::MalformedResponse)?
+        .unwrap_or(0);
+    let body_start = header_end + 4;
+    if buffer.len() < body_start + content_length {
+        return Ok(None);
+    }
+
+    let message = buffer.split_to(body_start + content_length).freeze();
+    let response = RtspResponse {
+        status,
+        headers,
+        body: message.slice(body_start..),
+    };
+    debug!(status = response.status, "Received RTSP response.");
+    Ok(Some(RtspMessage::Response(response)))
+}
+
+/// Authorization based on the challenge sent by the server. Digest
+/// ([RFC 2617](https://datatracker.ietf.org/doc/html/rfc2617)) is preferred over Basic.
+enum Authorization {
+    Basic {
+        credentials: String,
+    },
+    Digest {
+        username: String,
+        realm: String,
+        nonce: String,
+        /// MD5 of `username:realm:password`
+        ha1: String,
+        /// Set if the server requested `qop=auth`.
+        nonce_count: Option<u32>,
+    },
+}
+
+impl Authorization {
+    fn new(challenge: &str, username: &str, password: &str) -> Result<Self, RtspError> {
+        let (scheme, params) = challenge.split_once(' ').unwrap_or((challenge, ""));
+        if scheme.eq_ignore_ascii_case("Basic") {
+            return Ok(Self::Basic {
+                credentials: base64::Engine::encode(
+                    &base64::engine::general_purpose::STANDARD,
+                    format!("{username}:{password}"),
+                ),
+            });
+        }
+        if !scheme.eq_ignore_ascii_case("Digest") {
+            return Err(RtspError::Unauthorized);
+        }
+
+        let params = parse_auth_params(params);
+        let param = |name: &str| {
+            params
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value.clone())
+        };
+        let realm = param("realm").ok_or(RtspError::MalformedResponse)?;
+        let nonce = param("nonce").ok_or(RtspError::MalformedResponse)?;
+        let uses_qop =
+            matches!(param("qop"), Some(qop) if qop.split(',').any(|qop| qop.trim() == "auth"));
+
+        Ok(Self::Digest {
+            ha1: md5_hex(&format!("{username}:{realm}:{password}")),
+            username: username.to_string(),
+            realm,
+            nonce,
+            nonce_count: uses_qop.then_some(0),
+        })
+    }
+
+    fn header_value(&mut self, method: &str, uri: &str) -> String {
+        match self {
+            Authorization::Basic { credentials } => format!("Basic {credentials}"),
+            Authorization::Digest {
+                username,
+                realm,
+                nonce,
+                ha1,
+                nonce_count,
+            } => {
+                let ha2 = md5_hex(&format!("{method}:{uri}"));
+                match nonce_count {
+                    Some(nonce_count) => {
+                        *nonce_count += 1;
+                        let nc = format!("{nonce_count:08x}");
+                        let cnonce = format!("{:016x}", rand::thread_rng().gen::<u64>());
+                        let response = md5_hex(&format!("{ha1}:{nonce}:{nc}:{cnonce}:auth:{ha2}"));
+                        format!(
+                            "Digest username=\"{username}\", realm=\"{realm}\", nonce=\"{nonce}\", uri=\"{uri}\", qop=auth, nc={nc}, cnonce=\"{cnonce}\", response=\"{response}\""
+                        )
+                    }
+                    None => {
+                        let response = md5_hex(&format!("{ha1}:{nonce}:{ha2}"));
+                        format!(
+                            "Digest username=\"{username}\", realm=\"{realm}\", nonce=\"{nonce}\", uri=\"{uri}\", response=\"{response}\""
+                        )
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Parses comma separated `key=value` or `key="value"` pairs.
+fn parse_auth_params(params: &str) -> Vec<(String, String)> {
+    let mut result = Vec::new();
+    let mut rest = params.trim();
+    while let Some((key, value)) = rest.split_once('=') {
+        let key = key.trim().trim_start_matches(',').trim().to_string();
+        let value = value.trim_start();
+        let (value, remainder) = match value.strip_prefix('"') {
+            Some(quoted) => quoted.split_once('"').unwrap_or((quoted, "")),
+            None => value.split_once(',').unwrap_or((value, "")),
+        };
+        result.push((key, value.to_string()));
+        rest = remainder.trim_start().trim_start_matches(',');
+    }
+    result
+}
+
+fn md5_hex(value: &str) -> String {
+    Md5::digest(value.as_bytes())
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut result = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let decoded = match bytes[i] {
+            b'%' => value
+                .get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok()),
+            _ => None,
+        };
+        match decoded {
+            Some(byte) => {
+                result.push(byte);
+                i += 3;
+            }
+            None => {
+                result.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&result).into_owned()
+}
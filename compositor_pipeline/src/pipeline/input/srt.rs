@@ -0,0 +1,101 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
+
+use compositor_render::InputId;
+use ffmpeg_next::{self as ffmpeg, Dictionary};
+use tracing::trace;
+
+use crate::pipeline::{
+    decoder::{AudioDecoderOptions, DecoderOptions, VideoDecoderOptions},
+    srt::{SrtConnectionOptions, SRT_CONNECT_TIMEOUT},
+    AudioCodec, VideoCodec,
+};
+
+use super::{file::start_live_demuxer, ChunksReceiver};
+
+#[derive(Debug, thiserror::Error)]
+pub enum SrtReceiverError {
+    #[error("SRT input supports only H264 video, got {0:?}.")]
+    UnsupportedVideoCodec(VideoCodec),
+}
+
+pub struct SrtReceiverOptions {
+    pub connection: SrtConnectionOptions,
+    pub video: Option<VideoDecoderOptions>,
+    pub audio: Option<AudioDecoderOptions>,
+}
+
+/// Input that receives an MPEG-TS stream over SRT.
+///
+/// Streams are demuxed with ffmpeg into the same encoded chunks that other inputs
+/// produce. When the connection is lost, the receiver waits for a new one (listener)
+/// or reconnects (caller), and timestamps continue from where the previous
+/// connection ended.
+pub struct SrtReceiver {
+    should_close: Arc<AtomicBool>,
+}
+
+impl SrtReceiver {
+    pub fn new(
+        input_id: &InputId,
+        opts: SrtReceiverOptions,
+    ) -> Result<(Self, ChunksReceiver, DecoderOptions), SrtReceiverError> {
+        if let Some(video) = &opts.video {
+            if video.codec != VideoCodec::H264 {
+                return Err(SrtReceiverError::UnsupportedVideoCodec(video.codec));
+            }
+        }
+
+        let connection = opts.connection;
+        let (should_close, chunks_receiver) = start_live_demuxer(
+            input_id,
+            "SRT",
+            move || connect(&connection),
+            opts.video.as_ref().map(|video| video.codec),
+            opts.audio.as_ref().map(|audio| match audio {
+                AudioDecoderOptions::Opus(_) => AudioCodec::Opus,
+                AudioDecoderOptions::Aac(_) => AudioCodec::Aac,
+            }),
+        );
+
+        Ok((
+            Self { should_close },
+            chunks_receiver,
+            DecoderOptions {
+                video: opts.video,
+                audio: opts.audio,
+            },
+        ))
+    }
+}
+
+impl Drop for SrtReceiver {
+    fn drop(&mut self) {
+        self.should_close.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Waits for a single connection attempt. Returns `None` if no peer connected
+/// before the timeout.
+fn connect(connection: &SrtConnectionOptions) -> Option<ffmpeg::format::context::Input> {
+    let start = Instant::now();
+    let options = connection.protocol_options();
+    let options = options
+        .iter()
+        .map(|(key, value)| (key.as_str(), value.as_str()));
+
+    match ffmpeg::format::input_with_dictionary(&connection.url(), Dictionary::from_iter(options)) {
+        Ok(input_ctx) => Some(input_ctx),
+        Err(err) => {
+            trace!("SRT connection attempt failed: {err}.");
+            // Caller fails immediately if the listener rejects the connection.
+            std::thread::sleep(SRT_CONNECT_TIMEOUT.saturating_sub(start.elapsed()));
+            None
+        }
+    }
+}
@@ -0,0 +1,500 @@
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Arc, Mutex, Weak,
+};
+
+use bytes::Bytes;
+use compositor_render::InputId;
+use crossbeam_channel::{unbounded, Sender};
+use tracing::{debug, info, warn};
+use webrtc::{
+    api::media_engine::MediaEngine,
+    peer_connection::{peer_connection_state::RTCPeerConnectionState, RTCPeerConnection},
+    rtp_transceiver::rtp_codec::RTPCodecType,
+    track::track_remote::TrackRemote,
+};
+use webrtc_util::Marshal;
+
+use crate::pipeline::{
+    decoder::{AudioDecoderOptions, DecoderOptions},
+    rtp::{AUDIO_PAYLOAD_TYPE, VIDEO_PAYLOAD_TYPE},
+    structs::VideoCodec,
+    webrtc_utils,
+};
+
+use super::{
+    rtp::{
+        start_depayloader_thread, DepayloaderNewError, RtpInputStats, RtpStream,
+        DEFAULT_JITTER_BUFFER_LATENCY,
+    },
+    ChunksReceiver,
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum WhipReceiverError {
+    #[error("WebRTC supports only Opus audio.")]
+    UnsupportedAudioCodec,
+
+    #[error(transparent)]
+    DepayloaderError(#[from] DepayloaderNewError),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum WhipError {
+    #[error("Input \"{0}\" is not registered.")]
+    InputNotFound(InputId),
+
+    #[error("Input \"{0}\" is not a WHIP input.")]
+    NotWhipInput(InputId),
+
+    #[error("WHIP session for input \"{0}\" is already active.")]
+    SessionAlreadyActive(InputId),
+
+    #[error("There is no active WHIP session for input \"{0}\".")]
+    NoActiveSession(InputId),
+
+    #[error("Failed to create an SDP answer.")]
+    NoLocalDescription,
+
+    #[error("Failed to negotiate the WebRTC session.")]
+    WebRtc(#[from] webrtc::Error),
+}
+
+pub struct WhipReceiverOptions {
+    pub stream: RtpStream,
+}
+
+/// Input that receives a stream published over [WHIP](https://datatracker.ietf.org/doc/html/draft-ietf-wish-whip).
+///
+/// The input is registered before the client connects. The WebRTC session is negotiated
+/// when the client sends an SDP offer to the WHIP endpoint. Decrypted RTP packets are
+/// fed to the same depayloader that is used by the RTP input.
+pub struct WhipReceiver {
+    session: WhipSession,
+    stats: Arc<Mutex<RtpInputStats>>,
+}
+
+impl WhipReceiver {
+    pub fn new(
+        input_id: &InputId,
+        opts: WhipReceiverOptions,
+    ) -> Result<(Self, ChunksReceiver, DecoderOptions), WhipReceiverError> {
+        if let Some(audio) = &opts.stream.audio {
+            if !matches!(audio.options, AudioDecoderOptions::Opus(_)) {
+                return Err(WhipReceiverError::UnsupportedAudioCodec);
+            }
+        }
+
+        let (packets_sender, packets_receiver) = unbounded();
+        // Feedback is sent by the WebRTC stack, which also handles retransmissions.
+        let (chunks_receiver, stats) = start_depayloader_thread(
+            input_id,
+            packets_receiver,
+            &opts.stream,
+            DEFAULT_JITTER_BUFFER_LATENCY,
+            None,
+        )?;
+
+        let session = WhipSession {
+            input_id: input_id.clone(),
+            video_codec: opts.stream.video.as_ref().map(|v| v.options.codec),
+            has_audio: opts.stream.audio.is_some(),
+            packets_sender,
+            peer_connection: Arc::new(tokio::sync::Mutex::new(None)),
+            runtime: Arc::new(Mutex::new(None)),
+            closed: Arc::new(AtomicBool::new(false)),
+            next_session_id: Arc::new(AtomicU64::new(0)),
+        };
+
+        Ok((
+            Self { session, stats },
+            chunks_receiver,
+            DecoderOptions {
+                video: opts.stream.video.map(|v| v.options),
+                audio: opts.stream.audio.map(|a| a.options),
+            },
+        ))
+    }
+
+    pub fn session(&self) -> WhipSession {
+        self.session.clone()
+    }
+
+    pub fn stats(&self) -> RtpInputStats {
+        *self.stats.lock().unwrap()
+    }
+}
+
+impl Drop for WhipReceiver {
+    fn drop(&mut self) {
+        // Negotiation that is in progress checks this flag before the session is stored.
+        self.session.closed.store(true, Ordering::Relaxed);
+
+        let Some(runtime) = self.session.runtime.lock().unwrap().clone() else {
+            return;
+        };
+        let peer_connection = self.session.peer_connection.clone();
+        runtime.spawn(async move {
+            let active_connection = peer_connection.lock().await.take();
+            if let Some(ActivePeerConnection {
+                peer_connection, ..
+            }) = active_connection
+            {
+                if let Err(err) = peer_connection.close().await {
+                    warn!(%err, "Failed to close WHIP peer connection.");
+                }
+            }
+        });
+    }
+}
+
+type PeerConnectionSlot = tokio::sync::Mutex<Option<ActivePeerConnection>>;
+
+struct ActivePeerConnection {
+    /// Identifies the session, so a state change of an old peer connection
+    /// never closes a newer one.
+    id: u64,
+    peer_connection: Arc<RTCPeerConnection>,
+}
+
+/// Handle used by the WHIP endpoint to negotiate and terminate a session of a registered input.
+/// Only one session per input can be active at a time.
+#[derive(Clone)]
+pub struct WhipSession {
+    input_id: InputId,
+    video_codec: Option<VideoCodec>,
+    has_audio: bool,
+    packets_sender: Sender<Bytes>,
+    peer_connection: Arc<PeerConnectionSlot>,
+    /// Runtime on which sessions are negotiated, used to close the active session
+    /// when the input is unregistered.
+    runtime: Arc<Mutex<Option<tokio::runtime::Handle>>>,
+    /// Set when the input is unregistered.
+    closed: Arc<AtomicBool>,
+    next_session_id: Arc<AtomicU64>,
+}
+
+impl WhipSession {
+    /// Negotiates a new WebRTC session. Returns an SDP answer.
+    pub async fn handle_offer(&self, offer: String) -> Result<String, WhipError> {
+        if self.closed.load(Ordering::Relaxed) {
+            return Err(WhipError::InputNotFound(self.input_id.clone()));
+        }
+        *self.runtime.lock().unwrap() = Some(tokio::runtime::Handle::current());
+
+        let mut active_connection = self.peer_connection.lock().await;
+        if active_connection.is_some() {
+            return Err(WhipError::SessionAlreadyActive(self.input_id.clone()));
+        }
+
+        let session_id = self.next_session_id.fetch_add(1, Ordering::Relaxed);
+        let peer_connection = Arc::new(self.new_peer_connection().await?);
+
+        let packets_sender = self.packets_sender.clone();
+        peer_connection.on_track(Box::new(move |track, _, _| {
+            let packets_sender = packets_sender.clone();
+            Box::pin(async move {
+                tokio::spawn(forward_track_packets(track, packets_sender));
+            })
+        }));
+
+        let input_id = self.input_id.clone();
+        let slot = Arc::downgrade(&self.peer_connection);
+        peer_connection.on_peer_connection_state_change(Box::new(move |state| {
+            match state {
+                RTCPeerConnectionState::Connected => {
+                    info!(input_id = input_id.to_string(), "WHIP client connected.")
+                }
+                // ICE can recover from this state, the session is kept.
+                RTCPeerConnectionState::Disconnected => {
+                    warn!(input_id = input_id.to_string(), "WHIP client disconnected.")
+                }
+                RTCPeerConnectionState::Failed | RTCPeerConnectionState::Closed => {
+                    info!(
+                        input_id = input_id.to_string(),
+                        ?state,
+                        "WHIP session ended."
+                    );
+                    tokio::spawn(clear_session(slot.clone(), session_id));
+                }
+                _ => debug!(
+                    input_id = input_id.to_string(),
+                    ?state,
+                    "WHIP connection state changed."
+                ),
+            }
+            Box::pin(async {})
+        }));
+
+        let result = webrtc_utils::answer_offer(&peer_connection, offer)
+            .await
+            .map_err(WhipError::from)
+            .and_then(|answer| answer.ok_or(WhipError::NoLocalDescription))
+            .and_then(|answer| match self.closed.load(Ordering::Relaxed) {
+                true => Err(WhipError::InputNotFound(self.input_id.clone())),
+                false => Ok(answer),
+            });
+
+        match result {
+            Ok(answer) => {
+                *active_connection = Some(ActivePeerConnection {
+                    id: session_id,
+                    peer_connection,
+                });
+                Ok(answer.sdp)
+            }
+            Err(err) => {
+                if let Err(err) = peer_connection.close().await {
+                    debug!(%err, "Failed to close peer connection.");
+                }
+                Err(err)
+            }
+        }
+    }
+
+    /// Terminates the active session. The input stays registered, so a client can
+    /// publish again.
+    pub async fn terminate(&self) -> Result<(), WhipError> {
+        let active_connection = self.peer_connection.lock().await.take();
+        let Some(ActivePeerConnection {
+            peer_connection, ..
+        }) = active_connection
+        else {
+            return Err(WhipError::NoActiveSession(self.input_id.clone()));
+        };
+        peer_connection.close().await?;
+        Ok(())
+    }
+
+    async fn new_peer_connection(&self) -> Result<RTCPeerConnection, webrtc::Error> {
+        let mut media_engine = MediaEngine::default();
+        if let Some(codec) = self.video_codec {
+            media_engine.register_codec(
+                webrtc_utils::video_codec_parameters(codec),
+                RTPCodecType::Video,
+            )?;
+        }
+        if self.has_audio {
+            media_engine
+                .register_codec(webrtc_utils::opus_codec_parameters(), RTPCodecType::Audio)?;
+        }
+        webrtc_utils::new_peer_connection(media_engine).await
+    }
+}
+
+/// Closes the session that ended on its own (the client went away without
+/// terminating it), so the client can publish again.
+async fn clear_session(slot: Weak<PeerConnectionSlot>, session_id: u64) {
+    let Some(slot) = slot.upgrade() else {
+        return;
+    };
+    let active_connection = {
+        let mut slot = slot.lock().await;
+        match &*slot {
+            Some(active) if active.id == session_id => slot.take(),
+            _ => None,
+        }
+    };
+    if let Some(ActivePeerConnection {
+        peer_connection, ..
+    }) = active_connection
+    {
+        if let Err(err) = peer_connection.close().await {
+            debug!(%err, "Failed to close WHIP peer connection.");
+        }
+    }
+}
+
+/// Forwards packets of a remote track to the depayloader. Payload types negotiated
+/// with the client are replaced with the ones expected by the depayloader.
+async fn forward_track_packets(track: Arc<TrackRemote>, packets_sender: Sender<Bytes>) {
+    let payload_type = match track.kind() {
+        RTPCodecType::Video => VIDEO_PAYLOAD_TYPE,
+        RTPCodecType::Audio => AUDIO_PAYLOAD_TYPE,
+        _ => return,
+    };
+
+    loop {
+        let mut packet = match track.read_rtp().await {
+            Ok((packet, _)) => packet,
+            Err(err) => {
+                debug!(%err, "WHIP track ended.");
+                return;
+            }
+        };
+        packet.header.payload_type = payload_type;
+
+        match packet.marshal() {
+            Ok(packet) => {
+                if packets_sender.send(packet).is_err() {
+                    debug!("Failed to forward WHIP packet. Channel closed.");
+                    return;
+                }
+            }
+            Err(err) => warn!(%err, "Failed to serialize RTP packet."),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use bytes::Bytes;
+    use compositor_render::InputId;
+    use webrtc::{
+        api::{
+            media_engine::{MediaEngine, MIME_TYPE_OPUS},
+            APIBuilder,
+        },
+        peer_connection::{
+            configuration::RTCConfiguration, sdp::session_description::RTCSessionDescription,
+            RTCPeerConnection,
+        },
+        rtp::{header::Header, packet::Packet},
+        rtp_transceiver::rtp_codec::RTCRtpCodecCapability,
+        track::track_local::{track_local_static_rtp::TrackLocalStaticRTP, TrackLocalWriter},
+    };
+
+    use crate::{
+        pipeline::{
+            decoder::{AudioDecoderOptions, OpusDecoderOptions},
+            input::rtp::{InputAudioStream, RtpStream},
+            rtp::AUDIO_PAYLOAD_TYPE,
+        },
+        queue::PipelineEvent,
+    };
+
+    use super::{WhipError, WhipReceiver, WhipReceiverOptions, WhipSession};
+
+    async fn new_client() -> (RTCPeerConnection, Arc<TrackLocalStaticRTP>) {
+        let mut media_engine = MediaEngine::default();
+        media_engine.register_default_codecs().unwrap();
+        let api = APIBuilder::new().with_media_engine(media_engine).build();
+        let client = api
+            .new_peer_connection(RTCConfiguration::default())
+            .await
+            .unwrap();
+
+        let track = Arc::new(TrackLocalStaticRTP::new(
+            RTCRtpCodecCapability {
+                mime_type: MIME_TYPE_OPUS.to_owned(),
+                clock_rate: 48000,
+                channels: 2,
+                ..Default::default()
+            },
+            "audio".to_owned(),
+            "whip_test".to_owned(),
+        ));
+        client.add_track(track.clone()).await.unwrap();
+        (client, track)
+    }
+
+    async fn publish(client: &RTCPeerConnection, session: &WhipSession) -> Result<(), WhipError> {
+        let offer = client.create_offer(None).await.unwrap();
+        let mut gathering_complete = client.gathering_complete_promise().await;
+        client.set_local_description(offer).await.unwrap();
+        let _ = gathering_complete.recv().await;
+        let offer = client.local_description().await.unwrap();
+
+        let answer = session.handle_offer(offer.sdp).await?;
+        client
+            .set_remote_description(RTCSessionDescription::answer(answer).unwrap())
+            .await
+            .unwrap();
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn whip_session_lifecycle() {
+        let (receiver, chunks_receiver, _) = WhipReceiver::new(
+            &InputId("input_1".into()),
+            WhipReceiverOptions {
+                stream: RtpStream {
+                    video: None,
+                    audio: Some(InputAudioStream {
+                        options: AudioDecoderOptions::Opus(OpusDecoderOptions {
+                            forward_error_correction: false,
+                        }),
+                        payload_type: AUDIO_PAYLOAD_TYPE,
+                    }),
+                },
+            },
+        )
+        .unwrap();
+        let session = receiver.session();
+        let audio_receiver = chunks_receiver.audio.unwrap();
+
+        let (client, track) = new_client().await;
+        publish(&client, &session).await.unwrap();
+
+        let (second_client, _) = new_client().await;
+        assert!(matches!(
+            publish(&second_client, &session).await,
+            Err(WhipError::SessionAlreadyActive(_))
+        ));
+        second_client.close().await.unwrap();
+
+        // Packets are dropped until the connection is established.
+        let mut received_audio = false;
+        for sequence_number in 0..250u16 {
+            let packet = Packet {
+                header: Header {
+                    version: 2,
+                    payload_type: AUDIO_PAYLOAD_TYPE,
+                    sequence_number,
+                    timestamp: sequence_number as u32 * 960,
+                    ..Default::default()
+                },
+                payload: Bytes::from_static(&[0xf8, 0xff, 0xfe]),
+            };
+            // Writes fail until the DTLS handshake is finished.
+            let _ = track.write_rtp(&packet).await;
+            if let Ok(PipelineEvent::Data(_)) = audio_receiver.try_recv() {
+                received_audio = true;
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert!(received_audio, "No audio received over the WHIP session.");
+
+        // Client that goes away without terminating the session frees it once
+        // the connection is closed or ICE fails.
+        client.close().await.unwrap();
+        let mut republished = false;
+        for _ in 0..30 {
+            let (client, _) = new_client().await;
+            match publish(&client, &session).await {
+                Ok(()) => {
+                    republished = true;
+                    break;
+                }
+                Err(WhipError::SessionAlreadyActive(_)) => {
+                    client.close().await.unwrap();
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                }
+                Err(err) => panic!("Unexpected error: {err}"),
+            }
+        }
+        assert!(
+            republished,
+            "Session was not released after the client left."
+        );
+
+        // Unregistering the input closes the active session and rejects new ones.
+        drop(receiver);
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while session.peer_connection.lock().await.is_some() {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        })
+        .await
+        .expect("Active session was not closed after the input was dropped.");
+
+        let (client, _) = new_client().await;
+        assert!(matches!(
+            publish(&client, &session).await,
+            Err(WhipError::InputNotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,256 @@
+use std::{path::PathBuf, time::Duration};
+
+use compositor_render::{OutputId, Resolution};
+use crossbeam_channel::Receiver;
+use tracing::{span, Level};
+
+use crate::{
+    error::OutputInitError,
+    pipeline::{
+        encoder::{
+            fdk_aac::AacProfile, ffmpeg_h264::BitrateMode, AudioEncoderOptions, EncoderContext,
+            VideoEncoderOptions,
+        },
+        structs::EncoderOutputEvent,
+        AudioCodec, VideoCodec,
+    },
+};
+
+use super::muxer::{start_muxer_thread, MuxerOptions};
+
+pub const PLAYLIST_FILE_NAME: &str = "index.m3u8";
+pub const MASTER_PLAYLIST_FILE_NAME: &str = "master.m3u8";
+
+/// Used to estimate the bandwidth of video encoded without a bitrate limit,
+/// roughly 6 Mbps for 1080p.
+const ESTIMATED_VIDEO_BITS_PER_PIXEL: u64 = 3;
+/// Used for audio encoded without a bitrate limit.
+const ESTIMATED_AUDIO_BITRATE: u64 = 128_000;
+
+#[derive(Debug, Clone)]
+pub struct HlsOutputOptions {
+    /// Directory where the playlist and segments are written.
+    pub output_dir: PathBuf,
+    /// Target duration of a segment. Segments are cut only on H264 keyframes,
+    /// so the actual duration depends also on the encoder GOP size.
+    pub segment_duration: Duration,
+    /// Number of segments listed in the playlist. Older segments are removed from disk.
+    pub playlist_size: usize,
+    pub segment_type: HlsSegmentType,
+    /// Streams listed in the master playlist written next to the playlist. Empty if
+    /// the output has no renditions, then no master playlist is written.
+    pub variants: Vec<HlsVariant>,
+}
+
+/// Stream of an output or of one of its renditions, listed in the master playlist.
+#[derive(Debug, Clone)]
+pub struct HlsVariant {
+    /// Path of the stream playlist, relative to the output directory.
+    pub playlist_path: String,
+    pub resolution: Resolution,
+    /// Peak bitrate of the stream in bits per second.
+    pub bandwidth: u64,
+    /// Codec strings (RFC 6381) of the video and audio tracks, e.g. `avc1.64002a,mp4a.40.2`.
+    pub codecs: String,
+}
+
+impl HlsVariant {
+    /// Describes a stream with the bandwidth taken from the encoder bitrate limits.
+    /// For encoders without a limit, the bandwidth is estimated from the resolution.
+    pub fn new(
+        playlist_path: String,
+        video: &VideoEncoderOptions,
+        audio: Option<&AudioEncoderOptions>,
+    ) -> Self {
+        let resolution = video.resolution();
+        let video_bitrate = match video {
+            VideoEncoderOptions::H264(opts) => match opts.bitrate_mode {
+                BitrateMode::Vbr {
+                    bitrate,
+                    max_bitrate: None,
+                } => Some(bitrate),
+                bitrate_mode => bitrate_mode.max_bitrate(),
+            },
+            VideoEncoderOptions::Vp8(opts) | VideoEncoderOptions::Vp9(opts) => opts.bitrate,
+            VideoEncoderOptions::Av1(opts) => opts.bitrate,
+        }
+        .unwrap_or(
+            resolution.width as u64 * resolution.height as u64 * ESTIMATED_VIDEO_BITS_PER_PIXEL,
+        );
+        let audio_bitrate = match audio {
+            Some(AudioEncoderOptions::Aac(opts)) => opts
+                .bitrate
+                .map(u64::from)
+                .unwrap_or(ESTIMATED_AUDIO_BITRATE),
+            Some(AudioEncoderOptions::Opus(_)) => ESTIMATED_AUDIO_BITRATE,
+            None => 0,
+        };
+
+        let video_codec = match video {
+            VideoEncoderOptions::H264(opts) => Some(opts.codec_string()),
+            VideoEncoderOptions::Vp8(_)
+            | VideoEncoderOptions::Vp9(_)
+            | VideoEncoderOptions::Av1(_) => None,
+        };
+        let audio_codec = match audio {
+            Some(AudioEncoderOptions::Aac(opts)) => Some(match opts.profile {
+                AacProfile::Lc => "mp4a.40.2".to_string(),
+                AacProfile::He => "mp4a.40.5".to_string(),
+            }),
+            Some(AudioEncoderOptions::Opus(_)) | None => None,
+        };
+        let codecs = [video_codec, audio_codec]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(",");
+
+        Self {
+            playlist_path,
+            resolution,
+            bandwidth: video_bitrate + audio_bitrate,
+            codecs,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HlsSegmentType {
+    MpegTs,
+    Fmp4,
+}
+
+#[derive(Debug)]
+pub struct HlsWriter {
+    pub playlist_path: PathBuf,
+}
+
+impl HlsWriter {
+    pub fn new(
+        output_id: &OutputId,
+        options: HlsOutputOptions,
+        encoder_ctx: EncoderContext,
+        packets_receiver: Receiver<EncoderOutputEvent>,
+    ) -> Result<Self, OutputInitError> {
+        if let Some(video) = &encoder_ctx.video {
+            if video.codec != VideoCodec::H264 {
+                return Err(OutputInitError::UnsupportedVideoCodec(video.codec));
+            }
+        }
+        if let Some(audio) = &encoder_ctx.audio {
+            if audio.codec != AudioCodec::Aac {
+                return Err(OutputInitError::UnsupportedAudioCodec(audio.codec));
+            }
+        }
+
+        std::fs::create_dir_all(&options.output_dir).map_err(OutputInitError::CreateDirectory)?;
+        let playlist_path = options.output_dir.join(PLAYLIST_FILE_NAME);
+        if !options.variants.is_empty() {
+            std::fs::write(
+                options.output_dir.join(MASTER_PLAYLIST_FILE_NAME),
+                master_playlist(&options.variants),
+            )
+            .map_err(OutputInitError::WriteMasterPlaylist)?;
+        }
+
+        let (segment_type, segment_extension) = match options.segment_type {
+            HlsSegmentType::MpegTs => ("mpegts", "ts"),
+            HlsSegmentType::Fmp4 => ("fmp4", "m4s"),
+        };
+        let segment_filename = options
+            .output_dir
+            .join(format!("segment_%05d.{segment_extension}"));
+
+        let format_options = vec![
+            (
+                "hls_time".to_string(),
+                options.segment_duration.as_secs_f64().to_string(),
+            ),
+            (
+                "hls_list_size".to_string(),
+                options.playlist_size.to_string(),
+            ),
+            ("hls_segment_type".to_string(), segment_type.to_string()),
+            (
+                "hls_segment_filename".to_string(),
+                segment_filename.to_string_lossy().into_owned(),
+            ),
+            // Segments that fall out of the playlist window are removed from disk.
+            (
+                "hls_flags".to_string(),
+                "delete_segments+independent_segments".to_string(),
+            ),
+            ("hls_delete_threshold".to_string(), "1".to_string()),
+        ];
+
+        start_muxer_thread(
+            format!("HLS writer thread for output {}", output_id),
+            span!(Level::INFO, "HLS writer", output_id = output_id.to_string()),
+            MuxerOptions {
+                format: "hls",
+                path: playlist_path.clone(),
+                protocol_options: Vec::new(),
+                format_options,
+            },
+            encoder_ctx,
+            packets_receiver,
+        )?;
+
+        Ok(Self { playlist_path })
+    }
+}
+
+fn master_playlist(variants: &[HlsVariant]) -> String {
+    let mut playlist = String::from("#EXTM3U\n#EXT-X-VERSION:6\n#EXT-X-INDEPENDENT-SEGMENTS\n");
+    for variant in variants {
+        playlist.push_str(&format!(
+            "#EXT-X-STREAM-INF:BANDWIDTH={},RESOLUTION={}x{}",
+            variant.bandwidth, variant.resolution.width, variant.resolution.height,
+        ));
+        if !variant.codecs.is_empty() {
+            playlist.push_str(&format!(",CODECS=\"{}\"", variant.codecs));
+        }
+        playlist.push_str(&format!("\n{}\n", variant.playlist_path));
+    }
+    playlist
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn master_playlist_lists_variants() {
+        let variants = [
+            HlsVariant {
+                playlist_path: "index.m3u8".to_string(),
+                resolution: Resolution {
+                    width: 1920,
+                    height: 1080,
+                },
+                bandwidth: 6_000_000,
+                codecs: "avc1.64002a,mp4a.40.2".to_string(),
+            },
+            HlsVariant {
+                playlist_path: "rendition_0/index.m3u8".to_string(),
+                resolution: Resolution {
+                    width: 640,
+                    height: 360,
+                },
+                bandwidth: 1_000_000,
+                codecs: "avc1.64001e,mp4a.40.2".to_string(),
+            },
+        ];
+
+        assert_eq!(
+            master_playlist(&variants),
+            "#EXTM3U\n\
+             #EXT-X-VERSION:6\n\
+             #EXT-X-INDEPENDENT-SEGMENTS\n\
+             #EXT-X-STREAM-INF:BANDWIDTH=6000000,RESOLUTION=1920x1080,CODECS=\"avc1.64002a,mp4a.40.2\"\n\
+             index.m3u8\n\
+             #EXT-X-STREAM-INF:BANDWIDTH=1000000,RESOLUTION=640x360,CODECS=\"avc1.64001e,mp4a.40.2\"\n\
+             rendition_0/index.m3u8\n"
+        );
+    }
+}
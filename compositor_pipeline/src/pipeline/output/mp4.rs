@@ -0,0 +1,56 @@
+use std::path::PathBuf;
+
+use compositor_render::OutputId;
+use crossbeam_channel::Receiver;
+use tracing::{span, Level};
+
+use crate::{
+    error::OutputInitError,
+    pipeline::{encoder::EncoderContext, structs::EncoderOutputEvent, VideoCodec},
+};
+
+use super::muxer::{start_muxer_thread, MuxerOptions};
+
+#[derive(Debug, Clone)]
+pub struct Mp4OutputOptions {
+    pub output_path: PathBuf,
+}
+
+#[derive(Debug)]
+pub struct Mp4FileWriter {
+    pub output_path: PathBuf,
+}
+
+impl Mp4FileWriter {
+    pub fn new(
+        output_id: &OutputId,
+        options: Mp4OutputOptions,
+        encoder_ctx: EncoderContext,
+        packets_receiver: Receiver<EncoderOutputEvent>,
+    ) -> Result<Self, OutputInitError> {
+        // VP8 has no registered MP4 sample entry, ffmpeg would write a file that
+        // most players refuse to open.
+        if let Some(video) = &encoder_ctx.video {
+            if video.codec == VideoCodec::Vp8 {
+                return Err(OutputInitError::UnsupportedVideoCodec(video.codec));
+            }
+        }
+
+        start_muxer_thread(
+            format!("MP4 writer thread for output {}", output_id),
+            span!(Level::INFO, "MP4 writer", output_id = output_id.to_string()),
+            MuxerOptions {
+                format: "mp4",
+                path: options.output_path.clone(),
+                protocol_options: Vec::new(),
+                format_options: Vec::new(),
+            },
+            encoder_ctx,
+            packets_receiver,
+        )?;
+
+        Ok(Self {
+            output_path: options.output_path,
+        })
+    }
+}
@@ -0,0 +1,511 @@
+use std::{path::PathBuf, ptr, time::Duration};
+
+use bytes::{Bytes, BytesMut};
+use crossbeam_channel::{Receiver, Sender};
+use ffmpeg_next::{self as ffmpeg, Dictionary, Rational};
+use tracing::{debug, error, trace, warn, Span};
+
+use crate::{
+    audio_mixer::AudioChannels,
+    error::OutputInitError,
+    pipeline::{
+        encoder::{AudioEncoderContext, EncoderContext, VideoEncoderContext},
+        structs::{EncodedChunk, EncodedChunkKind, EncoderOutputEvent, IsKeyframe},
+        AudioCodec, VideoCodec,
+    },
+};
+
+const VIDEO_TIME_BASE: i32 = 90000;
+
+/// Options of an ffmpeg muxer that writes encoded chunks to a file (or any other
+/// destination supported by ffmpeg).
+pub(super) struct MuxerOptions {
+    /// Name of the ffmpeg output format e.g. `mp4` or `hls`.
+    pub format: &'static str,
+    pub path: PathBuf,
+    /// Options passed to the protocol when opening the destination.
+    pub protocol_options: Vec<(String, String)>,
+    /// Format specific options passed when writing the header.
+    pub format_options: Vec<(String, String)>,
+}
+
+pub(super) struct StreamState {
+    pub index: usize,
+    pub time_base: Rational,
+    pub eos_received: bool,
+}
+
+/// Starts a thread that writes chunks from `packets_receiver` using an ffmpeg muxer.
+/// Output is finalized when EOS is received on all tracks or when the channel is closed.
+pub(super) fn start_muxer_thread(
+    thread_name: String,
+    span: Span,
+    options: MuxerOptions,
+    encoder_ctx: EncoderContext,
+    packets_receiver: Receiver<EncoderOutputEvent>,
+) -> Result<(), OutputInitError> {
+    let (init_result_sender, init_result_receiver) = crossbeam_channel::bounded(0);
+
+    std::thread::Builder::new()
+        .name(thread_name)
+        .spawn(move || {
+            let _span = span.entered();
+            run_muxer_thread(options, encoder_ctx, packets_receiver, init_result_sender);
+            debug!("Closing muxer thread.");
+        })
+        .unwrap();
+
+    init_result_receiver
+        .recv()
+        .map_err(|_| OutputInitError::CannotReadInitResult)?
+}
+
+fn run_muxer_thread(
+    options: MuxerOptions,
+    encoder_ctx: EncoderContext,
+    packets_receiver: Receiver<EncoderOutputEvent>,
+    init_result_sender: Sender<Result<(), OutputInitError>>,
+) {
+    // H264 parameter sets are stored in the header (e.g. in the `avcC` box), so it is
+    // written after they are extracted from the first video chunk.
+    let wait_for_parameter_sets = encoder_ctx
+        .video
+        .as_ref()
+        .is_some_and(|video| video.codec == VideoCodec::H264);
+
+    let init_result = create_ffmpeg_output(&options, &encoder_ctx).and_then(|mut output| {
+        if !wait_for_parameter_sets {
+            output.write_header(&options)?;
+        }
+        Ok(output)
+    });
+    let mut output = match init_result {
+        Ok(output) => {
+            init_result_sender.send(Ok(())).unwrap();
+            output
+        }
+        Err(err) => {
+            init_result_sender.send(Err(err)).unwrap();
+            return;
+        }
+    };
+
+    // Chunks received before the header is written.
+    let mut pending_chunks = Vec::new();
+    let mut header_written = !wait_for_parameter_sets;
+
+    // Pipeline timestamps are relative to the pipeline start, but the output should
+    // start at 0 regardless of when the output was registered.
+    let mut timestamp_offset = None;
+
+    for event in packets_receiver {
+        match event {
+            EncoderOutputEvent::Data(chunk) if !header_written => {
+                let EncodedChunkKind::Video(_) = chunk.kind else {
+                    pending_chunks.push(chunk);
+                    continue;
+                };
+                let parameter_sets = match chunk.is_keyframe {
+                    IsKeyframe::Yes => h264_parameter_sets(&chunk.data),
+                    _ => None,
+                };
+                let Some(parameter_sets) = parameter_sets else {
+                    debug!(
+                        "Dropping video chunk received before a keyframe with H264 parameter sets."
+                    );
+                    continue;
+                };
+                output.set_video_extradata(&parameter_sets);
+                if let Err(err) = output.write_header(&options) {
+                    error!("Failed to write the output header: {err}");
+                    return;
+                }
+                header_written = true;
+                pending_chunks.push(chunk);
+                timestamp_offset = earliest_timestamp(&pending_chunks);
+                for chunk in pending_chunks.drain(..) {
+                    output.write(chunk, &mut timestamp_offset);
+                }
+            }
+            EncoderOutputEvent::Data(chunk) => output.write(chunk, &mut timestamp_offset),
+            EncoderOutputEvent::VideoEOS => match output.video_stream {
+                Some(ref mut stream) => stream.eos_received = true,
+                None => warn!("Received video EOS, but the output has no video track."),
+            },
+            EncoderOutputEvent::AudioEOS => match output.audio_stream {
+                Some(ref mut stream) => stream.eos_received = true,
+                None => warn!("Received audio EOS, but the output has no audio track."),
+            },
+        }
+
+        let video_done = output
+            .video_stream
+            .as_ref()
+            .map_or(true, |s| s.eos_received);
+        let audio_done = output
+            .audio_stream
+            .as_ref()
+            .map_or(true, |s| s.eos_received);
+        if video_done && audio_done {
+            break;
+        }
+    }
+
+    if !header_written {
+        if let Err(err) = output.write_header(&options) {
+            error!("Failed to write the output header: {err}");
+            return;
+        }
+        timestamp_offset = earliest_timestamp(&pending_chunks);
+        for chunk in pending_chunks {
+            output.write(chunk, &mut timestamp_offset);
+        }
+    }
+
+    // Reached either when EOS was received on all tracks or when the channel was
+    // closed because the output was unregistered.
+    if let Err(err) = output.ctx.write_trailer() {
+        error!("Failed to finalize the output: {err}");
+    }
+}
+
+/// Chunks buffered before the header is written are not ordered between tracks, and
+/// with B-frames video DTS is lower than PTS, so the earliest timestamp of all of
+/// them is used as the offset.
+fn earliest_timestamp(chunks: &[EncodedChunk]) -> Option<Duration> {
+    chunks
+        .iter()
+        .map(|chunk| chunk.dts.unwrap_or(chunk.pts))
+        .min()
+}
+
+/// ffmpeg output with streams matching the encoders.
+struct FfmpegOutput {
+    ctx: ffmpeg::format::context::Output,
+    video_stream: Option<StreamState>,
+    audio_stream: Option<StreamState>,
+}
+
+impl FfmpegOutput {
+    fn write_header(&mut self, options: &MuxerOptions) -> Result<(), OutputInitError> {
+        let format_options = options
+            .format_options
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()));
+        self.ctx
+            .write_header_with(Dictionary::from_iter(format_options))
+            .map_err(OutputInitError::FfmpegError)?;
+
+        // Muxer can change time base of the streams when writing the header.
+        for stream in [&mut self.video_stream, &mut self.audio_stream]
+            .into_iter()
+            .flatten()
+        {
+            stream.time_base = self.ctx.stream(stream.index).unwrap().time_base();
+        }
+        Ok(())
+    }
+
+    /// Has to be called before the header is written.
+    fn set_video_extradata(&mut self, extradata: &[u8]) {
+        let Some(ref video_stream) = self.video_stream else {
+            return;
+        };
+        let mut stream = self.ctx.stream_mut(video_stream.index).unwrap();
+        unsafe {
+            set_extradata(&mut *(*stream.as_mut_ptr()).codecpar, extradata);
+        }
+    }
+
+    fn write(&mut self, chunk: EncodedChunk, timestamp_offset: &mut Option<Duration>) {
+        let stream = match chunk.kind {
+            EncodedChunkKind::Video(_) => self.video_stream.as_ref(),
+            EncodedChunkKind::Audio(_) => self.audio_stream.as_ref(),
+        };
+        let Some(stream) = stream else {
+            error!(kind=?chunk.kind, "Received chunk for a track that is not present in the output.");
+            return;
+        };
+        let offset = *timestamp_offset.get_or_insert(chunk.dts.unwrap_or(chunk.pts));
+        if let Err(err) = write_chunk(&mut self.ctx, stream, chunk, offset) {
+            error!("Failed to write a chunk to the muxer: {err}");
+        }
+    }
+}
+
+/// Creates an ffmpeg output with streams matching the encoders and writes the header.
+///
+/// `video_extradata` is required by formats that need H264 parameter sets
+/// upfront (e.g. FLV), it can be extracted from a keyframe with [`h264_parameter_sets`].
+pub(super) fn init_ffmpeg_output(
+    options: &MuxerOptions,
+    encoder_ctx: &EncoderContext,
+    video_extradata: Option<&[u8]>,
+) -> Result<
+    (
+        ffmpeg::format::context::Output,
+        Option<StreamState>,
+        Option<StreamState>,
+    ),
+    OutputInitError,
+> {
+    let mut output = create_ffmpeg_output(options, encoder_ctx)?;
+    if let Some(extradata) = video_extradata {
+        output.set_video_extradata(extradata);
+    }
+    output.write_header(options)?;
+    Ok((output.ctx, output.video_stream, output.audio_stream))
+}
+
+fn create_ffmpeg_output(
+    options: &MuxerOptions,
+    encoder_ctx: &EncoderContext,
+) -> Result<FfmpegOutput, OutputInitError> {
+    let protocol_options = options
+        .protocol_options
+        .iter()
+        .map(|(key, value)| (key.as_str(), value.as_str()));
+    let mut ctx = ffmpeg::format::output_as_with(
+        &options.path,
+        options.format,
+        Dictionary::from_iter(protocol_options),
+    )
+    .map_err(OutputInitError::FfmpegError)?;
+
+    let video_stream = encoder_ctx
+        .video
+        .as_ref()
+        .map(|video| add_video_stream(&mut ctx, video))
+        .transpose()?;
+
+    let audio_stream = encoder_ctx
+        .audio
+        .as_ref()
+        .map(|audio| add_audio_stream(&mut ctx, audio))
+        .transpose()?;
+
+    Ok(FfmpegOutput {
+        ctx,
+        video_stream,
+        audio_stream,
+    })
+}
+
+fn add_video_stream(
+    output_ctx: &mut ffmpeg::format::context::Output,
+    video: &VideoEncoderContext,
+) -> Result<StreamState, OutputInitError> {
+    let codec_id = match video.codec {
+        VideoCodec::H264 => ffmpeg::codec::Id::H264,
+        VideoCodec::Vp8 => ffmpeg::codec::Id::VP8,
+        VideoCodec::Vp9 => ffmpeg::codec::Id::VP9,
+        VideoCodec::Av1 => ffmpeg::codec::Id::AV1,
+    };
+
+    let mut stream = output_ctx
+        .add_stream(codec_id)
+        .map_err(OutputInitError::FfmpegError)?;
+    stream.set_time_base(Rational::new(1, VIDEO_TIME_BASE));
+
+    unsafe {
+        let codecpar = &mut *(*stream.as_mut_ptr()).codecpar;
+        codecpar.codec_id = codec_id.into();
+        codecpar.codec_type = ffmpeg::ffi::AVMediaType::AVMEDIA_TYPE_VIDEO;
+        codecpar.width = video.resolution.width as i32;
+        codecpar.height = video.resolution.height as i32;
+    }
+
+    Ok(StreamState {
+        index: stream.index(),
+        time_base: Rational::new(1, VIDEO_TIME_BASE),
+        eos_received: false,
+    })
+}
+
+fn add_audio_stream(
+    output_ctx: &mut ffmpeg::format::context::Output,
+    audio: &AudioEncoderContext,
+) -> Result<StreamState, OutputInitError> {
+    let codec_id = match audio.codec {
+        AudioCodec::Opus => ffmpeg::codec::Id::OPUS,
+        AudioCodec::Aac => ffmpeg::codec::Id::AAC,
+    };
+    let channels = match audio.channels {
+        AudioChannels::Mono => 1,
+        AudioChannels::Stereo => 2,
+    };
+    let time_base = Rational::new(1, audio.sample_rate as i32);
+
+    let mut stream = output_ctx
+        .add_stream(codec_id)
+        .map_err(OutputInitError::FfmpegError)?;
+    stream.set_time_base(time_base);
+
+    unsafe {
+        let codecpar = &mut *(*stream.as_mut_ptr()).codecpar;
+        codecpar.codec_id = codec_id.into();
+        codecpar.codec_type = ffmpeg::ffi::AVMediaType::AVMEDIA_TYPE_AUDIO;
+        codecpar.sample_rate = audio.sample_rate as i32;
+        ffmpeg::ffi::av_channel_layout_default(&mut codecpar.ch_layout, channels);
+        if let Some(extradata) = &audio.extradata {
+            set_extradata(codecpar, extradata);
+        }
+    }
+
+    Ok(StreamState {
+        index: stream.index(),
+        time_base,
+        eos_received: false,
+    })
+}
+
+/// Extradata has to be allocated with `av_malloc`, because it is freed by ffmpeg
+/// together with the stream.
+unsafe fn set_extradata(codecpar: &mut ffmpeg::ffi::AVCodecParameters, extradata: &[u8]) {
+    let padding = ffmpeg::ffi::AV_INPUT_BUFFER_PADDING_SIZE as usize;
+    let data = ffmpeg::ffi::av_mallocz(extradata.len() + padding) as *mut u8;
+    ptr::copy_nonoverlapping(extradata.as_ptr(), data, extradata.len());
+    codecpar.extradata = data;
+    codecpar.extradata_size = extradata.len() as i32;
+}
+
+pub(super) fn write_chunk(
+    output_ctx: &mut ffmpeg::format::context::Output,
+    stream: &StreamState,
+    chunk: EncodedChunk,
+    timestamp_offset: Duration,
+) -> Result<(), ffmpeg::Error> {
+    // Chunks earlier than the offset get negative timestamps instead of being clamped
+    // to 0, so DTS stays monotonic. The muxer shifts them if the format requires it.
+    let to_timestamp = |time: Duration| {
+        let time = time.as_secs_f64() - timestamp_offset.as_secs_f64();
+        (time * stream.time_base.denominator() as f64 / stream.time_base.numerator() as f64).round()
+            as i64
+    };
+
+    let mut packet = ffmpeg::Packet::copy(&chunk.data);
+    packet.set_pts(Some(to_timestamp(chunk.pts)));
+    packet.set_dts(Some(to_timestamp(chunk.dts.unwrap_or(chunk.pts))));
+    packet.set_stream(stream.index);
+    if let IsKeyframe::Yes | IsKeyframe::NoKeyframes = chunk.is_keyframe {
+        packet.set_flags(ffmpeg::packet::Flags::KEY);
+    }
+
+    trace!(pts=?packet.pts(), kind=?chunk.kind, "Writing chunk to the muxer.");
+    packet.write_interleaved(output_ctx)
+}
+
+/// Extracts SPS and PPS NAL units (in Annex B format) from an H264 keyframe.
+/// Returns `None` if the chunk does not contain both of them.
+pub(super) fn h264_parameter_sets(data: &[u8]) -> Option<Bytes> {
+    const SPS: u8 = 7;
+    const PPS: u8 = 8;
+
+    let mut parameter_sets = BytesMut::new();
+    let mut has_sps = false;
+    let mut has_pps = false;
+    for nal in annex_b_nal_units(data) {
+        let Some(header) = nal.first() else {
+            continue;
+        };
+        match header & 0x1F {
+            SPS => has_sps = true,
+            PPS => has_pps = true,
+            _ => continue,
+        }
+        parameter_sets.extend_from_slice(&[0, 0, 0, 1]);
+        parameter_sets.extend_from_slice(nal);
+    }
+
+    (has_sps && has_pps).then(|| parameter_sets.freeze())
+}
+
+fn annex_b_nal_units(data: &[u8]) -> impl Iterator<Item = &[u8]> {
+    let mut start_codes = Vec::new();
+    let mut i = 0;
+    while i + 3 <= data.len() {
+        if data[i..i + 3] == [0, 0, 1] {
+            start_codes.push(i);
+            i += 3;
+        } else {
+            i += 1;
+        }
+    }
+
+    let nal_starts: Vec<usize> = start_codes.iter().map(|start| start + 3).collect();
+    let nal_ends = start_codes
+        .into_iter()
+        .skip(1)
+        .map(move |start| {
+            // 4-byte start code has an additional leading zero
+            if start > 0 && data[start - 1] == 0 {
+                start - 1
+            } else {
+                start
+            }
+        })
+        .chain(std::iter::once(data.len()));
+
+    nal_starts
+        .into_iter()
+        .zip(nal_ends)
+        .map(move |(start, end)| &data[start..end.max(start)])
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bytes::Bytes;
+
+    use super::{earliest_timestamp, h264_parameter_sets};
+    use crate::pipeline::{
+        structs::{EncodedChunk, EncodedChunkKind, IsKeyframe},
+        AudioCodec, VideoCodec,
+    };
+
+    fn chunk(kind: EncodedChunkKind, pts_ms: u64, dts_ms: Option<u64>) -> EncodedChunk {
+        EncodedChunk {
+            data: Bytes::new(),
+            pts: Duration::from_millis(pts_ms),
+            dts: dts_ms.map(Duration::from_millis),
+            is_keyframe: IsKeyframe::Unknown,
+            kind,
+        }
+    }
+
+    #[test]
+    fn offset_from_earliest_pending_chunk() {
+        let audio = EncodedChunkKind::Audio(AudioCodec::Aac);
+        let video = EncodedChunkKind::Video(VideoCodec::H264);
+        let chunks = [
+            chunk(audio, 120, None),
+            chunk(audio, 141, None),
+            chunk(video, 140, Some(73)),
+        ];
+        assert_eq!(earliest_timestamp(&chunks), Some(Duration::from_millis(73)));
+        assert_eq!(earliest_timestamp(&[]), None);
+    }
+
+    #[test]
+    fn extract_parameter_sets_from_keyframe() {
+        let keyframe = [
+            0, 0, 0, 1, 0x09, 0xF0, // AUD
+            0, 0, 0, 1, 0x67, 0x64, 0x00, 0x1F, // SPS
+            0, 0, 1, 0x68, 0xEE, 0x3C, 0x80, // PPS
+            0, 0, 1, 0x65, 0x88, 0x84, // IDR slice
+        ];
+
+        let parameter_sets = h264_parameter_sets(&keyframe).unwrap();
+        assert_eq!(
+            parameter_sets.as_ref(),
+            [0, 0, 0, 1, 0x67, 0x64, 0x00, 0x1F, 0, 0, 0, 1, 0x68, 0xEE, 0x3C, 0x80]
+        );
+    }
+
+    #[test]
+    fn missing_parameter_sets() {
+        let frame = [0, 0, 0, 1, 0x41, 0x9A, 0x02];
+        assert!(h264_parameter_sets(&frame).is_none());
+    }
+}
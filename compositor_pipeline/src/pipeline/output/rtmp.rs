@@ -0,0 +1,331 @@
+use std::{
+    io::ErrorKind,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+use compositor_render::OutputId;
+use crossbeam_channel::{Receiver, Sender, TryRecvError};
+use ffmpeg_next as ffmpeg;
+use tracing::{debug, error, info, span, warn, Level};
+
+use crate::{
+    error::OutputInitError,
+    pipeline::{
+        encoder::EncoderContext,
+        structs::{EncodedChunk, EncodedChunkKind, EncoderOutputEvent, IsKeyframe},
+        AudioCodec, VideoCodec,
+    },
+};
+
+use super::muxer::{
+    h264_parameter_sets, init_ffmpeg_output, write_chunk, MuxerOptions, StreamState,
+};
+
+const INITIAL_RECONNECT_DELAY: Duration = Duration::from_millis(500);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(30);
+
+/// Timeout (in microseconds) of a single read or write on the RTMP connection.
+/// Without it, a dead connection could block the sender thread indefinitely.
+const RW_TIMEOUT_MICROS: &str = "5000000";
+
+#[derive(Debug, Clone)]
+pub struct RtmpSenderOptions {
+    pub url: Arc<str>,
+}
+
+#[derive(Debug)]
+pub struct RtmpSender {
+    pub url: Arc<str>,
+    should_close: Arc<AtomicBool>,
+}
+
+impl RtmpSender {
+    pub fn new(
+        output_id: &OutputId,
+        options: RtmpSenderOptions,
+        encoder_ctx: EncoderContext,
+        packets_receiver: Receiver<EncoderOutputEvent>,
+    ) -> Result<Self, OutputInitError> {
+        if let Some(video) = &encoder_ctx.video {
+            if video.codec != VideoCodec::H264 {
+                return Err(OutputInitError::UnsupportedVideoCodec(video.codec));
+            }
+        }
+        if let Some(audio) = &encoder_ctx.audio {
+            if audio.codec != AudioCodec::Aac {
+                return Err(OutputInitError::UnsupportedAudioCodec(audio.codec));
+            }
+        }
+
+        let muxer_options = MuxerOptions {
+            format: "flv",
+            path: PathBuf::from(options.url.as_ref()),
+            protocol_options: vec![("rw_timeout".to_string(), RW_TIMEOUT_MICROS.to_string())],
+            format_options: Vec::new(),
+        };
+
+        let should_close = Arc::new(AtomicBool::new(false));
+        let stream = RtmpStream::new(muxer_options, encoder_ctx, should_close.clone());
+
+        let output_id = output_id.clone();
+        std::thread::Builder::new()
+            .name(format!("RTMP sender for output {}", output_id))
+            .spawn(move || {
+                let _span = span!(
+                    Level::INFO,
+                    "RTMP sender",
+                    output_id = output_id.to_string()
+                )
+                .entered();
+                stream.run(packets_receiver);
+                debug!("Closing RTMP sender thread.");
+            })
+            .unwrap();
+
+        Ok(Self {
+            url: options.url,
+            should_close,
+        })
+    }
+}
+
+impl Drop for RtmpSender {
+    fn drop(&mut self) {
+        self.should_close.store(true, Ordering::Relaxed);
+    }
+}
+
+struct RtmpConnection {
+    output_ctx: ffmpeg::format::context::Output,
+    video_stream: Option<StreamState>,
+    audio_stream: Option<StreamState>,
+}
+
+/// State of the RTMP sender thread.
+///
+/// Connection is established lazily, because FLV header requires H264 parameter
+/// sets that are only available after the first keyframe is produced. Connecting
+/// (including retries with an exponential backoff) happens on a separate thread and
+/// chunks are dropped until a connection is ready, so a dead server never blocks
+/// the encoder. After (re)connecting, the stream starts from the next video keyframe.
+struct RtmpStream {
+    muxer_options: Arc<MuxerOptions>,
+    encoder_ctx: EncoderContext,
+    should_close: Arc<AtomicBool>,
+
+    connection: Option<RtmpConnection>,
+    pending_connection: Option<Receiver<RtmpConnection>>,
+    waiting_for_keyframe: bool,
+
+    /// Latest SPS and PPS produced by the encoder.
+    video_parameter_sets: Option<Bytes>,
+
+    /// Timestamps are offset by the first chunk sent to the server (a video keyframe
+    /// if the output has video) for the whole lifetime of the output, so they stay
+    /// continuous across reconnects.
+    timestamp_offset: Option<Duration>,
+
+    video_eos_received: bool,
+    audio_eos_received: bool,
+}
+
+impl RtmpStream {
+    fn new(
+        muxer_options: MuxerOptions,
+        encoder_ctx: EncoderContext,
+        should_close: Arc<AtomicBool>,
+    ) -> Self {
+        Self {
+            video_eos_received: encoder_ctx.video.is_none(),
+            audio_eos_received: encoder_ctx.audio.is_none(),
+            muxer_options: Arc::new(muxer_options),
+            encoder_ctx,
+            should_close,
+            connection: None,
+            pending_connection: None,
+            waiting_for_keyframe: true,
+            video_parameter_sets: None,
+            timestamp_offset: None,
+        }
+    }
+
+    fn run(mut self, packets_receiver: Receiver<EncoderOutputEvent>) {
+        for event in packets_receiver {
+            match event {
+                EncoderOutputEvent::Data(chunk) => self.handle_chunk(chunk),
+                EncoderOutputEvent::VideoEOS => self.video_eos_received = true,
+                EncoderOutputEvent::AudioEOS => self.audio_eos_received = true,
+            }
+
+            if self.video_eos_received && self.audio_eos_received {
+                break;
+            }
+        }
+
+        // Reached either when EOS was received on all tracks or when the channel was
+        // closed because the output was unregistered.
+        self.should_close.store(true, Ordering::Relaxed);
+        if let Some(mut connection) = self.connection.take() {
+            if let Err(err) = connection.output_ctx.write_trailer() {
+                error!("Failed to finalize the RTMP stream: {err}");
+            }
+        }
+    }
+
+    fn handle_chunk(&mut self, chunk: EncodedChunk) {
+        let is_video_keyframe = matches!(chunk.kind, EncodedChunkKind::Video(_))
+            && matches!(chunk.is_keyframe, IsKeyframe::Yes);
+        if is_video_keyframe {
+            if let Some(parameter_sets) = h264_parameter_sets(&chunk.data) {
+                self.video_parameter_sets = Some(parameter_sets);
+            }
+        }
+
+        if self.connection.is_none() {
+            self.poll_connection();
+        }
+        let Some(connection) = &mut self.connection else {
+            return;
+        };
+
+        if self.waiting_for_keyframe {
+            if self.encoder_ctx.video.is_some() && !is_video_keyframe {
+                return;
+            }
+            self.waiting_for_keyframe = false;
+        }
+
+        let stream = match chunk.kind {
+            EncodedChunkKind::Video(_) => connection.video_stream.as_ref(),
+            EncodedChunkKind::Audio(_) => connection.audio_stream.as_ref(),
+        };
+        let Some(stream) = stream else {
+            error!(kind=?chunk.kind, "Received chunk for a track that is not present in the output.");
+            return;
+        };
+
+        // Chunks received later can have lower timestamps (e.g. audio encoded before the
+        // first keyframe), `write_chunk` passes them as negative timestamps.
+        let offset = *self
+            .timestamp_offset
+            .get_or_insert(chunk.dts.unwrap_or(chunk.pts));
+        match write_chunk(&mut connection.output_ctx, stream, chunk, offset) {
+            Ok(()) => (),
+            Err(err) if is_connection_error(&err) => {
+                warn!("RTMP connection lost: {err}. Reconnecting.");
+                self.connection = None;
+            }
+            Err(err) => error!("Failed to write a chunk to the RTMP stream: {err}"),
+        }
+    }
+
+    fn poll_connection(&mut self) {
+        if self.pending_connection.is_none() {
+            // With a video track, FLV header can be written only after SPS and PPS
+            // are known.
+            let video_extradata = match (&self.encoder_ctx.video, &self.video_parameter_sets) {
+                (Some(_), None) => return,
+                (Some(_), Some(parameter_sets)) => Some(parameter_sets.clone()),
+                (None, _) => None,
+            };
+            self.pending_connection = Some(self.start_connecting(video_extradata));
+        }
+        let Some(receiver) = &self.pending_connection else {
+            return;
+        };
+        match receiver.try_recv() {
+            Ok(connection) => {
+                info!("Connected to the RTMP server.");
+                self.connection = Some(connection);
+                self.pending_connection = None;
+                self.waiting_for_keyframe = true;
+                // The stream can start only from a keyframe, so don't wait for the next
+                // one from the regular keyframe interval.
+                if let Some(video) = &self.encoder_ctx.video {
+                    video.keyframe_request.request();
+                }
+            }
+            Err(TryRecvError::Empty) => (),
+            Err(TryRecvError::Disconnected) => self.pending_connection = None,
+        }
+    }
+
+    fn start_connecting(&self, video_extradata: Option<Bytes>) -> Receiver<RtmpConnection> {
+        let (sender, receiver) = crossbeam_channel::bounded(1);
+        let muxer_options = self.muxer_options.clone();
+        let encoder_ctx = self.encoder_ctx.clone();
+        let should_close = self.should_close.clone();
+        let span = tracing::Span::current();
+        std::thread::Builder::new()
+            .name("RTMP connector".to_string())
+            .spawn(move || {
+                let _span = span.entered();
+                run_connector_thread(
+                    &muxer_options,
+                    &encoder_ctx,
+                    video_extradata.as_deref(),
+                    &should_close,
+                    sender,
+                );
+            })
+            .unwrap();
+        receiver
+    }
+}
+
+/// Returns `true` if writing failed because of the connection, other errors (e.g.
+/// rejected timestamps) affect only a single chunk.
+fn is_connection_error(err: &ffmpeg::Error) -> bool {
+    match err {
+        ffmpeg::Error::Eof | ffmpeg::Error::Exit => true,
+        ffmpeg::Error::Other { errno } => !matches!(
+            std::io::Error::from_raw_os_error(*errno).kind(),
+            ErrorKind::InvalidInput | ErrorKind::OutOfMemory
+        ),
+        _ => false,
+    }
+}
+
+/// Repeats connection attempts with an exponential backoff until one succeeds or
+/// the output is unregistered.
+fn run_connector_thread(
+    muxer_options: &MuxerOptions,
+    encoder_ctx: &EncoderContext,
+    video_extradata: Option<&[u8]>,
+    should_close: &AtomicBool,
+    sender: Sender<RtmpConnection>,
+) {
+    let mut reconnect_delay = INITIAL_RECONNECT_DELAY;
+    while !should_close.load(Ordering::Relaxed) {
+        match init_ffmpeg_output(muxer_options, encoder_ctx, video_extradata) {
+            Ok((output_ctx, video_stream, audio_stream)) => {
+                let connection = RtmpConnection {
+                    output_ctx,
+                    video_stream,
+                    audio_stream,
+                };
+                if sender.send(connection).is_err() {
+                    debug!("RTMP sender was closed before the connection was established.");
+                }
+                return;
+            }
+            Err(err) => {
+                warn!(
+                    "Failed to connect to the RTMP server: {err}. Retrying in {:?}.",
+                    reconnect_delay
+                );
+                let retry_at = Instant::now() + reconnect_delay;
+                while Instant::now() < retry_at && !should_close.load(Ordering::Relaxed) {
+                    std::thread::sleep(Duration::from_millis(100));
+                }
+                reconnect_delay = (reconnect_delay * 2).min(MAX_RECONNECT_DELAY);
+            }
+        }
+    }
+}
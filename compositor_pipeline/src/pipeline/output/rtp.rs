@@ -1,17 +1,25 @@
 use compositor_render::OutputId;
 use crossbeam_channel::Receiver;
-use std::sync::{atomic::AtomicBool, Arc};
+use std::{
+    path::PathBuf,
+    sync::{atomic::AtomicBool, Arc},
+};
 use tracing::{debug, span, Level};
 
 use crate::{
     error::OutputInitError,
-    pipeline::{rtp::RequestedPort, structs::EncoderOutputEvent, AudioCodec, Port, VideoCodec},
+    pipeline::{
+        encoder::EncoderContext, rtp::RequestedPort, structs::EncoderOutputEvent, AudioCodec, Port,
+        VideoCodec,
+    },
 };
 
 use self::{packet_stream::PacketStream, payloader::Payloader};
 
-mod packet_stream;
-mod payloader;
+pub(super) mod packet_stream;
+pub(super) mod payloader;
+mod sdp;
+mod tcp_client;
 mod tcp_server;
 mod udp;
 
@@ -19,6 +27,9 @@ mod udp;
 pub struct RtpSender {
     pub connection_options: RtpConnectionOptions,
 
+    /// Session description of the stream sent by this output.
+    sdp: Arc<str>,
+
     /// should_close will be set after output is unregistered,
     /// but the primary way of controlling the shutdown is a channel
     /// receiver.
@@ -34,34 +45,85 @@ pub struct RtpSenderOptions {
     pub connection_options: RtpConnectionOptions,
     pub video: Option<VideoCodec>,
     pub audio: Option<AudioCodec>,
+    /// If set, SDP describing the stream is written to this path when the
+    /// output is registered.
+    pub sdp_path: Option<PathBuf>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum RtpConnectionOptions {
-    Udp { port: Port, ip: Arc<str> },
-    TcpServer { port: RequestedPort },
+    Udp {
+        port: Port,
+        ip: Arc<str>,
+    },
+    TcpServer {
+        port: RequestedPort,
+    },
+    /// Connect to a remote TCP server. Packets are dropped while the connection
+    /// is being (re-)established.
+    TcpClient {
+        port: Port,
+        ip: Arc<str>,
+    },
+}
+
+/// Socket created when the output is registered. In the client mode the connection
+/// is established by the sender thread.
+enum SenderSocket {
+    Udp(socket2::Socket),
+    TcpServer(socket2::Socket),
+    TcpClient { ip: Arc<str>, port: Port },
 }
 
 impl RtpSender {
     pub fn new(
         output_id: &OutputId,
         options: RtpSenderOptions,
+        encoder_ctx: EncoderContext,
         packets_receiver: Receiver<EncoderOutputEvent>,
     ) -> Result<(Self, Option<Port>), OutputInitError> {
-        let payloader = Payloader::new(options.video, options.audio);
+        let audio_sample_rate = encoder_ctx
+            .audio
+            .as_ref()
+            .map(|audio| audio.sample_rate)
+            .unwrap_or_default();
+        let payloader = Payloader::new(options.video, options.audio, audio_sample_rate);
         let mtu = match options.connection_options {
             RtpConnectionOptions::Udp { .. } => 1400,
-            RtpConnectionOptions::TcpServer { .. } => 64000,
+            RtpConnectionOptions::TcpServer { .. } | RtpConnectionOptions::TcpClient { .. } => {
+                64000
+            }
         };
         let packet_stream = PacketStream::new(packets_receiver, payloader, mtu);
+        let keyframe_request = encoder_ctx
+            .video
+            .as_ref()
+            .map(|video| video.keyframe_request.clone());
 
         let (socket, port) = match &options.connection_options {
-            RtpConnectionOptions::Udp { port, ip } => udp::udp_socket(ip, *port)?,
-            RtpConnectionOptions::TcpServer { port } => tcp_server::tcp_socket(*port)?,
+            RtpConnectionOptions::Udp { port, ip } => {
+                let (socket, port) = udp::udp_socket(ip, *port)?;
+                (SenderSocket::Udp(socket), port)
+            }
+            RtpConnectionOptions::TcpServer { port } => {
+                let (socket, port) = tcp_server::tcp_socket(*port)?;
+                (SenderSocket::TcpServer(socket), port)
+            }
+            RtpConnectionOptions::TcpClient { port, ip } => (
+                SenderSocket::TcpClient {
+                    ip: ip.clone(),
+                    port: *port,
+                },
+                *port,
+            ),
         };
 
+        let sdp: Arc<str> = sdp::session_description(&options, &encoder_ctx, port).into();
+        if let Some(path) = &options.sdp_path {
+            std::fs::write(path, sdp.as_bytes()).map_err(OutputInitError::WriteSdpFile)?;
+        }
+
         let should_close = Arc::new(AtomicBool::new(false));
-        let connection_options = options.connection_options.clone();
         let output_id = output_id.clone();
         let should_close2 = should_close.clone();
         std::thread::Builder::new()
@@ -69,13 +131,18 @@ impl RtpSender {
             .spawn(move || {
                 let _span =
                     span!(Level::INFO, "RTP sender", output_id = output_id.to_string()).entered();
-                match connection_options {
-                    RtpConnectionOptions::Udp { .. } => {
-                        udp::run_udp_sender_thread(socket, packet_stream)
-                    }
-                    RtpConnectionOptions::TcpServer { .. } => {
+                match socket {
+                    SenderSocket::Udp(socket) => udp::run_udp_sender_thread(socket, packet_stream),
+                    SenderSocket::TcpServer(socket) => {
                         tcp_server::run_tcp_sender_thread(socket, should_close2, packet_stream)
                     }
+                    SenderSocket::TcpClient { ip, port } => tcp_client::run_tcp_sender_thread(
+                        ip,
+                        port,
+                        should_close2,
+                        packet_stream,
+                        keyframe_request,
+                    ),
                 }
                 debug!("Closing RTP sender thread.")
             })
@@ -84,6 +151,7 @@ impl RtpSender {
         Ok((
             Self {
                 connection_options: options.connection_options,
+                sdp,
                 should_close,
             },
             Some(port),
@@ -91,6 +159,21 @@ impl RtpSender {
     }
 }
 
+impl RtpSender {
+    pub fn sdp(&self) -> Arc<str> {
+        self.sdp.clone()
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RtpOutputSdpError {
+    #[error("Output \"{0}\" is not registered.")]
+    OutputNotFound(OutputId),
+
+    #[error("Output \"{0}\" is not an RTP output.")]
+    NotRtpOutput(OutputId),
+}
+
 impl Drop for RtpSender {
     fn drop(&mut self) {
         self.should_close
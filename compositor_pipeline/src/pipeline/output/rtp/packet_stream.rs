@@ -6,7 +6,7 @@ use crate::pipeline::structs::EncoderOutputEvent;
 
 use super::payloader::{Payloader, PayloadingError};
 
-pub(super) struct PacketStream {
+pub(crate) struct PacketStream {
     packets_receiver: Receiver<EncoderOutputEvent>,
     state: VecDeque<bytes::Bytes>,
     payloader: Payloader,
@@ -14,7 +14,7 @@ pub(super) struct PacketStream {
 }
 
 impl PacketStream {
-    pub(super) fn new(
+    pub(crate) fn new(
         packets_receiver: Receiver<EncoderOutputEvent>,
         payloader: Payloader,
         mtu: usize,
@@ -1,10 +1,14 @@
-use bytes::Bytes;
-use std::{collections::VecDeque, fmt::Debug};
+use bytes::{BufMut, Bytes, BytesMut};
+use std::{
+    collections::VecDeque,
+    fmt::Debug,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 use tracing::error;
 use webrtc_util::Marshal;
 
 use rand::Rng;
-use rtp::codecs::{h264::H264Payloader, opus::OpusPayloader};
+use rtp::codecs::{h264::H264Payloader, opus::OpusPayloader, vp8::Vp8Payloader, vp9::Vp9Payloader};
 
 use crate::pipeline::{
     rtp::{AUDIO_PAYLOAD_TYPE, VIDEO_PAYLOAD_TYPE},
@@ -12,13 +16,29 @@ use crate::pipeline::{
     AudioCodec, VideoCodec,
 };
 
+use self::av1::Av1Payloader;
+
+mod av1;
+
 const H264_CLOCK_RATE: u32 = 90000;
+const VP8_CLOCK_RATE: u32 = 90000;
+const VP9_CLOCK_RATE: u32 = 90000;
+const AV1_CLOCK_RATE: u32 = 90000;
 const OPUS_CLOCK_RATE: u32 = 48000;
 
+const SENDER_REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Difference between the NTP epoch (1900) and the UNIX epoch (1970).
+const NTP_UNIX_EPOCH_OFFSET: Duration = Duration::from_secs(2_208_988_800);
+
 struct RtpStreamContext {
     ssrc: u32,
     next_sequence_number: u16,
     received_eos: bool,
+    packet_count: u32,
+    octet_count: u32,
+    /// PTS of the chunk for which the last sender report was sent.
+    last_sender_report_pts: Option<Duration>,
 }
 
 impl RtpStreamContext {
@@ -31,8 +51,38 @@ impl RtpStreamContext {
             ssrc,
             next_sequence_number,
             received_eos: false,
+            packet_count: 0,
+            octet_count: 0,
+            last_sender_report_pts: None,
         }
     }
+
+    /// [RFC 3550, section 6.4.1](https://datatracker.ietf.org/doc/html/rfc3550#section-6.4.1)
+    fn sender_report(
+        &mut self,
+        pts: Duration,
+        clock_rate: u32,
+        ntp_start_time: Duration,
+    ) -> Result<Option<Bytes>, PayloadingError> {
+        if let Some(last_pts) = self.last_sender_report_pts {
+            if pts.saturating_sub(last_pts) < SENDER_REPORT_INTERVAL {
+                return Ok(None);
+            }
+        }
+        self.last_sender_report_pts = Some(pts);
+
+        let ntp_time = ntp_start_time + pts;
+        let packet = rtcp::sender_report::SenderReport {
+            ssrc: self.ssrc,
+            ntp_time: (ntp_time.as_secs() << 32)
+                | (((ntp_time.subsec_nanos() as u64) << 32) / 1_000_000_000),
+            rtp_time: (pts.as_secs_f64() * clock_rate as f64) as u32,
+            packet_count: self.packet_count,
+            octet_count: self.octet_count,
+            ..Default::default()
+        };
+        Ok(Some(packet.marshal()?))
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -79,6 +129,10 @@ pub enum PayloadingError {
 pub struct Payloader {
     video: Option<VideoPayloader>,
     audio: Option<AudioPayloader>,
+    /// NTP wallclock time of PTS 0, set when the first chunk is payloaded. Both
+    /// tracks use the same value, so receivers can synchronize them based on
+    /// sender reports.
+    ntp_start_time: Option<Duration>,
 }
 
 enum VideoPayloader {
@@ -86,6 +140,18 @@ enum VideoPayloader {
         payloader: H264Payloader,
         context: RtpStreamContext,
     },
+    Vp8 {
+        payloader: Vp8Payloader,
+        context: RtpStreamContext,
+    },
+    Vp9 {
+        payloader: Vp9Payloader,
+        context: RtpStreamContext,
+    },
+    Av1 {
+        payloader: Av1Payloader,
+        context: RtpStreamContext,
+    },
 }
 
 enum AudioPayloader {
@@ -93,13 +159,25 @@ enum AudioPayloader {
         payloader: OpusPayloader,
         context: RtpStreamContext,
     },
+    Aac {
+        payloader: AacPayloader,
+        context: RtpStreamContext,
+        clock_rate: u32,
+    },
 }
 
 impl Payloader {
-    pub fn new(video: Option<VideoCodec>, audio: Option<AudioCodec>) -> Self {
+    /// `audio_sample_rate` is used as a clock rate for codecs that do not have
+    /// a fixed one (AAC).
+    pub fn new(
+        video: Option<VideoCodec>,
+        audio: Option<AudioCodec>,
+        audio_sample_rate: u32,
+    ) -> Self {
         Self {
             video: video.map(VideoPayloader::new),
-            audio: audio.map(AudioPayloader::new),
+            audio: audio.map(|codec| AudioPayloader::new(codec, audio_sample_rate)),
+            ntp_start_time: None,
         }
     }
 
@@ -108,6 +186,14 @@ impl Payloader {
         mtu: usize,
         data: EncodedChunk,
     ) -> Result<VecDeque<Bytes>, PayloadingError> {
+        let pts = data.pts;
+        let ntp_start_time = *self.ntp_start_time.get_or_insert_with(|| {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default();
+            (now + NTP_UNIX_EPOCH_OFFSET).saturating_sub(pts)
+        });
+
         match data.kind {
             EncodedChunkKind::Video(chunk_codec) => {
                 let Some(ref mut video_payloader) = self.video else {
@@ -121,7 +207,11 @@ impl Payloader {
                     });
                 }
 
-                video_payloader.payload(mtu, data)
+                let mut packets = video_payloader.payload(mtu, data)?;
+                let clock_rate = video_payloader.clock_rate();
+                let context = video_payloader.context_mut();
+                packets.extend(context.sender_report(pts, clock_rate, ntp_start_time)?);
+                Ok(packets)
             }
             EncodedChunkKind::Audio(chunk_codec) => {
                 let Some(ref mut audio_payloader) = self.audio else {
@@ -135,7 +225,11 @@ impl Payloader {
                     });
                 }
 
-                audio_payloader.payload(mtu, data)
+                let mut packets = audio_payloader.payload(mtu, data)?;
+                let clock_rate = audio_payloader.clock_rate();
+                let context = audio_payloader.context_mut();
+                packets.extend(context.sender_report(pts, clock_rate, ntp_start_time)?);
+                Ok(packets)
             }
         }
     }
@@ -186,12 +280,41 @@ impl VideoPayloader {
                 payloader: H264Payloader::default(),
                 context: RtpStreamContext::new(),
             },
+            VideoCodec::Vp8 => {
+                let mut payloader = Vp8Payloader::default();
+                // Picture ID is required by browsers to detect frame loss.
+                payloader.enable_picture_id = true;
+                Self::Vp8 {
+                    payloader,
+                    context: RtpStreamContext::new(),
+                }
+            }
+            VideoCodec::Vp9 => Self::Vp9 {
+                payloader: Vp9Payloader::default(),
+                context: RtpStreamContext::new(),
+            },
+            VideoCodec::Av1 => Self::Av1 {
+                payloader: Av1Payloader,
+                context: RtpStreamContext::new(),
+            },
+        }
+    }
+
+    fn clock_rate(&self) -> u32 {
+        match self {
+            VideoPayloader::H264 { .. } => H264_CLOCK_RATE,
+            VideoPayloader::Vp8 { .. } => VP8_CLOCK_RATE,
+            VideoPayloader::Vp9 { .. } => VP9_CLOCK_RATE,
+            VideoPayloader::Av1 { .. } => AV1_CLOCK_RATE,
         }
     }
 
     fn codec(&self) -> VideoCodec {
         match self {
             VideoPayloader::H264 { .. } => VideoCodec::H264,
+            VideoPayloader::Vp8 { .. } => VideoCodec::Vp8,
+            VideoPayloader::Vp9 { .. } => VideoCodec::Vp9,
+            VideoPayloader::Av1 { .. } => VideoCodec::Av1,
         }
     }
 
@@ -212,30 +335,78 @@ impl VideoPayloader {
                 VIDEO_PAYLOAD_TYPE,
                 H264_CLOCK_RATE,
             ),
+            VideoPayloader::Vp8 {
+                ref mut payloader,
+                ref mut context,
+            } => payload(
+                payloader,
+                context,
+                chunk,
+                mtu,
+                VIDEO_PAYLOAD_TYPE,
+                VP8_CLOCK_RATE,
+            ),
+            VideoPayloader::Vp9 {
+                ref mut payloader,
+                ref mut context,
+            } => payload(
+                payloader,
+                context,
+                chunk,
+                mtu,
+                VIDEO_PAYLOAD_TYPE,
+                VP9_CLOCK_RATE,
+            ),
+            VideoPayloader::Av1 {
+                ref mut payloader,
+                ref mut context,
+            } => payload(
+                payloader,
+                context,
+                chunk,
+                mtu,
+                VIDEO_PAYLOAD_TYPE,
+                AV1_CLOCK_RATE,
+            ),
         }
     }
 
     fn context_mut(&mut self) -> &mut RtpStreamContext {
         match self {
             VideoPayloader::H264 { context, .. } => context,
+            VideoPayloader::Vp8 { context, .. } => context,
+            VideoPayloader::Vp9 { context, .. } => context,
+            VideoPayloader::Av1 { context, .. } => context,
         }
     }
 }
 
 impl AudioPayloader {
-    fn new(codec: AudioCodec) -> Self {
+    fn new(codec: AudioCodec, sample_rate: u32) -> Self {
         match codec {
             AudioCodec::Opus => Self::Opus {
                 payloader: OpusPayloader,
                 context: RtpStreamContext::new(),
             },
-            AudioCodec::Aac => panic!("Aac audio output is not supported yet"),
+            AudioCodec::Aac => Self::Aac {
+                payloader: AacPayloader,
+                context: RtpStreamContext::new(),
+                clock_rate: sample_rate,
+            },
+        }
+    }
+
+    fn clock_rate(&self) -> u32 {
+        match self {
+            AudioPayloader::Opus { .. } => OPUS_CLOCK_RATE,
+            AudioPayloader::Aac { clock_rate, .. } => *clock_rate,
         }
     }
 
     fn codec(&self) -> AudioCodec {
         match self {
             AudioPayloader::Opus { .. } => AudioCodec::Opus,
+            AudioPayloader::Aac { .. } => AudioCodec::Aac,
         }
     }
 
@@ -256,16 +427,67 @@ impl AudioPayloader {
                 AUDIO_PAYLOAD_TYPE,
                 OPUS_CLOCK_RATE,
             ),
+            AudioPayloader::Aac {
+                ref mut payloader,
+                ref mut context,
+                clock_rate,
+            } => payload(
+                payloader,
+                context,
+                chunk,
+                mtu,
+                AUDIO_PAYLOAD_TYPE,
+                *clock_rate,
+            ),
         }
     }
 
     fn context_mut(&mut self) -> &mut RtpStreamContext {
         match self {
             AudioPayloader::Opus { context, .. } => context,
+            AudioPayloader::Aac { context, .. } => context,
         }
     }
 }
 
+/// Payloader for AAC in the high bitrate mode described in
+/// [RFC 3640, section 3.3.6](https://datatracker.ietf.org/doc/html/rfc3640#section-3.3.6).
+///
+/// Each packet contains a single AU header (13-bit size, 3-bit index) followed by
+/// an access unit. Access units larger than MTU are fragmented, every fragment
+/// carries the size of the whole access unit.
+#[derive(Debug, Clone)]
+struct AacPayloader;
+
+impl rtp::packetizer::Payloader for AacPayloader {
+    fn payload(&mut self, mtu: usize, payload: &Bytes) -> Result<Vec<Bytes>, rtp::Error> {
+        // AU-headers-length (16 bits) + single AU header (16 bits)
+        const HEADERS_LEN: usize = 4;
+        const AU_HEADERS_LENGTH_IN_BITS: u16 = 16;
+
+        if payload.is_empty() || mtu <= HEADERS_LEN {
+            return Ok(Vec::new());
+        }
+
+        let au_header = (payload.len() as u16) << 3;
+        let packets = payload
+            .chunks(mtu - HEADERS_LEN)
+            .map(|fragment| {
+                let mut packet = BytesMut::with_capacity(HEADERS_LEN + fragment.len());
+                packet.put_u16(AU_HEADERS_LENGTH_IN_BITS);
+                packet.put_u16(au_header);
+                packet.put_slice(fragment);
+                packet.freeze()
+            })
+            .collect();
+        Ok(packets)
+    }
+
+    fn clone_to(&self) -> Box<dyn rtp::packetizer::Payloader + Send + Sync> {
+        Box::new(self.clone())
+    }
+}
+
 fn payload<T: rtp::packetizer::Payloader>(
     payloader: &mut T,
     context: &mut RtpStreamContext,
@@ -293,8 +515,38 @@ fn payload<T: rtp::packetizer::Payloader>(
                 ..Default::default()
             };
             context.next_sequence_number = context.next_sequence_number.wrapping_add(1);
+            context.packet_count = context.packet_count.wrapping_add(1);
+            context.octet_count = context.octet_count.wrapping_add(payload.len() as u32);
 
             Ok(rtp::packet::Packet { header, payload }.marshal()?)
         })
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use rtp::packetizer::Payloader;
+
+    use super::AacPayloader;
+
+    #[test]
+    fn aac_single_access_unit() {
+        let au = Bytes::from_static(&[1, 2, 3, 4, 5]);
+        let payloads = AacPayloader.payload(1400, &au).unwrap();
+
+        assert_eq!(payloads.len(), 1);
+        assert_eq!(payloads[0].as_ref(), [0, 16, 0, 5 << 3, 1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn aac_fragmented_access_unit() {
+        let au = Bytes::from(vec![7u8; 10]);
+        let payloads = AacPayloader.payload(10, &au).unwrap();
+
+        // every fragment carries the size of the whole access unit
+        assert_eq!(payloads.len(), 2);
+        assert_eq!(payloads[0].as_ref(), [0, 16, 0, 10 << 3, 7, 7, 7, 7, 7, 7]);
+        assert_eq!(payloads[1].as_ref(), [0, 16, 0, 10 << 3, 7, 7, 7, 7]);
+    }
+}
@@ -0,0 +1,117 @@
+use bytes::{BufMut, Bytes, BytesMut};
+
+use crate::pipeline::rtp::av1::{
+    leb128_len, obu_type, split_obus, write_leb128, AGGREGATION_HEADER_N, AGGREGATION_HEADER_Y,
+    AGGREGATION_HEADER_Z, OBU_PADDING, OBU_SEQUENCE_HEADER, OBU_TEMPORAL_DELIMITER, OBU_TILE_LIST,
+};
+
+/// Payloader for AV1 described in [RTP Payload Format for AV1](https://aomediacodec.github.io/av1-rtp-spec/).
+///
+/// Each OBU element is preceded by its length (W=0), OBUs that do not fit into
+/// a single packet are fragmented.
+#[derive(Debug, Clone, Default)]
+pub(super) struct Av1Payloader;
+
+impl rtp::packetizer::Payloader for Av1Payloader {
+    fn payload(&mut self, mtu: usize, payload: &Bytes) -> Result<Vec<Bytes>, rtp::Error> {
+        // aggregation header + length field + at least one byte of OBU
+        if payload.is_empty() || mtu < 3 {
+            return Ok(Vec::new());
+        }
+
+        let obus = split_obus(payload).map_err(|err| rtp::Error::Other(err.to_string()))?;
+        let obus: Vec<Bytes> = obus
+            .into_iter()
+            // Temporal delimiters, tile lists and padding SHOULD be removed (section 5).
+            .filter(|obu| {
+                !matches!(
+                    obu_type(obu[0]),
+                    OBU_TEMPORAL_DELIMITER | OBU_TILE_LIST | OBU_PADDING
+                )
+            })
+            .collect();
+
+        let starts_sequence = obus
+            .iter()
+            .any(|obu| obu_type(obu[0]) == OBU_SEQUENCE_HEADER);
+
+        let mut packets = Vec::new();
+        let mut packet = new_packet(mtu);
+        let mut aggregation_header = match starts_sequence {
+            true => AGGREGATION_HEADER_N,
+            false => 0,
+        };
+
+        for obu in obus {
+            let mut remaining = obu.as_ref();
+            while !remaining.is_empty() {
+                let available = mtu - packet.len();
+                if available <= leb128_len(available) {
+                    packet[0] = aggregation_header;
+                    packets.push(packet.freeze());
+                    packet = new_packet(mtu);
+                    aggregation_header = 0;
+                    continue;
+                }
+
+                let len = usize::min(remaining.len(), available - leb128_len(available));
+                write_leb128(&mut packet, len);
+                packet.put_slice(&remaining[..len]);
+                remaining = &remaining[len..];
+
+                if !remaining.is_empty() {
+                    packet[0] = aggregation_header | AGGREGATION_HEADER_Y;
+                    packets.push(packet.freeze());
+                    packet = new_packet(mtu);
+                    aggregation_header = AGGREGATION_HEADER_Z;
+                }
+            }
+        }
+
+        if packet.len() > 1 {
+            packet[0] = aggregation_header;
+            packets.push(packet.freeze());
+        }
+        Ok(packets)
+    }
+
+    fn clone_to(&self) -> Box<dyn rtp::packetizer::Payloader + Send + Sync> {
+        Box::new(self.clone())
+    }
+}
+
+/// Creates a packet with a placeholder for the aggregation header.
+fn new_packet(mtu: usize) -> BytesMut {
+    let mut packet = BytesMut::with_capacity(mtu);
+    packet.put_u8(0);
+    packet
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use rtp::packetizer::Payloader;
+
+    use super::Av1Payloader;
+
+    #[test]
+    fn av1_fragmented_obu() {
+        // temporal delimiter, sequence header, frame OBU with 8 bytes of payload
+        let temporal_unit = Bytes::from_static(&[
+            0x12, 0x00, 0x0a, 0x02, 0xaa, 0xbb, 0x32, 0x08, 1, 2, 3, 4, 5, 6, 7, 8,
+        ]);
+        let payloads = Av1Payloader.payload(8, &temporal_unit).unwrap();
+
+        assert_eq!(
+            payloads,
+            vec![
+                // N=1, Y=1: sequence header and the beginning of the frame
+                Bytes::from_static(&[0x48, 0x03, 0x08, 0xaa, 0xbb, 0x02, 0x30, 1]),
+                // Z=1, Y=1: continuation of the frame
+                Bytes::from_static(&[0xc0, 0x06, 2, 3, 4, 5, 6, 7]),
+                // Z=1: end of the frame
+                Bytes::from_static(&[0x80, 0x01, 8]),
+            ]
+        );
+    }
+}
@@ -0,0 +1,170 @@
+use std::{fmt::Write, net::IpAddr};
+
+use crate::{
+    audio_mixer::AudioChannels,
+    pipeline::{
+        encoder::EncoderContext,
+        rtp::{AUDIO_PAYLOAD_TYPE, VIDEO_PAYLOAD_TYPE},
+        AudioCodec, Port, VideoCodec,
+    },
+};
+
+use super::{RtpConnectionOptions, RtpSenderOptions};
+
+/// Builds an SDP that allows tools like FFmpeg or GStreamer to receive the stream.
+/// Video and audio are described as separate media sections sent to the same port.
+/// RTCP is sent on that port too, which is signaled with `a=rtcp-mux` (RFC 5761).
+pub(super) fn session_description(
+    options: &RtpSenderOptions,
+    encoder_ctx: &EncoderContext,
+    port: Port,
+) -> String {
+    let (address, protocol) = match &options.connection_options {
+        RtpConnectionOptions::Udp { ip, .. } => (ip.as_ref(), "RTP/AVP"),
+        // Address of the server is not known here, receivers connect to the
+        // address they used to reach the API.
+        RtpConnectionOptions::TcpServer { .. } => ("0.0.0.0", "TCP/RTP/AVP"),
+        RtpConnectionOptions::TcpClient { ip, .. } => (ip.as_ref(), "TCP/RTP/AVP"),
+    };
+    let address_type = match address.parse::<IpAddr>() {
+        Ok(IpAddr::V6(_)) => "IP6",
+        _ => "IP4",
+    };
+
+    let mut sdp = String::new();
+    let _ = write!(
+        sdp,
+        "v=0\r\n\
+        o=- 0 0 IN {address_type} {address}\r\n\
+        s=LiveCompositor\r\n\
+        c=IN {address_type} {address}\r\n\
+        t=0 0\r\n"
+    );
+
+    if let Some(codec) = options.video {
+        let _ = write!(
+            sdp,
+            "m=video {} {protocol} {VIDEO_PAYLOAD_TYPE}\r\n",
+            port.0
+        );
+        let encoding = match codec {
+            VideoCodec::H264 => "H264",
+            VideoCodec::Vp8 => "VP8",
+            VideoCodec::Vp9 => "VP9",
+            VideoCodec::Av1 => "AV1",
+        };
+        let _ = write!(sdp, "a=rtpmap:{VIDEO_PAYLOAD_TYPE} {encoding}/90000\r\n");
+        if codec == VideoCodec::H264 {
+            let _ = write!(sdp, "a=fmtp:{VIDEO_PAYLOAD_TYPE} packetization-mode=1\r\n");
+        }
+        write_transport_attributes(&mut sdp, &options.connection_options);
+    }
+
+    if let (Some(codec), Some(audio_ctx)) = (options.audio, &encoder_ctx.audio) {
+        let _ = write!(
+            sdp,
+            "m=audio {} {protocol} {AUDIO_PAYLOAD_TYPE}\r\n",
+            port.0
+        );
+        let channels = match audio_ctx.channels {
+            AudioChannels::Mono => 1,
+            AudioChannels::Stereo => 2,
+        };
+        match codec {
+            AudioCodec::Opus => {
+                // RFC 7587: rtpmap always declares 2 channels, the actual channel
+                // count is signaled with "sprop-stereo".
+                let _ = write!(
+                    sdp,
+                    "a=rtpmap:{AUDIO_PAYLOAD_TYPE} opus/48000/2\r\n\
+                    a=fmtp:{AUDIO_PAYLOAD_TYPE} sprop-stereo={}\r\n",
+                    channels - 1
+                );
+            }
+            AudioCodec::Aac => {
+                let config = audio_ctx
+                    .extradata
+                    .as_ref()
+                    .map(|asc| asc.iter().map(|byte| format!("{byte:02X}")).collect())
+                    .unwrap_or_else(String::new);
+                let _ = write!(
+                    sdp,
+                    "a=rtpmap:{AUDIO_PAYLOAD_TYPE} MPEG4-GENERIC/{}/{channels}\r\n\
+                    a=fmtp:{AUDIO_PAYLOAD_TYPE} streamtype=5;profile-level-id=1;mode=AAC-hbr;\
+                    sizelength=13;indexlength=3;indexdeltalength=3;config={config}\r\n",
+                    audio_ctx.sample_rate
+                );
+            }
+        }
+        write_transport_attributes(&mut sdp, &options.connection_options);
+    }
+
+    sdp
+}
+
+/// Without `a=rtcp-mux`, receivers expect RTCP on the next port. For TCP,
+/// [RFC 4145](https://datatracker.ietf.org/doc/html/rfc4145) attributes describe
+/// which side opens the connection.
+fn write_transport_attributes(sdp: &mut String, connection_options: &RtpConnectionOptions) {
+    sdp.push_str("a=rtcp-mux\r\n");
+    match connection_options {
+        RtpConnectionOptions::Udp { .. } => (),
+        RtpConnectionOptions::TcpServer { .. } => {
+            sdp.push_str("a=setup:passive\r\na=connection:new\r\n")
+        }
+        RtpConnectionOptions::TcpClient { .. } => {
+            sdp.push_str("a=setup:active\r\na=connection:new\r\n")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use bytes::Bytes;
+
+    use crate::{
+        audio_mixer::AudioChannels,
+        pipeline::{
+            encoder::{AudioEncoderContext, EncoderContext},
+            output::rtp::{RtpConnectionOptions, RtpSenderOptions},
+            sdp::SdpRtpStream,
+            AudioCodec, Port, VideoCodec,
+        },
+    };
+
+    use super::session_description;
+
+    #[test]
+    fn generated_sdp_can_be_parsed() {
+        let options = RtpSenderOptions {
+            connection_options: RtpConnectionOptions::Udp {
+                port: Port(9000),
+                ip: Arc::from("127.0.0.1"),
+            },
+            video: Some(VideoCodec::H264),
+            audio: Some(AudioCodec::Aac),
+            sdp_path: None,
+        };
+        let encoder_ctx = EncoderContext {
+            video: None,
+            audio: Some(AudioEncoderContext {
+                codec: AudioCodec::Aac,
+                channels: AudioChannels::Stereo,
+                sample_rate: 48000,
+                extradata: Some(Bytes::from_static(&[0x11, 0x90])),
+            }),
+        };
+
+        let sdp = session_description(&options, &encoder_ctx, Port(9000));
+        assert!(sdp.contains("a=rtpmap:97 MPEG4-GENERIC/48000/2\r\n"));
+        assert!(sdp.contains("config=1190\r\n"));
+        assert_eq!(sdp.matches("a=rtcp-mux\r\n").count(), 2);
+
+        let SdpRtpStream { port, stream } = SdpRtpStream::parse(&sdp).unwrap();
+        assert_eq!(port, 9000);
+        assert_eq!(stream.video.unwrap().options.codec, VideoCodec::H264);
+        assert_eq!(stream.audio.unwrap().payload_type, 97);
+    }
+}
@@ -0,0 +1,124 @@
+use std::{
+    io,
+    net::TcpStream,
+    sync::{atomic::AtomicBool, Arc},
+    thread,
+    time::Instant,
+};
+
+use crossbeam_channel::{Receiver, Sender, TryRecvError};
+use tracing::{debug, error, info, trace, warn};
+
+use crate::pipeline::{
+    encoder::KeyframeRequest,
+    rtp::{connect_tcp, TCP_CONNECT_TIMEOUT},
+    Port,
+};
+
+use super::{packet_stream::PacketStream, tcp_server::TcpWritePacketStream};
+
+/// Sends RTP packets to a remote TCP server. The connection is established in the
+/// background, packets produced while it is not available are dropped. When the
+/// connection is lost, a new one is established the same way.
+pub(super) fn run_tcp_sender_thread(
+    ip: Arc<str>,
+    port: Port,
+    should_close: Arc<AtomicBool>,
+    packet_stream: PacketStream,
+    keyframe_request: Option<KeyframeRequest>,
+) {
+    let mut connection: Option<TcpWritePacketStream> = None;
+    let mut pending_connection: Option<Receiver<TcpStream>> = None;
+
+    for packet in packet_stream {
+        let packet = match packet {
+            Ok(packet) => packet,
+            Err(err) => {
+                error!("Failed to payload a packet: {}", err);
+                continue;
+            }
+        };
+
+        if connection.is_none() {
+            let receiver = pending_connection
+                .get_or_insert_with(|| start_connecting(ip.clone(), port, should_close.clone()));
+            match receiver.try_recv() {
+                Ok(socket) => {
+                    info!("RTP TCP connection established.");
+                    connection = Some(TcpWritePacketStream::new(
+                        socket.into(),
+                        should_close.clone(),
+                    ));
+                    pending_connection = None;
+                    // The receiver can decode the stream only from a keyframe, so don't
+                    // wait for the next one from the regular keyframe interval.
+                    if let Some(keyframe_request) = &keyframe_request {
+                        keyframe_request.request();
+                    }
+                }
+                Err(TryRecvError::Empty) => (),
+                Err(TryRecvError::Disconnected) => pending_connection = None,
+            }
+        }
+        let Some(socket) = &mut connection else {
+            continue;
+        };
+
+        trace!(size_bytes = packet.len(), "Send RTP TCP packet.");
+        if let Err(err) = socket.write_packet(packet) {
+            if err.kind() == io::ErrorKind::WouldBlock {
+                // this means that should_close is true
+                return;
+            }
+            warn!(%err, "RTP TCP connection lost. Reconnecting.");
+            connection = None;
+        }
+    }
+
+    if let Some(mut socket) = connection {
+        if let Err(err) = socket.flush() {
+            warn!(%err, "Failed to flush rest of the TCP buffer.");
+        }
+    }
+}
+
+fn start_connecting(
+    ip: Arc<str>,
+    port: Port,
+    should_close: Arc<AtomicBool>,
+) -> Receiver<TcpStream> {
+    let (sender, receiver) = crossbeam_channel::bounded(1);
+    let span = tracing::Span::current();
+    thread::Builder::new()
+        .name("RTP TCP connector".to_string())
+        .spawn(move || {
+            let _span = span.entered();
+            run_connector_thread(&ip, port, &should_close, sender);
+        })
+        .unwrap();
+    receiver
+}
+
+/// Repeats connection attempts until one succeeds or the output is unregistered.
+fn run_connector_thread(
+    ip: &str,
+    port: Port,
+    should_close: &AtomicBool,
+    sender: Sender<TcpStream>,
+) {
+    while !should_close.load(std::sync::atomic::Ordering::Relaxed) {
+        let start = Instant::now();
+        match connect_tcp(ip, port.0) {
+            Ok(socket) => {
+                if sender.send(socket).is_err() {
+                    debug!("RTP sender was closed before the connection was established.");
+                }
+                return;
+            }
+            Err(err) => {
+                trace!("RTP TCP connection attempt failed: {err}.");
+                thread::sleep(TCP_CONNECT_TIMEOUT.saturating_sub(start.elapsed()));
+            }
+        }
+    }
+}
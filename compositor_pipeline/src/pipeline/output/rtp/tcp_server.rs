@@ -63,7 +63,7 @@ pub(super) fn run_tcp_sender_thread(
                 continue;
             }
             None => {
-                if let Err(err) = socket.socket.flush() {
+                if let Err(err) = socket.flush() {
                     warn!(%err, "Failed to flush rest of the TCP buffer.");
                 }
                 return;
@@ -97,13 +97,13 @@ impl From<BindToPortError> for OutputInitError {
     }
 }
 
-struct TcpWritePacketStream {
+pub(super) struct TcpWritePacketStream {
     socket: socket2::Socket,
     should_close: Arc<AtomicBool>,
 }
 
 impl TcpWritePacketStream {
-    fn new(socket: socket2::Socket, should_close: Arc<AtomicBool>) -> Self {
+    pub(super) fn new(socket: socket2::Socket, should_close: Arc<AtomicBool>) -> Self {
         // Timeout to make sure we are not left with unregistered
         // connections that are still maintained by a client side.
         socket
@@ -118,12 +118,16 @@ impl TcpWritePacketStream {
         }
     }
 
-    fn write_packet(&mut self, data: bytes::Bytes) -> io::Result<()> {
+    pub(super) fn write_packet(&mut self, data: bytes::Bytes) -> io::Result<()> {
         self.write_bytes(&u16::to_be_bytes(data.len() as u16))?;
         self.write_bytes(&data[..])?;
         io::Result::Ok(())
     }
 
+    pub(super) fn flush(&mut self) -> io::Result<()> {
+        self.socket.flush()
+    }
+
     fn write_bytes(&mut self, data: &[u8]) -> io::Result<()> {
         let mut written_bytes = 0;
         loop {
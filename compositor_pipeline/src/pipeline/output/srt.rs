@@ -0,0 +1,255 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use compositor_render::OutputId;
+use crossbeam_channel::{Receiver, Sender, TryRecvError};
+use ffmpeg_next as ffmpeg;
+use tracing::{debug, error, info, span, trace, warn, Level};
+
+use crate::{
+    error::OutputInitError,
+    pipeline::{
+        encoder::EncoderContext,
+        srt::{SrtConnectionOptions, SRT_CONNECT_TIMEOUT},
+        structs::{EncodedChunk, EncodedChunkKind, EncoderOutputEvent, IsKeyframe},
+        VideoCodec,
+    },
+};
+
+use super::muxer::{init_ffmpeg_output, write_chunk, MuxerOptions, StreamState};
+
+#[derive(Debug, Clone)]
+pub struct SrtSenderOptions {
+    pub connection: SrtConnectionOptions,
+}
+
+#[derive(Debug)]
+pub struct SrtSender {
+    pub connection: SrtConnectionOptions,
+    should_close: Arc<AtomicBool>,
+}
+
+impl SrtSender {
+    pub fn new(
+        output_id: &OutputId,
+        options: SrtSenderOptions,
+        encoder_ctx: EncoderContext,
+        packets_receiver: Receiver<EncoderOutputEvent>,
+    ) -> Result<Self, OutputInitError> {
+        if let Some(video) = &encoder_ctx.video {
+            if video.codec != VideoCodec::H264 {
+                return Err(OutputInitError::UnsupportedVideoCodec(video.codec));
+            }
+        }
+
+        let muxer_options = MuxerOptions {
+            format: "mpegts",
+            path: options.connection.url(),
+            protocol_options: options.connection.protocol_options(),
+            format_options: Vec::new(),
+        };
+
+        let should_close = Arc::new(AtomicBool::new(false));
+        let stream = SrtStream::new(muxer_options, encoder_ctx, should_close.clone());
+
+        let output_id = output_id.clone();
+        std::thread::Builder::new()
+            .name(format!("SRT sender for output {}", output_id))
+            .spawn(move || {
+                let _span =
+                    span!(Level::INFO, "SRT sender", output_id = output_id.to_string()).entered();
+                stream.run(packets_receiver);
+                debug!("Closing SRT sender thread.");
+            })
+            .unwrap();
+
+        Ok(Self {
+            connection: options.connection,
+            should_close,
+        })
+    }
+}
+
+impl Drop for SrtSender {
+    fn drop(&mut self) {
+        self.should_close.store(true, Ordering::Relaxed);
+    }
+}
+
+struct SrtConnection {
+    output_ctx: ffmpeg::format::context::Output,
+    video_stream: Option<StreamState>,
+    audio_stream: Option<StreamState>,
+}
+
+/// State of the SRT sender thread.
+///
+/// Waiting for a peer (especially in listener mode) can take arbitrarily long, so
+/// connections are established on a separate thread. Until a connection is ready,
+/// chunks are dropped, so the encoder is never blocked. After (re)connecting, the
+/// stream starts from the next video keyframe.
+struct SrtStream {
+    muxer_options: Arc<MuxerOptions>,
+    encoder_ctx: EncoderContext,
+    should_close: Arc<AtomicBool>,
+
+    connection: Option<SrtConnection>,
+    pending_connection: Option<Receiver<SrtConnection>>,
+    waiting_for_keyframe: bool,
+
+    /// Timestamps are offset by the first chunk for the whole lifetime of the
+    /// output, so they stay continuous across reconnects.
+    timestamp_offset: Option<Duration>,
+
+    video_eos_received: bool,
+    audio_eos_received: bool,
+}
+
+impl SrtStream {
+    fn new(
+        muxer_options: MuxerOptions,
+        encoder_ctx: EncoderContext,
+        should_close: Arc<AtomicBool>,
+    ) -> Self {
+        Self {
+            video_eos_received: encoder_ctx.video.is_none(),
+            audio_eos_received: encoder_ctx.audio.is_none(),
+            muxer_options: Arc::new(muxer_options),
+            encoder_ctx,
+            should_close,
+            connection: None,
+            pending_connection: None,
+            waiting_for_keyframe: true,
+            timestamp_offset: None,
+        }
+    }
+
+    fn run(mut self, packets_receiver: Receiver<EncoderOutputEvent>) {
+        for event in packets_receiver {
+            match event {
+                EncoderOutputEvent::Data(chunk) => self.handle_chunk(chunk),
+                EncoderOutputEvent::VideoEOS => self.video_eos_received = true,
+                EncoderOutputEvent::AudioEOS => self.audio_eos_received = true,
+            }
+
+            if self.video_eos_received && self.audio_eos_received {
+                break;
+            }
+        }
+
+        // Reached either when EOS was received on all tracks or when the channel was
+        // closed because the output was unregistered.
+        self.should_close.store(true, Ordering::Relaxed);
+        if let Some(mut connection) = self.connection.take() {
+            if let Err(err) = connection.output_ctx.write_trailer() {
+                error!("Failed to finalize the SRT stream: {err}");
+            }
+        }
+    }
+
+    fn handle_chunk(&mut self, chunk: EncodedChunk) {
+        let offset = *self
+            .timestamp_offset
+            .get_or_insert(chunk.dts.unwrap_or(chunk.pts));
+
+        if self.connection.is_none() {
+            self.poll_connection();
+        }
+        let Some(connection) = &mut self.connection else {
+            return;
+        };
+
+        if self.waiting_for_keyframe {
+            let is_video_keyframe = matches!(chunk.kind, EncodedChunkKind::Video(_))
+                && matches!(chunk.is_keyframe, IsKeyframe::Yes);
+            if self.encoder_ctx.video.is_some() && !is_video_keyframe {
+                return;
+            }
+            self.waiting_for_keyframe = false;
+        }
+
+        let stream = match chunk.kind {
+            EncodedChunkKind::Video(_) => connection.video_stream.as_ref(),
+            EncodedChunkKind::Audio(_) => connection.audio_stream.as_ref(),
+        };
+        let Some(stream) = stream else {
+            error!(kind=?chunk.kind, "Received chunk for a track that is not present in the output.");
+            return;
+        };
+
+        if let Err(err) = write_chunk(&mut connection.output_ctx, stream, chunk, offset) {
+            warn!("SRT connection lost: {err}.");
+            self.connection = None;
+        }
+    }
+
+    fn poll_connection(&mut self) {
+        if self.pending_connection.is_none() {
+            self.pending_connection = Some(self.start_connecting());
+        }
+        let Some(receiver) = &self.pending_connection else {
+            return;
+        };
+        match receiver.try_recv() {
+            Ok(connection) => {
+                info!("SRT connection established.");
+                self.connection = Some(connection);
+                self.pending_connection = None;
+                self.waiting_for_keyframe = true;
+            }
+            Err(TryRecvError::Empty) => (),
+            Err(TryRecvError::Disconnected) => self.pending_connection = None,
+        }
+    }
+
+    fn start_connecting(&self) -> Receiver<SrtConnection> {
+        let (sender, receiver) = crossbeam_channel::bounded(1);
+        let muxer_options = self.muxer_options.clone();
+        let encoder_ctx = self.encoder_ctx.clone();
+        let should_close = self.should_close.clone();
+        let span = tracing::Span::current();
+        std::thread::Builder::new()
+            .name("SRT connector".to_string())
+            .spawn(move || {
+                let _span = span.entered();
+                run_connector_thread(&muxer_options, &encoder_ctx, &should_close, sender);
+            })
+            .unwrap();
+        receiver
+    }
+}
+
+/// Repeats connection attempts until one succeeds or the output is unregistered.
+fn run_connector_thread(
+    muxer_options: &MuxerOptions,
+    encoder_ctx: &EncoderContext,
+    should_close: &AtomicBool,
+    sender: Sender<SrtConnection>,
+) {
+    while !should_close.load(Ordering::Relaxed) {
+        let start = Instant::now();
+        match init_ffmpeg_output(muxer_options, encoder_ctx, None) {
+            Ok((output_ctx, video_stream, audio_stream)) => {
+                let connection = SrtConnection {
+                    output_ctx,
+                    video_stream,
+                    audio_stream,
+                };
+                if sender.send(connection).is_err() {
+                    debug!("SRT sender was closed before the connection was established.");
+                }
+                return;
+            }
+            Err(err) => {
+                trace!("SRT connection attempt failed: {err}.");
+                // Caller fails immediately if the listener rejects the connection.
+                std::thread::sleep(SRT_CONNECT_TIMEOUT.saturating_sub(start.elapsed()));
+            }
+        }
+    }
+}
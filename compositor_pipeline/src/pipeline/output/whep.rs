@@ -0,0 +1,377 @@
+use std::{any::Any, collections::HashMap, fmt::Debug, sync::Arc};
+
+use compositor_render::OutputId;
+use crossbeam_channel::Receiver;
+use rand::Rng;
+use tracing::{debug, error, info, span, warn, Level};
+use webrtc::{
+    api::media_engine::MediaEngine,
+    peer_connection::{peer_connection_state::RTCPeerConnectionState, RTCPeerConnection},
+    rtcp::payload_feedbacks::{
+        full_intra_request::FullIntraRequest, picture_loss_indication::PictureLossIndication,
+    },
+    rtp_transceiver::{rtp_codec::RTPCodecType, rtp_sender::RTCRtpSender},
+    track::track_local::{track_local_static_rtp::TrackLocalStaticRTP, TrackLocalWriter},
+};
+use webrtc_util::Unmarshal;
+
+use crate::pipeline::{
+    encoder::{EncoderContext, KeyframeRequest},
+    rtp::{AUDIO_PAYLOAD_TYPE, VIDEO_PAYLOAD_TYPE},
+    structs::EncoderOutputEvent,
+    webrtc_utils, AudioCodec, VideoCodec,
+};
+
+use super::rtp::{packet_stream::PacketStream, payloader::Payloader};
+
+/// Payloads have to fit into a single UDP datagram after adding SRTP
+/// and TURN overhead.
+const WEBRTC_MTU: usize = 1200;
+
+#[derive(Debug, thiserror::Error)]
+pub enum WhepSenderError {
+    #[error("WHEP output supports only H264 video.")]
+    UnsupportedVideoCodec,
+
+    #[error("WHEP output supports only Opus audio.")]
+    UnsupportedAudioCodec,
+
+    #[error("WHEP output has to be registered from within a Tokio runtime.")]
+    NoAsyncRuntime,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum WhepError {
+    #[error("Output \"{0}\" is not registered.")]
+    OutputNotFound(OutputId),
+
+    #[error("Output \"{0}\" is not a WHEP output.")]
+    NotWhepOutput(OutputId),
+
+    #[error("WHEP session \"{session_id}\" of output \"{output_id}\" does not exist.")]
+    SessionNotFound {
+        output_id: OutputId,
+        session_id: Arc<str>,
+    },
+
+    #[error("Failed to create an SDP answer.")]
+    NoLocalDescription,
+
+    #[error("Failed to negotiate the WebRTC session.")]
+    WebRtc(#[from] webrtc::Error),
+}
+
+#[derive(Debug, Clone)]
+pub struct WhepSenderOptions {
+    pub video: Option<VideoCodec>,
+    pub audio: Option<AudioCodec>,
+}
+
+/// Output that serves the encoded stream to viewers over
+/// [WHEP](https://datatracker.ietf.org/doc/html/draft-murillo-whep).
+///
+/// Packets are produced once, by a single payloader, and written to local tracks
+/// shared by all sessions, so viewers do not add any encoding work. A keyframe is
+/// requested from the encoder when a viewer connects or reports a picture loss.
+pub struct WhepSender {
+    sessions: WhepSessions,
+}
+
+impl WhepSender {
+    pub fn new(
+        output_id: &OutputId,
+        options: WhepSenderOptions,
+        encoder_ctx: EncoderContext,
+        packets_receiver: Receiver<EncoderOutputEvent>,
+    ) -> Result<Self, WhepSenderError> {
+        if matches!(options.video, Some(codec) if codec != VideoCodec::H264) {
+            return Err(WhepSenderError::UnsupportedVideoCodec);
+        }
+        if matches!(options.audio, Some(codec) if codec != AudioCodec::Opus) {
+            return Err(WhepSenderError::UnsupportedAudioCodec);
+        }
+
+        // Sessions are negotiated on the API runtime, tracks bound to them
+        // have to be written on the same one.
+        let runtime =
+            tokio::runtime::Handle::try_current().map_err(|_| WhepSenderError::NoAsyncRuntime)?;
+
+        let stream_id = format!("live-compositor-{output_id}");
+        let video_track = options.video.map(|codec| {
+            Arc::new(TrackLocalStaticRTP::new(
+                webrtc_utils::video_codec_parameters(codec).capability,
+                "video".to_owned(),
+                stream_id.clone(),
+            ))
+        });
+        let audio_track = options.audio.map(|_| {
+            Arc::new(TrackLocalStaticRTP::new(
+                webrtc_utils::opus_codec_parameters().capability,
+                "audio".to_owned(),
+                stream_id.clone(),
+            ))
+        });
+
+        let keyframe_request = encoder_ctx
+            .video
+            .as_ref()
+            .map(|video| video.keyframe_request.clone());
+        let audio_sample_rate = encoder_ctx
+            .audio
+            .map(|audio| audio.sample_rate)
+            .unwrap_or_default();
+        let payloader = Payloader::new(options.video, options.audio, audio_sample_rate);
+        let packet_stream = PacketStream::new(packets_receiver, payloader, WEBRTC_MTU);
+
+        let sessions = WhepSessions {
+            output_id: output_id.clone(),
+            video_track: video_track.clone(),
+            audio_track: audio_track.clone(),
+            keyframe_request,
+            peer_connections: Arc::new(tokio::sync::Mutex::new(HashMap::new())),
+            runtime: runtime.clone(),
+        };
+
+        let output_id = output_id.clone();
+        std::thread::Builder::new()
+            .name(format!("WHEP sender for output {}", output_id))
+            .spawn(move || {
+                let _span = span!(
+                    Level::INFO,
+                    "WHEP sender",
+                    output_id = output_id.to_string()
+                )
+                .entered();
+                run_whep_sender_thread(packet_stream, video_track, audio_track, runtime);
+                debug!("Closing WHEP sender thread.")
+            })
+            .unwrap();
+
+        Ok(Self { sessions })
+    }
+
+    pub fn sessions(&self) -> WhepSessions {
+        self.sessions.clone()
+    }
+}
+
+impl Debug for WhepSender {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WhepSender")
+            .field("output_id", &self.sessions.output_id)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for WhepSender {
+    fn drop(&mut self) {
+        let peer_connections = self.sessions.peer_connections.clone();
+        self.sessions.runtime.spawn(async move {
+            let peer_connections: Vec<_> = peer_connections.lock().await.drain().collect();
+            for (session_id, peer_connection) in peer_connections {
+                if let Err(err) = peer_connection.close().await {
+                    warn!(%err, session_id = %session_id, "Failed to close WHEP peer connection.");
+                }
+            }
+        });
+    }
+}
+
+/// Handle used by the WHEP endpoint to create and terminate viewer sessions
+/// of a registered output.
+#[derive(Clone)]
+pub struct WhepSessions {
+    output_id: OutputId,
+    video_track: Option<Arc<TrackLocalStaticRTP>>,
+    audio_track: Option<Arc<TrackLocalStaticRTP>>,
+    keyframe_request: Option<KeyframeRequest>,
+    peer_connections: Arc<tokio::sync::Mutex<HashMap<Arc<str>, Arc<RTCPeerConnection>>>>,
+    runtime: tokio::runtime::Handle,
+}
+
+impl WhepSessions {
+    /// Negotiates a new viewer session. Returns a session ID and an SDP answer.
+    pub async fn handle_offer(&self, offer: String) -> Result<(Arc<str>, String), WhepError> {
+        let session_id: Arc<str> = format!("{:016x}", rand::thread_rng().gen::<u64>()).into();
+        let peer_connection = Arc::new(self.new_peer_connection().await?);
+
+        if let Some(track) = &self.video_track {
+            let sender = peer_connection.add_track(track.clone()).await?;
+            tokio::spawn(read_rtcp(sender, self.keyframe_request.clone()));
+        }
+        if let Some(track) = &self.audio_track {
+            let sender = peer_connection.add_track(track.clone()).await?;
+            tokio::spawn(read_rtcp(sender, None));
+        }
+
+        let output_id = self.output_id.clone();
+        let peer_connections = self.peer_connections.clone();
+        let keyframe_request = self.keyframe_request.clone();
+        let state_session_id = session_id.clone();
+        peer_connection.on_peer_connection_state_change(Box::new(move |state| {
+            let output_id = output_id.clone();
+            let peer_connections = peer_connections.clone();
+            let keyframe_request = keyframe_request.clone();
+            let session_id = state_session_id.clone();
+            Box::pin(async move {
+                match state {
+                    RTCPeerConnectionState::Connected => {
+                        info!(
+                            output_id = output_id.to_string(),
+                            session_id = %session_id,
+                            "WHEP viewer connected."
+                        );
+                        // New viewer can't decode anything until the next keyframe.
+                        if let Some(keyframe_request) = keyframe_request {
+                            keyframe_request.request();
+                        }
+                    }
+                    RTCPeerConnectionState::Failed | RTCPeerConnectionState::Closed => {
+                        info!(
+                            output_id = output_id.to_string(),
+                            session_id = %session_id,
+                            ?state,
+                            "WHEP viewer disconnected."
+                        );
+                        peer_connections.lock().await.remove(&session_id);
+                    }
+                    _ => debug!(
+                        output_id = output_id.to_string(),
+                        session_id = %session_id,
+                        ?state,
+                        "WHEP connection state changed."
+                    ),
+                }
+            })
+        }));
+
+        let result = webrtc_utils::answer_offer(&peer_connection, offer)
+            .await
+            .map_err(WhepError::from)
+            .and_then(|answer| answer.ok_or(WhepError::NoLocalDescription));
+
+        match result {
+            Ok(answer) => {
+                self.peer_connections
+                    .lock()
+                    .await
+                    .insert(session_id.clone(), peer_connection);
+                Ok((session_id, answer.sdp))
+            }
+            Err(err) => {
+                if let Err(err) = peer_connection.close().await {
+                    debug!(%err, "Failed to close peer connection.");
+                }
+                Err(err)
+            }
+        }
+    }
+
+    /// Terminates a viewer session. Other viewers are not affected.
+    pub async fn terminate(&self, session_id: &str) -> Result<(), WhepError> {
+        let Some(peer_connection) = self.peer_connections.lock().await.remove(session_id) else {
+            return Err(WhepError::SessionNotFound {
+                output_id: self.output_id.clone(),
+                session_id: session_id.into(),
+            });
+        };
+        peer_connection.close().await?;
+        Ok(())
+    }
+
+    async fn new_peer_connection(&self) -> Result<RTCPeerConnection, webrtc::Error> {
+        let mut media_engine = MediaEngine::default();
+        if self.video_track.is_some() {
+            media_engine.register_codec(
+                webrtc_utils::video_codec_parameters(VideoCodec::H264),
+                RTPCodecType::Video,
+            )?;
+        }
+        if self.audio_track.is_some() {
+            media_engine
+                .register_codec(webrtc_utils::opus_codec_parameters(), RTPCodecType::Audio)?;
+        }
+        webrtc_utils::new_peer_connection(media_engine).await
+    }
+}
+
+/// RTCP packets have to be read for interceptors (e.g. NACK responder) to process them.
+/// Picture loss reported by a viewer is answered with a keyframe.
+async fn read_rtcp(sender: Arc<RTCRtpSender>, keyframe_request: Option<KeyframeRequest>) {
+    let mut buffer = vec![0; 1500];
+    while let Ok((packets, _)) = sender.read(&mut buffer).await {
+        let Some(keyframe_request) = &keyframe_request else {
+            continue;
+        };
+        if packets
+            .iter()
+            .any(|packet| is_keyframe_request(packet.as_any()))
+        {
+            debug!("WHEP viewer requested a keyframe.");
+            keyframe_request.request();
+        }
+    }
+}
+
+fn is_keyframe_request(packet: &(dyn Any + Send + Sync)) -> bool {
+    packet.is::<PictureLossIndication>() || packet.is::<FullIntraRequest>()
+}
+
+fn run_whep_sender_thread(
+    packet_stream: PacketStream,
+    video_track: Option<Arc<TrackLocalStaticRTP>>,
+    audio_track: Option<Arc<TrackLocalStaticRTP>>,
+    runtime: tokio::runtime::Handle,
+) {
+    for packet in packet_stream {
+        let packet = match packet {
+            Ok(packet) => packet,
+            Err(err) => {
+                error!("Failed to payload a packet: {}", err);
+                continue;
+            }
+        };
+        let packet = match rtp::packet::Packet::unmarshal(&mut packet.as_ref()) {
+            Ok(packet) => packet,
+            Err(err) => {
+                debug!(%err, "Failed to parse RTP packet.");
+                continue;
+            }
+        };
+
+        // Tracks rewrite SSRC and payload type to the values negotiated with each viewer.
+        // RTCP packets (sender reports and goodbye packets sent on EOS) do not match any
+        // payload type and are dropped. WebRTC stack sends its own sender reports and
+        // peer connections are closed when the output is unregistered.
+        let track = match packet.header.payload_type {
+            VIDEO_PAYLOAD_TYPE => &video_track,
+            AUDIO_PAYLOAD_TYPE => &audio_track,
+            _ => continue,
+        };
+        let Some(track) = track else {
+            continue;
+        };
+        if let Err(err) = runtime.block_on(track.write_rtp(&packet)) {
+            debug!(%err, "Failed to write RTP packet to WHEP track.");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use webrtc::rtcp::{
+        payload_feedbacks::{
+            full_intra_request::FullIntraRequest, picture_loss_indication::PictureLossIndication,
+        },
+        receiver_report::ReceiverReport,
+    };
+
+    use super::is_keyframe_request;
+
+    #[test]
+    fn keyframe_requests() {
+        assert!(is_keyframe_request(&PictureLossIndication::default()));
+        assert!(is_keyframe_request(&FullIntraRequest::default()));
+        assert!(!is_keyframe_request(&ReceiverReport::default()));
+    }
+}
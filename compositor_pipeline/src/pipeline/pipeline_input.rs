@@ -26,29 +26,39 @@ pub(super) fn register_pipeline_input(
         input_options,
         queue_options,
     } = register_options;
-    let (download_dir, output_sample_rate) = {
+    let (download_dir, shared_udp_sockets, output_sample_rate) = {
         let guard = pipeline.lock().unwrap();
         if guard.inputs.contains_key(&input_id) {
             return Err(RegisterInputError::AlreadyRegistered(input_id));
         }
-        (guard.download_dir.clone(), guard.output_sample_rate)
+        (
+            guard.download_dir.clone(),
+            guard.shared_udp_sockets.clone(),
+            guard.output_sample_rate,
+        )
     };
 
-    let (input, chunks_receiver, decoder_options, port) =
-        input::Input::new(&input_id, input_options, &download_dir)
+    let (input, input_receiver, port) =
+        input::Input::new(&input_id, input_options, &download_dir, &shared_udp_sockets)
             .map_err(|e| RegisterInputError::InputError(input_id.clone(), e))?;
 
+    let decoded_data_receiver = match input_receiver {
+        input::InputReceiver::Encoded {
+            chunks_receiver,
+            decoder_options,
+        } => decoder::start_decoder(
+            input_id.clone(),
+            chunks_receiver,
+            decoder_options,
+            output_sample_rate,
+        )
+        .map_err(|e| RegisterInputError::DecoderError(input_id.clone(), e))?,
+        input::InputReceiver::Decoded(receiver) => receiver,
+    };
     let (audio_eos_received, video_eos_received) = (
-        decoder_options.audio.as_ref().map(|_| false),
-        decoder_options.video.as_ref().map(|_| false),
+        decoded_data_receiver.audio.as_ref().map(|_| false),
+        decoded_data_receiver.video.as_ref().map(|_| false),
     );
-    let decoded_data_receiver = decoder::start_decoder(
-        input_id.clone(),
-        chunks_receiver,
-        decoder_options,
-        output_sample_rate,
-    )
-    .map_err(|e| RegisterInputError::DecoderError(input_id.clone(), e))?;
 
     let pipeline_input = PipelineInput {
         input,
@@ -10,9 +10,9 @@ use tracing::{info, warn};
 use crate::{audio_mixer::OutputSamples, error::RegisterOutputError, queue::PipelineEvent};
 
 use super::{
-    encoder::{self, opus, AudioEncoderOptions, Encoder, EncoderOptions},
+    encoder::{self, Encoder, EncoderOptions},
     output::{self, Output},
-    Pipeline, PipelineInput, Port, RegisterOutputOptions,
+    OutputAudioOptions, OutputVideoRendition, Pipeline, PipelineInput, Port, RegisterOutputOptions,
 };
 
 #[derive(Debug, Clone)]
@@ -27,10 +27,23 @@ pub enum PipelineOutputEndCondition {
 pub struct PipelineOutput {
     pub encoder: encoder::Encoder,
     pub output: output::Output,
+    pub renditions: Vec<PipelineOutputRendition>,
     pub video_end_condition: Option<PipelineOutputEndConditionState>,
     pub audio_end_condition: Option<PipelineOutputEndConditionState>,
 }
 
+/// Output that encodes frames rendered for its parent output, downscaled by the
+/// renderer. It can also encode the audio mixed for the parent output.
+pub struct PipelineOutputRendition {
+    /// ID under which the rendition is registered in the renderer.
+    pub output_id: OutputId,
+    pub encoder: encoder::Encoder,
+    pub output: output::Output,
+}
+
+/// Senders of an output encoder and of its renditions that encode audio.
+type SamplesSenders = Vec<Sender<PipelineEvent<OutputSamples>>>;
+
 pub(super) enum OutputSender<T> {
     ActiveSender(T),
     FinishedSender,
@@ -52,7 +65,29 @@ impl Pipeline {
             return Err(RegisterOutputError::NoVideoAndAudio(output_id));
         }
 
-        if self.outputs.contains_key(&output_id) {
+        let renditions = video
+            .as_ref()
+            .map(|video| video.renditions.clone())
+            .unwrap_or_default();
+        if let Some(video) = &video {
+            // Renditions are downscaled from frames rendered for the output.
+            let resolution = video.encoder_opts.resolution();
+            let is_too_large = |rendition: &OutputVideoRendition| {
+                let rendition_resolution = rendition.encoder_opts.resolution();
+                rendition_resolution.width > resolution.width
+                    || rendition_resolution.height > resolution.height
+            };
+            if renditions.iter().any(is_too_large) {
+                return Err(RegisterOutputError::RenditionResolutionTooLarge(output_id));
+            }
+        }
+        let rendition_ids: Vec<_> = (0..renditions.len())
+            .map(|index| rendition_output_id(&output_id, index))
+            .collect();
+        if std::iter::once(&output_id)
+            .chain(&rendition_ids)
+            .any(|id| self.is_output_id_used(id))
+        {
             return Err(RegisterOutputError::AlreadyRegistered(output_id));
         }
 
@@ -60,23 +95,29 @@ impl Pipeline {
             video: video
                 .as_ref()
                 .map(|video_opts| video_opts.encoder_opts.clone()),
-            audio: audio.as_ref().map(|audio_opts| {
-                AudioEncoderOptions::Opus(opus::Options {
-                    channels: audio_opts.channels,
-                    preset: audio_opts.encoder_preset,
-                })
-            }),
+            audio: audio
+                .as_ref()
+                .map(|audio_opts| audio_opts.encoder_opts.clone()),
         };
 
         let (encoder, packets) = Encoder::new(&output_id, encoder_opts, self.output_sample_rate)
             .map_err(|e| RegisterOutputError::EncoderError(output_id.clone(), e))?;
 
-        let (output, port) = Output::new(&output_id, output_options, packets)
+        let (output, port) = Output::new(&output_id, output_options, encoder.context(), packets)
             .map_err(|e| RegisterOutputError::OutputError(output_id.clone(), e))?;
 
+        let renditions = renditions
+            .into_iter()
+            .zip(rendition_ids)
+            .map(|(rendition, rendition_id)| {
+                self.create_rendition(&output_id, rendition_id, rendition, audio.as_ref())
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
         let output = PipelineOutput {
             encoder,
             output,
+            renditions,
             audio_end_condition: audio.as_ref().map(|audio| {
                 PipelineOutputEndConditionState::new_audio(
                     audio.end_condition.clone(),
@@ -102,6 +143,16 @@ impl Pipeline {
                 self.renderer.unregister_output(&output_id);
                 return Err(RegisterOutputError::SceneError(output_id.clone(), err));
             }
+
+            for rendition in &output.renditions {
+                if let Some(encoder) = &rendition.encoder.video {
+                    self.renderer.register_output_rendition(
+                        output_id.clone(),
+                        rendition.output_id.clone(),
+                        encoder.resolution(),
+                    );
+                }
+            }
         };
 
         if let Some(audio_opts) = audio.clone() {
@@ -109,7 +160,7 @@ impl Pipeline {
                 output_id.clone(),
                 audio_opts.initial,
                 audio_opts.mixing_strategy,
-                audio_opts.channels,
+                audio_opts.encoder_opts.channels(),
             );
         }
 
@@ -118,10 +169,53 @@ impl Pipeline {
         Ok(port)
     }
 
+    fn create_rendition(
+        &self,
+        output_id: &OutputId,
+        rendition_id: OutputId,
+        rendition: OutputVideoRendition,
+        audio: Option<&OutputAudioOptions>,
+    ) -> Result<PipelineOutputRendition, RegisterOutputError> {
+        let OutputVideoRendition {
+            encoder_opts,
+            output_options,
+            with_audio,
+        } = rendition;
+
+        let encoder_opts = EncoderOptions {
+            video: Some(encoder_opts),
+            audio: audio
+                .filter(|_| with_audio)
+                .map(|audio_opts| audio_opts.encoder_opts.clone()),
+        };
+        let (encoder, packets) = Encoder::new(&rendition_id, encoder_opts, self.output_sample_rate)
+            .map_err(|e| RegisterOutputError::EncoderError(output_id.clone(), e))?;
+        let (output, _) = Output::new(&rendition_id, output_options, encoder.context(), packets)
+            .map_err(|e| RegisterOutputError::OutputError(output_id.clone(), e))?;
+
+        Ok(PipelineOutputRendition {
+            output_id: rendition_id,
+            encoder,
+            output,
+        })
+    }
+
+    /// Checks IDs of outputs and their renditions, they share a namespace in the renderer.
+    fn is_output_id_used(&self, id: &OutputId) -> bool {
+        self.outputs.iter().any(|(output_id, output)| {
+            output_id == id
+                || output
+                    .renditions
+                    .iter()
+                    .any(|rendition| rendition.output_id == *id)
+        })
+    }
+
     pub(super) fn all_output_video_senders_iter(
         pipeline: &Arc<Mutex<Pipeline>>,
     ) -> impl Iterator<Item = (OutputId, OutputSender<Sender<PipelineEvent<Frame>>>)> {
-        let outputs: HashMap<_, _> = pipeline
+        // Renditions receive frames (and EOS) together with their parent output.
+        let outputs: Vec<_> = pipeline
             .lock()
             .unwrap()
             .outputs
@@ -129,8 +223,17 @@ impl Pipeline {
             .filter_map(|(output_id, output)| {
                 let eos_status = output.video_end_condition.as_mut()?.eos_status();
                 let sender = output.encoder.frame_sender()?.clone();
-                Some((output_id.clone(), (sender, eos_status)))
+                let renditions = output.renditions.iter().filter_map(|rendition| {
+                    let sender = rendition.encoder.frame_sender()?.clone();
+                    Some((rendition.output_id.clone(), sender))
+                });
+                let senders: Vec<_> = std::iter::once((output_id.clone(), sender))
+                    .chain(renditions)
+                    .map(|(output_id, sender)| (output_id, (sender, eos_status)))
+                    .collect();
+                Some(senders)
             })
+            .flatten()
             .collect();
 
         outputs
@@ -153,7 +256,8 @@ impl Pipeline {
 
     pub(super) fn all_output_audio_senders_iter(
         pipeline: &Arc<Mutex<Pipeline>>,
-    ) -> impl Iterator<Item = (OutputId, OutputSender<Sender<PipelineEvent<OutputSamples>>>)> {
+    ) -> impl Iterator<Item = (OutputId, OutputSender<SamplesSenders>)> {
+        // Samples mixed for an output are also sent to its renditions that encode audio.
         let outputs: HashMap<_, _> = pipeline
             .lock()
             .unwrap()
@@ -162,19 +266,27 @@ impl Pipeline {
             .filter_map(|(output_id, output)| {
                 let eos_status = output.audio_end_condition.as_mut()?.eos_status();
                 let sender = output.encoder.samples_batch_sender()?.clone();
-                Some((output_id.clone(), (sender, eos_status)))
+                let renditions = output
+                    .renditions
+                    .iter()
+                    .filter(|rendition| rendition.encoder.audio.is_some())
+                    .filter_map(|rendition| rendition.encoder.samples_batch_sender().cloned());
+                let senders: Vec<_> = std::iter::once(sender).chain(renditions).collect();
+                Some((output_id.clone(), (senders, eos_status)))
             })
             .collect();
 
         outputs
             .into_iter()
-            .filter_map(|(output_id, (sender, eos_status))| match eos_status {
-                EosStatus::None => Some((output_id, OutputSender::ActiveSender(sender))),
+            .filter_map(|(output_id, (senders, eos_status))| match eos_status {
+                EosStatus::None => Some((output_id, OutputSender::ActiveSender(senders))),
                 EosStatus::SendEos => {
                     info!(?output_id, "Sending audio EOS on output.");
-                    if sender.send(PipelineEvent::EOS).is_err() {
-                        warn!(?output_id, "Failed to send EOS from mixer. Channel closed.");
-                    };
+                    for sender in senders {
+                        if sender.send(PipelineEvent::EOS).is_err() {
+                            warn!(?output_id, "Failed to send EOS from mixer. Channel closed.");
+                        };
+                    }
                     Some((output_id, OutputSender::FinishedSender))
                 }
                 EosStatus::AlreadySent => None,
@@ -182,6 +294,12 @@ impl Pipeline {
     }
 }
 
+/// ID of a rendition in the renderer. Output IDs are checked against it, so it does
+/// not collide with any registered output.
+fn rendition_output_id(output_id: &OutputId, index: usize) -> OutputId {
+    OutputId(format!("{output_id}::rendition_{index}").into())
+}
+
 #[derive(Debug, Clone)]
 pub struct PipelineOutputEndConditionState {
     condition: PipelineOutputEndCondition,
@@ -196,6 +314,7 @@ enum StateChange<'a> {
     NoChanges,
 }
 
+#[derive(Clone, Copy)]
 enum EosStatus {
     None,
     SendEos,
@@ -1,14 +1,32 @@
-use std::net;
+use std::{
+    io,
+    net::{self, TcpStream, ToSocketAddrs},
+    sync::Arc,
+    time::Duration,
+};
 
 use super::Port;
 
-pub(crate) const VIDEO_PAYLOAD_TYPE: u8 = 96;
-pub(crate) const AUDIO_PAYLOAD_TYPE: u8 = 97;
+pub(crate) mod av1;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Default payload type of video tracks in RTP inputs and outputs.
+pub const VIDEO_PAYLOAD_TYPE: u8 = 96;
+/// Default payload type of audio tracks in RTP inputs and outputs.
+pub const AUDIO_PAYLOAD_TYPE: u8 = 97;
+
+/// Timeout of a single TCP connection attempt in the client mode. Failed attempts
+/// are retried at most that often.
+pub(super) const TCP_CONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum TransportProtocol {
     Udp,
     TcpServer,
+    /// Connect to a remote TCP server with the address `ip`. The connection is
+    /// re-established if it fails.
+    TcpClient {
+        ip: Arc<str>,
+    },
 }
 
 pub(super) enum BindToPortError {
@@ -63,3 +81,20 @@ pub(super) fn bind_to_requested_port(
     };
     Ok(Port(port))
 }
+
+/// Connects to a TCP server, trying all addresses `ip` resolves to.
+pub(super) fn connect_tcp(ip: &str, port: u16) -> io::Result<TcpStream> {
+    let mut last_err = None;
+    for address in (ip, port).to_socket_addrs()? {
+        match TcpStream::connect_timeout(&address, TCP_CONNECT_TIMEOUT) {
+            Ok(socket) => return Ok(socket),
+            Err(err) => last_err = Some(err),
+        }
+    }
+    Err(last_err.unwrap_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Address \"{ip}\" could not be resolved."),
+        )
+    }))
+}
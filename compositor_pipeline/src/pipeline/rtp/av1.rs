@@ -0,0 +1,169 @@
+//! Helpers for handling AV1 bitstream in the RTP payloader and depayloader.
+//! See [RTP Payload Format for AV1](https://aomediacodec.github.io/av1-rtp-spec/).
+
+use bytes::{BufMut, Bytes, BytesMut};
+
+pub(crate) const OBU_SEQUENCE_HEADER: u8 = 1;
+pub(crate) const OBU_TEMPORAL_DELIMITER: u8 = 2;
+pub(crate) const OBU_TILE_LIST: u8 = 8;
+pub(crate) const OBU_PADDING: u8 = 15;
+
+// Aggregation header flags, see section 4.4 of the AV1 RTP spec.
+
+/// The first OBU element is a continuation of an OBU fragment from the previous packet.
+pub(crate) const AGGREGATION_HEADER_Z: u8 = 0b1000_0000;
+/// The last OBU element will continue in the next packet.
+pub(crate) const AGGREGATION_HEADER_Y: u8 = 0b0100_0000;
+/// The packet is the first packet of a coded video sequence.
+pub(crate) const AGGREGATION_HEADER_N: u8 = 0b0000_1000;
+
+const OBU_EXTENSION_FLAG: u8 = 0b0000_0100;
+const OBU_HAS_SIZE_FIELD: u8 = 0b0000_0010;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ObuParseError {
+    #[error("OBU is truncated")]
+    Truncated,
+
+    #[error("Invalid LEB128 value")]
+    InvalidLeb128,
+}
+
+pub(crate) fn obu_type(obu_header: u8) -> u8 {
+    (obu_header >> 3) & 0b1111
+}
+
+/// Returns the decoded value and the number of bytes it occupied.
+pub(crate) fn read_leb128(data: &[u8]) -> Option<(usize, usize)> {
+    let mut value: u64 = 0;
+    for (i, byte) in data.iter().take(8).enumerate() {
+        value |= ((byte & 0x7f) as u64) << (i * 7);
+        if byte & 0x80 == 0 {
+            return Some((value as usize, i + 1));
+        }
+    }
+    None
+}
+
+pub(crate) fn write_leb128(buffer: &mut BytesMut, mut value: usize) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            buffer.put_u8(byte);
+            return;
+        }
+        buffer.put_u8(byte | 0x80);
+    }
+}
+
+pub(crate) fn leb128_len(mut value: usize) -> usize {
+    let mut len = 1;
+    while value >= 0x80 {
+        value >>= 7;
+        len += 1;
+    }
+    len
+}
+
+/// Splits a temporal unit in the low overhead bitstream format into OBUs.
+/// The size fields are removed, because RTP OBU elements carry their lengths
+/// in the aggregation structure instead.
+pub(crate) fn split_obus(data: &Bytes) -> Result<Vec<Bytes>, ObuParseError> {
+    let mut obus = Vec::new();
+    let mut offset = 0;
+    while offset < data.len() {
+        let header = data[offset];
+        let header_len = match header & OBU_EXTENSION_FLAG {
+            0 => 1,
+            _ => 2,
+        };
+        if offset + header_len > data.len() {
+            return Err(ObuParseError::Truncated);
+        }
+
+        if header & OBU_HAS_SIZE_FIELD == 0 {
+            // OBU without size field spans to the end of the temporal unit.
+            obus.push(data.slice(offset..));
+            break;
+        }
+
+        let (size, size_len) =
+            read_leb128(&data[offset + header_len..]).ok_or(ObuParseError::InvalidLeb128)?;
+        let payload_start = offset + header_len + size_len;
+        let end = payload_start + size;
+        if end > data.len() {
+            return Err(ObuParseError::Truncated);
+        }
+
+        let mut obu = BytesMut::with_capacity(header_len + size);
+        obu.put_u8(header & !OBU_HAS_SIZE_FIELD);
+        obu.put_slice(&data[offset + 1..offset + header_len]);
+        obu.put_slice(&data[payload_start..end]);
+        obus.push(obu.freeze());
+
+        offset = end;
+    }
+    Ok(obus)
+}
+
+/// Appends OBU to the `buffer` in the low overhead bitstream format, adding
+/// the size field if it is missing.
+pub(crate) fn write_obu_with_size_field(
+    buffer: &mut BytesMut,
+    obu: &[u8],
+) -> Result<(), ObuParseError> {
+    let Some(&header) = obu.first() else {
+        return Err(ObuParseError::Truncated);
+    };
+    if header & OBU_HAS_SIZE_FIELD != 0 {
+        buffer.put_slice(obu);
+        return Ok(());
+    }
+
+    let header_len = match header & OBU_EXTENSION_FLAG {
+        0 => 1,
+        _ => 2,
+    };
+    if obu.len() < header_len {
+        return Err(ObuParseError::Truncated);
+    }
+
+    buffer.put_u8(header | OBU_HAS_SIZE_FIELD);
+    buffer.put_slice(&obu[1..header_len]);
+    write_leb128(buffer, obu.len() - header_len);
+    buffer.put_slice(&obu[header_len..]);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn leb128_round_trip() {
+        for value in [0, 1, 127, 128, 300, 16_383, 16_384, 1 << 28] {
+            let mut buffer = BytesMut::new();
+            write_leb128(&mut buffer, value);
+            assert_eq!(buffer.len(), leb128_len(value));
+            assert_eq!(read_leb128(&buffer), Some((value, buffer.len())));
+        }
+    }
+
+    #[test]
+    fn split_and_restore_obus() {
+        // temporal delimiter, frame OBU with extension header
+        let temporal_unit = Bytes::from_static(&[0x12, 0x00, 0x36, 0x10, 0x03, 1, 2, 3]);
+
+        let obus = split_obus(&temporal_unit).unwrap();
+        assert_eq!(obus.len(), 2);
+        assert_eq!(obus[0].as_ref(), [0x10]);
+        assert_eq!(obus[1].as_ref(), [0x34, 0x10, 1, 2, 3]);
+
+        let mut restored = BytesMut::new();
+        for obu in obus {
+            write_obu_with_size_field(&mut restored, &obu).unwrap();
+        }
+        assert_eq!(restored.as_ref(), temporal_unit.as_ref());
+    }
+}
@@ -0,0 +1,499 @@
+use std::collections::HashMap;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use bytes::Bytes;
+
+use crate::pipeline::{
+    decoder::{
+        AacDecoderOptions, AacDepayloaderMode, AudioDecoderOptions, OpusDecoderOptions,
+        VideoDecoderOptions,
+    },
+    input::rtp::{InputAudioStream, InputVideoStream, RtpStream},
+    VideoCodec,
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum SdpError {
+    #[error("Invalid SDP line: \"{0}\".")]
+    InvalidLine(String),
+
+    #[error("Invalid media description: \"{0}\".")]
+    InvalidMediaDescription(String),
+
+    #[error("Invalid \"{0}\" attribute: \"{1}\".")]
+    InvalidAttribute(&'static str, String),
+
+    #[error("AAC track does not specify the AudioSpecificConfig (\"config\" fmtp parameter).")]
+    MissingAacConfig,
+
+    #[error("Payload type {payload_type} uses clock rate {clock_rate}, expected {expected}.")]
+    UnsupportedClockRate {
+        payload_type: u8,
+        clock_rate: u32,
+        expected: u32,
+    },
+
+    #[error("SDP {0} track does not use a supported codec.")]
+    UnsupportedCodec(&'static str),
+
+    #[error("SDP does not describe any video or audio track.")]
+    NoTracks,
+
+    #[error("Video and audio have to be sent to the same port, got {video} and {audio}.")]
+    PortMismatch { video: u16, audio: u16 },
+}
+
+/// Subset of a session description ([RFC 8866](https://datatracker.ietf.org/doc/html/rfc8866))
+/// needed to receive RTP streams described by it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct SessionDescription {
+    /// Address from the session-level `c=` line.
+    pub connection_address: Option<String>,
+    /// Session-level `a=control` attribute (RTSP).
+    pub control: Option<String>,
+    pub media: Vec<MediaDescription>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct MediaDescription {
+    pub kind: MediaKind,
+    pub port: u16,
+    /// Payload types in the order of preference.
+    pub payload_types: Vec<u8>,
+    /// Address from the media-level `c=` line.
+    pub connection_address: Option<String>,
+    /// Media-level `a=control` attribute (RTSP).
+    pub control: Option<String>,
+    rtpmaps: HashMap<u8, RtpMap>,
+    fmtps: HashMap<u8, HashMap<String, String>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum MediaKind {
+    Video,
+    Audio,
+    Other,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RtpMap {
+    /// Uppercase encoding name.
+    encoding: String,
+    clock_rate: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct SdpVideoTrack {
+    pub payload_type: u8,
+    pub options: VideoDecoderOptions,
+    /// H264 SPS and PPS from `sprop-parameter-sets`. Some senders transmit
+    /// them only in the SDP.
+    pub parameter_sets: Vec<Bytes>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct SdpAudioTrack {
+    pub payload_type: u8,
+    pub options: AudioDecoderOptions,
+}
+
+impl SessionDescription {
+    pub(crate) fn parse(sdp: &str) -> Result<Self, SdpError> {
+        let mut session = SessionDescription {
+            connection_address: None,
+            control: None,
+            media: Vec::new(),
+        };
+
+        for line in sdp.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            let Some((field, value)) = line.split_once('=') else {
+                return Err(SdpError::InvalidLine(line.to_string()));
+            };
+            match field {
+                "m" => session.media.push(MediaDescription::parse(value)?),
+                "c" => {
+                    let address = parse_connection_address(value)?;
+                    match session.media.last_mut() {
+                        Some(media) => media.connection_address = Some(address),
+                        None => session.connection_address = Some(address),
+                    }
+                }
+                "a" => {
+                    let (name, value) = value.split_once(':').unwrap_or((value, ""));
+                    match session.media.last_mut() {
+                        Some(media) => media.parse_attribute(name, value)?,
+                        None if name == "control" => session.control = Some(value.to_string()),
+                        None => (),
+                    }
+                }
+                _ => (),
+            }
+        }
+        Ok(session)
+    }
+
+    pub(crate) fn video(&self) -> Option<&MediaDescription> {
+        self.media
+            .iter()
+            .find(|media| media.kind == MediaKind::Video)
+    }
+
+    pub(crate) fn audio(&self) -> Option<&MediaDescription> {
+        self.media
+            .iter()
+            .find(|media| media.kind == MediaKind::Audio)
+    }
+}
+
+impl MediaDescription {
+    fn parse(value: &str) -> Result<Self, SdpError> {
+        let invalid = || SdpError::InvalidMediaDescription(value.to_string());
+        let mut fields = value.split_whitespace();
+        let kind = match fields.next().ok_or_else(invalid)? {
+            "video" => MediaKind::Video,
+            "audio" => MediaKind::Audio,
+            _ => MediaKind::Other,
+        };
+        // Port can be followed by a number of ports, e.g. "5004/2".
+        let port = fields
+            .next()
+            .and_then(|port| port.split('/').next())
+            .and_then(|port| port.parse().ok())
+            .ok_or_else(invalid)?;
+        let _protocol = fields.next().ok_or_else(invalid)?;
+        let payload_types = match kind {
+            MediaKind::Other => Vec::new(),
+            _ => fields
+                .map(|payload_type| payload_type.parse().map_err(|_| invalid()))
+                .collect::<Result<_, _>>()?,
+        };
+
+        Ok(Self {
+            kind,
+            port,
+            payload_types,
+            connection_address: None,
+            control: None,
+            rtpmaps: HashMap::new(),
+            fmtps: HashMap::new(),
+        })
+    }
+
+    fn parse_attribute(&mut self, name: &str, value: &str) -> Result<(), SdpError> {
+        match name {
+            "control" => self.control = Some(value.to_string()),
+            "rtpmap" => {
+                let invalid = || SdpError::InvalidAttribute("rtpmap", value.to_string());
+                let (payload_type, encoding) = value.split_once(' ').ok_or_else(invalid)?;
+                let mut encoding = encoding.trim().split('/');
+                let rtpmap = RtpMap {
+                    encoding: encoding.next().ok_or_else(invalid)?.to_ascii_uppercase(),
+                    clock_rate: encoding
+                        .next()
+                        .and_then(|rate| rate.parse().ok())
+                        .ok_or_else(invalid)?,
+                };
+                let payload_type = payload_type.parse().map_err(|_| invalid())?;
+                self.rtpmaps.insert(payload_type, rtpmap);
+            }
+            "fmtp" => {
+                let invalid = || SdpError::InvalidAttribute("fmtp", value.to_string());
+                let (payload_type, params) = value.split_once(' ').ok_or_else(invalid)?;
+                let params = params
+                    .split(';')
+                    .filter_map(|param| param.trim().split_once('='))
+                    .map(|(key, value)| (key.trim().to_ascii_lowercase(), value.trim().to_string()))
+                    .collect();
+                let payload_type = payload_type.parse().map_err(|_| invalid())?;
+                self.fmtps.insert(payload_type, params);
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+
+    /// Returns the first payload type with a supported video codec.
+    pub(crate) fn video_track(&self) -> Result<Option<SdpVideoTrack>, SdpError> {
+        for &payload_type in &self.payload_types {
+            let codec = match self.encoding(payload_type) {
+                Some("H264") => VideoCodec::H264,
+                Some("VP8") => VideoCodec::Vp8,
+                Some("VP9") => VideoCodec::Vp9,
+                Some("AV1") => VideoCodec::Av1,
+                _ => continue,
+            };
+
+            self.expect_clock_rate(payload_type, 90_000)?;
+            let parameter_sets = match self.fmtp(payload_type, "sprop-parameter-sets") {
+                Some(sets) if codec == VideoCodec::H264 => sets
+                    .split(',')
+                    .filter(|set| !set.is_empty())
+                    .map(|set| {
+                        STANDARD
+                            .decode(set)
+                            .map(Bytes::from)
+                            .map_err(|_| SdpError::InvalidAttribute("fmtp", sets.to_string()))
+                    })
+                    .collect::<Result<_, _>>()?,
+                _ => Vec::new(),
+            };
+
+            return Ok(Some(SdpVideoTrack {
+                payload_type,
+                options: VideoDecoderOptions { codec },
+                parameter_sets,
+            }));
+        }
+        Ok(None)
+    }
+
+    /// Returns the first payload type with a supported audio codec.
+    pub(crate) fn audio_track(&self) -> Result<Option<SdpAudioTrack>, SdpError> {
+        for &payload_type in &self.payload_types {
+            let options = match self.encoding(payload_type) {
+                Some("OPUS") => {
+                    self.expect_clock_rate(payload_type, 48_000)?;
+                    AudioDecoderOptions::Opus(OpusDecoderOptions {
+                        forward_error_correction: self.fmtp(payload_type, "useinbandfec")
+                            == Some("1"),
+                    })
+                }
+                // RFC 3640
+                Some("MPEG4-GENERIC") => {
+                    let asc = self
+                        .fmtp(payload_type, "config")
+                        .ok_or(SdpError::MissingAacConfig)?;
+                    let asc = parse_hex(asc)
+                        .ok_or_else(|| SdpError::InvalidAttribute("fmtp", asc.to_string()))?;
+                    let mode = self.fmtp(payload_type, "mode").map(str::to_ascii_lowercase);
+                    let depayloader_mode =
+                        match (mode.as_deref(), self.fmtp(payload_type, "sizelength")) {
+                            (Some("aac-lbr"), _) | (_, Some("6")) => AacDepayloaderMode::LowBitrate,
+                            _ => AacDepayloaderMode::HighBitrate,
+                        };
+                    AudioDecoderOptions::Aac(AacDecoderOptions {
+                        depayloader_mode: Some(depayloader_mode),
+                        asc: Some(asc),
+                    })
+                }
+                _ => continue,
+            };
+            return Ok(Some(SdpAudioTrack {
+                payload_type,
+                options,
+            }));
+        }
+        Ok(None)
+    }
+
+    fn encoding(&self, payload_type: u8) -> Option<&str> {
+        self.rtpmaps
+            .get(&payload_type)
+            .map(|rtpmap| rtpmap.encoding.as_str())
+    }
+
+    fn fmtp(&self, payload_type: u8, key: &str) -> Option<&str> {
+        self.fmtps
+            .get(&payload_type)
+            .and_then(|params| params.get(key))
+            .map(String::as_str)
+    }
+
+    /// Timestamps are converted assuming a fixed clock rate for each codec.
+    fn expect_clock_rate(&self, payload_type: u8, expected: u32) -> Result<(), SdpError> {
+        match self.rtpmaps.get(&payload_type) {
+            Some(rtpmap) if rtpmap.clock_rate != expected => Err(SdpError::UnsupportedClockRate {
+                payload_type,
+                clock_rate: rtpmap.clock_rate,
+                expected,
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// RTP stream described by an SDP, e.g. the one generated by FFmpeg for its RTP output.
+#[derive(Debug, Clone)]
+pub struct SdpRtpStream {
+    /// Port to which both tracks are sent.
+    pub port: u16,
+    pub stream: RtpStream,
+}
+
+impl SdpRtpStream {
+    pub fn parse(sdp: &str) -> Result<Self, SdpError> {
+        let sdp = SessionDescription::parse(sdp)?;
+
+        let video = sdp
+            .video()
+            .map(|media| {
+                let track = media
+                    .video_track()?
+                    .ok_or(SdpError::UnsupportedCodec("video"))?;
+                Ok::<_, SdpError>((media.port, track))
+            })
+            .transpose()?;
+        let audio = sdp
+            .audio()
+            .map(|media| {
+                let track = media
+                    .audio_track()?
+                    .ok_or(SdpError::UnsupportedCodec("audio"))?;
+                Ok::<_, SdpError>((media.port, track))
+            })
+            .transpose()?;
+
+        let port = match (&video, &audio) {
+            (Some((video, _)), Some((audio, _))) if video != audio => {
+                return Err(SdpError::PortMismatch {
+                    video: *video,
+                    audio: *audio,
+                })
+            }
+            (Some((port, _)), _) | (None, Some((port, _))) => *port,
+            (None, None) => return Err(SdpError::NoTracks),
+        };
+
+        Ok(Self {
+            port,
+            stream: RtpStream {
+                video: video.map(|(_, track)| InputVideoStream {
+                    options: track.options,
+                    payload_type: track.payload_type,
+                }),
+                audio: audio.map(|(_, track)| InputAudioStream {
+                    options: track.options,
+                    payload_type: track.payload_type,
+                }),
+            },
+        })
+    }
+}
+
+/// Parses `<nettype> <addrtype> <connection-address>`. TTL and number of addresses
+/// (e.g. `239.0.0.1/127`) are ignored.
+fn parse_connection_address(value: &str) -> Result<String, SdpError> {
+    value
+        .split_whitespace()
+        .nth(2)
+        .and_then(|address| address.split('/').next())
+        .map(str::to_string)
+        .ok_or_else(|| SdpError::InvalidLine(format!("c={value}")))
+}
+
+fn parse_hex(value: &str) -> Option<Bytes> {
+    if value.is_empty() {
+        return None;
+    }
+    value
+        .as_bytes()
+        .chunks(2)
+        .map(|byte| match byte {
+            [_, _] => u8::from_str_radix(std::str::from_utf8(byte).ok()?, 16).ok(),
+            _ => None,
+        })
+        .collect::<Option<Vec<u8>>>()
+        .map(Bytes::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+
+    use crate::pipeline::{
+        decoder::{AacDecoderOptions, AacDepayloaderMode, AudioDecoderOptions},
+        VideoCodec,
+    };
+
+    use super::{SdpError, SdpRtpStream, SessionDescription};
+
+    const CAMERA_SDP: &str = "v=0\r
+o=- 1 1 IN IP4 192.168.0.10\r
+s=Camera\r
+c=IN IP4 0.0.0.0\r
+t=0 0\r
+a=control:*\r
+m=video 0 RTP/AVP 96\r
+a=rtpmap:96 H264/90000\r
+a=fmtp:96 packetization-mode=1;profile-level-id=42e01f;sprop-parameter-sets=Z0LAHtkDxWhAAAADAEAAAAwDxYuS,aMuMsg==\r
+a=control:trackID=1\r
+m=audio 0 RTP/AVP 97\r
+a=rtpmap:97 MPEG4-GENERIC/44100/2\r
+a=fmtp:97 streamtype=5; profile-level-id=15; mode=AAC-hbr; config=1210; sizeLength=13; indexLength=3; indexDeltaLength=3\r
+a=control:trackID=2\r
+";
+
+    #[test]
+    fn parse_camera_sdp() {
+        let sdp = SessionDescription::parse(CAMERA_SDP).unwrap();
+        assert_eq!(sdp.control.as_deref(), Some("*"));
+
+        let video = sdp.video().unwrap();
+        assert_eq!(video.control.as_deref(), Some("trackID=1"));
+        let video_track = video.video_track().unwrap().unwrap();
+        assert_eq!(video_track.payload_type, 96);
+        assert_eq!(video_track.options.codec, VideoCodec::H264);
+        assert_eq!(video_track.parameter_sets.len(), 2);
+        assert_eq!(
+            video_track.parameter_sets[1].as_ref(),
+            [0x68, 0xCB, 0x8C, 0xB2]
+        );
+
+        let audio = sdp.audio().unwrap();
+        assert_eq!(audio.control.as_deref(), Some("trackID=2"));
+        let audio_track = audio.audio_track().unwrap().unwrap();
+        assert_eq!(audio_track.payload_type, 97);
+        assert_eq!(
+            audio_track.options,
+            AudioDecoderOptions::Aac(AacDecoderOptions {
+                depayloader_mode: Some(AacDepayloaderMode::HighBitrate),
+                asc: Some(Bytes::from_static(&[0x12, 0x10])),
+            })
+        );
+    }
+
+    #[test]
+    fn skip_unsupported_payload_types() {
+        let sdp = SessionDescription::parse(
+            "v=0\nc=IN IP4 239.1.1.1/32\nm=audio 5004 RTP/AVP 0 111\na=rtpmap:0 PCMU/8000\na=rtpmap:111 opus/48000/2\na=fmtp:111 useinbandfec=1\n",
+        )
+        .unwrap();
+        assert_eq!(sdp.connection_address.as_deref(), Some("239.1.1.1"));
+
+        let audio = sdp.audio().unwrap();
+        assert_eq!(audio.port, 5004);
+        let audio_track = audio.audio_track().unwrap().unwrap();
+        assert_eq!(audio_track.payload_type, 111);
+        assert!(matches!(
+            audio_track.options,
+            AudioDecoderOptions::Opus(options) if options.forward_error_correction
+        ));
+        assert!(sdp.video().is_none());
+    }
+
+    #[test]
+    fn parse_ffmpeg_rtp_sdp() {
+        let sdp = "v=0\no=- 0 0 IN IP4 127.0.0.1\ns=No Name\nt=0 0\na=tool:libavformat\nm=video 8002 RTP/AVP 96\nc=IN IP4 127.0.0.1\na=rtpmap:96 H264/90000\na=fmtp:96 packetization-mode=1\nm=audio 8002 RTP/AVP 102\nc=IN IP4 127.0.0.1\na=rtpmap:102 MPEG4-GENERIC/48000/2\na=fmtp:102 profile-level-id=1;mode=AAC-hbr;sizelength=13;indexlength=3;indexdeltalength=3; config=1190\n";
+        let SdpRtpStream { port, stream } = SdpRtpStream::parse(sdp).unwrap();
+        assert_eq!(port, 8002);
+        assert_eq!(stream.video.unwrap().payload_type, 96);
+        let audio = stream.audio.unwrap();
+        assert_eq!(audio.payload_type, 102);
+        assert_eq!(
+            audio.options,
+            AudioDecoderOptions::Aac(AacDecoderOptions {
+                depayloader_mode: Some(AacDepayloaderMode::HighBitrate),
+                asc: Some(Bytes::from_static(&[0x11, 0x90])),
+            })
+        );
+
+        let different_ports = sdp.replacen("8002", "8004", 1);
+        assert!(matches!(
+            SdpRtpStream::parse(&different_ports),
+            Err(SdpError::PortMismatch {
+                video: 8004,
+                audio: 8002
+            })
+        ));
+    }
+}
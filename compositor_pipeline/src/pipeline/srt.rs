@@ -0,0 +1,70 @@
+use std::{path::PathBuf, sync::Arc, time::Duration};
+
+/// How long waiting for a peer can block before the connection attempt is
+/// retried. It also bounds how long an unregistered input/output keeps waiting.
+pub(crate) const SRT_CONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Timeout of a single read or write on an established connection. Without it,
+/// a dead peer could block the thread indefinitely.
+const SRT_RW_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SrtMode {
+    /// Connect to a remote SRT listener.
+    Caller,
+    /// Wait for a remote SRT caller to connect.
+    Listener,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SrtConnectionOptions {
+    pub mode: SrtMode,
+    /// Remote address in caller mode. Ignored in listener mode, listener binds
+    /// to all interfaces.
+    pub ip: Option<Arc<str>>,
+    pub port: u16,
+    /// Receiver buffer latency negotiated with the peer.
+    pub latency: Duration,
+    /// Enables AES encryption. Both sides have to use the same passphrase.
+    pub passphrase: Option<Arc<str>>,
+}
+
+impl SrtConnectionOptions {
+    /// URL passed to the ffmpeg `libsrt` protocol.
+    pub(crate) fn url(&self) -> PathBuf {
+        let host = match (self.mode, &self.ip) {
+            (SrtMode::Caller, Some(ip)) => ip.as_ref(),
+            (SrtMode::Caller, None) | (SrtMode::Listener, _) => "0.0.0.0",
+        };
+        PathBuf::from(format!("srt://{host}:{}", self.port))
+    }
+
+    /// Options passed to the ffmpeg `libsrt` protocol when opening the connection.
+    pub(crate) fn protocol_options(&self) -> Vec<(String, String)> {
+        let mode = match self.mode {
+            SrtMode::Caller => "caller",
+            SrtMode::Listener => "listener",
+        };
+        let mut options = vec![
+            ("mode".to_string(), mode.to_string()),
+            ("transtype".to_string(), "live".to_string()),
+            ("latency".to_string(), self.latency.as_micros().to_string()),
+            (
+                "listen_timeout".to_string(),
+                SRT_CONNECT_TIMEOUT.as_micros().to_string(),
+            ),
+            (
+                "connect_timeout".to_string(),
+                SRT_CONNECT_TIMEOUT.as_millis().to_string(),
+            ),
+            (
+                "timeout".to_string(),
+                SRT_RW_TIMEOUT.as_micros().to_string(),
+            ),
+        ];
+        if let Some(passphrase) = &self.passphrase {
+            options.push(("passphrase".to_string(), passphrase.to_string()));
+        }
+        options
+    }
+}
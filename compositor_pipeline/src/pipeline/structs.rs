@@ -10,9 +10,22 @@ pub struct EncodedChunk {
     pub data: Bytes,
     pub pts: Duration,
     pub dts: Option<Duration>,
+    pub is_keyframe: IsKeyframe,
     pub kind: EncodedChunkKind,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IsKeyframe {
+    /// this is a keyframe
+    Yes,
+    /// this is not a keyframe
+    No,
+    /// it's unknown whether this frame is a keyframe or not
+    Unknown,
+    /// the codec this chunk is encoded in does not have keyframes at all
+    NoKeyframes,
+}
+
 pub enum EncoderOutputEvent {
     Data(EncodedChunk),
     AudioEOS,
@@ -31,6 +44,8 @@ pub enum ChunkFromFfmpegError {
     NoData,
     #[error("No pts")]
     NoPts,
+    #[error("Negative timestamp {0}")]
+    NegativeTimestamp(i64),
 }
 
 impl EncodedChunk {
@@ -44,15 +59,27 @@ impl EncodedChunk {
             None => return Err(ChunkFromFfmpegError::NoData),
         };
 
-        let rescale = |v: i64| Duration::from_secs_f64((v as f64) * (1.0 / timescale as f64));
+        let rescale = |v: i64| {
+            if v < 0 {
+                return Err(ChunkFromFfmpegError::NegativeTimestamp(v));
+            }
+            Ok(Duration::from_secs_f64(
+                (v as f64) * (1.0 / timescale as f64),
+            ))
+        };
 
         Ok(Self {
             data,
             pts: value
                 .pts()
-                .map(rescale)
-                .ok_or(ChunkFromFfmpegError::NoPts)?,
-            dts: value.dts().map(rescale),
+                .ok_or(ChunkFromFfmpegError::NoPts)
+                .and_then(rescale)?,
+            dts: value.dts().map(rescale).transpose()?,
+            is_keyframe: if value.is_key() {
+                IsKeyframe::Yes
+            } else {
+                IsKeyframe::No
+            },
             kind,
         })
     }
@@ -61,6 +88,9 @@ impl EncodedChunk {
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum VideoCodec {
     H264,
+    Vp8,
+    Vp9,
+    Av1,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -0,0 +1,123 @@
+use std::time::Duration;
+
+use webrtc::{
+    api::{
+        interceptor_registry::register_default_interceptors,
+        media_engine::{MediaEngine, MIME_TYPE_H264, MIME_TYPE_OPUS, MIME_TYPE_VP8, MIME_TYPE_VP9},
+        setting_engine::SettingEngine,
+        APIBuilder,
+    },
+    interceptor::registry::Registry,
+    peer_connection::{
+        configuration::RTCConfiguration, sdp::session_description::RTCSessionDescription,
+        RTCPeerConnection,
+    },
+    rtp_transceiver::{
+        rtp_codec::{RTCRtpCodecCapability, RTCRtpCodecParameters},
+        RTCPFeedback,
+    },
+};
+
+use super::{
+    rtp::{AUDIO_PAYLOAD_TYPE, VIDEO_PAYLOAD_TYPE},
+    VideoCodec,
+};
+
+const MIME_TYPE_AV1: &str = "video/AV1";
+
+/// Time without connectivity checks after which the connection is considered
+/// disconnected and, after another `ICE_FAILED_TIMEOUT`, failed. Failed sessions
+/// are closed, so a client that went away without terminating its session frees
+/// it in seconds instead of the default 30.
+const ICE_DISCONNECTED_TIMEOUT: Duration = Duration::from_secs(5);
+const ICE_FAILED_TIMEOUT: Duration = Duration::from_secs(5);
+const ICE_KEEPALIVE_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Creates a peer connection that supports only codecs registered in `media_engine`.
+pub(crate) async fn new_peer_connection(
+    mut media_engine: MediaEngine,
+) -> Result<RTCPeerConnection, webrtc::Error> {
+    let registry = register_default_interceptors(Registry::new(), &mut media_engine)?;
+
+    // The compositor is always the answering side and does not gather
+    // server reflexive candidates, so ICE-lite is sufficient.
+    let mut setting_engine = SettingEngine::default();
+    setting_engine.set_lite(true);
+    setting_engine.set_ice_timeouts(
+        Some(ICE_DISCONNECTED_TIMEOUT),
+        Some(ICE_FAILED_TIMEOUT),
+        Some(ICE_KEEPALIVE_INTERVAL),
+    );
+
+    let api = APIBuilder::new()
+        .with_media_engine(media_engine)
+        .with_interceptor_registry(registry)
+        .with_setting_engine(setting_engine)
+        .build();
+
+    api.new_peer_connection(RTCConfiguration::default()).await
+}
+
+/// Applies the remote offer and returns the local answer with all ICE candidates
+/// included. WHIP and WHEP do not require trickle ICE.
+pub(crate) async fn answer_offer(
+    peer_connection: &RTCPeerConnection,
+    offer: String,
+) -> Result<Option<RTCSessionDescription>, webrtc::Error> {
+    peer_connection
+        .set_remote_description(RTCSessionDescription::offer(offer)?)
+        .await?;
+    let answer = peer_connection.create_answer(None).await?;
+
+    let mut gathering_complete = peer_connection.gathering_complete_promise().await;
+    peer_connection.set_local_description(answer).await?;
+    let _ = gathering_complete.recv().await;
+
+    Ok(peer_connection.local_description().await)
+}
+
+pub(crate) fn video_codec_parameters(codec: VideoCodec) -> RTCRtpCodecParameters {
+    let (mime_type, sdp_fmtp_line) = match codec {
+        // Constrained baseline, WHEP output forces the matching encoder profile.
+        VideoCodec::H264 => (
+            MIME_TYPE_H264,
+            "level-asymmetry-allowed=1;packetization-mode=1;profile-level-id=42e01f",
+        ),
+        VideoCodec::Vp8 => (MIME_TYPE_VP8, ""),
+        VideoCodec::Vp9 => (MIME_TYPE_VP9, "profile-id=0"),
+        VideoCodec::Av1 => (MIME_TYPE_AV1, ""),
+    };
+    RTCRtpCodecParameters {
+        capability: RTCRtpCodecCapability {
+            mime_type: mime_type.to_owned(),
+            clock_rate: 90000,
+            channels: 0,
+            sdp_fmtp_line: sdp_fmtp_line.to_owned(),
+            // Retransmissions are handled by the interceptors, keyframe requests
+            // by the output.
+            rtcp_feedback: [("nack", ""), ("nack", "pli"), ("ccm", "fir")]
+                .into_iter()
+                .map(|(typ, parameter)| RTCPFeedback {
+                    typ: typ.to_owned(),
+                    parameter: parameter.to_owned(),
+                })
+                .collect(),
+        },
+        payload_type: VIDEO_PAYLOAD_TYPE,
+        ..Default::default()
+    }
+}
+
+pub(crate) fn opus_codec_parameters() -> RTCRtpCodecParameters {
+    RTCRtpCodecParameters {
+        capability: RTCRtpCodecCapability {
+            mime_type: MIME_TYPE_OPUS.to_owned(),
+            clock_rate: 48000,
+            channels: 2,
+            sdp_fmtp_line: "minptime=10;useinbandfec=1".to_owned(),
+            rtcp_feedback: vec![],
+        },
+        payload_type: AUDIO_PAYLOAD_TYPE,
+        ..Default::default()
+    }
+}
@@ -21,7 +21,7 @@ use crate::{
 use self::{
     audio_queue::AudioQueue,
     queue_thread::{QueueStartEvent, QueueThread},
-    utils::Clock,
+    utils::{Clock, InputStart},
     video_queue::VideoQueue,
 };
 
@@ -174,12 +174,14 @@ impl Queue {
     }
 
     pub fn add_input(&self, input_id: &InputId, receiver: DecodedDataReceiver, opts: InputOptions) {
+        let input_start = InputStart::default();
         if let Some(receiver) = receiver.video {
             self.video_queue.lock().unwrap().add_input(
                 input_id,
                 receiver,
                 opts,
                 self.clock.clone(),
+                input_start.clone(),
             );
         };
         if let Some(receiver) = receiver.audio {
@@ -188,6 +190,7 @@ impl Queue {
                 receiver,
                 opts,
                 self.clock.clone(),
+                input_start,
             );
         }
     }
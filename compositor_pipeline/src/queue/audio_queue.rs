@@ -7,7 +7,7 @@ use std::{
 use crate::{audio_mixer::InputSamples, event::Event};
 
 use super::{
-    utils::{Clock, InputProcessor},
+    utils::{Clock, InputProcessor, InputStart},
     InputOptions, PipelineEvent, QueueAudioOutput,
 };
 use compositor_render::{event_handler::emit_event, InputId};
@@ -33,6 +33,7 @@ impl AudioQueue {
         receiver: Receiver<PipelineEvent<InputSamples>>,
         opts: InputOptions,
         clock: Clock,
+        input_start: InputStart,
     ) {
         self.inputs.insert(
             input_id.clone(),
@@ -44,6 +45,7 @@ impl AudioQueue {
                     self.buffer_duration,
                     clock,
                     input_id.clone(),
+                    input_start,
                 ),
                 required: opts.required,
                 offset: opts.offset,
@@ -3,7 +3,7 @@ use std::{
     mem,
     sync::{
         atomic::{AtomicI64, Ordering},
-        Arc,
+        Arc, Mutex,
     },
     time::{Duration, Instant},
 };
@@ -27,7 +27,8 @@ use super::PipelineEvent;
 /// 3. Each new call to the `process_new_chunk` is adding frames to the buffer
 ///    until it reaches a specific size/duration.
 /// 4. After buffer reaches a certain size, calculate the offset and switch
-///    to the `Ready` state.
+///    to the `Ready` state. If the other track of the same input is already
+///    ready, its offset and start time are reused (see [`InputStart`]).
 /// 5. In `Ready` state `process_new_chunk` is immediately returning frame or sample
 ///    batch passed with arguments with modified pts.
 #[derive(Debug)]
@@ -42,6 +43,8 @@ pub(super) struct InputProcessor<Payload: InputProcessorMediaExt> {
     state: InputState<Payload>,
 
     clock: Clock,
+
+    input_start: InputStart,
 }
 
 #[derive(Debug)]
@@ -59,13 +62,19 @@ pub(super) enum InputState<Payload: InputProcessorMediaExt> {
 }
 
 impl<Payload: InputProcessorMediaExt> InputProcessor<Payload> {
-    pub(super) fn new(buffer_duration: Duration, clock: Clock, input_id: InputId) -> Self {
+    pub(super) fn new(
+        buffer_duration: Duration,
+        clock: Clock,
+        input_id: InputId,
+        input_start: InputStart,
+    ) -> Self {
         Self {
             buffer_duration,
             start_time: None,
             state: InputState::WaitingForStart,
             clock,
             input_id,
+            input_start,
         }
     }
 
@@ -92,15 +101,16 @@ impl<Payload: InputProcessorMediaExt> InputProcessor<Payload> {
             InputState::WaitingForStart => VecDeque::new(),
             InputState::Buffering { ref mut buffer } => {
                 let first_pts = buffer.first().map(|(_, p)| *p).unwrap_or(Duration::ZERO);
+                let (offset, start_time) = self.input_start.get_or_init(first_pts, Instant::now());
                 let chunks = mem::take(buffer)
                     .into_iter()
                     .map(|(mut buffer, _)| {
-                        buffer.apply_offset(first_pts);
+                        buffer.apply_offset(offset);
                         buffer
                     })
                     .collect();
                 self.state = InputState::Done;
-                self.start_time = Some(Instant::now());
+                self.start_time = Some(start_time);
                 chunks
             }
             InputState::Ready { .. } => {
@@ -132,7 +142,8 @@ impl<Payload: InputProcessorMediaExt> InputProcessor<Payload> {
                 if buffer_duration < self.buffer_duration {
                     VecDeque::new()
                 } else {
-                    let offset = first_pts;
+                    let (offset, start_time) =
+                        self.input_start.get_or_init(first_pts, self.clock.now());
 
                     let chunks = mem::take(buffer)
                         .into_iter()
@@ -142,7 +153,7 @@ impl<Payload: InputProcessorMediaExt> InputProcessor<Payload> {
                         })
                         .collect();
                     self.state = InputState::Ready { offset };
-                    self.start_time = Some(self.clock.now());
+                    self.start_time = Some(start_time);
                     self.on_ready();
                     chunks
                 }
@@ -166,6 +177,18 @@ impl<Payload: InputProcessorMediaExt> InputProcessor<Payload> {
     }
 }
 
+/// Offset and start time shared by the audio and video tracks of an input. Inputs
+/// deliver PTS of both tracks on a common timeline, so reusing the values from
+/// the track that became ready first keeps audio and video in sync.
+#[derive(Debug, Clone, Default)]
+pub(super) struct InputStart(Arc<Mutex<Option<(Duration, Instant)>>>);
+
+impl InputStart {
+    fn get_or_init(&self, offset: Duration, start_time: Instant) -> (Duration, Instant) {
+        *self.0.lock().unwrap().get_or_insert((offset, start_time))
+    }
+}
+
 pub(super) enum MediaType {
     Audio,
     Video,
@@ -234,3 +257,107 @@ impl Clock {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::VecDeque, sync::Arc, time::Duration};
+
+    use bytes::Bytes;
+    use compositor_render::{Frame, InputId, Resolution, YuvData, YuvVariant};
+
+    use crate::{audio_mixer::InputSamples, queue::PipelineEvent};
+
+    use super::{Clock, InputProcessor, InputStart};
+
+    const BUFFER_DURATION: Duration = Duration::from_millis(80);
+
+    fn frame(pts: Duration) -> Frame {
+        Frame {
+            data: YuvData {
+                variant: YuvVariant::YUV420P,
+                y_plane: Bytes::new(),
+                u_plane: Bytes::new(),
+                v_plane: Bytes::new(),
+            },
+            resolution: Resolution {
+                width: 0,
+                height: 0,
+            },
+            pts,
+        }
+    }
+
+    fn samples(start_pts: Duration) -> InputSamples {
+        InputSamples {
+            samples: Arc::new(Vec::new()),
+            start_pts,
+            end_pts: start_pts + Duration::from_millis(20),
+        }
+    }
+
+    /// Sends video frames every 40ms and audio batches every 20ms until both tracks
+    /// are ready. Returns PTS of the first frame and of the first audio batch.
+    fn start_tracks(
+        video_input_start: InputStart,
+        audio_input_start: InputStart,
+        video_start: Duration,
+        audio_start: Duration,
+    ) -> (Duration, Duration) {
+        let input_id = InputId("input".into());
+        let mut video = InputProcessor::<Frame>::new(
+            BUFFER_DURATION,
+            Clock::new(),
+            input_id.clone(),
+            video_input_start,
+        );
+        let mut audio = InputProcessor::<InputSamples>::new(
+            BUFFER_DURATION,
+            Clock::new(),
+            input_id,
+            audio_input_start,
+        );
+
+        let mut frames = VecDeque::new();
+        for index in 0..3 {
+            let pts = video_start + Duration::from_millis(40) * index;
+            frames.extend(video.process_new_chunk(PipelineEvent::Data(frame(pts))));
+        }
+        let mut batches = VecDeque::new();
+        for index in 0..5 {
+            let pts = audio_start + Duration::from_millis(20) * index;
+            batches.extend(audio.process_new_chunk(PipelineEvent::Data(samples(pts))));
+        }
+
+        assert!(video.start_time().is_some());
+        assert!(audio.start_time().is_some());
+        (frames[0].pts, batches[0].start_pts)
+    }
+
+    #[test]
+    fn tracks_of_an_input_share_start() {
+        let input_start = InputStart::default();
+        let (video_pts, audio_pts) = start_tracks(
+            input_start.clone(),
+            input_start,
+            Duration::from_secs(1),
+            Duration::from_millis(1200),
+        );
+
+        // Audio started 200ms after video, the same offset is applied to both.
+        assert_eq!(video_pts, Duration::ZERO);
+        assert_eq!(audio_pts, Duration::from_millis(200));
+    }
+
+    #[test]
+    fn tracks_with_separate_start_are_independent() {
+        let (video_pts, audio_pts) = start_tracks(
+            InputStart::default(),
+            InputStart::default(),
+            Duration::from_secs(1),
+            Duration::from_millis(1200),
+        );
+
+        assert_eq!(video_pts, Duration::ZERO);
+        assert_eq!(audio_pts, Duration::ZERO);
+    }
+}
@@ -13,6 +13,7 @@ use crate::event::Event;
 
 use super::utils::Clock;
 use super::utils::InputProcessor;
+use super::utils::InputStart;
 use super::InputOptions;
 use super::PipelineEvent;
 use super::QueueVideoOutput;
@@ -36,6 +37,7 @@ impl VideoQueue {
         receiver: Receiver<PipelineEvent<Frame>>,
         opts: InputOptions,
         clock: Clock,
+        input_start: InputStart,
     ) {
         self.inputs.insert(
             input_id.clone(),
@@ -47,6 +49,7 @@ impl VideoQueue {
                     self.buffer_duration,
                     clock,
                     input_id.clone(),
+                    input_start,
                 ),
                 required: opts.required,
                 offset: opts.offset,
@@ -169,6 +169,22 @@ impl Renderer {
             .unwrap()
             .update_scene(output_id, resolution, scene_root)
     }
+
+    /// Registers an output that receives frames rendered for `source_output_id`,
+    /// scaled to `resolution`. The scene is rendered only once for all of them.
+    /// Rendition is unregistered together with its source output.
+    pub fn register_output_rendition(
+        &self,
+        source_output_id: OutputId,
+        output_id: OutputId,
+        resolution: Resolution,
+    ) {
+        let mut guard = self.0.lock().unwrap();
+        let wgpu_ctx = guard.wgpu_ctx.clone();
+        guard
+            .render_graph
+            .register_rendition(&wgpu_ctx, source_output_id, output_id, resolution);
+    }
 }
 
 impl InnerRenderer {
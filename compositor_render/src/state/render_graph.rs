@@ -2,14 +2,18 @@ use std::collections::HashMap;
 
 use crate::scene::{self, OutputNode};
 use crate::wgpu::texture::{InputTexture, NodeTexture, OutputTexture};
-use crate::{error::UpdateSceneError, wgpu::WgpuErrorScope};
-use crate::{InputId, OutputId};
+use crate::{
+    error::UpdateSceneError,
+    wgpu::{WgpuCtx, WgpuErrorScope},
+};
+use crate::{InputId, OutputId, Resolution};
 
 use super::{node::RenderNode, RenderCtx};
 
 pub(super) struct RenderGraph {
     pub(super) outputs: HashMap<OutputId, OutputRenderTree>,
     pub(super) inputs: HashMap<InputId, (NodeTexture, InputTexture)>,
+    pub(super) renditions: HashMap<OutputId, OutputRendition>,
 }
 
 pub(super) struct OutputRenderTree {
@@ -17,11 +21,19 @@ pub(super) struct OutputRenderTree {
     pub(super) output_texture: OutputTexture,
 }
 
+/// Output that reuses the scene rendered for a different output, scaled to its
+/// own resolution.
+pub(super) struct OutputRendition {
+    pub(super) source_output_id: OutputId,
+    pub(super) output_texture: OutputTexture,
+}
+
 impl RenderGraph {
     pub fn empty() -> Self {
         Self {
             outputs: HashMap::new(),
             inputs: HashMap::new(),
+            renditions: HashMap::new(),
         }
     }
 
@@ -36,6 +48,25 @@ impl RenderGraph {
 
     pub(super) fn unregister_output(&mut self, output_id: &OutputId) {
         self.outputs.remove(output_id);
+        self.renditions.remove(output_id);
+        self.renditions
+            .retain(|_, rendition| rendition.source_output_id != *output_id);
+    }
+
+    pub(super) fn register_rendition(
+        &mut self,
+        wgpu_ctx: &WgpuCtx,
+        source_output_id: OutputId,
+        output_id: OutputId,
+        resolution: Resolution,
+    ) {
+        self.renditions.insert(
+            output_id,
+            OutputRendition {
+                source_output_id,
+                output_texture: OutputTexture::new(wgpu_ctx, resolution),
+            },
+        );
     }
 
     pub(super) fn update(
@@ -5,7 +5,7 @@ use log::error;
 use crate::{
     scene::RGBColor,
     state::{node::RenderNode, render_graph::RenderGraph, RenderCtx},
-    wgpu::texture::{InputTexture, NodeTexture},
+    wgpu::texture::{InputTexture, NodeTexture, OutputTexture},
     Frame, FrameSet, InputId, OutputId,
 };
 
@@ -49,41 +49,33 @@ pub(super) fn read_outputs(
     scene: &mut RenderGraph,
     pts: Duration,
 ) -> HashMap<OutputId, Frame> {
-    let mut pending_downloads = Vec::with_capacity(scene.outputs.len());
+    let mut pending_downloads = Vec::with_capacity(scene.outputs.len() + scene.renditions.len());
     for (output_id, output) in &scene.outputs {
-        match output.root.output_texture(&scene.inputs).state() {
-            Some(node) => {
-                ctx.wgpu_ctx.format.convert_rgba_to_yuv(
-                    ctx.wgpu_ctx,
-                    (node.rgba_texture(), node.bind_group()),
-                    output.output_texture.yuv_textures(),
-                );
-            }
-            None => {
-                let (y, u, v) = RGBColor::BLACK.to_yuv();
-                ctx.wgpu_ctx.utils.fill_r8_with_value(
-                    ctx.wgpu_ctx,
-                    output.output_texture.yuv_textures().plane(0),
-                    y,
-                );
-                ctx.wgpu_ctx.utils.fill_r8_with_value(
-                    ctx.wgpu_ctx,
-                    output.output_texture.yuv_textures().plane(1),
-                    u,
-                );
-                ctx.wgpu_ctx.utils.fill_r8_with_value(
-                    ctx.wgpu_ctx,
-                    output.output_texture.yuv_textures().plane(2),
-                    v,
-                );
-            }
-        };
-        let yuv_pending = output.output_texture.start_download(ctx.wgpu_ctx);
-        pending_downloads.push((
-            output_id,
-            yuv_pending,
-            output.output_texture.resolution().to_owned(),
-        ));
+        let renditions = scene
+            .renditions
+            .iter()
+            .filter(|(_, rendition)| rendition.source_output_id == *output_id)
+            .map(|(rendition_id, rendition)| (rendition_id, &rendition.output_texture));
+        let output_textures =
+            std::iter::once((output_id, &output.output_texture)).chain(renditions);
+
+        let root = output.root.output_texture(&scene.inputs).state();
+        for (target_id, output_texture) in output_textures {
+            match root {
+                // Renditions with lower resolution are box-filtered during the
+                // color conversion, see `rgba_to_yuv.wgsl`.
+                Some(node) => {
+                    ctx.wgpu_ctx.format.convert_rgba_to_yuv(
+                        ctx.wgpu_ctx,
+                        (node.rgba_texture(), node.bind_group()),
+                        output_texture.yuv_textures(),
+                    );
+                }
+                None => fill_with_black(ctx, output_texture),
+            };
+            let yuv_pending = output_texture.start_download(ctx.wgpu_ctx);
+            pending_downloads.push((target_id, yuv_pending, output_texture.resolution()));
+        }
     }
     ctx.wgpu_ctx.device.poll(wgpu::MaintainBase::Wait);
 
@@ -108,6 +100,20 @@ pub(super) fn read_outputs(
     result
 }
 
+fn fill_with_black(ctx: &RenderCtx, output_texture: &OutputTexture) {
+    let (y, u, v) = RGBColor::BLACK.to_yuv();
+    let yuv_textures = output_texture.yuv_textures();
+    ctx.wgpu_ctx
+        .utils
+        .fill_r8_with_value(ctx.wgpu_ctx, yuv_textures.plane(0), y);
+    ctx.wgpu_ctx
+        .utils
+        .fill_r8_with_value(ctx.wgpu_ctx, yuv_textures.plane(1), u);
+    ctx.wgpu_ctx
+        .utils
+        .fill_r8_with_value(ctx.wgpu_ctx, yuv_textures.plane(2), v);
+}
+
 pub(super) fn run_transforms(ctx: &mut RenderCtx, scene: &mut RenderGraph, pts: Duration) {
     for output in scene.outputs.values_mut() {
         render_node(ctx, &scene.inputs, pts, &mut output.root);
@@ -23,9 +23,39 @@ fn vs_main(input: VertexInput) -> VertexOutput {
 
 var<push_constant> plane_selector: u32;
 
+// Upper limit of samples per axis, enough for a 16x downscale.
+const MAX_SAMPLES: u32 = 8u;
+
+// Box filter over the area of the source texture covered by the output pixel.
+// A bilinear sample averages 2 texels per axis, so one sample is taken per
+// 2 texels of the footprint. Without scaling this is a single sample.
+fn sample_footprint(tex_coords: vec2<f32>, footprint: vec2<f32>) -> vec4<f32> {
+    let texel_size = 1.0 / vec2<f32>(textureDimensions(texture));
+    let samples = clamp(
+        vec2<u32>(ceil(footprint / 2.0 - 0.01)),
+        vec2<u32>(1u),
+        vec2<u32>(MAX_SAMPLES)
+    );
+    let step = footprint * texel_size / vec2<f32>(samples);
+
+    var color = vec4<f32>(0.0);
+    for (var y = 0u; y < samples.y; y++) {
+        for (var x = 0u; x < samples.x; x++) {
+            let offset = (vec2<f32>(f32(x), f32(y)) + 0.5 - vec2<f32>(samples) / 2.0) * step;
+            color += textureSampleLevel(texture, sampler_, tex_coords + offset, 0.0);
+        }
+    }
+    return color / f32(samples.x * samples.y);
+}
+
 @fragment
 fn fs_main(input: VertexOutput) -> @location(0) f32 {
-    let color = textureSample(texture, sampler_, input.tex_coords);
+    // Number of source texels covered by the output pixel on each axis.
+    let footprint = vec2<f32>(textureDimensions(texture)) * vec2<f32>(
+        abs(dpdx(input.tex_coords.x)),
+        abs(dpdy(input.tex_coords.y))
+    );
+    let color = sample_footprint(input.tex_coords, footprint);
     var conversion_weights: vec4<f32>;
     var conversion_bias: f32;
 
@@ -76,14 +76,14 @@ const sidebars: SidebarsConfig = {
           label: 'Outputs',
           collapsible: false,
           description: 'Elements that deliver generated media.',
-          items: ['api/outputs/rtp'],
+          items: ['api/outputs/rtp', 'api/outputs/mp4', 'api/outputs/hls', 'api/outputs/rtmp', 'api/outputs/whep', 'api/outputs/srt'],
         },
         {
           type: 'category',
           label: 'Inputs',
           collapsible: false,
           description: 'Elements that deliver media from external sources.',
-          items: ['api/inputs/rtp', 'api/inputs/mp4'],
+          items: ['api/inputs/rtp', 'api/inputs/mp4', 'api/inputs/whip', 'api/inputs/srt', 'api/inputs/mpeg_ts', 'api/inputs/rtsp', 'api/inputs/file', 'api/inputs/hls', 'api/inputs/raw'],
         },
       ],
     },
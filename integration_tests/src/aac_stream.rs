@@ -0,0 +1,102 @@
+use std::{fs, net::UdpSocket, path::Path, thread, time::Duration};
+
+use anyhow::Result;
+use bytes::{Bytes, BytesMut};
+use serde_json::json;
+
+use crate::{input_dump_from_disk, CommunicationProtocol, CompositorInstance, PacketSender};
+
+/// MPEG-TS packets sent in a single UDP datagram, the same amount ffmpeg uses.
+const TS_PACKETS_PER_DATAGRAM: usize = 7;
+
+/// Writes 5 seconds of `big_buck_bunny_10s_audio_aac.rtp` as an audio-only HLS playlist
+/// with AAC in `segment_type` (`"mpeg_ts"` or `"fmp4"`) segments. The compositor itself is
+/// used to produce the stream, so tests of MPEG-TS and fragmented MP4 based inputs do not
+/// depend on external tools.
+pub fn write_aac_hls_playlist(dir: &Path, segment_type: &str) -> Result<()> {
+    let instance = CompositorInstance::start();
+    let input_port = instance.get_port();
+
+    instance.send_request(
+        "output/output_1/register",
+        json!({
+            "type": "hls",
+            "directory": dir,
+            "segment_duration_ms": 1000,
+            "playlist_size": 10,
+            "segment_type": segment_type,
+            "audio": {
+                "encoder": {
+                    "type": "aac",
+                    "channels": "stereo",
+                },
+                "initial": {
+                    "inputs": [
+                        {
+                            "input_id": "input_1",
+                        },
+                    ]
+                },
+            },
+        }),
+    )?;
+
+    instance.send_request(
+        "output/output_1/unregister",
+        json!({
+            "schedule_time_ms": 5000,
+        }),
+    )?;
+
+    instance.send_request(
+        "input/input_1/register",
+        json!({
+            "type": "rtp_stream",
+            "transport_protocol": "tcp_server",
+            "port": input_port,
+            "audio": {
+                "decoder": "aac",
+                "audio_specific_config": "1210",
+                "rtp_mode": "high_bitrate",
+            },
+        }),
+    )?;
+
+    let packets_dump = input_dump_from_disk("big_buck_bunny_10s_audio_aac.rtp")?;
+    let mut packet_sender = PacketSender::new(CommunicationProtocol::Tcp, input_port)?;
+    packet_sender.send(&packets_dump)?;
+
+    instance.send_request("start", json!({}))?;
+
+    thread::sleep(Duration::from_secs(7));
+    Ok(())
+}
+
+/// Concatenates all segments listed in the `index.m3u8` playlist in `dir`. For fragmented
+/// MP4 segments, the initialization segment is written first, so the result is a single
+/// fragmented MP4 stream.
+pub fn read_hls_segments(dir: &Path) -> Result<Bytes> {
+    let playlist = fs::read_to_string(dir.join("index.m3u8"))?;
+    let mut stream = BytesMut::new();
+    for line in playlist.lines() {
+        let file = match line.strip_prefix("#EXT-X-MAP:URI=") {
+            Some(uri) => uri.split(',').next().unwrap_or_default().trim_matches('"'),
+            None if !line.is_empty() && !line.starts_with('#') => line,
+            None => continue,
+        };
+        stream.extend_from_slice(&fs::read(dir.join(file))?);
+    }
+    Ok(stream.freeze())
+}
+
+/// Sends an MPEG-TS stream to a local UDP port. Datagrams are spread out a bit, so the
+/// receiving socket buffer is not overrun.
+pub fn send_mpeg_ts_over_udp(port: u16, stream: &[u8]) -> Result<()> {
+    let socket = UdpSocket::bind("127.0.0.1:0")?;
+    socket.connect(("127.0.0.1", port))?;
+    for datagram in stream.chunks(188 * TS_PACKETS_PER_DATAGRAM) {
+        socket.send(datagram)?;
+        thread::sleep(Duration::from_millis(1));
+    }
+    Ok(())
+}
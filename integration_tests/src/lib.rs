@@ -1,3 +1,4 @@
+mod aac_stream;
 mod audio_decoder;
 mod common;
 mod compositor_instance;
@@ -9,6 +10,7 @@ mod video_decoder;
 #[cfg(test)]
 mod tests;
 
+pub use aac_stream::*;
 pub use common::*;
 pub use compositor_instance::*;
 pub use output_receiver::*;
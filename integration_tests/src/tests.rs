@@ -1,7 +1,15 @@
 mod aac;
 mod audio_mixing;
+mod hls_input;
+mod hls_output;
+mod mp4_output;
+mod mp4_push_input;
+mod mpeg_ts_input;
 mod muxed_video_audio;
 mod push_input_before_start;
 mod required_inputs;
+mod rtmp_output;
+mod rtp_output_sdp;
 mod schedule_update;
+mod srt_input;
 mod unregistering;
@@ -0,0 +1,254 @@
+use std::{
+    fs,
+    io::{BufRead, BufReader, Write},
+    net::{Ipv4Addr, TcpListener, TcpStream},
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
+
+use anyhow::{anyhow, Result};
+use serde_json::json;
+
+use crate::{
+    audible_audio_duration, audio_decoder::AudioChannels, input_dump_from_disk, unmarshal_packets,
+    write_aac_hls_playlist, CommunicationProtocol, CompositorInstance, OutputReceiver,
+    PacketSender,
+};
+
+/// Check if HLS input plays a VOD playlist served over HTTP until the end.
+///
+/// Write 5 seconds of `input_1` as an HLS playlist, serve the directory with a local
+/// HTTP server and send the stream pulled by the HLS input to an RTP output.
+#[test]
+pub fn hls_input() -> Result<()> {
+    let instance = CompositorInstance::start();
+    let input_port = instance.get_port();
+    let output_port = instance.get_port();
+    let playlist_dir = std::env::temp_dir().join(format!(
+        "live-compositor-hls-input-test-{}",
+        rand::random::<u64>()
+    ));
+
+    instance.send_request(
+        "output/output_1/register",
+        json!({
+            "type": "hls",
+            "directory": playlist_dir,
+            "segment_duration_ms": 1000,
+            "playlist_size": 10,
+            "video": {
+                "resolution": {
+                    "width": 640,
+                    "height": 360,
+                },
+                "encoder": {
+                    "type": "ffmpeg_h264",
+                    "preset": "ultrafast",
+                    "keyframe_interval": 30,
+                },
+                "initial": {
+                    "root": {
+                        "id": "input_1",
+                        "type": "input_stream",
+                        "input_id": "input_1",
+                    }
+                }
+            }
+        }),
+    )?;
+
+    instance.send_request(
+        "output/output_1/unregister",
+        json!({
+            "schedule_time_ms": 5000,
+        }),
+    )?;
+
+    instance.send_request(
+        "input/input_1/register",
+        json!({
+            "type": "rtp_stream",
+            "transport_protocol": "tcp_server",
+            "port": input_port,
+            "video": {
+                "decoder": "ffmpeg_h264"
+            },
+        }),
+    )?;
+
+    let packets_dump = input_dump_from_disk("8_colors_long_input_video.rtp")?;
+    let mut packet_sender = PacketSender::new(CommunicationProtocol::Tcp, input_port)?;
+    packet_sender.send(&packets_dump)?;
+
+    instance.send_request("start", json!({}))?;
+
+    thread::sleep(Duration::from_secs(7));
+
+    let http_port = start_http_server(playlist_dir.clone())?;
+
+    instance.send_request(
+        "output/output_2/register",
+        json!({
+            "type": "rtp_stream",
+            "transport_protocol": "tcp_server",
+            "port": output_port,
+            "video": {
+                "resolution": {
+                    "width": 640,
+                    "height": 360,
+                },
+                "encoder": {
+                    "type": "ffmpeg_h264",
+                    "preset": "ultrafast",
+                },
+                "send_eos_when": {
+                    "all_inputs": true,
+                },
+                "initial": {
+                    "root": {
+                        "id": "input_2",
+                        "type": "input_stream",
+                        "input_id": "input_2",
+                    }
+                }
+            }
+        }),
+    )?;
+
+    let output_receiver = OutputReceiver::start(output_port, CommunicationProtocol::Tcp)?;
+
+    instance.send_request(
+        "input/input_2/register",
+        json!({
+            "type": "hls",
+            "url": format!("http://127.0.0.1:{http_port}/index.m3u8"),
+        }),
+    )?;
+
+    let packets = unmarshal_packets(&output_receiver.wait_for_output()?)?;
+    let (Some(first), Some(last)) = (packets.first(), packets.last()) else {
+        return Err(anyhow!("Output does not contain any packets."));
+    };
+    let duration_secs =
+        last.header.timestamp.wrapping_sub(first.header.timestamp) as f64 / 90_000.0;
+    if !(3.0..7.0).contains(&duration_secs) {
+        return Err(anyhow!(
+            "Expected about 5 seconds of video, received {duration_secs:.2} seconds."
+        ));
+    }
+
+    fs::remove_dir_all(playlist_dir)?;
+    Ok(())
+}
+
+/// Check if AAC audio from MPEG-TS segments of an HLS playlist is decoded.
+#[test]
+pub fn hls_input_aac() -> Result<()> {
+    let playlist_dir = std::env::temp_dir().join(format!(
+        "live-compositor-hls-input-aac-test-{}",
+        rand::random::<u64>()
+    ));
+    write_aac_hls_playlist(&playlist_dir, "mpeg_ts")?;
+    let http_port = start_http_server(playlist_dir.clone())?;
+
+    let instance = CompositorInstance::start();
+    let output_port = instance.get_port();
+
+    let output_receiver = OutputReceiver::start(output_port, CommunicationProtocol::Udp)?;
+
+    instance.send_request(
+        "output/output_1/register",
+        json!({
+            "type": "rtp_stream",
+            "transport_protocol": "udp",
+            "ip": "127.0.0.1",
+            "port": output_port,
+            "audio": {
+                "initial": {
+                    "inputs": [
+                        {
+                            "input_id": "input_1",
+                        },
+                    ]
+                },
+                "encoder": {
+                    "type": "opus",
+                    "channels": "stereo",
+                }
+            },
+        }),
+    )?;
+
+    instance.send_request(
+        "output/output_1/unregister",
+        json!({
+            "schedule_time_ms": 7000,
+        }),
+    )?;
+
+    instance.send_request(
+        "input/input_1/register",
+        json!({
+            "type": "hls",
+            "url": format!("http://127.0.0.1:{http_port}/index.m3u8"),
+            "offset_ms": 0,
+        }),
+    )?;
+
+    instance.send_request("start", json!({}))?;
+
+    let output_dump = output_receiver.wait_for_output()?;
+    let audible = audible_audio_duration(&output_dump, AudioChannels::Stereo)?;
+    if audible < Duration::from_secs(3) {
+        return Err(anyhow!(
+            "Expected about 5 seconds of audio, received {:.2} seconds.",
+            audible.as_secs_f64()
+        ));
+    }
+
+    fs::remove_dir_all(playlist_dir)?;
+    Ok(())
+}
+
+/// Serves files from a directory over HTTP on a random port. Only the request line is
+/// interpreted, every request is answered with the requested file.
+fn start_http_server(dir: PathBuf) -> Result<u16> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+    let port = listener.local_addr()?.port();
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let dir = dir.clone();
+            thread::spawn(move || serve_file(stream, &dir));
+        }
+    });
+    Ok(port)
+}
+
+fn serve_file(mut stream: TcpStream, dir: &Path) -> Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header == "\r\n" {
+            break;
+        }
+    }
+
+    let path = request_line.split_whitespace().nth(1).unwrap_or("/");
+    match fs::read(dir.join(path.trim_start_matches('/'))) {
+        Ok(body) => {
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                body.len()
+            )?;
+            stream.write_all(&body)?;
+        }
+        Err(_) => stream.write_all(
+            b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        )?,
+    }
+    Ok(())
+}
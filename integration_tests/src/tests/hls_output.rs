@@ -0,0 +1,231 @@
+use std::{fs, path::Path, thread, time::Duration};
+
+use anyhow::{anyhow, Context, Result};
+use serde_json::json;
+
+use crate::{input_dump_from_disk, CommunicationProtocol, CompositorInstance, PacketSender};
+
+/// Check if HLS output writes a playlist that only references segments
+/// that exist on disk.
+///
+/// Show `input_1` for 10 seconds using 1 second segments.
+#[test]
+pub fn hls_output() -> Result<()> {
+    let instance = CompositorInstance::start();
+    let input_port = instance.get_port();
+    let output_dir = std::env::temp_dir().join(format!(
+        "live-compositor-hls-test-{}",
+        rand::random::<u64>()
+    ));
+
+    instance.send_request(
+        "output/output_1/register",
+        json!({
+            "type": "hls",
+            "directory": output_dir,
+            "segment_duration_ms": 1000,
+            "playlist_size": 3,
+            "video": {
+                "resolution": {
+                    "width": 640,
+                    "height": 360,
+                },
+                "encoder": {
+                    "type": "ffmpeg_h264",
+                    "preset": "ultrafast",
+                    "ffmpeg_options": {
+                        "g": "30"
+                    }
+                },
+                "initial": {
+                    "root": {
+                        "id": "input_1",
+                        "type": "input_stream",
+                        "input_id": "input_1",
+                    }
+                }
+            }
+        }),
+    )?;
+
+    instance.send_request(
+        "output/output_1/unregister",
+        json!({
+            "schedule_time_ms": 10000,
+        }),
+    )?;
+
+    instance.send_request(
+        "input/input_1/register",
+        json!({
+            "type": "rtp_stream",
+            "transport_protocol": "tcp_server",
+            "port": input_port,
+            "video": {
+                "decoder": "ffmpeg_h264"
+            },
+        }),
+    )?;
+
+    let packets_dump = input_dump_from_disk("8_colors_long_input_video.rtp")?;
+    let mut packet_sender = PacketSender::new(CommunicationProtocol::Tcp, input_port)?;
+    packet_sender.send(&packets_dump)?;
+
+    instance.send_request("start", json!({}))?;
+
+    thread::sleep(Duration::from_secs(12));
+
+    let playlist = fs::read_to_string(output_dir.join("index.m3u8"))?;
+    let segments: Vec<&str> = playlist
+        .lines()
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .collect();
+
+    if segments.is_empty() || segments.len() > 3 {
+        return Err(anyhow!(
+            "Expected between 1 and 3 segments in the playlist, found {}:\n{playlist}",
+            segments.len()
+        ));
+    }
+    for segment in segments {
+        if !output_dir.join(segment).exists() {
+            return Err(anyhow!(
+                "Segment {segment} from the playlist does not exist."
+            ));
+        }
+    }
+
+    fs::remove_dir_all(output_dir)?;
+    Ok(())
+}
+
+/// Check if HLS output with renditions writes a master playlist listing all
+/// streams, and if the rendition segments are downscaled.
+///
+/// Show `input_1` for 5 seconds at 640x360 with a 320x180 rendition.
+#[test]
+pub fn hls_output_renditions() -> Result<()> {
+    let instance = CompositorInstance::start();
+    let input_port = instance.get_port();
+    let output_dir = std::env::temp_dir().join(format!(
+        "live-compositor-hls-renditions-test-{}",
+        rand::random::<u64>()
+    ));
+
+    instance.send_request(
+        "output/output_1/register",
+        json!({
+            "type": "hls",
+            "directory": output_dir,
+            "segment_duration_ms": 1000,
+            "video": {
+                "resolution": {
+                    "width": 640,
+                    "height": 360,
+                },
+                "encoder": {
+                    "type": "ffmpeg_h264",
+                    "preset": "ultrafast",
+                    "bitrate_mode": { "type": "cbr", "bitrate": 1_000_000 },
+                    "ffmpeg_options": {
+                        "g": "30"
+                    }
+                },
+                "initial": {
+                    "root": {
+                        "id": "input_1",
+                        "type": "input_stream",
+                        "input_id": "input_1",
+                    }
+                }
+            },
+            "renditions": [{
+                "resolution": {
+                    "width": 320,
+                    "height": 180,
+                },
+                "encoder": {
+                    "type": "ffmpeg_h264",
+                    "preset": "ultrafast",
+                    "bitrate_mode": { "type": "cbr", "bitrate": 300_000 },
+                    "ffmpeg_options": {
+                        "g": "30"
+                    }
+                },
+            }]
+        }),
+    )?;
+
+    instance.send_request(
+        "output/output_1/unregister",
+        json!({
+            "schedule_time_ms": 5000,
+        }),
+    )?;
+
+    instance.send_request(
+        "input/input_1/register",
+        json!({
+            "type": "rtp_stream",
+            "transport_protocol": "tcp_server",
+            "port": input_port,
+            "video": {
+                "decoder": "ffmpeg_h264"
+            },
+        }),
+    )?;
+
+    let packets_dump = input_dump_from_disk("8_colors_long_input_video.rtp")?;
+    let mut packet_sender = PacketSender::new(CommunicationProtocol::Tcp, input_port)?;
+    packet_sender.send(&packets_dump)?;
+
+    instance.send_request("start", json!({}))?;
+
+    thread::sleep(Duration::from_secs(7));
+
+    let master_playlist = fs::read_to_string(output_dir.join("master.m3u8"))?;
+    let expected_variants = [
+        "#EXT-X-STREAM-INF:BANDWIDTH=1000000,RESOLUTION=640x360,CODECS=\"avc1.64001f\"\nindex.m3u8",
+        "#EXT-X-STREAM-INF:BANDWIDTH=300000,RESOLUTION=320x180,CODECS=\"avc1.64001e\"\nrendition_0/index.m3u8",
+    ];
+    for variant in expected_variants {
+        if !master_playlist.contains(variant) {
+            return Err(anyhow!(
+                "Master playlist does not list the variant:\n{variant}\n\n{master_playlist}"
+            ));
+        }
+    }
+
+    for (playlist_dir, expected_resolution) in [
+        (output_dir.clone(), (640, 360)),
+        (output_dir.join("rendition_0"), (320, 180)),
+    ] {
+        let playlist = fs::read_to_string(playlist_dir.join("index.m3u8"))?;
+        let segment = playlist
+            .lines()
+            .find(|line| !line.is_empty() && !line.starts_with('#'))
+            .ok_or_else(|| anyhow!("No segments in the playlist:\n{playlist}"))?;
+        let resolution = segment_resolution(&playlist_dir.join(segment))?;
+        if resolution != expected_resolution {
+            return Err(anyhow!(
+                "Expected segment {segment} resolution {expected_resolution:?}, found {resolution:?}."
+            ));
+        }
+    }
+
+    fs::remove_dir_all(output_dir)?;
+    Ok(())
+}
+
+fn segment_resolution(segment_path: &Path) -> Result<(u32, u32)> {
+    let input_ctx = ffmpeg_next::format::input(&segment_path)
+        .with_context(|| format!("Failed to open segment {segment_path:?}"))?;
+    let stream = input_ctx
+        .streams()
+        .best(ffmpeg_next::media::Type::Video)
+        .ok_or_else(|| anyhow!("No video stream in segment {segment_path:?}"))?;
+    let decoder = ffmpeg_next::codec::Context::from_parameters(stream.parameters())?
+        .decoder()
+        .video()?;
+    Ok((decoder.width(), decoder.height()))
+}
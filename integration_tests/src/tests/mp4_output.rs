@@ -0,0 +1,106 @@
+use std::{fs, thread, time::Duration};
+
+use anyhow::{anyhow, Context, Result};
+use serde_json::json;
+
+use crate::{input_dump_from_disk, CommunicationProtocol, CompositorInstance, PacketSender};
+
+/// Check if MP4 output writes a file that can be read back, with H264 parameter
+/// sets stored in the `avcC` box.
+///
+/// Show `input_1` for 5 seconds.
+#[test]
+pub fn mp4_output() -> Result<()> {
+    let instance = CompositorInstance::start();
+    let input_port = instance.get_port();
+    let output_path = std::env::temp_dir().join(format!(
+        "live-compositor-mp4-test-{}.mp4",
+        rand::random::<u64>()
+    ));
+
+    instance.send_request(
+        "output/output_1/register",
+        json!({
+            "type": "mp4",
+            "path": output_path,
+            "video": {
+                "resolution": {
+                    "width": 640,
+                    "height": 360,
+                },
+                "encoder": {
+                    "type": "ffmpeg_h264",
+                    "preset": "ultrafast",
+                },
+                "initial": {
+                    "root": {
+                        "id": "input_1",
+                        "type": "input_stream",
+                        "input_id": "input_1",
+                    }
+                }
+            }
+        }),
+    )?;
+
+    instance.send_request(
+        "output/output_1/unregister",
+        json!({
+            "schedule_time_ms": 5000,
+        }),
+    )?;
+
+    instance.send_request(
+        "input/input_1/register",
+        json!({
+            "type": "rtp_stream",
+            "transport_protocol": "tcp_server",
+            "port": input_port,
+            "video": {
+                "decoder": "ffmpeg_h264"
+            },
+        }),
+    )?;
+
+    let packets_dump = input_dump_from_disk("8_colors_long_input_video.rtp")?;
+    let mut packet_sender = PacketSender::new(CommunicationProtocol::Tcp, input_port)?;
+    packet_sender.send(&packets_dump)?;
+
+    instance.send_request("start", json!({}))?;
+
+    thread::sleep(Duration::from_secs(7));
+
+    let input_ctx = ffmpeg_next::format::input(&output_path).context("Failed to open MP4 file")?;
+    let stream = input_ctx
+        .streams()
+        .best(ffmpeg_next::media::Type::Video)
+        .ok_or_else(|| anyhow!("MP4 file does not contain a video track."))?;
+
+    let parameters = stream.parameters();
+    if parameters.id() != ffmpeg_next::codec::Id::H264 {
+        return Err(anyhow!("Expected H264 track, got {:?}.", parameters.id()));
+    }
+    // `avcC` box starts with configuration version 1.
+    let extradata: &[u8] = unsafe {
+        let codecpar = &*parameters.as_ptr();
+        match codecpar.extradata.is_null() {
+            true => &[],
+            false => {
+                std::slice::from_raw_parts(codecpar.extradata, codecpar.extradata_size as usize)
+            }
+        }
+    };
+    if extradata.first() != Some(&1) {
+        return Err(anyhow!("H264 track does not contain a valid avcC box."));
+    }
+
+    let duration = stream.duration() as f64 * f64::from(stream.time_base());
+    if !(4.0..=6.0).contains(&duration) {
+        return Err(anyhow!(
+            "Expected video track of about 5 seconds, got {duration}s."
+        ));
+    }
+
+    fs::remove_file(output_path)?;
+    Ok(())
+}
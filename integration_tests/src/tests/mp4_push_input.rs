@@ -0,0 +1,94 @@
+use std::{fs, thread, time::Duration};
+
+use anyhow::{anyhow, Result};
+use serde_json::json;
+
+use crate::{
+    audible_audio_duration, audio_decoder::AudioChannels, read_hls_segments,
+    write_aac_hls_playlist, CommunicationProtocol, CompositorInstance, OutputReceiver,
+};
+
+/// Check if AAC audio from a fragmented MP4 stream pushed to an MP4 input is decoded.
+#[test]
+pub fn mp4_push_input_aac() -> Result<()> {
+    let stream_dir = std::env::temp_dir().join(format!(
+        "live-compositor-mp4-push-input-test-{}",
+        rand::random::<u64>()
+    ));
+    write_aac_hls_playlist(&stream_dir, "fmp4")?;
+    let stream = read_hls_segments(&stream_dir)?;
+
+    let instance = CompositorInstance::start();
+    let output_port = instance.get_port();
+
+    let output_receiver = OutputReceiver::start(output_port, CommunicationProtocol::Udp)?;
+
+    instance.send_request(
+        "output/output_1/register",
+        json!({
+            "type": "rtp_stream",
+            "transport_protocol": "udp",
+            "ip": "127.0.0.1",
+            "port": output_port,
+            "audio": {
+                "initial": {
+                    "inputs": [
+                        {
+                            "input_id": "input_1",
+                        },
+                    ]
+                },
+                "encoder": {
+                    "type": "opus",
+                    "channels": "stereo",
+                }
+            },
+        }),
+    )?;
+
+    instance.send_request(
+        "output/output_1/unregister",
+        json!({
+            "schedule_time_ms": 6000,
+        }),
+    )?;
+
+    instance.send_request(
+        "input/input_1/register",
+        json!({
+            "type": "mp4",
+            "push": {
+                "audio": true,
+            },
+            "offset_ms": 0,
+        }),
+    )?;
+
+    instance.send_request("start", json!({}))?;
+
+    // The body is read only as fast as the input consumes it.
+    let http_client = instance.http_client.clone();
+    let push_url = format!(
+        "http://127.0.0.1:{}/api/input/input_1/fmp4",
+        instance.api_port
+    );
+    thread::spawn(move || {
+        http_client
+            .post(push_url)
+            .timeout(Duration::from_secs(30))
+            .body(stream.to_vec())
+            .send()
+    });
+
+    let output_dump = output_receiver.wait_for_output()?;
+    let audible = audible_audio_duration(&output_dump, AudioChannels::Stereo)?;
+    if audible < Duration::from_secs(3) {
+        return Err(anyhow!(
+            "Expected about 5 seconds of audio, received {:.2} seconds.",
+            audible.as_secs_f64()
+        ));
+    }
+
+    fs::remove_dir_all(stream_dir)?;
+    Ok(())
+}
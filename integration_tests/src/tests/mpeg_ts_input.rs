@@ -0,0 +1,88 @@
+use std::{fs, thread, time::Duration};
+
+use anyhow::{anyhow, Result};
+use serde_json::json;
+
+use crate::{
+    audible_audio_duration, audio_decoder::AudioChannels, read_hls_segments, send_mpeg_ts_over_udp,
+    write_aac_hls_playlist, CommunicationProtocol, CompositorInstance, OutputReceiver,
+};
+
+/// Check if AAC audio from an MPEG-TS stream received over UDP is decoded.
+///
+/// MPEG-TS carries AAC in ADTS frames without an out-of-band AudioSpecificConfig, so the
+/// decoder has to configure itself from the first frame header.
+#[test]
+pub fn mpeg_ts_input_aac() -> Result<()> {
+    let stream_dir = std::env::temp_dir().join(format!(
+        "live-compositor-mpeg-ts-input-test-{}",
+        rand::random::<u64>()
+    ));
+    write_aac_hls_playlist(&stream_dir, "mpeg_ts")?;
+    let stream = read_hls_segments(&stream_dir)?;
+
+    let instance = CompositorInstance::start();
+    let input_port = instance.get_port();
+    let output_port = instance.get_port();
+
+    let output_receiver = OutputReceiver::start(output_port, CommunicationProtocol::Udp)?;
+
+    instance.send_request(
+        "output/output_1/register",
+        json!({
+            "type": "rtp_stream",
+            "transport_protocol": "udp",
+            "ip": "127.0.0.1",
+            "port": output_port,
+            "audio": {
+                "initial": {
+                    "inputs": [
+                        {
+                            "input_id": "input_1",
+                        },
+                    ]
+                },
+                "encoder": {
+                    "type": "opus",
+                    "channels": "stereo",
+                }
+            },
+        }),
+    )?;
+
+    instance.send_request(
+        "output/output_1/unregister",
+        json!({
+            "schedule_time_ms": 6000,
+        }),
+    )?;
+
+    instance.send_request(
+        "input/input_1/register",
+        json!({
+            "type": "mpeg_ts",
+            "port": input_port,
+            "audio": {
+                "decoder": "aac",
+            },
+        }),
+    )?;
+
+    instance.send_request("start", json!({}))?;
+
+    // Give ffmpeg time to bind the UDP socket.
+    thread::sleep(Duration::from_secs(1));
+    send_mpeg_ts_over_udp(input_port, &stream)?;
+
+    let output_dump = output_receiver.wait_for_output()?;
+    let audible = audible_audio_duration(&output_dump, AudioChannels::Stereo)?;
+    if audible < Duration::from_secs(2) {
+        return Err(anyhow!(
+            "Expected about 5 seconds of audio, received {:.2} seconds.",
+            audible.as_secs_f64()
+        ));
+    }
+
+    fs::remove_dir_all(stream_dir)?;
+    Ok(())
+}
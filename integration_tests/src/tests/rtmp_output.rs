@@ -0,0 +1,118 @@
+use std::{thread, time::Duration};
+
+use anyhow::{anyhow, Context, Result};
+use ffmpeg_next::{codec::Id, Dictionary};
+use serde_json::json;
+
+use crate::{input_dump_from_disk, CommunicationProtocol, CompositorInstance, PacketSender};
+
+/// Check if RTMP output delivers H264 video and AAC audio to an RTMP server.
+///
+/// Stream `input_1` with audio to a local RTMP listener for 5 seconds.
+#[test]
+pub fn rtmp_output() -> Result<()> {
+    let instance = CompositorInstance::start();
+    let input_port = instance.get_port();
+    let rtmp_port = instance.get_port();
+    let url = format!("rtmp://127.0.0.1:{rtmp_port}/live/test");
+
+    let server_url = url.clone();
+    let server = thread::spawn(move || -> Result<(usize, usize)> {
+        let options = Dictionary::from_iter([("listen", "1"), ("timeout", "30")]);
+        let mut input_ctx = ffmpeg_next::format::input_with_dictionary(&server_url, options)
+            .context("Failed to accept RTMP connection")?;
+        let codecs: Vec<(usize, Id)> = input_ctx
+            .streams()
+            .map(|stream| (stream.index(), stream.parameters().id()))
+            .collect();
+
+        let (mut video_packets, mut audio_packets) = (0, 0);
+        for (stream, _) in input_ctx.packets() {
+            match codecs.iter().find(|(index, _)| *index == stream.index()) {
+                Some((_, Id::H264)) => video_packets += 1,
+                Some((_, Id::AAC)) => audio_packets += 1,
+                codec => return Err(anyhow!("Unexpected stream codec: {codec:?}")),
+            }
+        }
+        Ok((video_packets, audio_packets))
+    });
+
+    instance.send_request(
+        "output/output_1/register",
+        json!({
+            "type": "rtmp",
+            "url": url,
+            "video": {
+                "resolution": {
+                    "width": 640,
+                    "height": 360,
+                },
+                "encoder": {
+                    "type": "ffmpeg_h264",
+                    "preset": "ultrafast",
+                },
+                "initial": {
+                    "root": {
+                        "id": "input_1",
+                        "type": "input_stream",
+                        "input_id": "input_1",
+                    }
+                }
+            },
+            "audio": {
+                "initial": {
+                    "inputs": [
+                        {
+                            "input_id": "input_1",
+                        }
+                    ]
+                },
+                "encoder": {
+                    "type": "aac",
+                    "channels": "stereo"
+                }
+            }
+        }),
+    )?;
+
+    instance.send_request(
+        "output/output_1/unregister",
+        json!({
+            "schedule_time_ms": 5000,
+        }),
+    )?;
+
+    instance.send_request(
+        "input/input_1/register",
+        json!({
+            "type": "rtp_stream",
+            "transport_protocol": "tcp_server",
+            "port": input_port,
+            "video": {
+                "decoder": "ffmpeg_h264"
+            },
+            "audio": {
+                "decoder": "opus"
+            }
+        }),
+    )?;
+
+    let packets_dump = input_dump_from_disk("8_colors_input_video_audio.rtp")?;
+    let mut packet_sender = PacketSender::new(CommunicationProtocol::Tcp, input_port)?;
+    packet_sender.send(&packets_dump)?;
+
+    instance.send_request("start", json!({}))?;
+
+    thread::sleep(Duration::from_secs(7));
+
+    let (video_packets, audio_packets) = server
+        .join()
+        .map_err(|_| anyhow!("RTMP server thread panicked."))??;
+    if video_packets == 0 || audio_packets == 0 {
+        return Err(anyhow!(
+            "Expected both video and audio, received {video_packets} video and {audio_packets} audio packets."
+        ));
+    }
+
+    Ok(())
+}
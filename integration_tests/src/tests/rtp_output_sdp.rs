@@ -0,0 +1,133 @@
+use std::{fs, sync::mpsc, thread, time::Duration};
+
+use anyhow::{anyhow, Context, Result};
+use serde_json::json;
+
+use crate::{input_dump_from_disk, CommunicationProtocol, CompositorInstance, PacketSender};
+
+/// Check if FFmpeg can receive a UDP RTP output using the SDP returned by the API.
+///
+/// Show `input_1` for 10 seconds.
+#[test]
+pub fn rtp_output_sdp() -> Result<()> {
+    let instance = CompositorInstance::start();
+    let input_port = instance.get_port();
+    let output_port = instance.get_port();
+    let sdp_path = std::env::temp_dir().join(format!(
+        "live-compositor-rtp-sdp-test-{}.sdp",
+        rand::random::<u64>()
+    ));
+
+    instance.send_request(
+        "output/output_1/register",
+        json!({
+            "type": "rtp_stream",
+            "transport_protocol": "udp",
+            "ip": "127.0.0.1",
+            "port": output_port,
+            "video": {
+                "resolution": {
+                    "width": 640,
+                    "height": 360,
+                },
+                "encoder": {
+                    "type": "ffmpeg_h264",
+                    "preset": "ultrafast",
+                    "keyframe_interval": 30,
+                },
+                "initial": {
+                    "root": {
+                        "id": "input_1",
+                        "type": "input_stream",
+                        "input_id": "input_1",
+                    }
+                }
+            }
+        }),
+    )?;
+
+    instance.send_request(
+        "output/output_1/unregister",
+        json!({
+            "schedule_time_ms": 10000,
+        }),
+    )?;
+
+    instance.send_request(
+        "input/input_1/register",
+        json!({
+            "type": "rtp_stream",
+            "transport_protocol": "tcp_server",
+            "port": input_port,
+            "video": {
+                "decoder": "ffmpeg_h264"
+            },
+        }),
+    )?;
+
+    let packets_dump = input_dump_from_disk("8_colors_long_input_video.rtp")?;
+    let mut packet_sender = PacketSender::new(CommunicationProtocol::Tcp, input_port)?;
+    packet_sender.send(&packets_dump)?;
+
+    instance.send_request("start", json!({}))?;
+
+    let sdp = instance
+        .http_client
+        .get(format!(
+            "http://127.0.0.1:{}/api/output/output_1/sdp",
+            instance.api_port
+        ))
+        .timeout(Duration::from_secs(10))
+        .send()?
+        .error_for_status()?
+        .text()?;
+    if !sdp.contains("a=rtcp-mux\r\n") {
+        return Err(anyhow!("SDP does not signal RTCP multiplexing:\n{sdp}"));
+    }
+    fs::write(&sdp_path, sdp)?;
+
+    // FFmpeg blocks while waiting for packets, a broken stream would hang the test.
+    let (result_sender, result_receiver) = mpsc::channel();
+    let receiver_sdp_path = sdp_path.clone();
+    thread::spawn(move || {
+        let _ = result_sender.send(receive_video_packets(&receiver_sdp_path, 30));
+    });
+    let result = result_receiver
+        .recv_timeout(Duration::from_secs(20))
+        .context("FFmpeg did not receive the stream in time.")?;
+
+    fs::remove_file(sdp_path)?;
+    result
+}
+
+/// Opens the SDP with FFmpeg and reads `count` H264 packets.
+fn receive_video_packets(sdp_path: &std::path::Path, count: usize) -> Result<()> {
+    let mut options = ffmpeg_next::Dictionary::new();
+    options.set("protocol_whitelist", "file,udp,rtp");
+    let mut input_ctx = ffmpeg_next::format::input_with_dictionary(&sdp_path, options)
+        .context("Failed to open SDP")?;
+
+    let stream = input_ctx
+        .streams()
+        .best(ffmpeg_next::media::Type::Video)
+        .ok_or_else(|| anyhow!("SDP does not describe a video stream."))?;
+    if stream.parameters().id() != ffmpeg_next::codec::Id::H264 {
+        return Err(anyhow!(
+            "Expected H264 stream, got {:?}.",
+            stream.parameters().id()
+        ));
+    }
+    let stream_index = stream.index();
+
+    let mut received = 0;
+    while received < count {
+        let mut packet = ffmpeg_next::Packet::empty();
+        packet
+            .read(&mut input_ctx)
+            .context("Failed to read RTP stream")?;
+        if packet.stream() == stream_index && packet.size() > 0 {
+            received += 1;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,119 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use serde_json::json;
+
+use crate::{
+    audible_audio_duration, audio_decoder::AudioChannels, input_dump_from_disk,
+    CommunicationProtocol, CompositorInstance, OutputReceiver, PacketSender,
+};
+
+/// Check if AAC audio received over SRT is decoded.
+///
+/// `input_1` is sent by an SRT output of the same instance to an SRT input (`input_2`),
+/// and the audio of `input_2` is sent to an RTP output.
+#[test]
+pub fn srt_input_aac() -> Result<()> {
+    let instance = CompositorInstance::start();
+    let input_port = instance.get_port();
+    let srt_port = instance.get_port();
+    let output_port = instance.get_port();
+
+    let output_receiver = OutputReceiver::start(output_port, CommunicationProtocol::Udp)?;
+
+    instance.send_request(
+        "input/input_2/register",
+        json!({
+            "type": "srt",
+            "mode": "listener",
+            "port": srt_port,
+            "audio": {
+                "decoder": "aac",
+            },
+        }),
+    )?;
+
+    instance.send_request(
+        "output/output_1/register",
+        json!({
+            "type": "srt",
+            "mode": "caller",
+            "ip": "127.0.0.1",
+            "port": srt_port,
+            "audio": {
+                "encoder": {
+                    "type": "aac",
+                    "channels": "stereo",
+                },
+                "initial": {
+                    "inputs": [
+                        {
+                            "input_id": "input_1",
+                        },
+                    ]
+                },
+            },
+        }),
+    )?;
+
+    instance.send_request(
+        "output/output_2/register",
+        json!({
+            "type": "rtp_stream",
+            "transport_protocol": "udp",
+            "ip": "127.0.0.1",
+            "port": output_port,
+            "audio": {
+                "initial": {
+                    "inputs": [
+                        {
+                            "input_id": "input_2",
+                        },
+                    ]
+                },
+                "encoder": {
+                    "type": "opus",
+                    "channels": "stereo",
+                }
+            },
+        }),
+    )?;
+
+    instance.send_request(
+        "output/output_2/unregister",
+        json!({
+            "schedule_time_ms": 8000,
+        }),
+    )?;
+
+    instance.send_request(
+        "input/input_1/register",
+        json!({
+            "type": "rtp_stream",
+            "transport_protocol": "tcp_server",
+            "port": input_port,
+            "audio": {
+                "decoder": "aac",
+                "audio_specific_config": "1210",
+                "rtp_mode": "high_bitrate",
+            },
+        }),
+    )?;
+
+    let packets_dump = input_dump_from_disk("big_buck_bunny_10s_audio_aac.rtp")?;
+    let mut packet_sender = PacketSender::new(CommunicationProtocol::Tcp, input_port)?;
+    packet_sender.send(&packets_dump)?;
+
+    instance.send_request("start", json!({}))?;
+
+    let output_dump = output_receiver.wait_for_output()?;
+    let audible = audible_audio_duration(&output_dump, AudioChannels::Stereo)?;
+    if audible < Duration::from_secs(3) {
+        return Err(anyhow!(
+            "Expected at least 3 seconds of audio, received {:.2} seconds.",
+            audible.as_secs_f64()
+        ));
+    }
+
+    Ok(())
+}
@@ -75,6 +75,12 @@ pub fn compare_audio_dumps<P: AsRef<Path> + fmt::Debug>(
     Ok(())
 }
 
+/// Returns how long the audio in an RTP dump is not silent. Useful for inputs that can not be
+/// compared with a snapshot, e.g. because the stream is generated by the test itself.
+pub fn audible_audio_duration(actual: &Bytes, channels: AudioChannels) -> Result<Duration> {
+    audio::audible_duration(actual, channels, 100)
+}
+
 fn handle_error<P: AsRef<Path> + fmt::Debug>(
     err: anyhow::Error,
     snapshot_filename: P,
@@ -60,6 +60,41 @@ pub fn validate(
     Ok(())
 }
 
+/// Returns the total duration of decoded audio batches with samples above `threshold`.
+pub fn audible_duration(
+    actual: &Bytes,
+    channels: AudioChannels,
+    threshold: i16,
+) -> Result<Duration> {
+    let packets = unmarshal_packets(actual)?;
+    let audio_packets = find_packets_for_payload_type(&packets, 97);
+
+    let sample_rate = read_config().output_sample_rate;
+    let mut audio_decoder = AudioDecoder::new(sample_rate, channels)?;
+    for packet in audio_packets {
+        audio_decoder.decode(packet)?;
+    }
+
+    let channel_count = match channels {
+        AudioChannels::Mono => 1,
+        AudioChannels::Stereo => 2,
+    };
+    let audible_samples: usize = audio_decoder
+        .take_samples()
+        .iter()
+        .filter(|batch| {
+            batch
+                .samples
+                .iter()
+                .any(|s| s.unsigned_abs() > threshold as u16)
+        })
+        .map(|batch| batch.samples.len() / channel_count)
+        .sum();
+    Ok(Duration::from_secs_f64(
+        audible_samples as f64 / sample_rate as f64,
+    ))
+}
+
 fn find_sample_batches(
     samples: &[AudioSampleBatch],
     time_range: Range<Duration>,
@@ -1,7 +1,8 @@
 use docs_config::DocsConfig;
 use live_compositor::types::{
-    Image, ImageSpec, InputStream, Mp4, Rescaler, RtpInputStream, RtpOutputStream, Shader,
-    ShaderSpec, Text, Tiles, View, WebRendererSpec, WebView,
+    FileInput, HlsInput, HlsOutput, Image, ImageSpec, InputStream, Mp4, Mp4Output, MpegTsInput,
+    RawInput, Rescaler, RtmpOutput, RtpInputStream, RtpOutputStream, RtspInput, Shader, ShaderSpec,
+    SrtInput, SrtOutput, Text, Tiles, View, WebRendererSpec, WebView, WhepOutput, WhipInput,
 };
 use parsing::generate_docs;
 use std::{fs, path::PathBuf};
@@ -26,6 +27,13 @@ fn main() {
         generate_docs::<WebRendererSpec>("WebRenderer", &config),
         generate_docs::<RtpInputStream>("RtpInputStream", &config),
         generate_docs::<Mp4>("Mp4", &config),
+        generate_docs::<WhipInput>("WhipInput", &config),
+        generate_docs::<SrtInput>("SrtInput", &config),
+        generate_docs::<MpegTsInput>("MpegTsInput", &config),
+        generate_docs::<RtspInput>("RtspInput", &config),
+        generate_docs::<FileInput>("FileInput", &config),
+        generate_docs::<HlsInput>("HlsInput", &config),
+        generate_docs::<RawInput>("RawInput", &config),
     ];
 
     let component_pages = [
@@ -39,7 +47,14 @@ fn main() {
         generate_docs::<Rescaler>("Rescaler", &config),
     ];
 
-    let output_pages = [generate_docs::<RtpOutputStream>("OutputStream", &config)];
+    let output_pages = [
+        generate_docs::<RtpOutputStream>("OutputStream", &config),
+        generate_docs::<Mp4Output>("Mp4Output", &config),
+        generate_docs::<HlsOutput>("HlsOutput", &config),
+        generate_docs::<RtmpOutput>("RtmpOutput", &config),
+        generate_docs::<WhepOutput>("WhepOutput", &config),
+        generate_docs::<SrtOutput>("SrtOutput", &config),
+    ];
 
     for page in renderer_pages {
         fs::write(
@@ -3,7 +3,7 @@ use axum::{
     extract::{rejection::JsonRejection, ws::WebSocketUpgrade, FromRequest, Request, State},
     http::StatusCode,
     response::IntoResponse,
-    routing::{get, post},
+    routing::{delete, get, post},
     Router,
 };
 use compositor_pipeline::Pipeline;
@@ -16,20 +16,34 @@ use crate::{
 
 use self::{update_output::handle_output_update, ws::handle_ws_upgrade};
 
+mod input_stats;
+mod mp4_playback;
+mod push;
 mod register_request;
+mod sdp;
 mod unregister_request;
 mod update_output;
+mod whep;
+mod whip;
 mod ws;
 
 pub fn routes(state: ApiState) -> Router {
     let inputs = Router::new()
         .route("/:id/register", post(register_request::handle_input))
-        .route("/:id/unregister", post(unregister_request::handle_input));
+        .route("/:id/unregister", post(unregister_request::handle_input))
+        .route("/:id/stats", get(input_stats::handle_input_stats))
+        .route("/:id/pause", post(mp4_playback::handle_pause))
+        .route("/:id/resume", post(mp4_playback::handle_resume))
+        .route("/:id/seek", post(mp4_playback::handle_seek))
+        .route("/:id/restart", post(mp4_playback::handle_restart))
+        .route("/:id/fmp4", post(push::handle_mp4_push))
+        .route("/:id/frames", post(push::handle_raw_push));
 
     let outputs = Router::new()
         .route("/:id/register", post(register_request::handle_output))
         .route("/:id/unregister", post(unregister_request::handle_output))
-        .route("/:id/update", post(handle_output_update));
+        .route("/:id/update", post(handle_output_update))
+        .route("/:id/sdp", get(sdp::handle_output_sdp));
 
     let image = Router::new()
         .route("/:id/register", post(register_request::handle_image))
@@ -59,6 +73,14 @@ pub fn routes(state: ApiState) -> Router {
         .nest("/api/shader", shader)
         // Start request
         .route("/api/start", post(handle_start))
+        // WHIP ingest
+        .route(
+            "/whip/:id",
+            post(whip::handle_offer).delete(whip::handle_terminate),
+        )
+        // WHEP playback
+        .route("/whep/:id", post(whep::handle_offer))
+        .route("/whep/:id/:session_id", delete(whep::handle_terminate))
         // WebSocket - events
         .route("/ws", get(ws_handler))
         .route(
@@ -0,0 +1,50 @@
+use axum::extract::{Path, State};
+use compositor_pipeline::pipeline::input::rtp::{RtpInputStats, RtpTrackStats};
+use serde::Serialize;
+
+use crate::{error::ApiError, state::ApiState, types::InputId};
+
+#[derive(Serialize, Debug)]
+pub(super) struct InputStatsResponse {
+    video: Option<TrackStats>,
+    audio: Option<TrackStats>,
+}
+
+#[derive(Serialize, Debug)]
+struct TrackStats {
+    packets_received: u64,
+    packets_lost: u64,
+    packets_late: u64,
+    nacks_sent: u64,
+    plis_sent: u64,
+}
+
+/// Returns packet loss statistics of an RTP, RTSP or WHIP input.
+pub(super) async fn handle_input_stats(
+    State(api): State<ApiState>,
+    Path(input_id): Path<InputId>,
+) -> Result<axum::Json<InputStatsResponse>, ApiError> {
+    let stats = api.pipeline().rtp_input_stats(&input_id.into())?;
+    Ok(axum::Json(stats.into()))
+}
+
+impl From<RtpInputStats> for InputStatsResponse {
+    fn from(stats: RtpInputStats) -> Self {
+        Self {
+            video: stats.video.map(Into::into),
+            audio: stats.audio.map(Into::into),
+        }
+    }
+}
+
+impl From<RtpTrackStats> for TrackStats {
+    fn from(stats: RtpTrackStats) -> Self {
+        Self {
+            packets_received: stats.packets_received,
+            packets_lost: stats.packets_lost,
+            packets_late: stats.packets_late,
+            nacks_sent: stats.nacks_sent,
+            plis_sent: stats.plis_sent,
+        }
+    }
+}
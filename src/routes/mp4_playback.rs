@@ -0,0 +1,61 @@
+use std::time::Duration;
+
+use axum::extract::{Path, State};
+use serde::Deserialize;
+
+use crate::{
+    error::ApiError,
+    state::{ApiState, Response},
+    types::InputId,
+};
+
+use super::Json;
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub(super) struct SeekRequest {
+    /// Position in the MP4 file in milliseconds.
+    position_ms: f64,
+}
+
+pub(super) async fn handle_pause(
+    State(api): State<ApiState>,
+    Path(input_id): Path<InputId>,
+) -> Result<Response, ApiError> {
+    api.pipeline().mp4_playback(&input_id.into())?.pause()?;
+    Ok(Response::Ok {})
+}
+
+pub(super) async fn handle_resume(
+    State(api): State<ApiState>,
+    Path(input_id): Path<InputId>,
+) -> Result<Response, ApiError> {
+    api.pipeline().mp4_playback(&input_id.into())?.resume()?;
+    Ok(Response::Ok {})
+}
+
+/// Continues playback of an MP4 input from the keyframe preceding the requested position.
+pub(super) async fn handle_seek(
+    State(api): State<ApiState>,
+    Path(input_id): Path<InputId>,
+    Json(request): Json<SeekRequest>,
+) -> Result<Response, ApiError> {
+    if !request.position_ms.is_finite() || request.position_ms < 0.0 {
+        return Err(ApiError::malformed_request(
+            &"`position_ms` has to be a non-negative number.",
+        ));
+    }
+    let position = Duration::from_secs_f64(request.position_ms / 1000.0);
+    api.pipeline()
+        .mp4_playback(&input_id.into())?
+        .seek(position)?;
+    Ok(Response::Ok {})
+}
+
+pub(super) async fn handle_restart(
+    State(api): State<ApiState>,
+    Path(input_id): Path<InputId>,
+) -> Result<Response, ApiError> {
+    api.pipeline().mp4_playback(&input_id.into())?.restart()?;
+    Ok(Response::Ok {})
+}
@@ -0,0 +1,58 @@
+use axum::{
+    body::Body,
+    extract::{Path, State},
+};
+use bytes::Bytes;
+use futures_util::StreamExt;
+
+use crate::{
+    error::ApiError,
+    state::{ApiState, Response},
+    types::InputId,
+};
+
+/// Appends the request body to the fragmented MP4 stream of an MP4 input registered with
+/// `push`. The stream can be sent in one long (e.g. chunked) request or split into many
+/// requests sent one after another.
+pub(super) async fn handle_mp4_push(
+    State(api): State<ApiState>,
+    Path(input_id): Path<InputId>,
+    body: Body,
+) -> Result<Response, ApiError> {
+    let sender = api.pipeline().mp4_push_sender(&input_id.into())?;
+    push_body(body, move |data| sender.push(data)).await
+}
+
+/// Appends the request body to the frame stream of a raw input registered without a Unix
+/// socket. Frames don't have to be aligned with requests, a single frame can be split
+/// between requests and a single request can contain many frames.
+pub(super) async fn handle_raw_push(
+    State(api): State<ApiState>,
+    Path(input_id): Path<InputId>,
+    body: Body,
+) -> Result<Response, ApiError> {
+    let sender = api.pipeline().raw_push_sender(&input_id.into())?;
+    push_body(body, move |data| sender.push(data)).await
+}
+
+/// Pushing blocks while the input is behind, so it runs on the blocking thread pool. The
+/// next part of the body is read only after the previous one was accepted by the input.
+async fn push_body<E>(
+    body: Body,
+    push: impl Fn(Bytes) -> Result<(), E> + Clone + Send + 'static,
+) -> Result<Response, ApiError>
+where
+    E: Send + 'static,
+    ApiError: From<E>,
+{
+    let mut stream = body.into_data_stream();
+    while let Some(data) = stream.next().await {
+        let data = data.map_err(|err| ApiError::malformed_request(&err))?;
+        let push = push.clone();
+        tokio::task::spawn_blocking(move || push(data))
+            .await
+            // `unwrap()` panics only when the task panicked
+            .unwrap()?;
+    }
+    Ok(Response::Ok {})
+}
@@ -7,8 +7,9 @@ use crate::{
     routes::Json,
     state::{Pipeline, Response},
     types::{
-        ImageSpec, InputId, Mp4, OutputId, RendererId, RtpInputStream, RtpOutputStream, ShaderSpec,
-        WebRendererSpec,
+        FileInput, HlsInput, HlsOutput, ImageSpec, InputId, Mp4, Mp4Output, MpegTsInput, OutputId,
+        RawInput, RendererId, RtmpOutput, RtpInputStream, RtpOutputStream, RtspInput, ShaderSpec,
+        SrtInput, SrtOutput, WebRendererSpec, WhepOutput, WhipInput,
     },
 };
 
@@ -19,12 +20,24 @@ use super::ApiState;
 pub enum RegisterInput {
     RtpStream(RtpInputStream),
     Mp4(Mp4),
+    Whip(WhipInput),
+    Srt(SrtInput),
+    MpegTs(MpegTsInput),
+    Rtsp(RtspInput),
+    File(FileInput),
+    Hls(HlsInput),
+    Raw(RawInput),
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum RegisterOutput {
     RtpStream(RtpOutputStream),
+    Mp4(Mp4Output),
+    Hls(HlsOutput),
+    Rtmp(RtmpOutput),
+    Whep(WhepOutput),
+    Srt(SrtOutput),
 }
 
 pub(super) async fn handle_input(
@@ -41,6 +54,27 @@ pub(super) async fn handle_input(
             RegisterInput::Mp4(mp4) => {
                 Pipeline::register_input(&api.pipeline, input_id.into(), mp4.try_into()?)?
             }
+            RegisterInput::Whip(whip) => {
+                Pipeline::register_input(&api.pipeline, input_id.into(), whip.try_into()?)?
+            }
+            RegisterInput::Srt(srt) => {
+                Pipeline::register_input(&api.pipeline, input_id.into(), srt.try_into()?)?
+            }
+            RegisterInput::MpegTs(mpeg_ts) => {
+                Pipeline::register_input(&api.pipeline, input_id.into(), mpeg_ts.try_into()?)?
+            }
+            RegisterInput::Rtsp(rtsp) => {
+                Pipeline::register_input(&api.pipeline, input_id.into(), rtsp.try_into()?)?
+            }
+            RegisterInput::File(file) => {
+                Pipeline::register_input(&api.pipeline, input_id.into(), file.try_into()?)?
+            }
+            RegisterInput::Hls(hls) => {
+                Pipeline::register_input(&api.pipeline, input_id.into(), hls.try_into()?)?
+            }
+            RegisterInput::Raw(raw) => {
+                Pipeline::register_input(&api.pipeline, input_id.into(), raw.try_into()?)?
+            }
         };
         match response {
             Some(Port(port)) => Ok(Response::RegisteredPort { port }),
@@ -63,6 +97,21 @@ pub(super) async fn handle_output(
             RegisterOutput::RtpStream(rtp) => {
                 Pipeline::register_output(&mut api.pipeline(), output_id.into(), rtp.try_into()?)?
             }
+            RegisterOutput::Mp4(mp4) => {
+                Pipeline::register_output(&mut api.pipeline(), output_id.into(), mp4.try_into()?)?
+            }
+            RegisterOutput::Hls(hls) => {
+                Pipeline::register_output(&mut api.pipeline(), output_id.into(), hls.try_into()?)?
+            }
+            RegisterOutput::Rtmp(rtmp) => {
+                Pipeline::register_output(&mut api.pipeline(), output_id.into(), rtmp.try_into()?)?
+            }
+            RegisterOutput::Whep(whep) => {
+                Pipeline::register_output(&mut api.pipeline(), output_id.into(), whep.try_into()?)?
+            }
+            RegisterOutput::Srt(srt) => {
+                Pipeline::register_output(&mut api.pipeline(), output_id.into(), srt.try_into()?)?
+            }
         };
         match response {
             Some(Port(port)) => Ok(Response::RegisteredPort { port }),
@@ -0,0 +1,19 @@
+use axum::{
+    extract::{Path, State},
+    http::header,
+    response::IntoResponse,
+};
+
+use crate::{error::ApiError, state::ApiState, types::OutputId};
+
+use super::whip::SDP_CONTENT_TYPE;
+
+/// Returns the SDP describing the stream sent by an RTP output, e.g. to be passed
+/// to FFmpeg or GStreamer.
+pub(super) async fn handle_output_sdp(
+    State(api): State<ApiState>,
+    Path(output_id): Path<OutputId>,
+) -> Result<impl IntoResponse, ApiError> {
+    let sdp = api.pipeline().rtp_output_sdp(&output_id.into())?;
+    Ok(([(header::CONTENT_TYPE, SDP_CONTENT_TYPE)], sdp.to_string()))
+}
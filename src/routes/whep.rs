@@ -0,0 +1,43 @@
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+};
+
+use crate::{error::ApiError, state::ApiState, types::OutputId};
+
+use super::whip::{validate_sdp_content_type, SDP_CONTENT_TYPE};
+
+/// Handles an SDP offer sent by a WHEP viewer. Responds with an SDP answer and
+/// the URL of the created session resource.
+pub(super) async fn handle_offer(
+    State(api): State<ApiState>,
+    Path(output_id): Path<OutputId>,
+    headers: HeaderMap,
+    offer: String,
+) -> Result<impl IntoResponse, ApiError> {
+    validate_sdp_content_type(&headers)?;
+
+    let output_id: compositor_render::OutputId = output_id.into();
+    let sessions = api.pipeline().whep_sessions(&output_id)?;
+    let (session_id, answer) = sessions.handle_offer(offer).await?;
+
+    Ok((
+        StatusCode::CREATED,
+        [
+            (header::CONTENT_TYPE, SDP_CONTENT_TYPE.to_string()),
+            (header::LOCATION, format!("/whep/{output_id}/{session_id}")),
+        ],
+        answer,
+    ))
+}
+
+/// Terminates a single viewer session. The output stays registered.
+pub(super) async fn handle_terminate(
+    State(api): State<ApiState>,
+    Path((output_id, session_id)): Path<(OutputId, String)>,
+) -> Result<StatusCode, ApiError> {
+    let sessions = api.pipeline().whep_sessions(&output_id.into())?;
+    sessions.terminate(&session_id).await?;
+    Ok(StatusCode::OK)
+}
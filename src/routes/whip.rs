@@ -0,0 +1,58 @@
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+};
+
+use crate::{error::ApiError, state::ApiState, types::InputId};
+
+pub(super) const SDP_CONTENT_TYPE: &str = "application/sdp";
+
+/// Handles an SDP offer sent by a WHIP client. Responds with an SDP answer and
+/// the URL of the created session resource.
+pub(super) async fn handle_offer(
+    State(api): State<ApiState>,
+    Path(input_id): Path<InputId>,
+    headers: HeaderMap,
+    offer: String,
+) -> Result<impl IntoResponse, ApiError> {
+    validate_sdp_content_type(&headers)?;
+
+    let input_id: compositor_render::InputId = input_id.into();
+    let location = format!("/whip/{input_id}");
+    let session = api.pipeline().whip_session(&input_id)?;
+    let answer = session.handle_offer(offer).await?;
+
+    Ok((
+        StatusCode::CREATED,
+        [
+            (header::CONTENT_TYPE, SDP_CONTENT_TYPE.to_string()),
+            (header::LOCATION, location),
+        ],
+        answer,
+    ))
+}
+
+/// Terminates the WHIP session. The input stays registered.
+pub(super) async fn handle_terminate(
+    State(api): State<ApiState>,
+    Path(input_id): Path<InputId>,
+) -> Result<StatusCode, ApiError> {
+    let session = api.pipeline().whip_session(&input_id.into())?;
+    session.terminate().await?;
+    Ok(StatusCode::OK)
+}
+
+pub(super) fn validate_sdp_content_type(headers: &HeaderMap) -> Result<(), ApiError> {
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok());
+    if content_type != Some(SDP_CONTENT_TYPE) {
+        return Err(ApiError::new(
+            "UNSUPPORTED_CONTENT_TYPE",
+            format!("SDP offer has to be sent with \"Content-Type: {SDP_CONTENT_TYPE}\"."),
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+        ));
+    }
+    Ok(())
+}
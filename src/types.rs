@@ -20,6 +20,8 @@ mod renderer;
 mod util;
 mod video;
 
+#[cfg(test)]
+mod from_register_output_test;
 #[cfg(test)]
 mod from_util_test;
 
@@ -44,10 +46,34 @@ pub use component::WebView;
 #[allow(unused_imports)]
 pub use register_input::Mp4;
 #[allow(unused_imports)]
+pub use register_output::HlsOutput;
+#[allow(unused_imports)]
+pub use register_output::Mp4Output;
+#[allow(unused_imports)]
+pub use register_output::RtmpOutput;
+#[allow(unused_imports)]
 pub use register_output::RtpOutputStream;
+#[allow(unused_imports)]
+pub use register_output::SrtOutput;
+#[allow(unused_imports)]
+pub use register_output::WhepOutput;
 
+#[allow(unused_imports)]
+pub use register_input::FileInput;
+#[allow(unused_imports)]
+pub use register_input::HlsInput;
+#[allow(unused_imports)]
+pub use register_input::MpegTsInput;
+#[allow(unused_imports)]
+pub use register_input::RawInput;
 #[allow(unused_imports)]
 pub use register_input::RtpInputStream;
+#[allow(unused_imports)]
+pub use register_input::RtspInput;
+#[allow(unused_imports)]
+pub use register_input::SrtInput;
+#[allow(unused_imports)]
+pub use register_input::WhipInput;
 
 #[allow(unused_imports)]
 pub use renderer::ImageSpec;
@@ -1,14 +1,16 @@
-use std::time::Duration;
+use std::{net::Ipv4Addr, time::Duration};
 
 use bytes::Bytes;
 use compositor_pipeline::{
     pipeline::{
         self, decoder,
         input::{self, rtp::InputAudioStream},
+        sdp::SdpRtpStream,
     },
     queue,
 };
 
+use super::from_util::srt_connection_options;
 use super::register_input::*;
 use super::util::*;
 
@@ -50,6 +52,7 @@ impl TryFrom<InputRtpAudioOptions> for InputAudioStream {
                     options: decoder::AudioDecoderOptions::Opus(decoder::OpusDecoderOptions {
                         forward_error_correction,
                     }),
+                    payload_type: pipeline::rtp::AUDIO_PAYLOAD_TYPE,
                 })
             }
             InputRtpAudioOptions::Aac {
@@ -75,6 +78,7 @@ impl TryFrom<InputRtpAudioOptions> for InputAudioStream {
                         depayloader_mode,
                         asc: Some(asc),
                     }),
+                    payload_type: pipeline::rtp::AUDIO_PAYLOAD_TYPE,
                 })
             }
         }
@@ -87,35 +91,85 @@ impl TryFrom<RtpInputStream> for pipeline::RegisterInputOptions {
     fn try_from(value: RtpInputStream) -> Result<Self, Self::Error> {
         let RtpInputStream {
             port,
+            ip,
+            sdp,
             video,
             audio,
+            jitter_buffer_latency_ms,
             required,
             offset_ms,
             transport_protocol,
+            ssrc,
         } = value;
 
         const NO_VIDEO_AUDIO_SPEC: &str =
-            "At least one of `video` and `audio` has to be specified in `register_input` request.";
+            "At least one of `video`, `audio` and `sdp` has to be specified in `register_input` request.";
+        const SDP_AND_STREAM_SPEC: &str =
+            "Fields `video` and `audio` can not be specified together with `sdp`.";
+        const NO_PORT_SPEC: &str = "Field `port` is required when `sdp` is not specified.";
+        const SSRC_WITH_TCP: &str =
+            "Field `ssrc` can only be specified for inputs with \"udp\" transport protocol.";
+        const EMPTY_SSRC: &str = "Field `ssrc` has to contain at least one SSRC.";
+        const NO_IP_SPEC: &str =
+            "Field `ip` is required for inputs with \"tcp_client\" transport protocol.";
+        const IP_WITHOUT_TCP_CLIENT: &str =
+            "Field `ip` can only be specified for inputs with \"tcp_client\" transport protocol.";
 
-        if video.is_none() && audio.is_none() {
-            return Err(TypeError::new(NO_VIDEO_AUDIO_SPEC));
+        let transport_protocol = transport_protocol.unwrap_or(TransportProtocol::Udp);
+        if let Some(ssrc) = &ssrc {
+            if !matches!(transport_protocol, TransportProtocol::Udp) {
+                return Err(TypeError::new(SSRC_WITH_TCP));
+            }
+            if ssrc.is_empty() {
+                return Err(TypeError::new(EMPTY_SSRC));
+            }
         }
+        let transport_protocol = match (transport_protocol, ip) {
+            (TransportProtocol::Udp, None) => pipeline::rtp::TransportProtocol::Udp,
+            (TransportProtocol::TcpServer, None) => pipeline::rtp::TransportProtocol::TcpServer,
+            (TransportProtocol::TcpClient, Some(ip)) => {
+                pipeline::rtp::TransportProtocol::TcpClient { ip }
+            }
+            (TransportProtocol::TcpClient, None) => return Err(TypeError::new(NO_IP_SPEC)),
+            (_, Some(_)) => return Err(TypeError::new(IP_WITHOUT_TCP_CLIENT)),
+        };
 
-        let rtp_stream = input::rtp::RtpStream {
-            video: video.as_ref().map(|video| input::rtp::InputVideoStream {
-                options: match video {
-                    InputRtpVideoOptions::FfmepgH264 => decoder::VideoDecoderOptions {
-                        codec: pipeline::VideoCodec::H264,
-                    },
-                },
-            }),
-            audio: audio.map(TryFrom::try_from).transpose()?,
+        let (port, rtp_stream) = match sdp {
+            Some(sdp) => {
+                if video.is_some() || audio.is_some() {
+                    return Err(TypeError::new(SDP_AND_STREAM_SPEC));
+                }
+                // Inline session descriptions always start with the protocol version line.
+                let sdp = match sdp.trim_start().starts_with("v=") {
+                    true => sdp,
+                    false => std::fs::read_to_string(&sdp)?,
+                };
+                let SdpRtpStream {
+                    port: sdp_port,
+                    stream,
+                } = SdpRtpStream::parse(&sdp)?;
+                (port.unwrap_or(PortOrPortRange::U16(sdp_port)), stream)
+            }
+            None => {
+                if video.is_none() && audio.is_none() {
+                    return Err(TypeError::new(NO_VIDEO_AUDIO_SPEC));
+                }
+                let stream = input::rtp::RtpStream {
+                    video: video.map(Into::into),
+                    audio: audio.map(TryFrom::try_from).transpose()?,
+                };
+                (port.ok_or(TypeError::new(NO_PORT_SPEC))?, stream)
+            }
         };
 
         let input_options = input::InputOptions::Rtp(input::rtp::RtpReceiverOptions {
             port: port.try_into()?,
             stream: rtp_stream,
-            transport_protocol: transport_protocol.unwrap_or(TransportProtocol::Udp).into(),
+            transport_protocol,
+            jitter_buffer_latency: jitter_buffer_latency_ms
+                .map(|latency_ms| Duration::from_millis(latency_ms as u64))
+                .unwrap_or(input::rtp::DEFAULT_JITTER_BUFFER_LATENCY),
+            ssrc,
         });
 
         let queue_options = queue::InputOptions {
@@ -130,6 +184,361 @@ impl TryFrom<RtpInputStream> for pipeline::RegisterInputOptions {
     }
 }
 
+impl TryFrom<WhipInput> for pipeline::RegisterInputOptions {
+    type Error = TypeError;
+
+    fn try_from(value: WhipInput) -> Result<Self, Self::Error> {
+        let WhipInput {
+            video,
+            audio,
+            required,
+            offset_ms,
+        } = value;
+
+        const NO_VIDEO_AUDIO_SPEC: &str =
+            "At least one of `video` and `audio` has to be specified in `register_input` request.";
+
+        if video.is_none() && audio.is_none() {
+            return Err(TypeError::new(NO_VIDEO_AUDIO_SPEC));
+        }
+        if let Some(InputRtpAudioOptions::Aac { .. }) = audio {
+            return Err(TypeError::new("WHIP input supports only Opus audio."));
+        }
+
+        let stream = input::rtp::RtpStream {
+            video: video.map(Into::into),
+            audio: audio.map(TryFrom::try_from).transpose()?,
+        };
+
+        let queue_options = queue::InputOptions {
+            required: required.unwrap_or(false),
+            offset: offset_ms.map(|offset_ms| Duration::from_secs_f64(offset_ms / 1000.0)),
+        };
+
+        Ok(pipeline::RegisterInputOptions {
+            input_options: input::InputOptions::Whip(input::whip::WhipReceiverOptions { stream }),
+            queue_options,
+        })
+    }
+}
+
+impl TryFrom<SrtInput> for pipeline::RegisterInputOptions {
+    type Error = TypeError;
+
+    fn try_from(value: SrtInput) -> Result<Self, Self::Error> {
+        let SrtInput {
+            mode,
+            port,
+            ip,
+            latency_ms,
+            passphrase,
+            video,
+            audio,
+            required,
+            offset_ms,
+        } = value;
+
+        const NO_VIDEO_AUDIO_SPEC: &str =
+            "At least one of `video` and `audio` has to be specified in `register_input` request.";
+
+        if video.is_none() && audio.is_none() {
+            return Err(TypeError::new(NO_VIDEO_AUDIO_SPEC));
+        }
+
+        let connection = srt_connection_options(
+            mode.unwrap_or(SrtMode::Listener),
+            port,
+            ip,
+            latency_ms,
+            passphrase,
+        )?;
+
+        let queue_options = queue::InputOptions {
+            required: required.unwrap_or(false),
+            offset: offset_ms.map(|offset_ms| Duration::from_secs_f64(offset_ms / 1000.0)),
+        };
+
+        Ok(pipeline::RegisterInputOptions {
+            input_options: input::InputOptions::Srt(input::srt::SrtReceiverOptions {
+                connection,
+                video: video.map(Into::into),
+                audio: audio.map(Into::into),
+            }),
+            queue_options,
+        })
+    }
+}
+
+impl TryFrom<MpegTsInput> for pipeline::RegisterInputOptions {
+    type Error = TypeError;
+
+    fn try_from(value: MpegTsInput) -> Result<Self, Self::Error> {
+        let MpegTsInput {
+            port,
+            multicast_group,
+            multicast_interface,
+            video,
+            audio,
+            required,
+            offset_ms,
+        } = value;
+
+        const NO_VIDEO_AUDIO_SPEC: &str =
+            "At least one of `video` and `audio` has to be specified in `register_input` request.";
+
+        if video.is_none() && audio.is_none() {
+            return Err(TypeError::new(NO_VIDEO_AUDIO_SPEC));
+        }
+
+        let multicast =
+            match (multicast_group, multicast_interface) {
+                (Some(group), interface) => {
+                    let group: Ipv4Addr = group.parse().map_err(|_| {
+                        TypeError::new("`multicast_group` has to be a valid IPv4 address.")
+                    })?;
+                    if !group.is_multicast() {
+                        return Err(TypeError::new(
+                            "`multicast_group` has to be an IPv4 multicast address (224.0.0.0/4).",
+                        ));
+                    }
+                    let interface = match interface {
+                        Some(interface) => interface.parse().map_err(|_| {
+                            TypeError::new("`multicast_interface` has to be a valid IPv4 address.")
+                        })?,
+                        None => Ipv4Addr::UNSPECIFIED,
+                    };
+                    Some(input::mpeg_ts::MulticastOptions { group, interface })
+                }
+                (None, Some(_)) => return Err(TypeError::new(
+                    "`multicast_interface` can only be specified together with `multicast_group`.",
+                )),
+                (None, None) => None,
+            };
+
+        let queue_options = queue::InputOptions {
+            required: required.unwrap_or(false),
+            offset: offset_ms.map(|offset_ms| Duration::from_secs_f64(offset_ms / 1000.0)),
+        };
+
+        Ok(pipeline::RegisterInputOptions {
+            input_options: input::InputOptions::MpegTs(input::mpeg_ts::MpegTsReceiverOptions {
+                port: port.try_into()?,
+                multicast,
+                video: video.map(Into::into),
+                audio: audio.map(Into::into),
+            }),
+            queue_options,
+        })
+    }
+}
+
+impl TryFrom<RtspInput> for pipeline::RegisterInputOptions {
+    type Error = TypeError;
+
+    fn try_from(value: RtspInput) -> Result<Self, Self::Error> {
+        let RtspInput {
+            url,
+            transport_protocol,
+            video,
+            audio,
+            required,
+            offset_ms,
+        } = value;
+
+        if !url.starts_with("rtsp://") {
+            return Err(TypeError::new("`url` has to start with \"rtsp://\"."));
+        }
+
+        let video = video.unwrap_or(true);
+        let audio = audio.unwrap_or(true);
+        if !video && !audio {
+            return Err(TypeError::new(
+                "At least one of `video` and `audio` has to be enabled in `register_input` request.",
+            ));
+        }
+
+        let transport = match transport_protocol.unwrap_or(RtspTransportProtocol::Tcp) {
+            RtspTransportProtocol::Tcp => input::rtsp::RtspTransport::Tcp,
+            RtspTransportProtocol::Udp => input::rtsp::RtspTransport::Udp,
+        };
+
+        let queue_options = queue::InputOptions {
+            required: required.unwrap_or(false),
+            offset: offset_ms.map(|offset_ms| Duration::from_secs_f64(offset_ms / 1000.0)),
+        };
+
+        Ok(pipeline::RegisterInputOptions {
+            input_options: input::InputOptions::Rtsp(input::rtsp::RtspReceiverOptions {
+                url,
+                transport,
+                video,
+                audio,
+            }),
+            queue_options,
+        })
+    }
+}
+
+impl TryFrom<FileInput> for pipeline::RegisterInputOptions {
+    type Error = TypeError;
+
+    fn try_from(value: FileInput) -> Result<Self, Self::Error> {
+        let FileInput {
+            path,
+            video,
+            audio,
+            required,
+            offset_ms,
+        } = value;
+
+        let track = |track: Option<FileInputTrack>| match track {
+            Some(FileInputTrack { index, language }) => input::file::FileTrack::Select {
+                index: index.map(|index| index as usize),
+                language,
+            },
+            None => input::file::FileTrack::Disabled,
+        };
+        let (video, audio) = match (video, audio) {
+            (None, None) => (
+                input::file::FileTrack::Default,
+                input::file::FileTrack::Default,
+            ),
+            (video, audio) => (track(video), track(audio)),
+        };
+
+        let queue_options = queue::InputOptions {
+            required: required.unwrap_or(false),
+            offset: offset_ms.map(|offset_ms| Duration::from_secs_f64(offset_ms / 1000.0)),
+        };
+
+        Ok(pipeline::RegisterInputOptions {
+            input_options: input::InputOptions::File(input::file::FileInputOptions {
+                path: path.into(),
+                video,
+                audio,
+            }),
+            queue_options,
+        })
+    }
+}
+
+impl TryFrom<HlsInput> for pipeline::RegisterInputOptions {
+    type Error = TypeError;
+
+    fn try_from(value: HlsInput) -> Result<Self, Self::Error> {
+        let HlsInput {
+            url,
+            path,
+            video,
+            audio,
+            required,
+            offset_ms,
+        } = value;
+
+        const BAD_URL_PATH_SPEC: &str =
+            "Exactly one of `url` or `path` has to be specified in a register request for an HLS input.";
+
+        let url = match (url, path) {
+            (Some(url), None) => url,
+            (None, Some(path)) => path,
+            _ => return Err(TypeError::new(BAD_URL_PATH_SPEC)),
+        };
+
+        let video = video.unwrap_or(true);
+        let audio = audio.unwrap_or(true);
+        if !video && !audio {
+            return Err(TypeError::new(
+                "At least one of `video` and `audio` has to be enabled in `register_input` request.",
+            ));
+        }
+
+        let queue_options = queue::InputOptions {
+            required: required.unwrap_or(false),
+            offset: offset_ms.map(|offset_ms| Duration::from_secs_f64(offset_ms / 1000.0)),
+        };
+
+        Ok(pipeline::RegisterInputOptions {
+            input_options: input::InputOptions::Hls(input::hls::HlsInputOptions {
+                url,
+                video,
+                audio,
+            }),
+            queue_options,
+        })
+    }
+}
+
+impl TryFrom<RawInput> for pipeline::RegisterInputOptions {
+    type Error = TypeError;
+
+    fn try_from(value: RawInput) -> Result<Self, Self::Error> {
+        let RawInput {
+            unix_socket_path,
+            required,
+            offset_ms,
+        } = value;
+
+        let transport = match unix_socket_path {
+            Some(path) => input::raw::RawInputTransport::UnixSocket(path.into()),
+            None => input::raw::RawInputTransport::Push,
+        };
+
+        let queue_options = queue::InputOptions {
+            required: required.unwrap_or(false),
+            offset: offset_ms.map(|offset_ms| Duration::from_secs_f64(offset_ms / 1000.0)),
+        };
+
+        Ok(pipeline::RegisterInputOptions {
+            input_options: input::InputOptions::Raw(input::raw::RawInputOptions { transport }),
+            queue_options,
+        })
+    }
+}
+
+impl From<InputMpegTsVideoOptions> for decoder::VideoDecoderOptions {
+    fn from(value: InputMpegTsVideoOptions) -> Self {
+        match value {
+            InputMpegTsVideoOptions::FfmpegH264 => decoder::VideoDecoderOptions {
+                codec: pipeline::VideoCodec::H264,
+            },
+        }
+    }
+}
+
+impl From<InputMpegTsAudioOptions> for decoder::AudioDecoderOptions {
+    fn from(value: InputMpegTsAudioOptions) -> Self {
+        match value {
+            InputMpegTsAudioOptions::Aac => {
+                // The decoder takes the config from the first ADTS header.
+                decoder::AudioDecoderOptions::Aac(decoder::AacDecoderOptions {
+                    depayloader_mode: None,
+                    asc: None,
+                })
+            }
+            InputMpegTsAudioOptions::Opus => {
+                decoder::AudioDecoderOptions::Opus(decoder::OpusDecoderOptions {
+                    forward_error_correction: false,
+                })
+            }
+        }
+    }
+}
+
+impl From<InputRtpVideoOptions> for input::rtp::InputVideoStream {
+    fn from(value: InputRtpVideoOptions) -> Self {
+        let codec = match value {
+            InputRtpVideoOptions::FfmepgH264 => pipeline::VideoCodec::H264,
+            InputRtpVideoOptions::FfmpegVp8 => pipeline::VideoCodec::Vp8,
+            InputRtpVideoOptions::FfmpegVp9 => pipeline::VideoCodec::Vp9,
+            InputRtpVideoOptions::FfmpegAv1 => pipeline::VideoCodec::Av1,
+        };
+        input::rtp::InputVideoStream {
+            options: decoder::VideoDecoderOptions { codec },
+            payload_type: pipeline::rtp::VIDEO_PAYLOAD_TYPE,
+        }
+    }
+}
+
 impl TryFrom<Mp4> for pipeline::RegisterInputOptions {
     type Error = TypeError;
 
@@ -137,20 +546,33 @@ impl TryFrom<Mp4> for pipeline::RegisterInputOptions {
         let Mp4 {
             url,
             path,
+            push,
+            should_loop,
             required,
             offset_ms,
         } = value;
 
         const BAD_URL_PATH_SPEC: &str =
-            "Exactly one of `url` or `path` has to be specified in a register request for an mp4 input.";
+            "Exactly one of `url`, `path` or `push` has to be specified in a register request for an mp4 input.";
+        const NO_PUSHED_TRACKS: &str =
+            "At least one of `push.video` and `push.audio` has to be enabled.";
+        const LOOP_WITH_PUSH: &str = "`loop` can not be used with a pushed MP4 stream.";
 
-        let source = match (url, path) {
-            (Some(_), Some(_)) | (None, None) => {
-                return Err(TypeError::new(BAD_URL_PATH_SPEC));
+        let source = match (url, path, push) {
+            (Some(url), None, None) => input::mp4::Source::Url(url),
+            (None, Some(path), None) => input::mp4::Source::File(path.into()),
+            (None, None, Some(push)) => {
+                let video = push.video.unwrap_or(false);
+                let audio = push.audio.unwrap_or(false);
+                if !video && !audio {
+                    return Err(TypeError::new(NO_PUSHED_TRACKS));
+                }
+                if should_loop == Some(true) {
+                    return Err(TypeError::new(LOOP_WITH_PUSH));
+                }
+                input::mp4::Source::Push { video, audio }
             }
-
-            (Some(url), None) => input::mp4::Source::Url(url),
-            (None, Some(path)) => input::mp4::Source::File(path.into()),
+            _ => return Err(TypeError::new(BAD_URL_PATH_SPEC)),
         };
 
         let queue_options = queue::InputOptions {
@@ -159,7 +581,10 @@ impl TryFrom<Mp4> for pipeline::RegisterInputOptions {
         };
 
         Ok(pipeline::RegisterInputOptions {
-            input_options: input::InputOptions::Mp4(input::mp4::Mp4Options { source }),
+            input_options: input::InputOptions::Mp4(input::mp4::Mp4Options {
+                source,
+                should_loop: should_loop.unwrap_or(false),
+            }),
             queue_options,
         })
     }
@@ -1,13 +1,13 @@
+use std::{collections::HashMap, path::PathBuf, time::Duration};
+
 use compositor_pipeline::pipeline::{
     self,
-    encoder::{
-        self,
-        ffmpeg_h264::{self, Options},
-    },
+    encoder::{self, ffmpeg_h264},
     output::{self, rtp::RtpSenderOptions},
     rtp,
 };
 
+use super::from_util::srt_connection_options;
 use super::register_output::*;
 use super::util::*;
 use super::*;
@@ -22,6 +22,7 @@ impl TryFrom<RtpOutputStream> for pipeline::RegisterOutputOptions {
             transport_protocol,
             video,
             audio,
+            sdp_path,
         } = request;
 
         if video.is_none() && audio.is_none() {
@@ -30,51 +31,10 @@ impl TryFrom<RtpOutputStream> for pipeline::RegisterOutputOptions {
             ));
         }
 
-        let output_video_options = match video.clone() {
-            Some(v) => {
-                if v.resolution.width % 2 != 0 || v.resolution.height % 2 != 0 {
-                    return Err(TypeError::new(
-                        "Output video width and height has to be divisible by 2",
-                    ));
-                };
-
-                let VideoEncoderOptions::FfmpegH264 {
-                    preset,
-                    ffmpeg_options,
-                } = v.encoder;
-
-                Some(pipeline::OutputVideoOptions {
-                    initial: v.initial.try_into()?,
-                    encoder_opts: pipeline::encoder::VideoEncoderOptions::H264(Options {
-                        preset: preset.into(),
-                        resolution: v.resolution.into(),
-                        raw_options: ffmpeg_options.unwrap_or_default().into_iter().collect(),
-                    }),
-                    end_condition: v.send_eos_when.unwrap_or_default().try_into()?,
-                })
-            }
-            None => None,
-        };
-
-        let output_audio_options = match audio.clone() {
-            Some(a) => {
-                let AudioEncoderOptions::Opus {
-                    channels,
-                    preset,
-                    forward_error_correction,
-                } = a.encoder;
-
-                Some(pipeline::OutputAudioOptions {
-                    initial: a.initial.try_into()?,
-                    channels: channels.into(),
-                    forward_error_correction: forward_error_correction.unwrap_or(false),
-                    encoder_preset: preset.unwrap_or(OpusEncoderPreset::Voip).into(),
-                    end_condition: a.send_eos_when.unwrap_or_default().try_into()?,
-                    mixing_strategy: a.mixing_strategy.unwrap_or(MixingStrategy::SumClip).into(),
-                })
-            }
-            None => None,
-        };
+        let output_audio_options = audio
+            .clone()
+            .map(|a| output_audio_options(a.mixing_strategy, a.send_eos_when, a.encoder, a.initial))
+            .transpose()?;
 
         let connection_options = match transport_protocol.unwrap_or(TransportProtocol::Udp) {
             TransportProtocol::Udp => {
@@ -104,12 +64,47 @@ impl TryFrom<RtpOutputStream> for pipeline::RegisterOutputOptions {
                     port: port.try_into()?,
                 }
             }
+            TransportProtocol::TcpClient => {
+                let rtp::RequestedPort::Exact(port) = port.try_into()? else {
+                    return Err(TypeError::new(
+                        "Port range can not be used with TCP client connection (transport_protocol=\"tcp_client\").",
+                    ));
+                };
+                let Some(ip) = ip else {
+                    return Err(TypeError::new(
+                        "\"ip\" field is required when registering TCP client connection (transport_protocol=\"tcp_client\").",
+                    ));
+                };
+                output::rtp::RtpConnectionOptions::TcpClient {
+                    port: pipeline::Port(port),
+                    ip,
+                }
+            }
         };
 
+        let output_video_options = video
+            .clone()
+            .map(|v| {
+                let mut options =
+                    output_video_options(v.resolution, v.send_eos_when, v.encoder, v.initial)?;
+                options.renditions = v
+                    .renditions
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|rendition| rtp_video_rendition(rendition, &connection_options))
+                    .collect::<Result<_, _>>()?;
+                Ok::<_, TypeError>(options)
+            })
+            .transpose()?;
+
         let output_options = output::OutputOptions::Rtp(RtpSenderOptions {
             connection_options,
-            video: video.map(|_| pipeline::VideoCodec::H264),
-            audio: audio.map(|_| pipeline::AudioCodec::Opus),
+            video: video.map(|v| video_codec(&v.encoder)),
+            audio: audio.map(|a| match a.encoder {
+                AudioEncoderOptions::Opus { .. } => pipeline::AudioCodec::Opus,
+                AudioEncoderOptions::Aac { .. } => pipeline::AudioCodec::Aac,
+            }),
+            sdp_path: sdp_path.map(Into::into),
         });
 
         Ok(Self {
@@ -120,6 +115,634 @@ impl TryFrom<RtpOutputStream> for pipeline::RegisterOutputOptions {
     }
 }
 
+impl TryFrom<Mp4Output> for pipeline::RegisterOutputOptions {
+    type Error = TypeError;
+
+    fn try_from(request: Mp4Output) -> Result<Self, Self::Error> {
+        let Mp4Output { path, video, audio } = request;
+
+        if video.is_none() && audio.is_none() {
+            return Err(TypeError::new(
+                "At least one of \"video\" and \"audio\" fields have to be specified.",
+            ));
+        }
+        if let Some(OutputVideoOptions {
+            encoder: VideoEncoderOptions::FfmpegVp8 { .. },
+            ..
+        }) = video
+        {
+            return Err(TypeError::new(
+                "MP4 output does not support VP8 video. Use H264, VP9 or AV1 encoder instead.",
+            ));
+        }
+
+        let output_video_options = video
+            .map(|v| output_video_options(v.resolution, v.send_eos_when, v.encoder, v.initial))
+            .transpose()?;
+
+        let output_audio_options = audio
+            .map(|a| output_audio_options(a.mixing_strategy, a.send_eos_when, a.encoder, a.initial))
+            .transpose()?;
+
+        let output_options = output::OutputOptions::Mp4(output::mp4::Mp4OutputOptions {
+            output_path: path.into(),
+        });
+
+        Ok(Self {
+            output_options,
+            video: output_video_options,
+            audio: output_audio_options,
+        })
+    }
+}
+
+impl TryFrom<HlsOutput> for pipeline::RegisterOutputOptions {
+    type Error = TypeError;
+
+    fn try_from(request: HlsOutput) -> Result<Self, Self::Error> {
+        let HlsOutput {
+            directory,
+            segment_duration_ms,
+            playlist_size,
+            segment_type,
+            video,
+            audio,
+            renditions,
+        } = request;
+
+        if video.is_none() && audio.is_none() {
+            return Err(TypeError::new(
+                "At least one of \"video\" and \"audio\" fields have to be specified.",
+            ));
+        }
+        let renditions = renditions.unwrap_or_default();
+        if video.is_none() && !renditions.is_empty() {
+            return Err(TypeError::new(
+                "\"renditions\" can only be specified for outputs with \"video\".",
+            ));
+        }
+        if let Some(OutputAudioOptions {
+            encoder: AudioEncoderOptions::Opus { .. },
+            ..
+        }) = audio
+        {
+            return Err(TypeError::new(
+                "HLS output does not support Opus audio. Use AAC encoder instead.",
+            ));
+        }
+        let video_encoders = video
+            .iter()
+            .map(|video| &video.encoder)
+            .chain(renditions.iter().map(|rendition| &rendition.encoder));
+        for encoder in video_encoders {
+            if let VideoEncoderOptions::FfmpegVp8 { .. }
+            | VideoEncoderOptions::FfmpegVp9 { .. }
+            | VideoEncoderOptions::FfmpegAv1 { .. } = encoder
+            {
+                return Err(TypeError::new("HLS output supports only H264 video."));
+            }
+        }
+
+        let segment_duration_ms = segment_duration_ms.unwrap_or(2000.0);
+        if segment_duration_ms <= 0.0 {
+            return Err(TypeError::new(
+                "\"segment_duration_ms\" has to be a positive number.",
+            ));
+        }
+        let playlist_size = playlist_size.unwrap_or(5);
+        if playlist_size == 0 {
+            return Err(TypeError::new(
+                "\"playlist_size\" has to be greater than 0.",
+            ));
+        }
+
+        let mut output_video_options = video
+            .map(|v| output_video_options(v.resolution, v.send_eos_when, v.encoder, v.initial))
+            .transpose()?;
+
+        let output_audio_options = audio
+            .map(|a| output_audio_options(a.mixing_strategy, a.send_eos_when, a.encoder, a.initial))
+            .transpose()?;
+
+        let output_dir = PathBuf::from(directory);
+        let segment_type = match segment_type.unwrap_or(HlsSegmentType::MpegTs) {
+            HlsSegmentType::MpegTs => output::hls::HlsSegmentType::MpegTs,
+            HlsSegmentType::Fmp4 => output::hls::HlsSegmentType::Fmp4,
+        };
+        let hls_options = |output_dir, variants| output::hls::HlsOutputOptions {
+            output_dir,
+            segment_duration: Duration::from_secs_f64(segment_duration_ms / 1000.0),
+            playlist_size,
+            segment_type,
+            variants,
+        };
+        let audio_encoder_opts = output_audio_options
+            .as_ref()
+            .map(|audio| &audio.encoder_opts);
+
+        let mut variants = Vec::new();
+        if let Some(video) = output_video_options.as_mut() {
+            if !renditions.is_empty() {
+                variants.push(output::hls::HlsVariant::new(
+                    output::hls::PLAYLIST_FILE_NAME.to_string(),
+                    &video.encoder_opts,
+                    audio_encoder_opts,
+                ));
+            }
+            for (index, rendition) in renditions.into_iter().enumerate() {
+                let directory = format!("rendition_{index}");
+                let encoder_opts = video_encoder_options(rendition.resolution, rendition.encoder)?;
+                variants.push(output::hls::HlsVariant::new(
+                    format!("{directory}/{}", output::hls::PLAYLIST_FILE_NAME),
+                    &encoder_opts,
+                    audio_encoder_opts,
+                ));
+                video.renditions.push(pipeline::OutputVideoRendition {
+                    encoder_opts,
+                    output_options: output::OutputOptions::Hls(hls_options(
+                        output_dir.join(directory),
+                        Vec::new(),
+                    )),
+                    with_audio: true,
+                });
+            }
+        }
+
+        let output_options = output::OutputOptions::Hls(hls_options(output_dir, variants));
+
+        Ok(Self {
+            output_options,
+            video: output_video_options,
+            audio: output_audio_options,
+        })
+    }
+}
+
+impl TryFrom<RtmpOutput> for pipeline::RegisterOutputOptions {
+    type Error = TypeError;
+
+    fn try_from(request: RtmpOutput) -> Result<Self, Self::Error> {
+        let RtmpOutput { url, video, audio } = request;
+
+        if video.is_none() && audio.is_none() {
+            return Err(TypeError::new(
+                "At least one of \"video\" and \"audio\" fields have to be specified.",
+            ));
+        }
+        if !url.starts_with("rtmp://") && !url.starts_with("rtmps://") {
+            return Err(TypeError::new(
+                "\"url\" has to start with \"rtmp://\" or \"rtmps://\".",
+            ));
+        }
+        if let Some(OutputAudioOptions {
+            encoder: AudioEncoderOptions::Opus { .. },
+            ..
+        }) = audio
+        {
+            return Err(TypeError::new(
+                "RTMP output does not support Opus audio. Use AAC encoder instead.",
+            ));
+        }
+        if let Some(OutputVideoOptions {
+            encoder:
+                VideoEncoderOptions::FfmpegVp8 { .. }
+                | VideoEncoderOptions::FfmpegVp9 { .. }
+                | VideoEncoderOptions::FfmpegAv1 { .. },
+            ..
+        }) = video
+        {
+            return Err(TypeError::new("RTMP output supports only H264 video."));
+        }
+
+        let output_video_options = video
+            .map(|v| output_video_options(v.resolution, v.send_eos_when, v.encoder, v.initial))
+            .transpose()?;
+
+        let output_audio_options = audio
+            .map(|a| output_audio_options(a.mixing_strategy, a.send_eos_when, a.encoder, a.initial))
+            .transpose()?;
+
+        let output_options =
+            output::OutputOptions::Rtmp(output::rtmp::RtmpSenderOptions { url: url.into() });
+
+        Ok(Self {
+            output_options,
+            video: output_video_options,
+            audio: output_audio_options,
+        })
+    }
+}
+
+impl TryFrom<SrtOutput> for pipeline::RegisterOutputOptions {
+    type Error = TypeError;
+
+    fn try_from(request: SrtOutput) -> Result<Self, Self::Error> {
+        let SrtOutput {
+            mode,
+            port,
+            ip,
+            latency_ms,
+            passphrase,
+            video,
+            audio,
+        } = request;
+
+        if video.is_none() && audio.is_none() {
+            return Err(TypeError::new(
+                "At least one of \"video\" and \"audio\" fields have to be specified.",
+            ));
+        }
+        if let Some(OutputVideoOptions {
+            encoder:
+                VideoEncoderOptions::FfmpegVp8 { .. }
+                | VideoEncoderOptions::FfmpegVp9 { .. }
+                | VideoEncoderOptions::FfmpegAv1 { .. },
+            ..
+        }) = video
+        {
+            return Err(TypeError::new("SRT output supports only H264 video."));
+        }
+
+        let connection = srt_connection_options(
+            mode.unwrap_or(SrtMode::Caller),
+            port,
+            ip,
+            latency_ms,
+            passphrase,
+        )?;
+
+        let output_video_options = video
+            .map(|v| output_video_options(v.resolution, v.send_eos_when, v.encoder, v.initial))
+            .transpose()?;
+
+        let output_audio_options = audio
+            .map(|a| output_audio_options(a.mixing_strategy, a.send_eos_when, a.encoder, a.initial))
+            .transpose()?;
+
+        let output_options =
+            output::OutputOptions::Srt(output::srt::SrtSenderOptions { connection });
+
+        Ok(Self {
+            output_options,
+            video: output_video_options,
+            audio: output_audio_options,
+        })
+    }
+}
+
+impl TryFrom<WhepOutput> for pipeline::RegisterOutputOptions {
+    type Error = TypeError;
+
+    fn try_from(request: WhepOutput) -> Result<Self, Self::Error> {
+        let WhepOutput { video, audio } = request;
+
+        if video.is_none() && audio.is_none() {
+            return Err(TypeError::new(
+                "At least one of \"video\" and \"audio\" fields have to be specified.",
+            ));
+        }
+        if let Some(OutputAudioOptions {
+            encoder: AudioEncoderOptions::Aac { .. },
+            ..
+        }) = audio
+        {
+            return Err(TypeError::new(
+                "WHEP output does not support AAC audio. Use Opus encoder instead.",
+            ));
+        }
+        if let Some(OutputVideoOptions {
+            encoder:
+                VideoEncoderOptions::FfmpegVp8 { .. }
+                | VideoEncoderOptions::FfmpegVp9 { .. }
+                | VideoEncoderOptions::FfmpegAv1 { .. },
+            ..
+        }) = video
+        {
+            return Err(TypeError::new("WHEP output supports only H264 video."));
+        }
+        // Viewers negotiate the constrained baseline profile.
+        if let Some(OutputVideoOptions {
+            encoder:
+                VideoEncoderOptions::FfmpegH264 {
+                    profile, b_frames, ..
+                },
+            ..
+        }) = &video
+        {
+            if matches!(profile, Some(H264Profile::Main | H264Profile::High))
+                || b_frames.unwrap_or(0) > 0
+            {
+                return Err(TypeError::new(
+                    "WHEP output supports only the \"baseline\" H264 profile without B-frames.",
+                ));
+            }
+        }
+
+        let output_options = output::OutputOptions::Whep(output::whep::WhepSenderOptions {
+            video: video.as_ref().map(|_| pipeline::VideoCodec::H264),
+            audio: audio.as_ref().map(|_| pipeline::AudioCodec::Opus),
+        });
+
+        let mut output_video_options = video
+            .map(|v| output_video_options(v.resolution, v.send_eos_when, v.encoder, v.initial))
+            .transpose()?;
+        if let Some(pipeline::OutputVideoOptions {
+            encoder_opts: encoder::VideoEncoderOptions::H264(options),
+            ..
+        }) = &mut output_video_options
+        {
+            options.profile = Some(ffmpeg_h264::Profile::Baseline);
+            options.b_frames = Some(0);
+        }
+
+        let output_audio_options = audio
+            .map(|a| output_audio_options(a.mixing_strategy, a.send_eos_when, a.encoder, a.initial))
+            .transpose()?;
+
+        Ok(Self {
+            output_options,
+            video: output_video_options,
+            audio: output_audio_options,
+        })
+    }
+}
+
+fn rtp_video_rendition(
+    rendition: RtpVideoRendition,
+    connection_options: &output::rtp::RtpConnectionOptions,
+) -> Result<pipeline::OutputVideoRendition, TypeError> {
+    let RtpVideoRendition {
+        port,
+        resolution,
+        encoder,
+        sdp_path,
+    } = rendition;
+
+    let connection_options = match connection_options {
+        output::rtp::RtpConnectionOptions::Udp { ip, .. } => {
+            output::rtp::RtpConnectionOptions::Udp {
+                port: pipeline::Port(port),
+                ip: ip.clone(),
+            }
+        }
+        output::rtp::RtpConnectionOptions::TcpServer { .. } => {
+            output::rtp::RtpConnectionOptions::TcpServer {
+                port: rtp::RequestedPort::Exact(port),
+            }
+        }
+        output::rtp::RtpConnectionOptions::TcpClient { ip, .. } => {
+            output::rtp::RtpConnectionOptions::TcpClient {
+                port: pipeline::Port(port),
+                ip: ip.clone(),
+            }
+        }
+    };
+
+    Ok(pipeline::OutputVideoRendition {
+        output_options: output::OutputOptions::Rtp(RtpSenderOptions {
+            connection_options,
+            video: Some(video_codec(&encoder)),
+            audio: None,
+            sdp_path: sdp_path.map(Into::into),
+        }),
+        encoder_opts: video_encoder_options(resolution, encoder)?,
+        with_audio: false,
+    })
+}
+
+fn video_codec(encoder: &VideoEncoderOptions) -> pipeline::VideoCodec {
+    match encoder {
+        VideoEncoderOptions::FfmpegH264 { .. } => pipeline::VideoCodec::H264,
+        VideoEncoderOptions::FfmpegVp8 { .. } => pipeline::VideoCodec::Vp8,
+        VideoEncoderOptions::FfmpegVp9 { .. } => pipeline::VideoCodec::Vp9,
+        VideoEncoderOptions::FfmpegAv1 { .. } => pipeline::VideoCodec::Av1,
+    }
+}
+
+fn output_video_options(
+    resolution: Resolution,
+    send_eos_when: Option<OutputEndCondition>,
+    encoder: VideoEncoderOptions,
+    initial: Video,
+) -> Result<pipeline::OutputVideoOptions, TypeError> {
+    Ok(pipeline::OutputVideoOptions {
+        initial: initial.try_into()?,
+        encoder_opts: video_encoder_options(resolution, encoder)?,
+        end_condition: send_eos_when.unwrap_or_default().try_into()?,
+        renditions: Vec::new(),
+    })
+}
+
+fn video_encoder_options(
+    resolution: Resolution,
+    encoder: VideoEncoderOptions,
+) -> Result<pipeline::encoder::VideoEncoderOptions, TypeError> {
+    if resolution.width % 2 != 0 || resolution.height % 2 != 0 {
+        return Err(TypeError::new(
+            "Output video width and height has to be divisible by 2",
+        ));
+    };
+
+    let encoder_opts = match encoder {
+        VideoEncoderOptions::FfmpegH264 {
+            preset,
+            bitrate_mode,
+            vbv_buffer_size,
+            keyframe_interval,
+            b_frames,
+            profile,
+            level,
+            ffmpeg_options,
+        } => {
+            if keyframe_interval == Some(0) {
+                return Err(TypeError::new(
+                    "\"keyframe_interval\" has to be greater than 0.",
+                ));
+            }
+            if matches!(profile, Some(H264Profile::Baseline)) && b_frames.unwrap_or(0) > 0 {
+                return Err(TypeError::new(
+                    "\"baseline\" H264 profile does not support B-frames.",
+                ));
+            }
+            pipeline::encoder::VideoEncoderOptions::H264(ffmpeg_h264::Options {
+                preset: preset.into(),
+                resolution: resolution.into(),
+                bitrate_mode: h264_bitrate_mode(bitrate_mode, vbv_buffer_size)?,
+                vbv_buffer_size,
+                keyframe_interval: keyframe_interval.unwrap_or(250),
+                b_frames,
+                profile: profile.map(Into::into),
+                level: level.map(|level| level.parse()).transpose()?,
+                raw_options: ffmpeg_options.unwrap_or_default().into_iter().collect(),
+            })
+        }
+        VideoEncoderOptions::FfmpegVp8 {
+            bitrate,
+            deadline,
+            keyframe_interval,
+            ffmpeg_options,
+        } => pipeline::encoder::VideoEncoderOptions::Vp8(vpx_options(
+            resolution,
+            bitrate,
+            deadline,
+            keyframe_interval,
+            ffmpeg_options,
+        )?),
+        VideoEncoderOptions::FfmpegVp9 {
+            bitrate,
+            deadline,
+            keyframe_interval,
+            ffmpeg_options,
+        } => pipeline::encoder::VideoEncoderOptions::Vp9(vpx_options(
+            resolution,
+            bitrate,
+            deadline,
+            keyframe_interval,
+            ffmpeg_options,
+        )?),
+        VideoEncoderOptions::FfmpegAv1 {
+            bitrate,
+            usage,
+            keyframe_interval,
+            ffmpeg_options,
+        } => {
+            if bitrate == Some(0) {
+                return Err(TypeError::new("\"bitrate\" has to be greater than 0."));
+            }
+            if keyframe_interval == Some(0) {
+                return Err(TypeError::new(
+                    "\"keyframe_interval\" has to be greater than 0.",
+                ));
+            }
+            pipeline::encoder::VideoEncoderOptions::Av1(encoder::ffmpeg_av1::Options {
+                resolution: resolution.into(),
+                bitrate,
+                usage: usage.unwrap_or(Av1Usage::Realtime).into(),
+                keyframe_interval: keyframe_interval.unwrap_or(250),
+                raw_options: ffmpeg_options.unwrap_or_default().into_iter().collect(),
+            })
+        }
+    };
+
+    Ok(encoder_opts)
+}
+
+fn h264_bitrate_mode(
+    bitrate_mode: Option<H264BitrateMode>,
+    vbv_buffer_size: Option<u64>,
+) -> Result<ffmpeg_h264::BitrateMode, TypeError> {
+    let (bitrate_mode, bitrate, max_bitrate) = match bitrate_mode {
+        None => (ffmpeg_h264::BitrateMode::default(), None, None),
+        Some(H264BitrateMode::Crf { crf, max_bitrate }) => {
+            if crf > 51 {
+                return Err(TypeError::new("\"crf\" has to be in the 0-51 range."));
+            }
+            let mode = ffmpeg_h264::BitrateMode::Crf { crf, max_bitrate };
+            (mode, None, max_bitrate)
+        }
+        Some(H264BitrateMode::Cbr { bitrate }) => {
+            let mode = ffmpeg_h264::BitrateMode::Cbr { bitrate };
+            (mode, Some(bitrate), Some(bitrate))
+        }
+        Some(H264BitrateMode::Vbr {
+            bitrate,
+            max_bitrate,
+        }) => {
+            if matches!(max_bitrate, Some(max_bitrate) if max_bitrate < bitrate) {
+                return Err(TypeError::new(
+                    "\"max_bitrate\" has to be greater or equal to \"bitrate\".",
+                ));
+            }
+            let mode = ffmpeg_h264::BitrateMode::Vbr {
+                bitrate,
+                max_bitrate,
+            };
+            (mode, Some(bitrate), max_bitrate)
+        }
+    };
+
+    if bitrate == Some(0) || max_bitrate == Some(0) {
+        return Err(TypeError::new("\"bitrate\" has to be greater than 0."));
+    }
+    match (vbv_buffer_size, max_bitrate) {
+        (Some(0), _) => Err(TypeError::new(
+            "\"vbv_buffer_size\" has to be greater than 0.",
+        )),
+        (Some(_), None) => Err(TypeError::new(
+            "\"vbv_buffer_size\" can only be specified if the bitrate mode defines a max bitrate.",
+        )),
+        _ => Ok(bitrate_mode),
+    }
+}
+
+fn vpx_options(
+    resolution: Resolution,
+    bitrate: Option<u64>,
+    deadline: Option<VpxDeadline>,
+    keyframe_interval: Option<u32>,
+    ffmpeg_options: Option<HashMap<String, String>>,
+) -> Result<encoder::ffmpeg_vpx::Options, TypeError> {
+    if bitrate == Some(0) {
+        return Err(TypeError::new("\"bitrate\" has to be greater than 0."));
+    }
+    if keyframe_interval == Some(0) {
+        return Err(TypeError::new(
+            "\"keyframe_interval\" has to be greater than 0.",
+        ));
+    }
+
+    Ok(encoder::ffmpeg_vpx::Options {
+        resolution: resolution.into(),
+        bitrate,
+        deadline: deadline.unwrap_or(VpxDeadline::Realtime).into(),
+        keyframe_interval: keyframe_interval.unwrap_or(250),
+        raw_options: ffmpeg_options.unwrap_or_default().into_iter().collect(),
+    })
+}
+
+fn output_audio_options(
+    mixing_strategy: Option<MixingStrategy>,
+    send_eos_when: Option<OutputEndCondition>,
+    encoder: AudioEncoderOptions,
+    initial: Audio,
+) -> Result<pipeline::OutputAudioOptions, TypeError> {
+    let encoder_opts = match encoder {
+        AudioEncoderOptions::Opus {
+            channels,
+            preset,
+            forward_error_correction,
+        } => encoder::AudioEncoderOptions::Opus(encoder::opus::Options {
+            channels: channels.into(),
+            preset: preset.unwrap_or(OpusEncoderPreset::Voip).into(),
+            forward_error_correction: forward_error_correction.unwrap_or(false),
+        }),
+        AudioEncoderOptions::Aac {
+            channels,
+            profile,
+            bitrate,
+        } => {
+            if bitrate == Some(0) {
+                return Err(TypeError::new("\"bitrate\" has to be greater than 0."));
+            }
+            encoder::AudioEncoderOptions::Aac(encoder::fdk_aac::Options {
+                channels: channels.into(),
+                profile: match profile.unwrap_or(AacProfile::Lc) {
+                    AacProfile::Lc => encoder::fdk_aac::AacProfile::Lc,
+                    AacProfile::He => encoder::fdk_aac::AacProfile::He,
+                },
+                bitrate,
+            })
+        }
+    };
+
+    Ok(pipeline::OutputAudioOptions {
+        initial: initial.try_into()?,
+        encoder_opts,
+        end_condition: send_eos_when.unwrap_or_default().try_into()?,
+        mixing_strategy: mixing_strategy.unwrap_or(MixingStrategy::SumClip).into(),
+    })
+}
+
 impl TryFrom<OutputEndCondition> for pipeline::PipelineOutputEndCondition {
     type Error = TypeError;
 
@@ -166,6 +789,16 @@ impl TryFrom<OutputEndCondition> for pipeline::PipelineOutputEndCondition {
     }
 }
 
+impl From<H264Profile> for encoder::ffmpeg_h264::Profile {
+    fn from(value: H264Profile) -> Self {
+        match value {
+            H264Profile::Baseline => ffmpeg_h264::Profile::Baseline,
+            H264Profile::Main => ffmpeg_h264::Profile::Main,
+            H264Profile::High => ffmpeg_h264::Profile::High,
+        }
+    }
+}
+
 impl From<H264EncoderPreset> for encoder::ffmpeg_h264::EncoderPreset {
     fn from(value: H264EncoderPreset) -> Self {
         match value {
@@ -183,6 +816,25 @@ impl From<H264EncoderPreset> for encoder::ffmpeg_h264::EncoderPreset {
     }
 }
 
+impl From<VpxDeadline> for encoder::ffmpeg_vpx::Deadline {
+    fn from(value: VpxDeadline) -> Self {
+        match value {
+            VpxDeadline::Realtime => encoder::ffmpeg_vpx::Deadline::Realtime,
+            VpxDeadline::Good => encoder::ffmpeg_vpx::Deadline::Good,
+            VpxDeadline::Best => encoder::ffmpeg_vpx::Deadline::Best,
+        }
+    }
+}
+
+impl From<Av1Usage> for encoder::ffmpeg_av1::Usage {
+    fn from(value: Av1Usage) -> Self {
+        match value {
+            Av1Usage::Realtime => encoder::ffmpeg_av1::Usage::Realtime,
+            Av1Usage::Good => encoder::ffmpeg_av1::Usage::Good,
+        }
+    }
+}
+
 impl From<OpusEncoderPreset> for encoder::AudioEncoderPreset {
     fn from(value: OpusEncoderPreset) -> Self {
         match value {
@@ -0,0 +1,157 @@
+use std::path::PathBuf;
+
+use compositor_pipeline::pipeline::{
+    self,
+    encoder::{self, ffmpeg_h264},
+    output::{self, hls::HlsOutputOptions},
+};
+use serde_json::json;
+
+use crate::types::{HlsOutput, TypeError, WhepOutput};
+
+fn whep_output(encoder: serde_json::Value) -> WhepOutput {
+    serde_json::from_value(json!({
+        "video": {
+            "resolution": { "width": 1280, "height": 720 },
+            "encoder": encoder,
+            "initial": { "root": { "type": "view" } }
+        }
+    }))
+    .unwrap()
+}
+
+#[test]
+fn test_whep_output_h264_profile() {
+    let options = pipeline::RegisterOutputOptions::try_from(whep_output(json!({
+        "type": "ffmpeg_h264",
+        "preset": "ultrafast"
+    })))
+    .unwrap();
+    let Some(pipeline::OutputVideoOptions {
+        encoder_opts: encoder::VideoEncoderOptions::H264(options),
+        ..
+    }) = options.video
+    else {
+        panic!("Expected H264 encoder options.");
+    };
+    assert_eq!(options.profile, Some(ffmpeg_h264::Profile::Baseline));
+    assert_eq!(options.b_frames, Some(0));
+
+    for encoder in [
+        json!({ "type": "ffmpeg_h264", "preset": "ultrafast", "profile": "high" }),
+        json!({ "type": "ffmpeg_h264", "preset": "ultrafast", "b_frames": 2 }),
+    ] {
+        assert_eq!(
+            pipeline::RegisterOutputOptions::try_from(whep_output(encoder)).err(),
+            Some(TypeError::new(
+                "WHEP output supports only the \"baseline\" H264 profile without B-frames."
+            ))
+        );
+    }
+}
+
+#[test]
+fn test_hls_output_renditions() {
+    let request: HlsOutput = serde_json::from_value(json!({
+        "directory": "/tmp/hls",
+        "video": {
+            "resolution": { "width": 1920, "height": 1080 },
+            "encoder": {
+                "type": "ffmpeg_h264",
+                "preset": "ultrafast",
+                "bitrate_mode": { "type": "cbr", "bitrate": 6_000_000 }
+            },
+            "initial": { "root": { "type": "view" } }
+        },
+        "audio": {
+            "encoder": { "type": "aac", "channels": "stereo", "bitrate": 128_000 },
+            "initial": { "inputs": [] }
+        },
+        "renditions": [{
+            "resolution": { "width": 640, "height": 360 },
+            "encoder": { "type": "ffmpeg_h264", "preset": "ultrafast" }
+        }]
+    }))
+    .unwrap();
+    let options = pipeline::RegisterOutputOptions::try_from(request).unwrap();
+
+    let output::OutputOptions::Hls(HlsOutputOptions { variants, .. }) = options.output_options
+    else {
+        panic!("Expected HLS output options.");
+    };
+    let variants: Vec<_> = variants
+        .iter()
+        .map(|variant| {
+            (
+                variant.playlist_path.as_str(),
+                variant.bandwidth,
+                variant.codecs.as_str(),
+            )
+        })
+        .collect();
+    // The rendition without a bitrate limit has the bandwidth estimated from its resolution.
+    assert_eq!(
+        variants,
+        [
+            ("index.m3u8", 6_128_000, "avc1.64002a,mp4a.40.2"),
+            (
+                "rendition_0/index.m3u8",
+                640 * 360 * 3 + 128_000,
+                "avc1.64001f,mp4a.40.2"
+            )
+        ]
+    );
+
+    let renditions = options.video.unwrap().renditions;
+    let [pipeline::OutputVideoRendition {
+        output_options: output::OutputOptions::Hls(rendition_options),
+        with_audio: true,
+        ..
+    }] = renditions.as_slice()
+    else {
+        panic!("Expected a single HLS rendition with audio.");
+    };
+    assert_eq!(
+        rendition_options.output_dir,
+        PathBuf::from("/tmp/hls/rendition_0")
+    );
+    assert!(rendition_options.variants.is_empty());
+}
+
+#[test]
+fn test_hls_output_codecs() {
+    let hls_output = |video_encoder: serde_json::Value, audio_encoder: serde_json::Value| {
+        let request: HlsOutput = serde_json::from_value(json!({
+            "directory": "/tmp/hls",
+            "video": {
+                "resolution": { "width": 1280, "height": 720 },
+                "encoder": { "type": "ffmpeg_h264", "preset": "ultrafast" },
+                "initial": { "root": { "type": "view" } }
+            },
+            "audio": {
+                "encoder": audio_encoder,
+                "initial": { "inputs": [] }
+            },
+            "renditions": [{
+                "resolution": { "width": 640, "height": 360 },
+                "encoder": video_encoder
+            }]
+        }))
+        .unwrap();
+        pipeline::RegisterOutputOptions::try_from(request).err()
+    };
+    let h264 = json!({ "type": "ffmpeg_h264", "preset": "ultrafast" });
+    let aac = json!({ "type": "aac", "channels": "stereo" });
+
+    assert_eq!(hls_output(h264.clone(), aac.clone()), None);
+    assert_eq!(
+        hls_output(json!({ "type": "ffmpeg_vp9" }), aac),
+        Some(TypeError::new("HLS output supports only H264 video."))
+    );
+    assert_eq!(
+        hls_output(h264, json!({ "type": "opus", "channels": "stereo" })),
+        Some(TypeError::new(
+            "HLS output does not support Opus audio. Use AAC encoder instead."
+        ))
+    );
+}
@@ -1,6 +1,6 @@
-use std::time::Duration;
+use std::{sync::Arc, time::Duration};
 
-use compositor_pipeline::pipeline::rtp;
+use compositor_pipeline::pipeline::{rtp, srt};
 use compositor_render::scene;
 
 use super::util::*;
@@ -229,11 +229,54 @@ impl TryFrom<PortOrPortRange> for rtp::RequestedPort {
     }
 }
 
-impl From<TransportProtocol> for rtp::TransportProtocol {
-    fn from(value: TransportProtocol) -> Self {
+impl From<SrtMode> for srt::SrtMode {
+    fn from(value: SrtMode) -> Self {
         match value {
-            TransportProtocol::Udp => rtp::TransportProtocol::Udp,
-            TransportProtocol::TcpServer => rtp::TransportProtocol::TcpServer,
+            SrtMode::Caller => srt::SrtMode::Caller,
+            SrtMode::Listener => srt::SrtMode::Listener,
         }
     }
 }
+
+/// Validates connection options shared by SRT inputs and outputs.
+pub(super) fn srt_connection_options(
+    mode: SrtMode,
+    port: u16,
+    ip: Option<Arc<str>>,
+    latency_ms: Option<u32>,
+    passphrase: Option<Arc<str>>,
+) -> Result<srt::SrtConnectionOptions, TypeError> {
+    if port == 0 {
+        return Err(TypeError::new(
+            "Port needs to be a number between 1 and 65535.",
+        ));
+    }
+    match (mode, &ip) {
+        (SrtMode::Caller, None) => {
+            return Err(TypeError::new(
+                "\"ip\" field is required in SRT caller mode (mode=\"caller\").",
+            ))
+        }
+        (SrtMode::Listener, Some(_)) => {
+            return Err(TypeError::new(
+                "\"ip\" field is not allowed in SRT listener mode (mode=\"listener\").",
+            ))
+        }
+        _ => (),
+    }
+    if let Some(passphrase) = &passphrase {
+        if !(10..=79).contains(&passphrase.len()) {
+            return Err(TypeError::new(
+                "SRT \"passphrase\" has to be between 10 and 79 characters long.",
+            ));
+        }
+    }
+
+    Ok(srt::SrtConnectionOptions {
+        mode: mode.into(),
+        ip,
+        port,
+        latency: Duration::from_millis(latency_ms.unwrap_or(120).into()),
+        passphrase,
+    })
+}
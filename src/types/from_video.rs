@@ -8,6 +8,9 @@ impl From<VideoCodec> for pipeline::VideoCodec {
     fn from(value: VideoCodec) -> Self {
         match value {
             VideoCodec::H264 => pipeline::VideoCodec::H264,
+            VideoCodec::Vp8 => pipeline::VideoCodec::Vp8,
+            VideoCodec::Vp9 => pipeline::VideoCodec::Vp9,
+            VideoCodec::Av1 => pipeline::VideoCodec::Av1,
         }
     }
 }
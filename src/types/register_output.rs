@@ -17,13 +17,135 @@ pub struct RtpOutputStream {
     ///   - `udp` - An UDP port number that RTP packets will be sent to.
     ///
     ///   - `tcp_server` - A local TCP port number or a port range that LiveCompositor will listen for incoming connections.
+    ///
+    ///   - `tcp_client` - A TCP port number of the remote server that LiveCompositor will connect to.
     pub port: PortOrPortRange,
-    /// Only valid if `transport_protocol="udp"`. IP address where RTP packets should be sent to.
+    /// Only valid if `transport_protocol="udp"` or `transport_protocol="tcp_client"`. IP address
+    /// where RTP packets should be sent to.
     pub ip: Option<Arc<str>>,
     /// (**default=`"udp"`**) Transport layer protocol that will be used to send RTP packets.
     pub transport_protocol: Option<TransportProtocol>,
     pub video: Option<OutputRtpVideoOptions>,
     pub audio: Option<OutputRtpAudioOptions>,
+    /// Path where an SDP file describing the stream will be written. The same session
+    /// description is also available at `GET /api/output/:output_id/sdp`.
+    pub sdp_path: Option<String>,
+}
+
+/// Output that writes encoded video and audio to an MP4 file.
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Mp4Output {
+    /// Path to the output MP4 file. The file is finalized when the output is unregistered or
+    /// when EOS is sent on all tracks (see `send_eos_when`).
+    pub path: String,
+    /// Video track configuration.
+    pub video: Option<OutputVideoOptions>,
+    /// Audio track configuration.
+    pub audio: Option<OutputAudioOptions>,
+}
+
+/// Output that writes encoded video and audio as an HLS stream. A rolling `index.m3u8`
+/// playlist and media segments are written to the specified directory.
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct HlsOutput {
+    /// Path to the directory where the playlist and segments should be written.
+    pub directory: String,
+    /// (**default=`2000`**) Target duration of a segment in milliseconds. Segments are cut
+    /// on video keyframes, so the actual duration also depends on the encoder keyframe interval.
+    pub segment_duration_ms: Option<f64>,
+    /// (**default=`5`**) Number of segments kept in the playlist. Segments removed from the
+    /// playlist are also deleted from disk.
+    pub playlist_size: Option<usize>,
+    /// (**default=`"mpeg_ts"`**) Container format of the segments.
+    pub segment_type: Option<HlsSegmentType>,
+    /// Video track configuration. HLS output supports only H264 video.
+    pub video: Option<OutputVideoOptions>,
+    /// Audio track configuration. HLS output supports only AAC audio.
+    pub audio: Option<OutputAudioOptions>,
+    /// Additional encodings of the video for adaptive streaming. Each rendition is written
+    /// with the audio track to a `rendition_{index}` subdirectory, and all streams are listed
+    /// in a `master.m3u8` playlist. The scene is rendered once at `video.resolution` and
+    /// downscaled for each rendition.
+    pub renditions: Option<Vec<HlsVideoRendition>>,
+}
+
+/// Video stream encoded from the scene of an HLS output at a different resolution or bitrate.
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct HlsVideoRendition {
+    /// Rendition resolution in pixels. Can not be larger than the output resolution.
+    pub resolution: Resolution,
+    /// Video encoder options.
+    pub encoder: VideoEncoderOptions,
+}
+
+/// Output that muxes encoded video (H264) and audio (AAC) into FLV and pushes it to an
+/// RTMP server. When the connection drops, the compositor reconnects with an exponential
+/// backoff and resumes the stream from the next video keyframe.
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct RtmpOutput {
+    /// RTMP server URL including the stream key, e.g. `rtmp://example.com/live/stream_key`.
+    pub url: String,
+    /// Video track configuration.
+    pub video: Option<OutputVideoOptions>,
+    /// Audio track configuration. RTMP output supports only AAC audio.
+    pub audio: Option<OutputAudioOptions>,
+}
+
+/// Output that sends encoded video (H264) and audio (AAC or Opus) muxed into MPEG-TS
+/// over [SRT](https://datatracker.ietf.org/doc/html/draft-sharabayko-srt). When the
+/// connection drops, the compositor reconnects (or waits for a new caller in listener mode)
+/// and resumes the stream from the next video keyframe.
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct SrtOutput {
+    /// (**default=`"caller"`**) SRT connection mode.
+    pub mode: Option<SrtMode>,
+    /// Depends on the value of the `mode` field:
+    ///
+    ///   - `caller` - A port of the remote SRT listener.
+    ///
+    ///   - `listener` - A local port on which LiveCompositor waits for the caller.
+    pub port: u16,
+    /// Only valid if `mode="caller"`. IP address or hostname of the remote SRT listener.
+    pub ip: Option<Arc<str>>,
+    /// (**default=`120`**) SRT latency in milliseconds. The higher value of the sender
+    /// and receiver latency is used for the connection.
+    pub latency_ms: Option<u32>,
+    /// Passphrase used to encrypt the stream. Has to be between 10 and 79 characters long
+    /// and match the passphrase of the receiver. If not specified, the stream is not encrypted.
+    pub passphrase: Option<Arc<str>>,
+    /// Video track configuration. SRT output supports only H264 video.
+    pub video: Option<OutputVideoOptions>,
+    /// Audio track configuration.
+    pub audio: Option<OutputAudioOptions>,
+}
+
+/// Output that serves encoded video (H264) and audio (Opus) to browsers over
+/// [WHEP](https://datatracker.ietf.org/doc/html/draft-murillo-whep) (WebRTC-HTTP egress protocol).
+///
+/// After the output is registered, viewers can start a session by sending an SDP offer
+/// to `POST /whep/:output_id`. All viewers share the same encoder.
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct WhepOutput {
+    /// Video track configuration. WHEP output supports only H264 video encoded with
+    /// the `"baseline"` profile (used if `profile` is not specified) and without B-frames.
+    pub video: Option<OutputVideoOptions>,
+    /// Audio track configuration. WHEP output supports only Opus audio.
+    pub audio: Option<OutputAudioOptions>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HlsSegmentType {
+    /// MPEG Transport Stream segments (`.ts`).
+    MpegTs,
+    /// Fragmented MP4 segments (`.m4s`).
+    Fmp4,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
@@ -37,6 +159,25 @@ pub struct OutputRtpVideoOptions {
     pub encoder: VideoEncoderOptions,
     /// Root of a component tree/scene that should be rendered for the output. Use [`update_output` request](../routes.md#update-output) to update this value after registration. [Learn more](../../concept/component.md).
     pub initial: Video,
+    /// Additional encodings of the same scene, each sent as a separate video-only RTP stream.
+    /// The scene is rendered once at `resolution` and downscaled for each rendition.
+    pub renditions: Option<Vec<RtpVideoRendition>>,
+}
+
+/// Video stream encoded from the scene of an RTP output at a different resolution or bitrate.
+/// It is sent using the same transport protocol and IP address as the output.
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct RtpVideoRendition {
+    /// Port of the rendition stream. Has the same meaning as the `port` of the output,
+    /// but has to be different from it and from ports of other renditions.
+    pub port: u16,
+    /// Rendition resolution in pixels. Can not be larger than the output resolution.
+    pub resolution: Resolution,
+    /// Video encoder options.
+    pub encoder: VideoEncoderOptions,
+    /// Path where an SDP file describing the rendition stream will be written.
+    pub sdp_path: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
@@ -52,6 +193,32 @@ pub struct OutputRtpAudioOptions {
     pub initial: Audio,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct OutputVideoOptions {
+    /// Output resolution in pixels.
+    pub resolution: Resolution,
+    /// Defines when output stream should end if some of the input streams are finished. If output includes both audio and video streams, then EOS needs to be sent on both.
+    pub send_eos_when: Option<OutputEndCondition>,
+    /// Video encoder options.
+    pub encoder: VideoEncoderOptions,
+    /// Root of a component tree/scene that should be rendered for the output. Use [`update_output` request](../routes.md#update-output) to update this value after registration. [Learn more](../../concept/component.md).
+    pub initial: Video,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct OutputAudioOptions {
+    /// (**default="sum_clip"**) Specifies how audio should be mixed.
+    pub mixing_strategy: Option<MixingStrategy>,
+    /// Condition for termination of output stream based on the input streams states.
+    pub send_eos_when: Option<OutputEndCondition>,
+    /// Audio encoder options.
+    pub encoder: AudioEncoderOptions,
+    /// Initial audio mixer configuration for output.
+    pub initial: Audio,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 #[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
 pub enum VideoEncoderOptions {
@@ -60,9 +227,132 @@ pub enum VideoEncoderOptions {
         /// (**default=`"fast"`**) Preset for an encoder. See `FFmpeg` [docs](https://trac.ffmpeg.org/wiki/Encode/H.264#Preset) to learn more.
         preset: H264EncoderPreset,
 
+        /// (**default=`{ "type": "crf", "crf": 23 }`**) Rate control mode of the encoder.
+        bitrate_mode: Option<H264BitrateMode>,
+
+        /// Size of the VBV (video buffering verifier) buffer in bits. Limits how much the
+        /// bitrate can exceed the max bitrate over short periods. Can be specified only if
+        /// the bitrate mode defines a max bitrate. Defaults to the max bitrate (1 second buffer).
+        vbv_buffer_size: Option<u64>,
+
+        /// (**default=`250`**) Maximum number of frames between keyframes (GOP length).
+        keyframe_interval: Option<u32>,
+
+        /// Maximum number of consecutive B-frames. B-frames add latency, use `0` to disable
+        /// them. If not specified, the value is based on the preset.
+        b_frames: Option<u32>,
+
+        /// H264 profile. If not specified, it is selected based on other options.
+        profile: Option<H264Profile>,
+
+        /// H264 level, e.g. `"3.1"` or `"4.1"`. If not specified, it is selected based
+        /// on the resolution and bitrate.
+        level: Option<String>,
+
         /// Raw FFmpeg encoder options. See [docs](https://ffmpeg.org/ffmpeg-codecs.html) for more.
+        /// Options defined here override the typed options above.
         ffmpeg_options: Option<HashMap<String, String>>,
     },
+    #[serde(rename = "ffmpeg_vp8")]
+    FfmpegVp8 {
+        /// Target bitrate in bits per second. If not specified, the encoder runs in the
+        /// constrained quality mode with a 2 Mbps upper bound.
+        bitrate: Option<u64>,
+
+        /// (**default=`"realtime"`**) Trade-off between encoding speed and quality.
+        deadline: Option<VpxDeadline>,
+
+        /// (**default=`250`**) Maximum number of frames between keyframes.
+        keyframe_interval: Option<u32>,
+
+        /// Raw FFmpeg encoder options. See [docs](https://ffmpeg.org/ffmpeg-codecs.html#libvpx) for more.
+        ffmpeg_options: Option<HashMap<String, String>>,
+    },
+    #[serde(rename = "ffmpeg_vp9")]
+    FfmpegVp9 {
+        /// Target bitrate in bits per second. If not specified, the encoder runs in the
+        /// constant quality mode.
+        bitrate: Option<u64>,
+
+        /// (**default=`"realtime"`**) Trade-off between encoding speed and quality.
+        deadline: Option<VpxDeadline>,
+
+        /// (**default=`250`**) Maximum number of frames between keyframes.
+        keyframe_interval: Option<u32>,
+
+        /// Raw FFmpeg encoder options. See [docs](https://ffmpeg.org/ffmpeg-codecs.html#libvpx) for more.
+        ffmpeg_options: Option<HashMap<String, String>>,
+    },
+    #[serde(rename = "ffmpeg_av1")]
+    FfmpegAv1 {
+        /// Target bitrate in bits per second. If not specified, the encoder runs in the
+        /// constant quality mode.
+        bitrate: Option<u64>,
+
+        /// (**default=`"realtime"`**) Encoder usage mode. Use `"good"` for high-quality
+        /// outputs that do not need low latency, e.g. archive recordings.
+        usage: Option<Av1Usage>,
+
+        /// (**default=`250`**) Maximum number of frames between keyframes.
+        keyframe_interval: Option<u32>,
+
+        /// Raw FFmpeg encoder options. See [docs](https://ffmpeg.org/ffmpeg-codecs.html#libaom) for more.
+        ffmpeg_options: Option<HashMap<String, String>>,
+    },
+}
+
+/// Rate control mode of the H264 encoder. All bitrates are in bits per second.
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum H264BitrateMode {
+    /// Constant quality mode. The bitrate changes with the complexity of the scene.
+    Crf {
+        /// Quality in the `0-51` range, lower values mean better quality.
+        crf: u8,
+        /// Upper bound of the bitrate (capped CRF). If exceeded, the quality is lowered.
+        max_bitrate: Option<u64>,
+    },
+    /// Constant bitrate mode.
+    Cbr {
+        /// Target bitrate.
+        bitrate: u64,
+    },
+    /// Variable bitrate mode.
+    Vbr {
+        /// Average target bitrate.
+        bitrate: u64,
+        /// Upper bound of the bitrate. Has to be greater or equal to `bitrate`.
+        max_bitrate: Option<u64>,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum H264Profile {
+    /// Profile without B-frames, supported by all decoders.
+    Baseline,
+    Main,
+    High,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum VpxDeadline {
+    /// Fastest encoding, suitable for live streaming.
+    Realtime,
+    /// Default libvpx deadline, balances quality and speed.
+    Good,
+    /// Best quality, very slow.
+    Best,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Av1Usage {
+    /// Low latency encoding, suitable for live streaming.
+    Realtime,
+    /// Good quality encoding, much slower than realtime.
+    Good,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
@@ -79,6 +369,25 @@ pub enum AudioEncoderOptions {
         /// For more information, check out [RFC](https://datatracker.ietf.org/doc/html/rfc6716#section-2.1.7).
         forward_error_correction: Option<bool>,
     },
+    Aac {
+        channels: AudioChannels,
+
+        /// (**default="lc"**) AAC profile.
+        profile: Option<AacProfile>,
+
+        /// Target bitrate in bits per second. If not specified, the encoder picks
+        /// a bitrate based on the profile and the number of channels.
+        bitrate: Option<u32>,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AacProfile {
+    /// AAC Low Complexity.
+    Lc,
+    /// High Efficiency AAC (HE-AAC v1). Provides better quality at low bitrates.
+    He,
 }
 
 /// This type defines when end of an input stream should trigger end of the output stream. Only one of those fields can be set at the time.
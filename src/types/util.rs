@@ -79,6 +79,15 @@ pub enum PortOrPortRange {
     U16(u16),
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SrtMode {
+    /// LiveCompositor connects to a remote SRT listener.
+    Caller,
+    /// LiveCompositor waits for a remote SRT caller to connect.
+    Listener,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum TransportProtocol {
@@ -86,6 +95,8 @@ pub enum TransportProtocol {
     Udp,
     /// TCP protocol where LiveCompositor is the server side of the connection.
     TcpServer,
+    /// TCP protocol where LiveCompositor is the client side of the connection.
+    TcpClient,
 }
 
 impl<E> From<E> for TypeError
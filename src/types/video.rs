@@ -8,6 +8,12 @@ use super::Component;
 pub enum VideoCodec {
     /// H264 video.
     H264,
+    /// VP8 video.
+    Vp8,
+    /// VP9 video.
+    Vp9,
+    /// AV1 video.
+    Av1,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]